@@ -0,0 +1,513 @@
+use anyhow::Error;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::{Path, PathBuf},
+};
+use swc_bundler::Resolve;
+use swc_common::{sync::Lrc, FileName, SourceFile, SourceMap, Span};
+use swc_ecma_ast::{
+    CallExpr, Callee, ExportAll, Expr, ImportDecl, Lit, Module, NamedExport, Str,
+};
+use swc_ecma_visit::{VisitMut, VisitMutWith};
+
+use crate::{
+    parse_module,
+    resolver::{absolute, relative_path, PathResolver},
+    transforms::TransformOptions,
+};
+
+/// Prefix of the specifiers of the imports between chunks while they are bundled,
+/// they are replaced by the paths of the chunk files once they are known
+const CHUNK_PREFIX: &str = "kedo-chunk:";
+
+/// Files imported by a module
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ModuleImports {
+    /// Files of the `import` and `export ... from` declarations
+    pub imports: Vec<PathBuf>,
+    /// Files of the `import()` calls with a string literal
+    pub dynamic_imports: Vec<PathBuf>,
+}
+
+pub(crate) struct ModuleFile {
+    pub fm: Lrc<SourceFile>,
+    pub module: Module,
+    /// Resolved specifiers of the module, the externals are left out
+    pub specifiers: HashMap<String, PathBuf>,
+}
+
+/// | ------------------------------- |
+/// |           ModuleGraph           |
+/// | ------------------------------- |
+/// | - files: parsed modules         |
+/// | - imports: edges of the graph   |
+/// | ------------------------------- |
+///
+/// Every file reached from the entries, parsed once and resolved like the
+/// bundler resolves them. The chunks are split from the `imports`.
+pub(crate) struct ModuleGraph {
+    pub files: HashMap<PathBuf, ModuleFile>,
+    pub imports: HashMap<PathBuf, ModuleImports>,
+}
+
+impl ModuleGraph {
+    pub fn load(
+        cm: &Lrc<SourceMap>,
+        resolver: &PathResolver,
+        external_modules: &[String],
+        entries: &[PathBuf],
+        options: &TransformOptions,
+    ) -> Result<Self, Error> {
+        let mut graph = Self {
+            files: HashMap::new(),
+            imports: HashMap::new(),
+        };
+
+        let mut pending = entries.to_vec();
+        while let Some(path) = pending.pop() {
+            if graph.files.contains_key(&path) {
+                continue;
+            }
+
+            let (fm, mut module) = parse_module(cm, &path, options)?;
+            let mut found = vec![];
+            module.visit_mut_with(&mut ImportSpecifiers::new(
+                |specifier: &str, dynamic| {
+                    found.push((specifier.to_string(), dynamic));
+                    None
+                },
+            ));
+
+            let mut specifiers = HashMap::new();
+            let mut imports = ModuleImports::default();
+            for (specifier, dynamic) in found {
+                if external_modules.contains(&specifier) {
+                    continue;
+                }
+
+                let resolution =
+                    resolver.resolve(&FileName::Real(path.clone()), &specifier)?;
+                let FileName::Real(file) = resolution.filename else {
+                    continue;
+                };
+
+                match dynamic {
+                    true => imports.dynamic_imports.push(file.clone()),
+                    false => imports.imports.push(file.clone()),
+                }
+                pending.push(file.clone());
+                specifiers.insert(specifier, file);
+            }
+
+            graph.files.insert(
+                path.clone(),
+                ModuleFile {
+                    fm,
+                    module,
+                    specifiers,
+                },
+            );
+            graph.imports.insert(path, imports);
+        }
+
+        Ok(graph)
+    }
+}
+
+/// A file written by the bundler, with the files it bundles
+#[derive(Debug, PartialEq)]
+pub(crate) struct Chunk {
+    /// Entry of the chunk, e.g. an entry of the bundle or a file of an `import()`
+    pub root: PathBuf,
+    pub files: BTreeSet<PathBuf>,
+    /// Indexes of the chunks imported by the files of this one
+    pub imports: BTreeSet<usize>,
+}
+
+/// Splits the files reached from `entries` into chunks, the entries come first.
+/// The files of an `import()` get a chunk of their own, and so do the files
+/// imported by more than one chunk, so every file is evaluated once.
+pub(crate) fn split(
+    graph: &HashMap<PathBuf, ModuleImports>,
+    entries: &[PathBuf],
+) -> Vec<Chunk> {
+    let mut roots = Vec::<PathBuf>::new();
+    for entry in entries {
+        if !roots.contains(entry) {
+            roots.push(entry.clone());
+        }
+    }
+
+    let dynamic = reachable(graph, entries)
+        .into_iter()
+        .filter_map(|file| graph.get(file))
+        .flat_map(|imports| &imports.dynamic_imports)
+        .filter(|file| !roots.contains(file))
+        .cloned()
+        .collect::<BTreeSet<_>>();
+    roots.extend(dynamic);
+
+    loop {
+        let owners = owners(graph, &roots);
+        let mut shared = BTreeSet::new();
+        for (file, chunks) in &owners {
+            if chunks.len() < 2 || roots.contains(file) {
+                continue;
+            }
+
+            // a shared file becomes a chunk where one of its importers belongs to
+            // other chunks, the files it imports with the same owners stay with it
+            let boundary = owners.iter().any(|(importer, importer_chunks)| {
+                let imports_file = graph
+                    .get(importer)
+                    .is_some_and(|imports| imports.imports.contains(file));
+                imports_file && (roots.contains(importer) || importer_chunks != chunks)
+            });
+            if boundary {
+                shared.insert(file.clone());
+            }
+        }
+
+        if shared.is_empty() {
+            break;
+        }
+        roots.extend(shared);
+    }
+
+    let owners = owners(graph, &roots);
+    roots
+        .iter()
+        .enumerate()
+        .map(|(index, root)| {
+            let files = owners
+                .iter()
+                .filter(|(_, chunks)| chunks.contains(&index))
+                .map(|(file, _)| file.clone())
+                .collect::<BTreeSet<_>>();
+
+            let imports = files
+                .iter()
+                .filter_map(|file| graph.get(file))
+                .flat_map(|imports| {
+                    imports.imports.iter().chain(&imports.dynamic_imports)
+                })
+                .filter_map(|file| roots.iter().position(|root| root == file))
+                .filter(|chunk| *chunk != index)
+                .collect();
+
+            Chunk {
+                root: root.clone(),
+                files,
+                imports,
+            }
+        })
+        .collect()
+}
+
+/// Files reached from the entries by the imports and the `import()` calls
+fn reachable<'a>(
+    graph: &'a HashMap<PathBuf, ModuleImports>,
+    entries: &'a [PathBuf],
+) -> BTreeSet<&'a Path> {
+    let mut reached = BTreeSet::new();
+    let mut pending = entries.iter().collect::<Vec<_>>();
+    while let Some(file) = pending.pop() {
+        if !reached.insert(file.as_path()) {
+            continue;
+        }
+
+        if let Some(imports) = graph.get(file) {
+            pending.extend(imports.imports.iter().chain(&imports.dynamic_imports));
+        }
+    }
+    reached
+}
+
+/// Chunks of every file, a file belongs to the roots that reach it by the static
+/// imports without going through another root
+fn owners(
+    graph: &HashMap<PathBuf, ModuleImports>,
+    roots: &[PathBuf],
+) -> HashMap<PathBuf, BTreeSet<usize>> {
+    let mut owners = HashMap::<PathBuf, BTreeSet<usize>>::new();
+    for (index, root) in roots.iter().enumerate() {
+        let mut pending = vec![root];
+        while let Some(file) = pending.pop() {
+            if !owners.entry(file.clone()).or_default().insert(index) {
+                continue;
+            }
+
+            if let Some(imports) = graph.get(file) {
+                pending
+                    .extend(imports.imports.iter().filter(|file| !roots.contains(file)));
+            }
+        }
+    }
+    owners
+}
+
+/// SHA-256 content hashes of the chunks, the hash of a chunk changes with the
+/// chunks it imports too since their file names are part of its code. `options`
+/// are the bundle options that change the output, e.g. the minification.
+pub(crate) fn chunk_hashes<'a>(
+    chunks: &[Chunk],
+    options: &str,
+    source: impl Fn(&Path) -> &'a str,
+) -> Vec<String> {
+    let own = chunks
+        .iter()
+        .map(|chunk| {
+            let mut hasher = Sha256::new();
+            for file in &chunk.files {
+                hasher.update(source(file).as_bytes());
+                hasher.update([0u8]);
+            }
+            hasher.finalize()
+        })
+        .collect::<Vec<_>>();
+
+    (0..chunks.len())
+        .map(|index| {
+            let mut imported = BTreeSet::new();
+            let mut pending = vec![index];
+            while let Some(chunk) = pending.pop() {
+                if imported.insert(chunk) {
+                    pending.extend(&chunks[chunk].imports);
+                }
+            }
+
+            let mut hasher = Sha256::new();
+            hasher.update(options.as_bytes());
+            hasher.update([0u8]);
+            hasher.update(&own[index]);
+            for chunk in imported {
+                hasher.update(&own[chunk]);
+            }
+            format!("{:x}", hasher.finalize())[..8].to_string()
+        })
+        .collect()
+}
+
+/// File of a chunk that isn't an entry, e.g. `dist/page-1a2b3c4d.js`
+pub(crate) fn chunk_path(dir: &Path, root: &Path, hash: &str) -> PathBuf {
+    let name = root
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "chunk".to_string());
+    dir.join(format!("{}-{}.js", name, hash))
+}
+
+/// Specifier of a chunk while it's bundled, an external of the bundler
+pub(crate) fn chunk_specifier(index: usize) -> String {
+    format!("{}{}", CHUNK_PREFIX, index)
+}
+
+/// Index of the chunk of a specifier made by [`chunk_specifier`]
+pub(crate) fn chunk_index(specifier: &str) -> Option<usize> {
+    specifier.strip_prefix(CHUNK_PREFIX)?.parse().ok()
+}
+
+/// Specifier that imports the file `to` from the file `from`, e.g. `./page-1a2b3c4d.js`
+pub(crate) fn import_path(from: &Path, to: &Path) -> String {
+    let dir = absolute(from.parent().unwrap_or(Path::new("")));
+    let path = relative_path(&absolute(to), &dir);
+    match path.starts_with("../") {
+        true => path,
+        false => format!("./{}", path),
+    }
+}
+
+/// Visits the specifiers of the imports, the re-exports and the `import()` calls
+/// with a string literal. `rename` gets the specifier and whether it's dynamic and
+/// returns the new specifier, if it changes.
+pub(crate) struct ImportSpecifiers<F>
+where
+    F: FnMut(&str, bool) -> Option<String>,
+{
+    rename: F,
+    /// Span of the string literal and new specifier of every renamed import
+    pub renamed: Vec<(Span, String)>,
+}
+
+impl<F> ImportSpecifiers<F>
+where
+    F: FnMut(&str, bool) -> Option<String>,
+{
+    pub fn new(rename: F) -> Self {
+        Self {
+            rename,
+            renamed: vec![],
+        }
+    }
+
+    fn visit(&mut self, specifier: &mut Str, dynamic: bool) {
+        if let Some(renamed) = (self.rename)(&specifier.value, dynamic) {
+            self.renamed.push((specifier.span, renamed.clone()));
+            specifier.value = renamed.into();
+            specifier.raw = None;
+        }
+    }
+}
+
+impl<F> VisitMut for ImportSpecifiers<F>
+where
+    F: FnMut(&str, bool) -> Option<String>,
+{
+    fn visit_mut_import_decl(&mut self, import: &mut ImportDecl) {
+        self.visit(&mut import.src, false);
+    }
+
+    fn visit_mut_export_all(&mut self, export: &mut ExportAll) {
+        self.visit(&mut export.src, false);
+    }
+
+    fn visit_mut_named_export(&mut self, export: &mut NamedExport) {
+        if let Some(src) = &mut export.src {
+            self.visit(src, false);
+        }
+    }
+
+    fn visit_mut_call_expr(&mut self, call: &mut CallExpr) {
+        call.visit_mut_children_with(self);
+        if !matches!(call.callee, Callee::Import(_)) {
+            return;
+        }
+
+        if let Some(argument) = call.args.first_mut() {
+            if let Expr::Lit(Lit::Str(specifier)) = &mut *argument.expr {
+                self.visit(specifier, true);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(edges: &[(&str, &[&str], &[&str])]) -> HashMap<PathBuf, ModuleImports> {
+        edges
+            .iter()
+            .map(|(file, imports, dynamic_imports)| {
+                let paths = |files: &[&str]| files.iter().map(PathBuf::from).collect();
+                let imports = ModuleImports {
+                    imports: paths(imports),
+                    dynamic_imports: paths(dynamic_imports),
+                };
+                (PathBuf::from(file), imports)
+            })
+            .collect()
+    }
+
+    fn files(files: &[&str]) -> BTreeSet<PathBuf> {
+        files.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_split_single_entry() {
+        let graph = graph(&[
+            ("main.ts", &["a.ts", "b.ts"], &[]),
+            ("a.ts", &["b.ts"], &[]),
+            ("b.ts", &[], &[]),
+        ]);
+
+        let chunks = split(&graph, &["main.ts".into()]);
+        assert_eq!(
+            chunks,
+            vec![Chunk {
+                root: "main.ts".into(),
+                files: files(&["main.ts", "a.ts", "b.ts"]),
+                imports: BTreeSet::new(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_split_shared_and_dynamic() {
+        let graph = graph(&[
+            ("home.ts", &["shared.ts", "home_view.ts"], &["page.ts"]),
+            ("admin.ts", &["shared.ts"], &[]),
+            ("home_view.ts", &[], &[]),
+            ("shared.ts", &["util.ts"], &[]),
+            ("util.ts", &[], &[]),
+            ("page.ts", &["util.ts"], &[]),
+        ]);
+
+        let chunks = split(&graph, &["home.ts".into(), "admin.ts".into()]);
+        let roots = chunks.iter().map(|chunk| &chunk.root).collect::<Vec<_>>();
+        assert_eq!(
+            roots,
+            ["home.ts", "admin.ts", "page.ts", "shared.ts", "util.ts"]
+                .map(PathBuf::from)
+                .iter()
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(chunks[0].files, files(&["home.ts", "home_view.ts"]));
+        assert_eq!(chunks[0].imports, BTreeSet::from([2, 3]));
+        assert_eq!(chunks[1].imports, BTreeSet::from([3]));
+        assert_eq!(chunks[2].imports, BTreeSet::from([4]));
+        assert_eq!(chunks[3].files, files(&["shared.ts"]));
+        assert_eq!(chunks[3].imports, BTreeSet::from([4]));
+        assert_eq!(chunks[4].files, files(&["util.ts"]));
+    }
+
+    #[test]
+    fn test_split_keeps_shared_dependencies_together() {
+        let graph = graph(&[
+            ("a.ts", &["shared.ts"], &[]),
+            ("b.ts", &["shared.ts"], &[]),
+            ("shared.ts", &["inner.ts"], &[]),
+            ("inner.ts", &[], &[]),
+        ]);
+
+        let chunks = split(&graph, &["a.ts".into(), "b.ts".into()]);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[2].files, files(&["shared.ts", "inner.ts"]));
+    }
+
+    #[test]
+    fn test_chunk_hashes() {
+        let graph = graph(&[("main.ts", &[], &["page.ts"]), ("page.ts", &[], &[])]);
+        let chunks = split(&graph, &["main.ts".into()]);
+        let hashes = |page: &'static str, options: &str| {
+            chunk_hashes(&chunks, options, |file| match file.to_str() {
+                Some("page.ts") => page,
+                _ => "main",
+            })
+        };
+
+        let before = hashes("page", "");
+        let after = hashes("changed page", "");
+        assert_eq!(before[0].len(), 8);
+        assert_eq!(before, hashes("page", ""));
+        // the entry imports the page, its file name changes with it
+        assert_ne!(before[0], after[0]);
+        assert_ne!(before[1], after[1]);
+
+        let minified = hashes("page", "minify");
+        assert_ne!(before[0], minified[0]);
+        assert_ne!(before[1], minified[1]);
+    }
+
+    #[test]
+    fn test_chunk_paths() {
+        assert_eq!(
+            chunk_path(Path::new("dist"), Path::new("src/page.ts"), "1a2b3c4d"),
+            PathBuf::from("dist/page-1a2b3c4d.js")
+        );
+        assert_eq!(chunk_index(&chunk_specifier(3)), Some(3));
+        assert_eq!(chunk_index("./page.js"), None);
+        assert_eq!(
+            import_path(Path::new("/app/dist/main.js"), Path::new("/app/dist/a.js")),
+            "./a.js"
+        );
+        assert_eq!(
+            import_path(
+                Path::new("/app/dist/main.js"),
+                Path::new("/app/chunks/a.js")
+            ),
+            "../chunks/a.js"
+        );
+    }
+}
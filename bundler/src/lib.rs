@@ -1,148 +1,193 @@
-use std::{collections::HashMap, fs, path::PathBuf, time::Duration};
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use anyhow::Error;
-use swc_bundler::{
-    Bundle, Bundler, Config, Hook, Load, ModuleData, ModuleRecord, Resolve,
+use anyhow::{anyhow, bail, Context, Error};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chunks::{
+    chunk_hashes, chunk_index, chunk_path, chunk_specifier, import_path, split, Chunk,
+    ImportSpecifiers, ModuleGraph,
 };
+use resolver::{normalize, PathResolver};
+use source_map::{build_source_map, source_map_path};
+use swc_bundler::{BundleKind, Bundler, Config, Hook, Load, ModuleData, ModuleRecord};
 use swc_common::{
     comments::SingleThreadedComments,
     errors::{ColorConfig, Handler},
     sync::Lrc,
-    FileName, Globals, Mark, SourceFile, SourceMap, Span, GLOBALS,
+    BytePos, FileName, Globals, LineCol, Mark, SourceFile, SourceMap, Span, GLOBALS,
+};
+use swc_ecma_ast::{
+    Bool, EsVersion, Expr, Ident, KeyValueProp, Lit, Module, Program, PropName, Str,
 };
-use swc_ecma_ast::{EsVersion, Expr, Ident, KeyValueProp, Lit, Program, PropName, Str};
 use swc_ecma_codegen::{
     text_writer::{omit_trailing_semi, JsWriter, WriteJs},
     to_code_default, Emitter,
 };
-use swc_ecma_loader::{resolve::Resolution, resolvers::lru::CachingResolver};
-// use swc_ecma_minifier::option::{
-//     CompressOptions, ExtraOptions, MinifyOptions, TopLevelOptions,
-// };
+use swc_ecma_loader::resolvers::lru::CachingResolver;
+use swc_ecma_minifier::option::{
+    CompressOptions, ExtraOptions, MangleOptions, MinifyOptions, TopLevelOptions,
+};
 use swc_ecma_parser::{
-    lexer::Lexer, parse_file_as_module, Parser, StringInput, Syntax, TsSyntax,
+    lexer::Lexer, parse_file_as_module, EsSyntax, Parser, StringInput, Syntax, TsSyntax,
 };
 use swc_ecma_transforms_base::{fixer::fixer, hygiene::hygiene, resolver};
 use swc_ecma_transforms_typescript::strip;
 use swc_ecma_visit::{FoldWith, VisitMutWith};
+use transforms::{needs_transform, transform_module, ClearContexts};
+use url::Url;
+
+mod chunks;
+mod resolver;
+mod source_map;
+mod top_level_await;
+mod transforms;
 
-pub struct KedoHook;
+pub use resolver::{probe, PackageResolver, EXTENSIONS};
+pub use source_map::SourceMapMode;
+pub use top_level_await::wrap_top_level_await;
+pub use transforms::{Decorators, JsxRuntime, TransformOptions};
+
+/// `import.meta` of the bundled files, `main` is true for the entries of the bundle
+/// and false for the roots of the other chunks
+pub struct KedoHook {
+    entries: Vec<PathBuf>,
+}
 
 impl Hook for KedoHook {
+    /// Same properties as the runtime `import.meta` of a file, except `resolve`
     fn get_import_meta_props(
         &self,
         span: Span,
         module_record: &ModuleRecord,
     ) -> Result<Vec<KeyValueProp>, Error> {
-        let file_url = module_record.file_name.to_string();
-
-        let dir = file_url
-            .rsplitn(2, '/')
-            .last()
-            .map(|s| s.to_string())
-            .unwrap_or_default();
-        let file = file_url
-            .rsplitn(2, '/')
-            .next()
-            .map(|s| s.to_string())
+        let filename = module_record.file_name.to_string();
+        let path = Path::new(&filename);
+        let dirname = path
+            .parent()
+            .map(|dir| dir.to_string_lossy().to_string())
             .unwrap_or_default();
+        let url = Url::from_file_path(path)
+            .map(|url| url.to_string())
+            .unwrap_or_else(|_| filename.clone());
+        let main = module_record.is_entry
+            && self.entries.iter().any(|entry| entry.as_path() == path);
+
+        let prop = |key: &str, value: Expr| KeyValueProp {
+            key: PropName::Ident(Ident::new(key.into(), span)),
+            value: Box::new(value),
+        };
+        let string = |value: String| {
+            Expr::Lit(Lit::Str(Str {
+                span,
+                raw: None,
+                value: value.into(),
+            }))
+        };
 
         Ok(vec![
-            KeyValueProp {
-                key: PropName::Ident(Ident::new("url".into(), span)),
-                value: Box::new(Expr::Lit(Lit::Str(Str {
-                    span,
-                    raw: None,
-                    value: file_url.into(),
-                }))),
-            },
-            KeyValueProp {
-                key: PropName::Ident(Ident::new("dir".into(), span)),
-                value: Box::new(Expr::Lit(Lit::Str(Str {
-                    span,
-                    raw: None,
-                    value: dir.into(),
-                }))),
-            },
-            KeyValueProp {
-                key: PropName::Ident(Ident::new("filename".into(), span)),
-                value: Box::new(Expr::Lit(Lit::Str(Str {
-                    span,
-                    raw: None,
-                    value: file.into(),
-                }))),
-            },
+            prop("url", string(url)),
+            prop("dirname", string(dirname)),
+            prop("filename", string(filename)),
+            prop("main", Expr::Lit(Lit::Bool(Bool { span, value: main }))),
         ])
     }
 }
 
-struct PathLoader {
-    cm: Lrc<SourceMap>,
+/// | ------------------------------- |
+/// |           PathLoader            |
+/// | ------------------------------- |
+/// | - graph: parsed files           |
+/// | - chunks: chunks of the graph   |
+/// | ------------------------------- |
+///
+/// Loads the files of the graph, the imports of the roots of other chunks are
+/// replaced by the external specifiers of those chunks.
+struct PathLoader<'a> {
+    graph: &'a ModuleGraph,
+    chunks: &'a [Chunk],
 }
 
-impl Load for PathLoader {
+impl Load for PathLoader<'_> {
     fn load(&self, file: &FileName) -> Result<ModuleData, Error> {
-        let file = match file {
-            FileName::Real(v) => v,
-            _ => unreachable!(),
+        let path = match file {
+            FileName::Real(path) => path,
+            _ => bail!("Cannot load {}", file),
         };
 
-        let fm = self.cm.load_file(file)?;
+        let Some(module_file) = self.graph.files.get(path) else {
+            bail!("Cannot load {}", path.display());
+        };
 
-        let module = parse_file_as_module(
-            &fm,
-            Syntax::Typescript(TsSyntax {
-                tsx: false,
-                decorators: false,
-                dts: false,
-                no_early_errors: false,
-                ..Default::default()
-            }),
-            EsVersion::Es2022,
-            None,
-            &mut vec![],
-        )
-        .expect("This should not happen");
+        let owner = self
+            .chunks
+            .iter()
+            .position(|chunk| chunk.files.contains(path));
+        let mut module = module_file.module.clone();
+        module.visit_mut_with(&mut ImportSpecifiers::new(|specifier: &str, _| {
+            let file = module_file.specifiers.get(specifier)?;
+            let chunk = self.chunks.iter().position(|chunk| &chunk.root == file)?;
+            (Some(chunk) != owner).then(|| chunk_specifier(chunk))
+        }));
 
         Ok(ModuleData {
-            fm,
+            fm: module_file.fm.clone(),
             module,
             helpers: Default::default(),
         })
     }
 }
 
-struct PathResolver;
+/// Parses a file of the bundle with the syntax of its extension, the JSX and the
+/// decorators are compiled with `options`. Needs the `GLOBALS` of the bundle.
+pub(crate) fn parse_module(
+    cm: &Lrc<SourceMap>,
+    path: &Path,
+    options: &TransformOptions,
+) -> Result<(Lrc<SourceFile>, Module), Error> {
+    let fm = cm
+        .load_file(path)
+        .with_context(|| format!("Cannot read {}", path.display()))?;
 
-impl Resolve for PathResolver {
-    fn resolve(
-        &self,
-        base: &FileName,
-        module_specifier: &str,
-    ) -> Result<Resolution, Error> {
-        assert!(
-            module_specifier.starts_with('.'),
-            "{}",
-            format!(
-                "Module specifier should start with '.': {}",
-                module_specifier.to_string()
-            )
-        );
+    let mut errors = vec![];
+    let module =
+        parse_file_as_module(&fm, syntax(path), EsVersion::Es2022, None, &mut errors)
+            .map_err(|err| syntax_error(cm, err))?;
 
-        let base = match base {
-            FileName::Real(v) => v,
-            _ => unreachable!(),
-        };
+    if let Some(err) = errors.into_iter().next() {
+        return Err(syntax_error(cm, err));
+    }
 
-        Ok(Resolution {
-            filename: FileName::Real(
-                base.parent()
-                    .unwrap()
-                    .join(module_specifier)
-                    .with_extension("ts"),
-            ),
-            slug: None,
-        })
+    // compiled apart from the bundle, the imports of `jsx-runtime` are bundled too
+    if needs_transform(path, &module) {
+        let mut module = transform_module(module, cm, None, options);
+        module.visit_mut_with(&mut ClearContexts);
+        return Ok((fm, module));
+    }
+
+    Ok((fm, module))
+}
+
+/// JavaScript files are parsed as JavaScript, any other file as TypeScript
+fn syntax(path: &Path) -> Syntax {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("js" | "mjs" | "cjs") => Syntax::Es(EsSyntax {
+            decorators: true,
+            ..Default::default()
+        }),
+        Some("jsx") => Syntax::Es(EsSyntax {
+            jsx: true,
+            decorators: true,
+            ..Default::default()
+        }),
+        extension => Syntax::Typescript(TsSyntax {
+            tsx: extension == Some("tsx"),
+            decorators: true,
+            ..Default::default()
+        }),
     }
 }
 
@@ -150,163 +195,390 @@ pub struct BundleArgs {
     pub external_modules: Vec<String>,
     pub entries: Vec<(String, PathBuf)>,
     pub outputs: Vec<PathBuf>,
+    /// Compresses and mangles the bundle
     pub minify: bool,
+    /// Keeps the names of the classes when minifying, e.g. for `constructor.name`
+    pub keep_classnames: bool,
+    /// Keeps the names of the functions when minifying, e.g. for `function.name`
+    pub keep_fnames: bool,
+    /// Resolves the bare specifiers that are not external, they fail without it
+    pub packages: Option<PackageResolver>,
+    /// Writes the source map of the bundles, `None` writes no source map
+    pub source_map: Option<SourceMapMode>,
+    /// Removes the code that isn't used by the entries
+    pub tree_shaking: bool,
+    /// Moves the files imported by more than one entry to chunks of their own
+    /// instead of copying them to every entry. The files of the `import()` calls
+    /// get a chunk either way, the chunks are written next to the first output.
+    pub splitting: bool,
+    /// Compiles the JSX and the decorators of the files
+    pub transform: TransformOptions,
+}
+
+/// A file written by [`bundle`], sizes are in bytes and leave out the source map
+pub struct BundleOutput {
+    pub path: PathBuf,
+    pub size: usize,
+    /// Size of the bundle without minification, `None` when it isn't minified
+    pub unminified_size: Option<usize>,
+    /// Path of the external source map
+    pub source_map: Option<PathBuf>,
+}
+
+impl fmt::Display for BundleOutput {
+    /// e.g. `dist/main.js (4.1kb, 12.5kb unminified, -67%)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let size = |bytes: usize| format!("{:.1}kb", bytes as f64 / 1024.0);
+        match self.unminified_size {
+            Some(unminified) if unminified > 0 => write!(
+                f,
+                "{} ({}, {} unminified, -{:.0}%)",
+                self.path.display(),
+                size(self.size),
+                size(unminified),
+                100.0 - self.size as f64 * 100.0 / unminified as f64
+            ),
+            _ => write!(f, "{} ({})", self.path.display(), size(self.size)),
+        }
+    }
 }
 
 pub struct BundleResult {
     pub duration: Duration,
+    pub outputs: Vec<BundleOutput>,
 }
 
 pub fn bundle(args: BundleArgs) -> Result<BundleResult, Error> {
     let cm = Lrc::new(SourceMap::default());
     let globals = Globals::new();
+    let start = std::time::Instant::now();
+
+    let resolver = PathResolver {
+        packages: args.packages.clone(),
+    };
+    let entries = args
+        .entries
+        .iter()
+        .map(|(_, path)| normalize(path))
+        .collect::<Vec<_>>();
+    if args.outputs.len() < entries.len() {
+        bail!(
+            "Missing the output path of the entry {}",
+            args.outputs.len()
+        );
+    }
+
+    let graph = GLOBALS.set(&globals, || {
+        ModuleGraph::load(
+            &cm,
+            &resolver,
+            &args.external_modules,
+            &entries,
+            &args.transform,
+        )
+    })?;
+
+    // without splitting every entry is bundled with all the files it imports,
+    // the groups are the entries bundled together with their first output
+    let groups = match args.splitting {
+        true => vec![(0, entries.clone())],
+        false => entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| (index, vec![entry.clone()]))
+            .collect(),
+    };
 
-    let loader = PathLoader { cm: cm.clone() };
-    let path_resolver = PathResolver;
+    let minify = args
+        .minify
+        .then(|| minify_options(args.keep_classnames, args.keep_fnames));
+
+    // the file names of the chunks change with the options that change their code
+    let options = format!(
+        "{:?}",
+        (
+            args.minify,
+            args.keep_classnames,
+            args.keep_fnames,
+            args.tree_shaking,
+            &args.transform
+        )
+    );
+
+    let mut outputs = Vec::<BundleOutput>::new();
+    for (first, group) in groups {
+        let chunks = split(&graph.imports, &group);
+        let hashes = chunk_hashes(&chunks, &options, |file| {
+            graph
+                .files
+                .get(file)
+                .map(|file| file.fm.src.as_str())
+                .unwrap_or_default()
+        });
+
+        let dir = args.outputs[first].parent().unwrap_or(Path::new(""));
+        let paths = chunks
+            .iter()
+            .enumerate()
+            .map(|(index, chunk)| match index < group.len() {
+                true => args.outputs[first + index].clone(),
+                false => chunk_path(dir, &chunk.root, &hashes[index]),
+            })
+            .collect::<Vec<_>>();
+
+        let hook = KedoHook {
+            entries: entries.clone(),
+        };
+        let modules =
+            bundle_chunks(&globals, &cm, &graph, &resolver, &args, &chunks, hook)?;
+        for (index, module) in modules.into_iter().enumerate() {
+            let path = &paths[index];
+            // a chunk of an `import()` can be shared by the entries bundled apart
+            if outputs.iter().any(|output| &output.path == path) {
+                continue;
+            }
 
-    let external_modules = args
+            let (mut module, unminified) =
+                GLOBALS.set(&globals, || transform(module, cm.clone(), minify.as_ref()));
+
+            // the imports of other chunks point to their files
+            module.visit_mut_with(&mut ImportSpecifiers::new(|specifier: &str, _| {
+                let chunk = paths.get(chunk_index(specifier)?)?;
+                Some(import_path(path, chunk))
+            }));
+
+            outputs.push(write_bundle(&cm, &module, unminified, path, &args)?);
+        }
+    }
+
+    Ok(BundleResult {
+        duration: start.elapsed(),
+        outputs,
+    })
+}
+
+/// Bundles every chunk with the files it owns, the imports of the other chunks are
+/// kept as externals. The modules are in the order of the chunks.
+fn bundle_chunks(
+    globals: &Globals,
+    cm: &Lrc<SourceMap>,
+    graph: &ModuleGraph,
+    resolver: &PathResolver,
+    args: &BundleArgs,
+    chunks: &[Chunk],
+    hook: KedoHook,
+) -> Result<Vec<Module>, Error> {
+    let mut external_modules = args
         .external_modules
-        .into_iter()
-        .map(|v| v.into())
+        .iter()
+        .map(|module| module.as_str().into())
         .collect::<Vec<_>>();
+    external_modules.extend((0..chunks.len()).map(|index| chunk_specifier(index).into()));
 
     let mut bundler = Bundler::new(
-        &globals,
+        globals,
         cm.clone(),
-        loader,
-        CachingResolver::new(4096, path_resolver),
+        PathLoader { graph, chunks },
+        CachingResolver::new(4096, resolver.clone()),
         Config {
             require: false,
             external_modules,
-            disable_dce: true,
+            disable_dce: !args.tree_shaking,
             ..Default::default()
         },
-        Box::new(KedoHook),
+        Box::new(hook),
     );
 
-    let mut entries = HashMap::default();
-
-    let start = std::time::Instant::now();
+    let entries = chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| (index.to_string(), FileName::Real(chunk.root.clone())))
+        .collect::<HashMap<_, _>>();
 
-    for (path, entry) in args.entries.iter() {
-        entries.insert(path.clone(), entry.clone().into());
+    let mut modules = vec![None; chunks.len()];
+    for bundled in bundler.bundle(entries)? {
+        let index = match &bundled.kind {
+            BundleKind::Named { name } => name.parse::<usize>().ok(),
+            _ => None,
+        };
+        match index.and_then(|index| modules.get_mut(index)) {
+            Some(module) => *module = Some(bundled.module),
+            None => bail!("Unexpected bundle {:?}", bundled.kind),
+        }
     }
 
-    // entries.insert("main".into(), args.entries[0].1.clone().into());
-    let mut modules = bundler.bundle(entries)?;
-    // let c = swc::Compiler::new(cm.clone());
-
-    if args.minify {
-        modules = modules
-            .into_iter()
-            .map(|mut b| {
-                GLOBALS.set(&globals, || {
-                    let unresolved_mark = Mark::new();
-                    let top_level_mark = Mark::new();
-                    // Conduct identifier scope analysis
-                    b.module = b.module.fold_with(&mut resolver(
-                        unresolved_mark,
-                        top_level_mark,
-                        true,
-                    ));
-
-                    b.module = b.module.fold_with(&mut hygiene());
-                    // Remove typescript types
-                    let program: Program = b.module.into();
-                    b.module = program
-                        .fold_with(&mut strip(top_level_mark))
-                        .expect_module();
-                    // b.module.visit_mut_with(&mut strip(top_level_mark));
-                    // b.module = swc_ecma_minifier::optimize(
-                    //     b.module.into(),
-                    //     cm.clone(),
-                    //     None,
-                    //     None,
-                    //     &MinifyOptions {
-                    //         compress: Some(CompressOptions {
-                    //             top_level: Some(TopLevelOptions { functions: true }),
-                    //             keep_classnames: true,
-                    //             keep_fargs: true,
-                    //             ..Default::default()
-                    //         }),
-                    //         mangle: None,
-                    //         // Some(MangleOptions {
-                    //         //     keep_class_names: true,
-                    //         //     safari10: true,
-                    //         //     top_level: Some(true),
-                    //         //     keep_private_props: true,
-                    //         //     ..Default::default()
-                    //         // }),
-                    //         ..Default::default()
-                    //     },
-                    //     &ExtraOptions {
-                    //         unresolved_mark,
-                    //         top_level_mark,
-                    //     },
-                    // )
-                    // .expect_module();
-                    b.module.visit_mut_with(&mut fixer(None));
-                    b
-                })
-            })
-            .collect();
+    modules
+        .into_iter()
+        .zip(chunks)
+        .map(|(module, chunk)| {
+            module
+                .ok_or_else(|| anyhow!("Missing the bundle of {}", chunk.root.display()))
+        })
+        .collect()
+}
+
+/// Writes the code of a bundle and its source map
+fn write_bundle(
+    cm: &Lrc<SourceMap>,
+    module: &Module,
+    unminified: Option<Module>,
+    path: &Path,
+    args: &BundleArgs,
+) -> Result<BundleOutput, Error> {
+    let unminified_size = match unminified {
+        Some(unminified) => Some(emit(cm.clone(), &unminified, false, None)?.len()),
+        None => None,
+    };
+
+    let mut mappings = vec![];
+    let mut code = emit(
+        cm.clone(),
+        module,
+        args.minify,
+        args.source_map.is_some().then_some(&mut mappings),
+    )?;
+    let size = code.len();
+
+    validate_file_dir(path).map_err(|err| anyhow!(err))?;
+    let mut source_map = None;
+    if let Some(mode) = args.source_map {
+        let json = build_source_map(cm, &mut mappings, path)?;
+        let url = match mode {
+            SourceMapMode::Inline => {
+                format!("data:application/json;base64,{}", STANDARD.encode(json))
+            }
+            SourceMapMode::External => {
+                let map_path = source_map_path(path);
+                fs::write(&map_path, json)
+                    .with_context(|| format!("Cannot write {}", map_path.display()))?;
+                let url = map_path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                source_map = Some(map_path);
+                url
+            }
+        };
+        code.push_str(&format!("\n//# sourceMappingURL={}\n", url));
     }
 
-    write_output(&args.outputs, cm, modules, args.minify);
+    fs::write(path, &code).with_context(|| format!("Cannot write {}", path.display()))?;
 
-    Ok(BundleResult {
-        duration: start.elapsed(),
+    Ok(BundleOutput {
+        path: path.to_path_buf(),
+        size,
+        unminified_size,
+        source_map,
     })
 }
 
-fn write_output(
-    outputs: &Vec<PathBuf>,
+/// Compression and mangling of the top level names too, the bundle is a module
+/// so its exports keep their names.
+fn minify_options(keep_classnames: bool, keep_fnames: bool) -> MinifyOptions {
+    MinifyOptions {
+        compress: Some(CompressOptions {
+            top_level: Some(TopLevelOptions { functions: true }),
+            keep_classnames,
+            keep_fnames,
+            keep_fargs: true,
+            ..Default::default()
+        }),
+        mangle: Some(MangleOptions {
+            top_level: Some(true),
+            keep_class_names: keep_classnames,
+            keep_fn_names: keep_fnames,
+            keep_private_props: true,
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Strips the TypeScript syntax of a bundle and minifies it with `minify`.
+/// The module before the minification is returned too, to compare the sizes.
+fn transform(
+    module: Module,
     cm: Lrc<SourceMap>,
-    modules: Vec<Bundle>,
-    minify: bool,
-) {
-    let mut index = 0;
-    for bundled in modules {
-        let code = {
-            let mut buf = vec![];
-
-            {
-                let wr = JsWriter::new(cm.clone(), "\n", &mut buf, None);
-                let mut emitter = Emitter {
-                    cfg: swc_ecma_codegen::Config::default().with_minify(minify),
-                    cm: cm.clone(),
-                    comments: None,
-                    wr: if minify {
-                        Box::new(omit_trailing_semi(wr)) as Box<dyn WriteJs>
-                    } else {
-                        Box::new(wr) as Box<dyn WriteJs>
-                    },
-                };
-
-                emitter.emit_module(&bundled.module).unwrap();
-            }
+    minify: Option<&MinifyOptions>,
+) -> (Module, Option<Module>) {
+    let unresolved_mark = Mark::new();
+    let top_level_mark = Mark::new();
 
-            String::from_utf8_lossy(&buf).to_string()
-        };
+    // Conduct identifier scope analysis
+    let mut program = Program::Module(module)
+        .fold_with(&mut resolver(unresolved_mark, top_level_mark, true))
+        .fold_with(&mut strip(top_level_mark));
 
-        let output_path = &outputs[index];
-        println!(
-            "Created {} ({}kb)",
-            output_path.display(),
-            code.len() / 1024
+    let mut unminified = None;
+    if let Some(options) = minify {
+        unminified = Some(
+            program
+                .clone()
+                .fold_with(&mut hygiene())
+                .fold_with(&mut fixer(None))
+                .expect_module(),
         );
 
-        validate_file_dir(output_path).unwrap();
-        fs::write(output_path, &code).unwrap();
-        index += 1;
+        program = swc_ecma_minifier::optimize(
+            program,
+            cm,
+            None,
+            None,
+            options,
+            &ExtraOptions {
+                unresolved_mark,
+                top_level_mark,
+            },
+        );
     }
+
+    // Fix up any identifiers with the same name, but different contexts
+    let module = program
+        .fold_with(&mut hygiene())
+        .fold_with(&mut fixer(None))
+        .expect_module();
+
+    (module, unminified)
+}
+
+/// Code of a module, the positions of the source map are collected in `mappings`
+fn emit(
+    cm: Lrc<SourceMap>,
+    module: &Module,
+    minify: bool,
+    mappings: Option<&mut Vec<(BytePos, LineCol)>>,
+) -> Result<String, Error> {
+    let mut buf = vec![];
+
+    {
+        let wr = JsWriter::new(cm.clone(), "\n", &mut buf, mappings);
+        let mut emitter = Emitter {
+            cfg: swc_ecma_codegen::Config::default().with_minify(minify),
+            cm,
+            comments: None,
+            wr: if minify {
+                Box::new(omit_trailing_semi(wr)) as Box<dyn WriteJs>
+            } else {
+                Box::new(wr) as Box<dyn WriteJs>
+            },
+        };
+
+        emitter.emit_module(module)?;
+    }
+
+    Ok(String::from_utf8_lossy(&buf).to_string())
 }
 
 // if the dir path of a given file does not exist, it must be created
-pub fn validate_file_dir(path: &PathBuf) -> Result<(), String> {
-    let dir = path.parent().unwrap();
+pub fn validate_file_dir(path: &Path) -> Result<(), String> {
+    let dir = match path.parent() {
+        Some(dir) => dir,
+        None => return Ok(()),
+    };
+
     if !dir.exists() {
-        fs::create_dir_all(dir).unwrap();
+        fs::create_dir_all(dir)
+            .map_err(|err| format!("Cannot create {}: {}", dir.display(), err))?;
     }
     Ok(())
 }
@@ -363,3 +635,411 @@ pub fn compile_typescript(fm: &SourceFile, cm: Lrc<SourceMap>) {
         println!("{}", to_code_default(cm, Some(&comments), &program));
     })
 }
+
+/// Output of [`transpile`], the source map is serialized as JSON
+pub struct Transpiled {
+    pub code: String,
+    pub source_map: String,
+}
+
+/// Strips the TypeScript syntax of a single module and compiles its JSX and
+/// decorators with `options`, `.tsx` and `.jsx` files are parsed with JSX.
+/// The source map points back to the positions of the TypeScript source.
+pub fn transpile(
+    source: &str,
+    path: &Path,
+    options: &TransformOptions,
+) -> Result<Transpiled, Error> {
+    let cm: Lrc<SourceMap> = Default::default();
+    let fm = cm.new_source_file(FileName::Real(path.to_path_buf()), source.to_string());
+    let comments = SingleThreadedComments::default();
+
+    let mut errors = vec![];
+    let module = parse_file_as_module(
+        &fm,
+        syntax(path),
+        EsVersion::Es2022,
+        Some(&comments),
+        &mut errors,
+    )
+    .map_err(|err| syntax_error(&cm, err))?;
+
+    if let Some(err) = errors.into_iter().next() {
+        return Err(syntax_error(&cm, err));
+    }
+
+    let globals = Globals::new();
+    let program = GLOBALS.set(&globals, || {
+        let module = transform_module(module, &cm, Some(&comments), options);
+        Program::Module(module).fold_with(&mut fixer(Some(&comments)))
+    });
+
+    let mut code = vec![];
+    let mut mappings = vec![];
+    {
+        let mut emitter = Emitter {
+            cfg: swc_ecma_codegen::Config::default(),
+            cm: cm.clone(),
+            comments: Some(&comments),
+            wr: JsWriter::new(cm.clone(), "\n", &mut code, Some(&mut mappings)),
+        };
+        emitter.emit_program(&program)?;
+    }
+
+    let mut source_map = vec![];
+    cm.build_source_map(&mut mappings)
+        .to_writer(&mut source_map)?;
+
+    Ok(Transpiled {
+        code: String::from_utf8(code)?,
+        source_map: String::from_utf8(source_map)?,
+    })
+}
+
+/// Rewrites the specifiers of the imports, the re-exports and the `import()` calls
+/// with a string literal of a single module, `rewrite` returns the new specifier
+/// if it changes. The rest of the source is kept as it is, the syntax is the one
+/// of the extension of `path`.
+pub fn rewrite_imports(
+    source: &str,
+    path: &Path,
+    mut rewrite: impl FnMut(&str) -> Option<String>,
+) -> Result<String, Error> {
+    let cm: Lrc<SourceMap> = Default::default();
+    let fm = cm.new_source_file(FileName::Real(path.to_path_buf()), source.to_string());
+
+    let mut errors = vec![];
+    let mut module =
+        parse_file_as_module(&fm, syntax(path), EsVersion::Es2022, None, &mut errors)
+            .map_err(|err| syntax_error(&cm, err))?;
+
+    if let Some(err) = errors.into_iter().next() {
+        return Err(syntax_error(&cm, err));
+    }
+
+    let mut specifiers = ImportSpecifiers::new(|specifier: &str, _| rewrite(specifier));
+    module.visit_mut_with(&mut specifiers);
+    specifiers.renamed.sort_by_key(|(span, _)| span.lo);
+
+    // only the text between the quotes changes, the quotes are kept
+    let mut code = String::with_capacity(source.len());
+    let mut copied = 0;
+    for (span, specifier) in specifiers.renamed {
+        let lo = (span.lo - fm.start_pos).0 as usize + 1;
+        let hi = (span.hi - fm.start_pos).0 as usize - 1;
+        code.push_str(&source[copied..lo]);
+        code.push_str(&specifier);
+        copied = hi;
+    }
+    code.push_str(&source[copied..]);
+
+    Ok(code)
+}
+
+fn syntax_error(cm: &SourceMap, error: swc_ecma_parser::error::Error) -> Error {
+    let loc = cm.lookup_char_pos(error.span().lo);
+    anyhow!(
+        "SyntaxError: {} at {}:{}:{}",
+        error.kind().msg(),
+        loc.file.name,
+        loc.line,
+        loc.col_display + 1
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_bundle_minify() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path().to_path_buf();
+        fs::write(
+            dir.join("counter.ts"),
+            concat!(
+                "export class Counter {\n",
+                "    count: number = 0;\n",
+                "    increment(): void {\n",
+                "        const step = 1;\n",
+                "        this.count += step;\n",
+                "    }\n",
+                "}\n",
+            ),
+        )
+        .unwrap();
+        fs::write(
+            dir.join("main.ts"),
+            concat!(
+                "import { Counter } from './counter';\n",
+                "const counter: Counter = new Counter();\n",
+                "counter.increment();\n",
+                "console.log(Counter.name, counter.count);\n",
+            ),
+        )
+        .unwrap();
+
+        let result = bundle(BundleArgs {
+            external_modules: vec![],
+            entries: vec![("main".to_string(), dir.join("main.ts"))],
+            outputs: vec![dir.join("dist/main.js")],
+            minify: true,
+            keep_classnames: true,
+            keep_fnames: false,
+            packages: None,
+            source_map: None,
+            tree_shaking: false,
+            splitting: false,
+            transform: TransformOptions::default(),
+        })
+        .unwrap();
+
+        let output = &result.outputs[0];
+        assert!(output.size < output.unminified_size.unwrap());
+        let code = fs::read_to_string(&output.path).unwrap();
+        assert!(code.contains("class Counter"));
+        assert!(!code.contains("step"));
+    }
+
+    #[test]
+    fn test_bundle_source_map() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path().to_path_buf();
+        fs::write(
+            dir.join("lib.ts"),
+            "export const add = (a: number) => a + 1;\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("main.ts"),
+            "import { add } from './lib.ts';\nconsole.log(add(1));\n",
+        )
+        .unwrap();
+
+        let result = bundle(BundleArgs {
+            external_modules: vec![],
+            entries: vec![("main".to_string(), dir.join("main.ts"))],
+            outputs: vec![dir.join("dist/main.js")],
+            minify: false,
+            keep_classnames: false,
+            keep_fnames: false,
+            packages: None,
+            source_map: Some(SourceMapMode::External),
+            tree_shaking: false,
+            splitting: false,
+            transform: TransformOptions::default(),
+        })
+        .unwrap();
+
+        let output = &result.outputs[0];
+        let code = fs::read_to_string(&output.path).unwrap();
+        assert!(code.ends_with("//# sourceMappingURL=main.js.map\n"));
+        assert_eq!(output.source_map, Some(dir.join("dist/main.js.map")));
+
+        let map: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(dir.join("dist/main.js.map")).unwrap(),
+        )
+        .unwrap();
+        let sources = map["sources"].as_array().unwrap();
+        assert!(sources.contains(&"../lib.ts".into()));
+        assert!(sources.contains(&"../main.ts".into()));
+        assert!(map["sourcesContent"][0].is_string());
+    }
+
+    #[test]
+    fn test_bundle_splitting() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path().to_path_buf();
+        for (file, code) in [
+            (
+                "shared.ts",
+                "export const shared = (name: string) => name;\n",
+            ),
+            ("page.ts", "export const page = 'page';\n"),
+            (
+                "home.ts",
+                concat!(
+                    "import { shared } from './shared';\n",
+                    "console.log(shared('home'));\n",
+                    "import('./page').then(({ page }) => console.log(page));\n",
+                ),
+            ),
+            (
+                "admin.ts",
+                "import { shared } from './shared';\nconsole.log(shared('admin'));\n",
+            ),
+        ] {
+            fs::write(dir.join(file), code).unwrap();
+        }
+
+        let result = bundle(BundleArgs {
+            external_modules: vec![],
+            entries: vec![
+                ("home".to_string(), dir.join("home.ts")),
+                ("admin".to_string(), dir.join("admin.ts")),
+            ],
+            outputs: vec![dir.join("dist/home.js"), dir.join("dist/admin.js")],
+            minify: false,
+            keep_classnames: false,
+            keep_fnames: false,
+            packages: None,
+            source_map: None,
+            tree_shaking: false,
+            splitting: true,
+            transform: TransformOptions::default(),
+        })
+        .unwrap();
+
+        let names = result
+            .outputs
+            .iter()
+            .map(|output| {
+                output
+                    .path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names.len(), 4);
+        assert_eq!(names[..2], ["home.js", "admin.js"]);
+        let page = names.iter().find(|name| name.starts_with("page-")).unwrap();
+        let shared = names
+            .iter()
+            .find(|name| name.starts_with("shared-"))
+            .unwrap();
+
+        let home = fs::read_to_string(dir.join("dist/home.js")).unwrap();
+        assert!(home.contains(&format!("./{}", shared)));
+        assert!(home.contains(&format!("./{}", page)));
+        assert!(!home.contains("kedo-chunk:"));
+        let admin = fs::read_to_string(dir.join("dist/admin.js")).unwrap();
+        assert!(admin.contains(&format!("./{}", shared)));
+        assert!(!admin.contains("=> name"));
+    }
+
+    #[test]
+    fn test_bundle_jsx_and_decorators() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path().to_path_buf();
+        for (file, code) in [
+            (
+                "jsx-runtime.js",
+                "export const jsx = (type, props) => ({ type, props });\n",
+            ),
+            (
+                "page.tsx",
+                concat!(
+                    "const routes: string[] = [];\n",
+                    "function route(path: string) {\n",
+                    "    return (target: any) => {\n",
+                    "        routes.push(path);\n",
+                    "        return target;\n",
+                    "    };\n",
+                    "}\n",
+                    "@route('/')\n",
+                    "export class Page {\n",
+                    "    render(name: string) { return <h1>{name}</h1>; }\n",
+                    "}\n",
+                ),
+            ),
+            (
+                "main.ts",
+                concat!(
+                    "import { Page } from './page';\n",
+                    "console.log(new Page().render('kedo'));\n",
+                ),
+            ),
+        ] {
+            fs::write(dir.join(file), code).unwrap();
+        }
+
+        let runtime = dir.join("jsx-runtime.js");
+        let result = bundle(BundleArgs {
+            external_modules: vec![],
+            entries: vec![("main".to_string(), dir.join("main.ts"))],
+            outputs: vec![dir.join("dist/main.js")],
+            minify: false,
+            keep_classnames: false,
+            keep_fnames: false,
+            packages: Some(std::sync::Arc::new(move |specifier, _| match specifier {
+                "react/jsx-runtime" => Ok(runtime.clone()),
+                _ => Err(format!("Cannot find module '{}'", specifier)),
+            })),
+            source_map: None,
+            tree_shaking: false,
+            splitting: false,
+            transform: TransformOptions {
+                decorators: Decorators::Legacy,
+                ..Default::default()
+            },
+        })
+        .unwrap();
+
+        let code = fs::read_to_string(&result.outputs[0].path).unwrap();
+        assert!(code.contains("({ type, props })"));
+        assert!(code.contains("_ts_decorate"));
+        assert!(!code.contains("<h1>"));
+        assert!(!code.contains("import "));
+    }
+
+    #[test]
+    fn test_bundle_output_display() {
+        let mut output = BundleOutput {
+            path: PathBuf::from("dist/main.js"),
+            size: 1024,
+            unminified_size: Some(4096),
+            source_map: None,
+        };
+        assert_eq!(
+            output.to_string(),
+            "dist/main.js (1.0kb, 4.0kb unminified, -75%)"
+        );
+
+        output.unminified_size = None;
+        assert_eq!(output.to_string(), "dist/main.js (1.0kb)");
+    }
+
+    #[test]
+    fn test_rewrite_imports() {
+        let source = concat!(
+            "import { add } from \"./math.ts\";\n",
+            "import './side_effect.js';\n",
+            "export * from \"./all.js\";\n",
+            "export { sub } from './sub.js';\n",
+            "import type { Value } from './types.ts';\n",
+            "const util = await import(\"./util.js\");\n",
+            "// import \"./commented.js\";\n",
+            "const path = \"./not_an_import.js\";\n",
+            "const template = `import \"./template.js\"`;\n",
+        );
+
+        let code = rewrite_imports(source, Path::new("mod.ts"), |specifier| {
+            Some(specifier.replace("./", "https://example.com/"))
+        })
+        .unwrap();
+        assert_eq!(
+            code,
+            concat!(
+                "import { add } from \"https://example.com/math.ts\";\n",
+                "import 'https://example.com/side_effect.js';\n",
+                "export * from \"https://example.com/all.js\";\n",
+                "export { sub } from 'https://example.com/sub.js';\n",
+                "import type { Value } from 'https://example.com/types.ts';\n",
+                "const util = await import(\"https://example.com/util.js\");\n",
+                "// import \"./commented.js\";\n",
+                "const path = \"./not_an_import.js\";\n",
+                "const template = `import \"./template.js\"`;\n",
+            )
+        );
+
+        let error = rewrite_imports(
+            "import { add from \"./math.js\";",
+            Path::new("mod.js"),
+            |_| None,
+        );
+        assert!(error.unwrap_err().to_string().starts_with("SyntaxError:"));
+    }
+}
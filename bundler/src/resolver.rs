@@ -0,0 +1,257 @@
+use anyhow::{anyhow, bail, Error};
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+use swc_bundler::Resolve;
+use swc_common::FileName;
+use swc_ecma_loader::resolve::Resolution;
+use url::Url;
+
+/// Extensions probed for the imports without one, in order
+pub const EXTENSIONS: [&str; 6] = ["ts", "tsx", "mts", "js", "jsx", "mjs"];
+
+/// Resolves a bare specifier imported by a file, e.g. with the packages of `node_modules`
+pub type PackageResolver =
+    Arc<dyn Fn(&str, &Path) -> Result<PathBuf, String> + Send + Sync>;
+
+/// | ------------------------------- |
+/// |          PathResolver           |
+/// | ------------------------------- |
+/// | - packages: PackageResolver     |
+/// | ------------------------------- |
+///
+/// Resolves the relative, absolute and `file://` imports to the files of the
+/// project, probing the extensions and the `index` files of the directories.
+/// Bare specifiers are resolved by `packages`, the externals never reach it.
+#[derive(Clone)]
+pub(crate) struct PathResolver {
+    pub packages: Option<PackageResolver>,
+}
+
+impl PathResolver {
+    fn resolve_path(&self, base: &Path, specifier: &str) -> Result<PathBuf, String> {
+        let not_found = || format!("Module not found: '{}'", specifier);
+        if specifier.starts_with("file://") {
+            let path = Url::parse(specifier)
+                .ok()
+                .and_then(|url| url.to_file_path().ok())
+                .ok_or_else(not_found)?;
+            return probe(&path, &EXTENSIONS).ok_or_else(not_found);
+        }
+
+        if is_relative(specifier) || Path::new(specifier).is_absolute() {
+            let dir = base.parent().unwrap_or(Path::new(""));
+            let path = normalize(&dir.join(specifier));
+            return probe(&path, &EXTENSIONS).ok_or_else(not_found);
+        }
+
+        if specifier.starts_with("@kedo/") || specifier.starts_with("@kedo:") {
+            return Err(format!(
+                "Module not found: '{}' is not a module of the standard library",
+                specifier
+            ));
+        }
+
+        match &self.packages {
+            Some(packages) => packages(specifier, base),
+            None => Err(format!(
+                "Module not found: '{}', packages can't be resolved",
+                specifier
+            )),
+        }
+    }
+}
+
+impl Resolve for PathResolver {
+    fn resolve(
+        &self,
+        base: &FileName,
+        module_specifier: &str,
+    ) -> Result<Resolution, Error> {
+        let base = match base {
+            FileName::Real(base) => base,
+            _ => bail!("Cannot resolve '{}' from {}", module_specifier, base),
+        };
+
+        let path = self
+            .resolve_path(base, module_specifier)
+            .map_err(|message| anyhow!("{} imported from {}", message, base.display()))?;
+
+        Ok(Resolution {
+            filename: FileName::Real(path),
+            slug: None,
+        })
+    }
+}
+
+fn is_relative(specifier: &str) -> bool {
+    specifier == "."
+        || specifier == ".."
+        || specifier.starts_with("./")
+        || specifier.starts_with("../")
+}
+
+/// Finds the file of an import with the first of `extensions` that exists, e.g.
+/// `./util` is `./util.ts` or `./util/index.ts` and `./util.js` is `./util.ts`
+/// when only the TypeScript source exists.
+pub fn probe(path: &Path, extensions: &[&str]) -> Option<PathBuf> {
+    if path.is_file() {
+        return Some(path.to_path_buf());
+    }
+
+    let with_extension = |path: &Path| {
+        extensions.iter().find_map(|extension| {
+            let mut file = path.as_os_str().to_owned();
+            file.push(format!(".{}", extension));
+            let file = PathBuf::from(file);
+            file.is_file().then_some(file)
+        })
+    };
+
+    if let Some(file) = with_extension(path) {
+        return Some(file);
+    }
+
+    let javascript = matches!(
+        path.extension().and_then(|extension| extension.to_str()),
+        Some("js" | "mjs")
+    );
+    if javascript {
+        if let Some(file) = with_extension(&path.with_extension("")) {
+            return Some(file);
+        }
+    }
+
+    with_extension(&path.join("index"))
+}
+
+/// Removes the `.` and `..` of a path without touching the file system
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Path from the current directory, without `.` and `..`
+pub(crate) fn absolute(path: &Path) -> PathBuf {
+    match std::env::current_dir() {
+        Ok(cwd) => normalize(&cwd.join(path)),
+        Err(_) => normalize(path),
+    }
+}
+
+/// Path from the directory `dir` to `path`, both absolute,
+/// e.g. `/app/src/main.ts` from `/app/dist` is `../src/main.ts`
+pub(crate) fn relative_path(path: &Path, dir: &Path) -> String {
+    let path = path.components().collect::<Vec<_>>();
+    let dir = dir.components().collect::<Vec<_>>();
+    let common = path
+        .iter()
+        .zip(&dir)
+        .take_while(|(path, dir)| path == dir)
+        .count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..dir.len() {
+        relative.push(Component::ParentDir);
+    }
+    for component in &path[common..] {
+        relative.push(component);
+    }
+
+    relative.to_string_lossy().replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Empty `.ts`, `.tsx` and `.mjs` sources to probe, returned with the
+    /// canonical root of the directory
+    fn project() -> (TempDir, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        let root = std::fs::canonicalize(dir.path()).unwrap();
+        std::fs::create_dir_all(root.join("src/util")).unwrap();
+        for file in [
+            "src/main.ts",
+            "src/app.tsx",
+            "src/lib.mjs",
+            "src/util/index.ts",
+        ] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+        (dir, root)
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        let (_dir, root) = project();
+        let main = root.join("src/main.ts");
+        let resolver = PathResolver { packages: None };
+        let resolve = |specifier: &str| resolver.resolve_path(&main, specifier);
+
+        assert_eq!(resolve("./app"), Ok(root.join("src/app.tsx")));
+        assert_eq!(resolve("./lib"), Ok(root.join("src/lib.mjs")));
+        assert_eq!(resolve("./main.js"), Ok(main.clone()));
+        assert_eq!(resolve("./util"), Ok(root.join("src/util/index.ts")));
+        assert_eq!(resolve("../src/util/"), Ok(root.join("src/util/index.ts")));
+        assert_eq!(
+            resolve(Url::from_file_path(&main).unwrap().as_str()),
+            Ok(main.clone())
+        );
+        assert_eq!(
+            resolve("./missing"),
+            Err("Module not found: './missing'".to_string())
+        );
+        assert!(resolve("@kedo/missing")
+            .unwrap_err()
+            .contains("not a module of the standard library"));
+        assert!(resolve("lodash").is_err());
+    }
+
+    #[test]
+    fn test_resolve_packages() {
+        let (_dir, root) = project();
+        let resolver = PathResolver {
+            packages: Some(Arc::new(|specifier, base| {
+                let dir = base.parent().unwrap();
+                match specifier {
+                    "lodash" => Ok(dir.join("node_modules/lodash/index.js")),
+                    _ => Err(format!("Cannot find module '{}'", specifier)),
+                }
+            })),
+        };
+
+        let main = root.join("src/main.ts");
+        assert_eq!(
+            resolver.resolve_path(&main, "lodash"),
+            Ok(root.join("src/node_modules/lodash/index.js"))
+        );
+        assert_eq!(
+            resolver.resolve_path(&main, "react"),
+            Err("Cannot find module 'react'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_relative_path() {
+        let relative =
+            |path: &str, dir: &str| relative_path(Path::new(path), Path::new(dir));
+        assert_eq!(relative("/app/src/main.ts", "/app/dist"), "../src/main.ts");
+        assert_eq!(relative("/app/dist/lib.ts", "/app/dist"), "lib.ts");
+        assert_eq!(
+            relative("/app/src/util/index.ts", "/app/build/@std/dist"),
+            "../../../src/util/index.ts"
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use anyhow::Error;
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use swc_common::{
+    source_map::SourceMapGenConfig, sync::Lrc, BytePos, FileName, LineCol, SourceMap,
+};
+
+use crate::resolver::{absolute, relative_path};
+
+/// Where the source map of a bundle is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceMapMode {
+    /// A `.map` file next to the bundle, e.g. `dist/main.js.map`
+    External,
+    /// A base64 `data:` URL at the end of the bundle
+    Inline,
+}
+
+impl FromStr for SourceMapMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "external" => Ok(Self::External),
+            "inline" => Ok(Self::Inline),
+            _ => Err(format!(
+                "Invalid source map '{}', expected 'external' or 'inline'",
+                value
+            )),
+        }
+    }
+}
+
+/// The sources of the map are relative to the directory of the bundle and their
+/// content is embedded, so the map works without the original files.
+struct SourceMapConfig {
+    dir: PathBuf,
+}
+
+impl SourceMapGenConfig for SourceMapConfig {
+    fn file_name_to_source(&self, file: &FileName) -> String {
+        match file {
+            FileName::Real(path) => relative_path(&absolute(path), &self.dir),
+            _ => file.to_string(),
+        }
+    }
+
+    fn inline_sources_content(&self, _: &FileName) -> bool {
+        true
+    }
+}
+
+/// JSON of the source map of a bundle written to `output`
+pub(crate) fn build_source_map(
+    cm: &Lrc<SourceMap>,
+    mappings: &mut Vec<(BytePos, LineCol)>,
+    output: &Path,
+) -> Result<String, Error> {
+    let config = SourceMapConfig {
+        dir: absolute(output.parent().unwrap_or(Path::new(""))),
+    };
+
+    let mut json = vec![];
+    cm.build_source_map_with_config(mappings, None, config)
+        .to_writer(&mut json)?;
+    Ok(String::from_utf8(json)?)
+}
+
+/// Path of the `.map` file of a bundle, e.g. `dist/main.js.map`
+pub(crate) fn source_map_path(output: &Path) -> PathBuf {
+    let mut path = output.as_os_str().to_owned();
+    path.push(".map");
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_map_mode() {
+        assert_eq!("inline".parse(), Ok(SourceMapMode::Inline));
+        assert_eq!("external".parse(), Ok(SourceMapMode::External));
+        assert!("hidden".parse::<SourceMapMode>().is_err());
+        assert_eq!(
+            source_map_path(Path::new("dist/main.js")),
+            PathBuf::from("dist/main.js.map")
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use anyhow::Error;
+use swc_common::{sync::Lrc, FileName, SourceMap, Span, Spanned};
+use swc_ecma_ast::{
+    ArrowExpr, AwaitExpr, BindingIdent, Decl, EsVersion, Expr, ForOfStmt, Function,
+    ModuleItem, PropName, Stmt, VarDeclKind,
+};
+use swc_ecma_parser::{parse_file_as_module, EsSyntax, Syntax};
+use swc_ecma_visit::{Visit, VisitWith};
+
+use crate::syntax_error;
+
+/// Wraps a script with top level `await` in an async function, so it can be
+/// evaluated as a classic script. The top level declarations are moved out of the
+/// function to keep them in the global scope, e.g.
+///
+/// ```text
+/// const { data } = await load();
+/// data.length
+/// ```
+///
+/// is evaluated as
+///
+/// ```text
+/// let data;
+/// (async () => {
+/// ({ data } = await load());
+/// return (data.length);
+/// })()
+/// ```
+///
+/// Returns `None` when the script has no top level `await`.
+pub fn wrap_top_level_await(source: &str) -> Result<Option<String>, Error> {
+    let cm: Lrc<SourceMap> = Default::default();
+    let fm = cm.new_source_file(FileName::Anon, source.to_string());
+    let mut errors = vec![];
+    let module = parse_file_as_module(
+        &fm,
+        Syntax::Es(EsSyntax::default()),
+        EsVersion::Es2022,
+        None,
+        &mut errors,
+    )
+    .map_err(|err| syntax_error(&cm, err))?;
+
+    if let Some(err) = errors.into_iter().next() {
+        return Err(syntax_error(&cm, err));
+    }
+
+    let mut finder = AwaitFinder(false);
+    module.visit_with(&mut finder);
+    if !finder.0 {
+        return Ok(None);
+    }
+
+    let text = |span: Span| {
+        let lo = (span.lo - fm.start_pos).0 as usize;
+        let hi = (span.hi - fm.start_pos).0 as usize;
+        &source[lo..hi]
+    };
+
+    let mut declarations = vec![];
+    let mut body = vec![];
+    let last = module.body.len().saturating_sub(1);
+    for (index, item) in module.body.iter().enumerate() {
+        let stmt = match item {
+            ModuleItem::Stmt(stmt) => stmt,
+            ModuleItem::ModuleDecl(decl) => {
+                body.push(text(decl.span()).to_string());
+                continue;
+            }
+        };
+
+        match stmt {
+            Stmt::Decl(Decl::Var(var)) => {
+                let keyword = match var.kind {
+                    VarDeclKind::Var => "var",
+                    VarDeclKind::Let | VarDeclKind::Const => "let",
+                };
+                let mut names = BindingNames(vec![]);
+                for declarator in &var.decls {
+                    declarator.name.visit_with(&mut names);
+                    if let Some(init) = &declarator.init {
+                        body.push(format!(
+                            "({} = {});",
+                            text(declarator.name.span()),
+                            text(init.span())
+                        ));
+                    }
+                }
+                declarations.push(format!("{} {};", keyword, names.0.join(", ")));
+            }
+            Stmt::Decl(Decl::Fn(function)) => {
+                declarations.push(text(function.span()).to_string());
+            }
+            Stmt::Decl(Decl::Class(class)) => {
+                declarations.push(format!("let {};", class.ident.sym));
+                body.push(format!("{} = {};", class.ident.sym, text(class.span())));
+            }
+            // the value of the last expression is the value of the script
+            Stmt::Expr(expr) if index == last => {
+                body.push(format!("return ({});", text(expr.expr.span())));
+            }
+            stmt => body.push(text(stmt.span()).to_string()),
+        }
+    }
+
+    Ok(Some(format!(
+        "{}\n(async () => {{\n{}\n}})()",
+        declarations.join("\n"),
+        body.join("\n")
+    )))
+}
+
+/// Finds an `await` outside of the functions
+struct AwaitFinder(bool);
+
+impl Visit for AwaitFinder {
+    fn visit_await_expr(&mut self, _: &AwaitExpr) {
+        self.0 = true;
+    }
+
+    fn visit_for_of_stmt(&mut self, stmt: &ForOfStmt) {
+        self.0 |= stmt.is_await;
+        stmt.visit_children_with(self);
+    }
+
+    fn visit_function(&mut self, _: &Function) {}
+
+    fn visit_arrow_expr(&mut self, _: &ArrowExpr) {}
+}
+
+/// Names bound by a pattern, the default values and computed keys are skipped
+struct BindingNames(Vec<String>);
+
+impl Visit for BindingNames {
+    fn visit_binding_ident(&mut self, ident: &BindingIdent) {
+        self.0.push(ident.id.sym.to_string());
+    }
+
+    fn visit_expr(&mut self, _: &Expr) {}
+
+    fn visit_prop_name(&mut self, _: &PropName) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_top_level_await() {
+        assert_eq!(wrap_top_level_await("const x = 1;").unwrap(), None);
+        assert_eq!(
+            wrap_top_level_await("async function f() { await g(); }").unwrap(),
+            None
+        );
+
+        let script = wrap_top_level_await("const x = await f();\nx + 1").unwrap();
+        assert_eq!(
+            script.as_deref(),
+            Some("let x;\n(async () => {\n(x = await f());\nreturn (x + 1);\n})()")
+        );
+
+        let script = wrap_top_level_await(concat!(
+            "var { a, b: [c] } = await f(), d;\n",
+            "function g() { return a; }\n",
+            "class H {}\n",
+            "await g();\n",
+        ))
+        .unwrap()
+        .unwrap();
+        assert!(script.starts_with(concat!(
+            "var a, c, d;\n",
+            "function g() { return a; }\n",
+            "let H;\n",
+        )));
+        assert!(script.contains("({ a, b: [c] } = await f());\n"));
+        assert!(script.contains("H = class H {};\n"));
+        assert!(script.ends_with("return (await g());\n})()"));
+    }
+
+    #[test]
+    fn test_wrap_syntax_error() {
+        let error = wrap_top_level_await("const = await f();").unwrap_err();
+        assert!(error.to_string().starts_with("SyntaxError:"));
+    }
+}
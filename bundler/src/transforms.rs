@@ -0,0 +1,256 @@
+use std::path::Path;
+
+use swc_common::{
+    comments::SingleThreadedComments, sync::Lrc, Mark, SourceMap, Span, SyntaxContext,
+};
+use swc_ecma_ast::{Decorator, Module, Program};
+use swc_ecma_transforms_base::{
+    helpers::{inject_helpers, Helpers, HELPERS},
+    hygiene::hygiene,
+    resolver,
+};
+use swc_ecma_transforms_proposal::{decorator_2022_03::decorator_2022_03, decorators};
+use swc_ecma_transforms_react::{react, Options as ReactOptions, Runtime};
+use swc_ecma_transforms_typescript::strip;
+use swc_ecma_visit::{FoldWith, Visit, VisitMut, VisitWith};
+
+/// How the JSX elements are compiled
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum JsxRuntime {
+    /// Calls the functions of `<import_source>/jsx-runtime`, which are imported
+    /// by the module, e.g. `react/jsx-runtime`
+    Automatic { import_source: String },
+    /// Calls `pragma`, e.g. `React.createElement`, the module must import it
+    Classic { pragma: String, pragma_frag: String },
+}
+
+impl Default for JsxRuntime {
+    fn default() -> Self {
+        Self::Automatic {
+            import_source: "react".to_string(),
+        }
+    }
+}
+
+/// Semantics of the class decorators
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Decorators {
+    /// The `experimentalDecorators` of TypeScript
+    Legacy,
+    /// The decorators of the TC39 proposal, as TypeScript 5 compiles them
+    #[default]
+    Tc39,
+}
+
+/// | ------------------------------- |
+/// |        TransformOptions         |
+/// | ------------------------------- |
+/// | - jsx: JsxRuntime               |
+/// | - decorators: Decorators        |
+/// | ------------------------------- |
+///
+/// Compiles the JSX and the decorators of a module to plain JavaScript, the
+/// runtime supports neither of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct TransformOptions {
+    pub jsx: JsxRuntime,
+    pub decorators: Decorators,
+}
+
+impl TransformOptions {
+    fn react(&self) -> ReactOptions {
+        match &self.jsx {
+            JsxRuntime::Automatic { import_source } => ReactOptions {
+                runtime: Some(Runtime::Automatic),
+                import_source: Some(import_source.clone().into()),
+                ..Default::default()
+            },
+            JsxRuntime::Classic {
+                pragma,
+                pragma_frag,
+            } => ReactOptions {
+                runtime: Some(Runtime::Classic),
+                pragma: Some(pragma.clone().into()),
+                pragma_frag: Some(pragma_frag.clone().into()),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// True for the modules that [`transform_module`] changes, the JSX files and
+/// the modules with decorators
+pub(crate) fn needs_transform(path: &Path, module: &Module) -> bool {
+    let jsx = matches!(
+        path.extension().and_then(|extension| extension.to_str()),
+        Some("jsx" | "tsx")
+    );
+    if jsx {
+        return true;
+    }
+
+    let mut finder = DecoratorFinder(false);
+    module.visit_with(&mut finder);
+    finder.0
+}
+
+/// Compiles the decorators and the JSX of a module and strips its TypeScript.
+/// The React transform runs before the TypeScript one, so the import of a
+/// classic `pragma` isn't removed as unused. Needs the `GLOBALS` of the caller.
+pub(crate) fn transform_module(
+    module: Module,
+    cm: &Lrc<SourceMap>,
+    comments: Option<&SingleThreadedComments>,
+    options: &TransformOptions,
+) -> Module {
+    let unresolved_mark = Mark::new();
+    let top_level_mark = Mark::new();
+
+    HELPERS.set(&Helpers::new(false), || {
+        let program = Program::Module(module).fold_with(&mut resolver(
+            unresolved_mark,
+            top_level_mark,
+            true,
+        ));
+
+        let program = match options.decorators {
+            Decorators::Legacy => {
+                program.fold_with(&mut decorators(decorators::Config {
+                    legacy: true,
+                    ..Default::default()
+                }))
+            }
+            Decorators::Tc39 => program.fold_with(&mut decorator_2022_03()),
+        };
+
+        program
+            .fold_with(&mut react(
+                cm.clone(),
+                comments,
+                options.react(),
+                top_level_mark,
+                unresolved_mark,
+            ))
+            .fold_with(&mut strip(top_level_mark))
+            // the helpers of the decorators are declared in the module
+            .fold_with(&mut inject_helpers(unresolved_mark))
+            .fold_with(&mut hygiene())
+            .expect_module()
+    })
+}
+
+/// Removes the marks of the transforms from a module, the names are unique once
+/// [`hygiene`] ran and the bundler resolves the module again
+pub(crate) struct ClearContexts;
+
+impl VisitMut for ClearContexts {
+    fn visit_mut_span(&mut self, span: &mut Span) {
+        span.ctxt = SyntaxContext::empty();
+    }
+}
+
+struct DecoratorFinder(bool);
+
+impl Visit for DecoratorFinder {
+    fn visit_decorator(&mut self, _: &Decorator) {
+        self.0 = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use swc_common::{FileName, Globals, GLOBALS};
+    use swc_ecma_ast::EsVersion;
+    use swc_ecma_codegen::to_code_default;
+    use swc_ecma_parser::{parse_file_as_module, Syntax, TsSyntax};
+
+    fn compile(source: &str, options: &TransformOptions) -> String {
+        let cm: Lrc<SourceMap> = Default::default();
+        let fm = cm.new_source_file(FileName::Anon, source.to_string());
+        let syntax = Syntax::Typescript(TsSyntax {
+            tsx: true,
+            decorators: true,
+            ..Default::default()
+        });
+        let module =
+            parse_file_as_module(&fm, syntax, EsVersion::Es2022, None, &mut vec![])
+                .unwrap();
+
+        GLOBALS.set(&Globals::new(), || {
+            let module = transform_module(module, &cm, None, options);
+            to_code_default(cm.clone(), None, &module)
+        })
+    }
+
+    #[test]
+    fn test_jsx_runtime() {
+        let source =
+            "export const App = ({ name }: { name: string }) => <><h1>{name}</h1></>;";
+
+        let code = compile(source, &TransformOptions::default());
+        assert!(code.contains("from \"react/jsx-runtime\""));
+        assert!(!code.contains("<h1>"));
+        assert!(!code.contains(": string"));
+
+        let code = compile(
+            &format!("import {{ h, Fragment }} from 'preact';\n{}", source),
+            &TransformOptions {
+                jsx: JsxRuntime::Classic {
+                    pragma: "h".to_string(),
+                    pragma_frag: "Fragment".to_string(),
+                },
+                ..Default::default()
+            },
+        );
+        // the pragma import is used by the compiled elements
+        assert!(code.contains("import { h, Fragment } from 'preact'"));
+        assert!(code.contains("h(Fragment, null, h(\"h1\", null, name))"));
+    }
+
+    #[test]
+    fn test_decorators() {
+        let source = concat!(
+            "function route(path: string) {\n",
+            "    return (target: any, context?: any) => target;\n",
+            "}\n",
+            "@route('/') export class Home {}\n",
+        );
+
+        let legacy = compile(
+            source,
+            &TransformOptions {
+                decorators: Decorators::Legacy,
+                ..Default::default()
+            },
+        );
+        assert!(!legacy.contains("@route"));
+        assert!(legacy.contains("_ts_decorate"));
+
+        let tc39 = compile(source, &TransformOptions::default());
+        assert!(!tc39.contains("@route"));
+        assert!(tc39.contains("_apply_decs_2203_r"));
+    }
+
+    #[test]
+    fn test_needs_transform() {
+        let module = |source: &str| {
+            let cm: Lrc<SourceMap> = Default::default();
+            let fm = cm.new_source_file(FileName::Anon, source.to_string());
+            let syntax = Syntax::Typescript(TsSyntax {
+                decorators: true,
+                ..Default::default()
+            });
+            parse_file_as_module(&fm, syntax, EsVersion::Es2022, None, &mut vec![])
+                .unwrap()
+        };
+
+        let plain = module("export class Home {}");
+        assert!(!needs_transform(Path::new("home.ts"), &plain));
+        assert!(needs_transform(Path::new("home.tsx"), &plain));
+        assert!(needs_transform(
+            Path::new("home.ts"),
+            &module("@sealed export class Home {}")
+        ));
+    }
+}
@@ -0,0 +1,197 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use bundler::{Decorators, JsxRuntime, TransformOptions};
+use kedo_runtime::{ImportMap, KedoError, KedoResult};
+use serde_json::Value;
+use url::Url;
+
+/// Project configuration, looked up in the working directory and its parents
+const CONFIG_FILE: &str = "kedo.json";
+/// Hashes of the remote modules, next to the project configuration
+const LOCK_FILE: &str = "kedo.lock";
+
+/// | ------------------------------- |
+/// |          ProjectConfig          |
+/// | ------------------------------- |
+/// | - path: PathBuf                 |
+/// | - value: Value                  |
+/// | ------------------------------- |
+///
+/// The `kedo.json` of the project, the import map can be inlined or a path
+/// relative to the config file. The JSX and decorators are configured with the
+/// `compilerOptions` of `tsconfig.json`.
+///
+/// e.g.
+/// ```json
+/// { "importMap": "./import_map.json" }
+/// { "imports": { "lodash": "./vendor/lodash.js" } }
+/// { "compilerOptions": { "jsx": "react", "jsxFactory": "h" } }
+/// ```
+pub struct ProjectConfig {
+    path: PathBuf,
+    value: Value,
+}
+
+impl ProjectConfig {
+    /// Finds the closest `kedo.json`, returns `None` outside of a project
+    pub fn find() -> KedoResult<Option<Self>> {
+        let path = match config_file() {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        let source = std::fs::read_to_string(&path)?;
+        let value = serde_json::from_str(&source).map_err(|err| {
+            invalid_data(format!("Invalid {}: {}", path.display(), err))
+        })?;
+
+        Ok(Some(Self { path, value }))
+    }
+
+    pub fn import_map(&self) -> KedoResult<Option<ImportMap>> {
+        let dir = self.path.parent().unwrap_or(Path::new("."));
+        if let Some(import_map) = self.value.get("importMap") {
+            return match import_map.as_str() {
+                Some(path) => load_import_map(&dir.join(path)).map(Some),
+                None => Err(invalid_data(format!(
+                    "\"importMap\" of {} must be a path",
+                    self.path.display()
+                ))),
+            };
+        }
+
+        let mut import_map = serde_json::Map::new();
+        for key in ["imports", "scopes"] {
+            if let Some(value) = self.value.get(key) {
+                import_map.insert(key.to_string(), value.clone());
+            }
+        }
+
+        if import_map.is_empty() {
+            return Ok(None);
+        }
+
+        let path = std::fs::canonicalize(&self.path)?;
+        let base = Url::from_file_path(&path)
+            .map_err(|_| invalid_data(format!("Invalid path {}", path.display())))?;
+        let import_map = ImportMap::parse(&Value::Object(import_map).to_string(), base)
+            .map_err(|err| invalid_data(err.to_string()))?;
+
+        print_warnings(&import_map);
+        Ok(Some(import_map))
+    }
+
+    /// `jsx` is `react-jsx` (the default) for the automatic runtime of
+    /// `jsxImportSource`, or `react` for the classic one of `jsxFactory`
+    pub fn transform_options(&self) -> KedoResult<TransformOptions> {
+        let Some(options) = self.value.get("compilerOptions") else {
+            return Ok(TransformOptions::default());
+        };
+
+        let string = |key: &str, default: &str| match options.get(key) {
+            None => Ok(default.to_string()),
+            Some(Value::String(value)) => Ok(value.clone()),
+            Some(_) => Err(invalid_data(format!(
+                "\"compilerOptions.{}\" of {} must be a string",
+                key,
+                self.path.display()
+            ))),
+        };
+
+        let jsx = match string("jsx", "react-jsx")?.as_str() {
+            "react-jsx" => JsxRuntime::Automatic {
+                import_source: string("jsxImportSource", "react")?,
+            },
+            "react" => JsxRuntime::Classic {
+                pragma: string("jsxFactory", "React.createElement")?,
+                pragma_frag: string("jsxFragmentFactory", "React.Fragment")?,
+            },
+            jsx => return Err(invalid_data(format!(
+                "\"compilerOptions.jsx\" of {} must be \"react-jsx\" or \"react\": {}",
+                self.path.display(),
+                jsx
+            ))),
+        };
+
+        let decorators = match options.get("experimentalDecorators") {
+            Some(Value::Bool(true)) => Decorators::Legacy,
+            _ => Decorators::Tc39,
+        };
+
+        Ok(TransformOptions { jsx, decorators })
+    }
+}
+
+/// Path of the closest `kedo.json`, even if it's invalid
+pub fn config_file() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    cwd.ancestors()
+        .map(|dir| dir.join(CONFIG_FILE))
+        .find(|path| path.is_file())
+}
+
+/// Directory of the caches, `$KEDO_DIR` or the user cache directory
+pub fn cache_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("KEDO_DIR") {
+        return PathBuf::from(dir);
+    }
+
+    let cache = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+        .unwrap_or_else(std::env::temp_dir);
+    cache.join("kedo")
+}
+
+/// The `kedo.lock` of the project, in the working directory outside of a project
+pub fn lock_file() -> KedoResult<PathBuf> {
+    let dir = match ProjectConfig::find()? {
+        Some(config) => config.path.parent().map(Path::to_path_buf),
+        None => None,
+    };
+
+    match dir {
+        Some(dir) => Ok(dir.join(LOCK_FILE)),
+        None => Ok(std::env::current_dir()?.join(LOCK_FILE)),
+    }
+}
+
+/// The JSX and decorators options of the project config, the defaults outside of one
+pub fn transform_options() -> KedoResult<TransformOptions> {
+    match ProjectConfig::find()? {
+        Some(config) => config.transform_options(),
+        None => Ok(TransformOptions::default()),
+    }
+}
+
+/// The import map given with `--import-map` takes precedence over the project config
+pub fn import_map(path: Option<&Path>) -> KedoResult<Option<ImportMap>> {
+    if let Some(path) = path {
+        return load_import_map(path).map(Some);
+    }
+
+    match ProjectConfig::find()? {
+        Some(config) => config.import_map(),
+        None => Ok(None),
+    }
+}
+
+fn load_import_map(path: &Path) -> KedoResult<ImportMap> {
+    let import_map =
+        ImportMap::from_file(path).map_err(|err| invalid_data(err.to_string()))?;
+    print_warnings(&import_map);
+    Ok(import_map)
+}
+
+fn print_warnings(import_map: &ImportMap) {
+    for warning in import_map.warnings() {
+        eprintln!("Warning: {}", warning);
+    }
+}
+
+fn invalid_data(message: String) -> KedoError {
+    KedoError::from((ErrorKind::InvalidData, message))
+}
@@ -0,0 +1,346 @@
+use std::path::{Path, PathBuf};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bundler::{probe, TransformOptions, EXTENSIONS};
+use kedo_runtime::{ModuleError, ModuleLoader};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+/// Modules transpiled before they are evaluated
+const TYPESCRIPT_EXTENSIONS: [&str; 4] = ["ts", "mts", "tsx", "jsx"];
+/// Extensions probed, in order, for the `require()` calls without one
+const REQUIRE_EXTENSIONS: [&str; 3] = ["js", "cjs", "json"];
+/// Changes every cache key, bump it when the transpiler output changes
+const CACHE_VERSION: &str = "2";
+
+/// | ------------------------------- |
+/// |        FileModuleLoader         |
+/// | ------------------------------- |
+/// | - transpiler: Transpiler        |
+/// | ------------------------------- |
+///
+/// Loads the modules of the file system in place of the builtin loader,
+/// relative specifiers are resolved from the importer. TypeScript and JSX modules
+/// are transpiled on fetch.
+pub struct FileModuleLoader {
+    transpiler: Transpiler,
+}
+
+impl FileModuleLoader {
+    pub fn new(cache_dir: Option<PathBuf>) -> Self {
+        Self {
+            transpiler: Transpiler {
+                cache_dir,
+                options: TransformOptions::default(),
+            },
+        }
+    }
+
+    /// Compiles the JSX and the decorators of the modules with `options`
+    pub fn transform(mut self, options: TransformOptions) -> Self {
+        self.transpiler.options = options;
+        self
+    }
+
+    /// Transpiler of the modules, the other loaders share its cache and options
+    pub fn transpiler(&self) -> &Transpiler {
+        &self.transpiler
+    }
+
+    fn resolve_with(
+        &self,
+        module_id: &str,
+        referrer: Option<&str>,
+        extensions: &[&str],
+    ) -> Result<String, ModuleError> {
+        let not_found = || ModuleError::NotFound(module_id.to_string());
+        // the entry module has no referrer and can be any path
+        if referrer.is_some() && !self.can_handle(module_id) {
+            return Err(not_found());
+        }
+
+        // a remote module can't read the files of the machine it runs on
+        if referrer.is_some_and(is_remote) {
+            return Err(not_found());
+        }
+
+        let cwd = std::env::current_dir().map_err(|_| not_found())?;
+        let dir = referrer
+            .filter(|referrer| Path::new(referrer).is_absolute())
+            .and_then(|referrer| Path::new(referrer).parent())
+            .unwrap_or(&cwd);
+
+        let path = match module_id.starts_with("file://") {
+            true => Url::parse(module_id)
+                .ok()
+                .and_then(|url| url.to_file_path().ok())
+                .ok_or_else(not_found)?,
+            false => dir.join(module_id),
+        };
+        let path = probe(&path, extensions).ok_or_else(not_found)?;
+        let path = std::fs::canonicalize(path).map_err(|_| not_found())?;
+        Ok(path.to_string_lossy().to_string())
+    }
+}
+
+/// | ------------------------------- |
+/// |           Transpiler            |
+/// | ------------------------------- |
+/// | - cache_dir: Option<PathBuf>    |
+/// | - options: TransformOptions     |
+/// | ------------------------------- |
+///
+/// Transpiles the TypeScript and JSX modules, the output is cached in `cache_dir`
+/// by content hash and carries an inline source map to the TypeScript positions.
+#[derive(Debug, Clone)]
+pub struct Transpiler {
+    cache_dir: Option<PathBuf>,
+    options: TransformOptions,
+}
+
+impl Transpiler {
+    /// `path` is the file name of the source map
+    pub fn transpile(&self, path: &Path, source: &str) -> Result<String, ModuleError> {
+        let cached = self.cache_dir.as_ref().map(|dir| {
+            dir.join(format!("{}.js", cache_key(path, source, &self.options)))
+        });
+
+        if let Some(code) = cached
+            .as_ref()
+            .and_then(|file| std::fs::read_to_string(file).ok())
+        {
+            return Ok(code);
+        }
+
+        let transpiled = bundler::transpile(source, path, &self.options)
+            .map_err(|err| ModuleError::InvalidModule(err.to_string()))?;
+        let code = format!(
+            "{}\n//# sourceMappingURL=data:application/json;base64,{}\n",
+            transpiled.code,
+            STANDARD.encode(transpiled.source_map)
+        );
+
+        // the cache is an optimization, a failed write only costs a transpilation
+        if let Some(file) = cached {
+            let _ = std::fs::create_dir_all(file.parent().unwrap_or(Path::new(".")))
+                .and_then(|_| std::fs::write(&file, &code));
+        }
+
+        Ok(code)
+    }
+}
+
+impl ModuleLoader for FileModuleLoader {
+    fn can_handle(&self, module_id: &str) -> bool {
+        module_id.starts_with("./")
+            || module_id.starts_with("../")
+            || Path::new(module_id).is_absolute()
+            || module_id.starts_with("file://")
+    }
+
+    fn resolve(&self, module_id: &str) -> Result<String, ModuleError> {
+        self.resolve_from(module_id, None)
+    }
+
+    fn resolve_from(
+        &self,
+        module_id: &str,
+        referrer: Option<&str>,
+    ) -> Result<String, ModuleError> {
+        self.resolve_with(module_id, referrer, &EXTENSIONS)
+    }
+
+    fn resolve_require(
+        &self,
+        module_id: &str,
+        referrer: Option<&str>,
+    ) -> Result<String, ModuleError> {
+        self.resolve_with(module_id, referrer, &REQUIRE_EXTENSIONS)
+    }
+
+    fn load(&self, module_id: &str) -> Result<String, ModuleError> {
+        let path = Path::new(module_id);
+        let source = std::fs::read_to_string(path)
+            .map_err(|_| ModuleError::LoadError(module_id.to_string()))?;
+
+        match is_typescript(path) {
+            true => self.transpiler.transpile(path, &source),
+            false => Ok(source),
+        }
+    }
+
+    fn load_bytes(&self, module_id: &str) -> Result<Vec<u8>, ModuleError> {
+        std::fs::read(module_id)
+            .map_err(|_| ModuleError::LoadError(module_id.to_string()))
+    }
+}
+
+/// The module was imported with an `http://` or `https://` URL
+pub fn is_remote(module_id: &str) -> bool {
+    module_id.starts_with("https://") || module_id.starts_with("http://")
+}
+
+pub fn is_typescript(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| TYPESCRIPT_EXTENSIONS.contains(&extension))
+}
+
+/// SHA-256 of everything the transpiled output depends on, the fields are
+/// separated by a NUL byte
+fn cache_key(path: &Path, source: &str, options: &TransformOptions) -> String {
+    let path = path.to_string_lossy();
+    let options = format!("{:?}", options);
+    let mut hasher = Sha256::new();
+    for field in [CACHE_VERSION, path.as_ref(), source, options.as_str()] {
+        hasher.update(field.as_bytes());
+        hasher.update([0u8]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// An empty `src/util` tree, the tests write the modules they load.
+    /// `root` is canonical so it prefixes the resolved paths.
+    fn project() -> (TempDir, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("src/util")).unwrap();
+        let root = std::fs::canonicalize(dir.path()).unwrap();
+        (dir, root)
+    }
+
+    #[test]
+    fn test_resolve_probing() {
+        let (_dir, root) = project();
+        std::fs::write(root.join("src/main.ts"), "").unwrap();
+        std::fs::write(root.join("src/math.ts"), "").unwrap();
+        std::fs::write(root.join("src/util/index.ts"), "").unwrap();
+
+        let loader = FileModuleLoader::new(None);
+        let main = root.join("src/main.ts");
+        let resolve = |specifier| {
+            let path = loader.resolve_from(specifier, main.to_str()).unwrap();
+            PathBuf::from(path).strip_prefix(&root).unwrap().to_owned()
+        };
+
+        assert_eq!(resolve("./math"), Path::new("src/math.ts"));
+        assert_eq!(resolve("./math.js"), Path::new("src/math.ts"));
+        assert_eq!(resolve("./util"), Path::new("src/util/index.ts"));
+        assert_eq!(resolve("../src/main.ts"), Path::new("src/main.ts"));
+        assert!(loader.resolve_from("./missing", main.to_str()).is_err());
+        assert!(!loader.can_handle("@kedo/fs"));
+
+        // file URLs are decoded, e.g. the spaces of a directory
+        std::fs::create_dir_all(root.join("my app")).unwrap();
+        std::fs::write(root.join("my app/main.ts"), "").unwrap();
+        let url = Url::from_file_path(root.join("my app/main")).unwrap();
+        assert!(url.as_str().contains("my%20app"));
+        assert_eq!(resolve(url.as_str()), Path::new("my app/main.ts"));
+
+        // the remote modules can't import files
+        let remote = Some("https://example.com/mod.ts");
+        assert!(loader.resolve_from(main.to_str().unwrap(), remote).is_err());
+        let file_url = format!("file://{}", main.display());
+        assert!(loader.resolve_from(&file_url, remote).is_err());
+        assert!(loader.resolve_require("./main.ts", remote).is_err());
+    }
+
+    #[test]
+    fn test_resolve_require() {
+        let (_dir, root) = project();
+        std::fs::write(root.join("src/main.js"), "").unwrap();
+        std::fs::write(root.join("src/lib.cjs"), "").unwrap();
+        std::fs::write(root.join("src/data.json"), "{}").unwrap();
+        std::fs::write(root.join("src/util/index.json"), "{}").unwrap();
+        std::fs::write(root.join("src/types.ts"), "").unwrap();
+
+        let loader = FileModuleLoader::new(None);
+        let main = root.join("src/main.js");
+        let require = |specifier| {
+            let path = loader.resolve_require(specifier, main.to_str()).ok()?;
+            Some(PathBuf::from(path).strip_prefix(&root).unwrap().to_owned())
+        };
+
+        assert_eq!(require("./lib").as_deref(), Some(Path::new("src/lib.cjs")));
+        assert_eq!(
+            require("./data").as_deref(),
+            Some(Path::new("src/data.json"))
+        );
+        assert_eq!(
+            require("./util").as_deref(),
+            Some(Path::new("src/util/index.json"))
+        );
+        assert_eq!(require("./types"), None);
+    }
+
+    #[test]
+    fn test_transpile_cache() {
+        let (_dir, root) = project();
+        let main = root.join("src/main.ts");
+        std::fs::write(
+            &main,
+            "const answer: number = 42;\nexport default answer;\n",
+        )
+        .unwrap();
+
+        let loader = FileModuleLoader::new(Some(root.join("cache")));
+        let code = loader.load(main.to_str().unwrap()).unwrap();
+        assert!(code.contains("const answer = 42;"));
+        assert!(code.contains("sourceMappingURL=data:application/json;base64,"));
+        assert_eq!(std::fs::read_dir(root.join("cache")).unwrap().count(), 1);
+
+        // the same content is served from the cache
+        assert_eq!(loader.load(main.to_str().unwrap()).unwrap(), code);
+        assert_eq!(std::fs::read_dir(root.join("cache")).unwrap().count(), 1);
+
+        std::fs::write(&main, "const answer: = 42;").unwrap();
+        let error = loader.load(main.to_str().unwrap()).unwrap_err();
+        assert!(matches!(error, ModuleError::InvalidModule(_)));
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let path = Path::new("/app/main.ts");
+        let options = TransformOptions::default();
+        let key = cache_key(path, "export {};", &options);
+        assert_eq!(key.len(), 64);
+        assert_eq!(key, cache_key(path, "export {};", &options));
+
+        assert_ne!(key, cache_key(path, "export {}; ", &options));
+        assert_ne!(
+            key,
+            cache_key(Path::new("/app/util.ts"), "export {};", &options)
+        );
+        let legacy = TransformOptions {
+            decorators: bundler::Decorators::Legacy,
+            ..Default::default()
+        };
+        assert_ne!(key, cache_key(path, "export {};", &legacy));
+    }
+
+    #[test]
+    fn test_transpile_jsx() {
+        let (_dir, root) = project();
+        let app = root.join("src/app.jsx");
+        std::fs::write(&app, "export const App = () => <h1>Hello</h1>;\n").unwrap();
+
+        let loader = FileModuleLoader::new(None);
+        let code = loader.load(app.to_str().unwrap()).unwrap();
+        assert!(code.contains("from \"react/jsx-runtime\""));
+
+        let loader = FileModuleLoader::new(None).transform(TransformOptions {
+            jsx: bundler::JsxRuntime::Classic {
+                pragma: "h".to_string(),
+                pragma_frag: "Fragment".to_string(),
+            },
+            ..Default::default()
+        });
+        let code = loader.load(app.to_str().unwrap()).unwrap();
+        assert!(code.contains("h(\"h1\", null, \"Hello\")"));
+        assert!(!code.contains("react/jsx-runtime"));
+    }
+}
@@ -1,7 +1,26 @@
-use clap::{Parser, Subcommand};
-use kedo_runtime::runtime::Runtime;
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
 
+use bundler::{BundleArgs, SourceMapMode, TransformOptions};
+use clap::{Args, Parser, Subcommand};
+use file_loader::FileModuleLoader;
+use kedo_runtime::{
+    runtime::Runtime, ImportMap, KedoResult, ModuleLoader, NodeModulesLoader,
+    RuntimeBuilder,
+};
+use permissions::PermissionFlags;
+use remote_loader::{RemoteFlags, RemoteModuleLoader};
+
+mod config;
+mod file_loader;
+mod permissions;
+mod remote_loader;
+mod repl;
 mod std_loader;
+mod test_runner;
+mod watcher;
 
 const STD_INDEX: &str = include_str!("../build/@std/dist/index.js");
 
@@ -32,22 +51,95 @@ enum Commands {
         #[arg(short, long)]
         strict: bool,
 
+        /// Report the resources left open once the event loop finishes
+        #[arg(long)]
+        check_leaks: bool,
+
+        /// Restart the script when one of its modules changes
+        #[arg(long)]
+        watch: bool,
+
+        /// Extra files watched in watch mode, e.g. `--watch-path="data/**/*.json"`
+        #[arg(long, require_equals = true, value_name = "GLOB", requires = "watch")]
+        watch_path: Vec<String>,
+
+        /// Clear the terminal before every restart in watch mode
+        #[arg(long, requires = "watch")]
+        clear_screen: bool,
+
+        /// Import map applied to the specifiers, overrides the one of `kedo.json`
+        #[arg(long, require_equals = true, value_name = "FILE")]
+        import_map: Option<PathBuf>,
+
+        #[command(flatten)]
+        permissions: PermissionFlags,
+
+        #[command(flatten)]
+        remote: RemoteFlags,
+
         /// Path to the script
         file: String,
     },
-    /// Build a project
-    Bundle {
-        /// Path to the project
-        #[arg(short, long)]
-        output: String,
+    /// Start an interactive read-eval-print loop
+    Repl {
+        #[command(flatten)]
+        permissions: PermissionFlags,
+    },
+    /// Run the `*.test.{js,ts}` files registered with `Kedo.test`
+    Test(test_runner::TestArgs),
+    /// Bundle scripts and their imports into ES modules
+    Bundle(BundleFlags),
+}
 
-        #[arg(short, long)]
-        entry: String,
+#[derive(Args)]
+struct BundleFlags {
+    /// Paths of the entry scripts
+    #[arg(short, long, required = true, num_args = 1..)]
+    entry: Vec<String>,
 
-        /// Minify the output
-        #[arg(short, long)]
-        minify: bool,
-    },
+    /// Path of the bundled file of a single entry
+    #[arg(
+        short,
+        long,
+        conflicts_with = "outdir",
+        required_unless_present = "outdir"
+    )]
+    output: Option<String>,
+
+    /// Directory of the bundled files, named after their entries, e.g. `dist/main.js`
+    #[arg(long, value_name = "DIR")]
+    outdir: Option<String>,
+
+    /// Compress and mangle the output
+    #[arg(short, long)]
+    minify: bool,
+
+    /// Keep the names of the classes when minifying
+    #[arg(long, requires = "minify")]
+    keep_classnames: bool,
+
+    /// Keep the names of the functions when minifying
+    #[arg(long, requires = "minify")]
+    keep_fnames: bool,
+
+    /// Write a source map, `external` to a `.map` file next to the output
+    /// or `inline` at the end of the output
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        value_name = "KIND",
+        default_missing_value = "external"
+    )]
+    sourcemap: Option<SourceMapMode>,
+
+    /// Remove the code that isn't used by the entries
+    #[arg(long)]
+    tree_shaking: bool,
+
+    /// Move the files imported by more than one entry to shared chunks
+    #[arg(long)]
+    splitting: bool,
 }
 
 fn create_tokio_runtime() -> tokio::runtime::Runtime {
@@ -62,62 +154,326 @@ fn create_tokio_runtime() -> tokio::runtime::Runtime {
         .unwrap()
 }
 
+/// Builder of the main runtime and of every worker, workers inherit the permissions,
+/// the import map, the remote flags and the transforms and can spawn their own workers.
+fn runtime_builder(
+    permissions: PermissionFlags,
+    import_map: Option<ImportMap>,
+    remote: RemoteFlags,
+    lock_file: PathBuf,
+    transform: TransformOptions,
+) -> RuntimeBuilder {
+    let worker_permissions = permissions.clone();
+    let worker_import_map = import_map.clone();
+    let worker_remote = remote.clone();
+    let worker_lock_file = lock_file.clone();
+    let worker_transform = transform.clone();
+    let file_loader = FileModuleLoader::new(Some(config::cache_dir().join("transpiled")))
+        .transform(transform);
+    let remote_loader = RemoteModuleLoader::new(
+        config::cache_dir().join("remote"),
+        file_loader.transpiler().clone(),
+    )
+    .flags(&remote)
+    .lockfile(lock_file)
+    .permissions(permissions.to_permissions());
+
+    let builder = RuntimeBuilder::new()
+        .with_loader(std_loader::StdModuleLoader::default())
+        .with_loader(remote_loader)
+        .with_loader(NodeModulesLoader::default())
+        .with_file_system_loader(file_loader)
+        .permissions(permissions.to_permissions())
+        .std_bundle(STD_INDEX, "src/@std/index.js")
+        .workers(move || {
+            runtime_builder(
+                worker_permissions.clone(),
+                worker_import_map.clone(),
+                worker_remote.clone(),
+                worker_lock_file.clone(),
+                worker_transform.clone(),
+            )
+        });
+
+    match import_map {
+        Some(import_map) => builder.import_map(import_map),
+        None => builder,
+    }
+}
+
+/// Creates a runtime with the standard library loaded, the import map defaults
+/// to the one of the project config and the transforms are the ones of the project
+fn create_runtime(
+    permissions: &PermissionFlags,
+    import_map: Option<&Path>,
+    remote: &RemoteFlags,
+) -> KedoResult<Runtime> {
+    let import_map = config::import_map(import_map)?;
+    let lock_file = config::lock_file()?;
+    let transform = config::transform_options()?;
+    runtime_builder(
+        permissions.clone(),
+        import_map,
+        remote.clone(),
+        lock_file,
+        transform,
+    )
+    .build()
+}
+
+/// Runs the script until one of its modules or the files of `--watch-path`
+/// change, then shuts the runtime down and runs it again. Scripts that fail,
+/// even before they start, or finish wait for the next change.
+async fn run_watch(
+    file: &str,
+    check_leaks: bool,
+    watch_paths: &[String],
+    clear_screen: bool,
+    create: impl Fn() -> KedoResult<Runtime>,
+) {
+    let mut file_watcher = match watcher::FileWatcher::new(watch_paths) {
+        Ok(file_watcher) => file_watcher,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    // watched even if it can't be loaded, e.g. until it's created
+    let entry = std::env::current_dir().unwrap_or_default().join(file);
+
+    loop {
+        if clear_screen {
+            watcher::clear_screen();
+        }
+
+        let mut runtime = match create() {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                // e.g. an invalid `kedo.json`, the next change may fix it
+                eprintln!("Error: {}", e);
+                file_watcher
+                    .watch(config::config_file().into_iter().chain([entry.clone()]));
+                let changed = tokio::select! {
+                    changed = file_watcher.changed() => changed,
+                    _ = tokio::signal::ctrl_c() => return,
+                };
+                print_changed(&changed);
+                continue;
+            }
+        };
+
+        let result = runtime.evaluate_module(file);
+        let watch_files = |runtime: &Runtime| {
+            let mut files = runtime.state().module_loader().borrow().loaded_files();
+            files.push(entry.clone());
+            files
+        };
+        file_watcher.watch(watch_files(&runtime));
+
+        let mut finished = result.is_err();
+        if let Err(e) = result {
+            eprintln!("{}", runtime.format_error(e));
+        }
+
+        let changed = loop {
+            if finished {
+                eprintln!("Watcher: process finished, restarting on changes...");
+                tokio::select! {
+                    changed = file_watcher.changed() => break changed,
+                    _ = tokio::signal::ctrl_c() => return,
+                }
+            }
+
+            tokio::select! {
+                changed = file_watcher.changed() => break changed,
+                _ = runtime.idle() => finished = true,
+                _ = tokio::signal::ctrl_c() => return,
+            }
+
+            // modules imported dynamically while the script ran
+            file_watcher.watch(watch_files(&runtime));
+            if check_leaks {
+                report_leaks(&runtime);
+            }
+        };
+
+        print_changed(&changed);
+        runtime.shutdown();
+        // the resources of the old runtime are released before the new one starts
+        drop(runtime);
+    }
+}
+
+fn print_changed(changed: &[PathBuf]) {
+    let changed = changed
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>();
+    eprintln!("Watcher: {} changed, restarting...", changed.join(", "));
+}
+
+/// Bundles the entries with the files they import, the packages are resolved from
+/// `node_modules` like `kedo run` does and the `@kedo/*` modules are kept as imports.
+/// The JSX and the decorators are compiled with the options of the project config.
+fn bundle(flags: &BundleFlags) -> Result<(), String> {
+    let mut entries = Vec::new();
+    let mut outputs = Vec::<PathBuf>::new();
+    for entry in &flags.entry {
+        let path = std::fs::canonicalize(entry)
+            .map_err(|_| format!("Module not found: '{}'", entry))?;
+        let output = match (&flags.output, &flags.outdir) {
+            (Some(_), _) if flags.entry.len() > 1 => {
+                return Err("--output bundles a single entry, use --outdir".to_string())
+            }
+            (Some(output), _) => PathBuf::from(output),
+            (None, outdir) => {
+                let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                Path::new(outdir.as_deref().unwrap_or(".")).join(format!("{}.js", name))
+            }
+        };
+
+        if outputs.contains(&output) {
+            return Err(format!(
+                "The entries write the same file {}",
+                output.display()
+            ));
+        }
+        entries.push((output.display().to_string(), path));
+        outputs.push(output);
+    }
+
+    // one loader for the whole bundle, the package.json files are read once
+    let node_modules = Mutex::new(NodeModulesLoader::default());
+    let packages: bundler::PackageResolver = Arc::new(move |specifier, referrer| {
+        let referrer = referrer.to_string_lossy();
+        node_modules
+            .lock()
+            .unwrap()
+            .resolve_from(specifier, Some(&referrer))
+            .map(PathBuf::from)
+            .map_err(|error| error.to_string())
+    });
+
+    let args = BundleArgs {
+        external_modules: std_loader::StdModuleLoader::default().modules(),
+        entries,
+        outputs,
+        minify: flags.minify,
+        keep_classnames: flags.keep_classnames,
+        keep_fnames: flags.keep_fnames,
+        packages: Some(packages),
+        source_map: flags.sourcemap,
+        tree_shaking: flags.tree_shaking,
+        splitting: flags.splitting,
+        transform: config::transform_options().map_err(|error| error.to_string())?,
+    };
+
+    let result = bundler::bundle(args).map_err(|error| format!("{:#}", error))?;
+    for output in &result.outputs {
+        println!("Created {}", output);
+        if let Some(source_map) = &output.source_map {
+            println!("Created {}", source_map.display());
+        }
+    }
+    println!("Bundled in {}ms", result.duration.as_millis());
+    Ok(())
+}
+
+/// Prints the resources still open, returns true if there are any
+fn report_leaks(runtime: &Runtime) -> bool {
+    let leaks = runtime.state().resources().borrow().entries();
+    if leaks.is_empty() {
+        return false;
+    }
+
+    eprintln!("Error: {} resources were not closed", leaks.len());
+    for leak in leaks {
+        eprintln!("  - {} (rid {})", leak.kind, leak.rid);
+    }
+    true
+}
+
 fn main() {
     let cli = Cli::parse();
 
     match &cli.command {
-        Some(Commands::Run { strict, file }) => {
+        Some(Commands::Run {
+            strict,
+            check_leaks,
+            watch,
+            watch_path,
+            clear_screen,
+            import_map,
+            permissions,
+            remote,
+            file,
+        }) => {
             if *strict {
                 println!("Strict mode enabled");
             }
 
-            let mut runtime = Runtime::new();
-            runtime.add_loader(std_loader::StdModuleLoader::default());
-            // Load the standard library
-            let result =
-                runtime.evaluate_module_from_source(STD_INDEX, "src/@std/index.js", None);
-            // let result = runtime.evaluate_module("./build/@std/dist/index.js");
-            match result {
-                Ok(_) => {}
+            if *watch {
+                let create =
+                    || create_runtime(permissions, import_map.as_deref(), remote);
+                create_tokio_runtime().block_on(run_watch(
+                    file,
+                    *check_leaks,
+                    watch_path,
+                    *clear_screen,
+                    create,
+                ));
+                return;
+            }
+
+            let result = create_runtime(permissions, import_map.as_deref(), remote);
+            let mut runtime = match result {
+                Ok(runtime) => runtime,
                 Err(e) => {
-                    println!("Error: {}", e.message().unwrap());
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
                 }
-            }
+            };
 
             create_tokio_runtime().block_on(async {
                 let result = runtime.evaluate_module(file);
                 match result {
                     Ok(_) => {
                         runtime.idle().await;
+                        if *check_leaks && report_leaks(&runtime) {
+                            std::process::exit(1);
+                        }
                     }
                     Err(e) => {
-                        println!("Error CLI: {}", e.message().unwrap());
+                        eprintln!("{}", runtime.format_error(e));
+                        std::process::exit(1);
                     }
                 }
             });
         }
-        // Some(Commands::Bundle {
-        //     output,
-        //     entry,
-        //     minify,
-        // }) => {
-        //     // let args = BundleArgs {
-        //     //     external_modules: vec!["@kedo:op/web".to_string()],
-        //     //     entry: entry.into(),
-        //     //     output: output.into(),
-        //     //     minify: *minify,
-        //     // };
-
-        //     // let result = bundler::bundle(args);
-        //     // match result {
-        //     //     Ok(_) => {
-        //     //         println!("Bundle complete");
-        //     //     }
-        //     //     Err(e) => {
-        //     //         println!("Error: {}", e);
-        //     //     }
-        //     // }
-        // }
+        Some(Commands::Repl { permissions }) => {
+            let mut runtime =
+                match create_runtime(permissions, None, &RemoteFlags::default()) {
+                    Ok(runtime) => runtime,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+
+            create_tokio_runtime().block_on(repl::run(&mut runtime));
+        }
+        Some(Commands::Test(args)) => {
+            let success = create_tokio_runtime().block_on(test_runner::run(args));
+            if !success {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Bundle(flags)) => {
+            if let Err(e) = bundle(flags) {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+        }
         _ => {}
     }
 }
@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use kedo_runtime::{AllowList, Permissions};
+
+/// Permission flags shared by the commands that execute code.
+/// Nothing is allowed unless granted, e.g. `kedo run --allow-read=./data --allow-net main.js`
+#[derive(Args, Debug, Clone, Default)]
+pub struct PermissionFlags {
+    /// Allow all permissions
+    #[arg(short = 'A', long)]
+    pub allow_all: bool,
+
+    /// Allow file system read access, optionally limited to the given paths
+    #[arg(long, num_args = 0.., require_equals = true, value_delimiter = ',', value_name = "PATH")]
+    pub allow_read: Option<Vec<PathBuf>>,
+
+    /// Allow file system write access, optionally limited to the given paths
+    #[arg(long, num_args = 0.., require_equals = true, value_delimiter = ',', value_name = "PATH")]
+    pub allow_write: Option<Vec<PathBuf>>,
+
+    /// Allow network access, optionally limited to the given hosts (host[:port])
+    #[arg(long, num_args = 0.., require_equals = true, value_delimiter = ',', value_name = "HOST")]
+    pub allow_net: Option<Vec<String>>,
+
+    /// Allow environment access, optionally limited to the given variables
+    #[arg(long, num_args = 0.., require_equals = true, value_delimiter = ',', value_name = "VARIABLE")]
+    pub allow_env: Option<Vec<String>>,
+}
+
+impl PermissionFlags {
+    pub fn to_permissions(&self) -> Permissions {
+        if self.allow_all {
+            return Permissions::allow_all();
+        }
+
+        Permissions::deny_all()
+            .allow_read(AllowList::from_option(self.allow_read.clone()))
+            .allow_write(AllowList::from_option(self.allow_write.clone()))
+            .allow_net(AllowList::from_option(self.allow_net.clone()))
+            .allow_env(AllowList::from_option(self.allow_env.clone()))
+    }
+}
@@ -0,0 +1,597 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    pin::pin,
+    sync::Arc,
+};
+
+use clap::Args;
+use futures::StreamExt;
+use hyper::Uri;
+use kedo_runtime::{ModuleError, ModuleLoader, PermissionError, Permissions};
+use kedo_std::{
+    FetchClient, FetchError, HttpRequestBuilder, RedirectCheck, ResponseBody,
+};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::file_loader::{is_remote, is_typescript, Transpiler};
+
+/// Version of the `kedo.lock` format
+const LOCK_VERSION: &str = "1";
+
+/// Flags of the commands that import remote modules
+#[derive(Args, Debug, Clone, Default)]
+pub struct RemoteFlags {
+    /// Download the remote modules again instead of using the cache
+    #[arg(long)]
+    pub reload: bool,
+
+    /// Load the remote modules from the cache only, without network access
+    #[arg(long, conflicts_with = "reload")]
+    pub offline: bool,
+}
+
+/// | ------------------------------- |
+/// |       RemoteModuleLoader        |
+/// | ------------------------------- |
+/// | - cache_dir: PathBuf            |
+/// | - reload: bool                  |
+/// | - offline: bool                 |
+/// | - lockfile: Option<Lockfile>    |
+/// | - transpiler: Transpiler        |
+/// | - permissions: Arc<Permissions> |
+/// | ------------------------------- |
+///
+/// Loads the `http://` and `https://` imports. Downloads are stored in `cache_dir`
+/// by content hash, the relative imports of a remote module are rewritten to the
+/// URL they point to and TypeScript modules are transpiled like the local ones.
+/// The hash of every module is recorded in the lockfile and verified once the
+/// module is loaded again. The host of every module and redirect needs the net
+/// permission, unless the module is loaded from the cache with `--offline`.
+///
+/// e.g.
+/// ```text
+/// <cache_dir>/blobs/<sha256 of the content>
+/// <cache_dir>/urls/<sha256 of the URL>.json
+/// ```
+pub struct RemoteModuleLoader {
+    cache_dir: PathBuf,
+    reload: bool,
+    offline: bool,
+    lockfile: Option<Lockfile>,
+    transpiler: Transpiler,
+    permissions: Arc<Permissions>,
+}
+
+impl RemoteModuleLoader {
+    pub fn new(cache_dir: PathBuf, transpiler: Transpiler) -> Self {
+        Self {
+            cache_dir,
+            reload: false,
+            offline: false,
+            lockfile: None,
+            transpiler,
+            permissions: Arc::new(Permissions::default()),
+        }
+    }
+
+    pub fn flags(mut self, flags: &RemoteFlags) -> Self {
+        self.reload = flags.reload;
+        self.offline = flags.offline;
+        self
+    }
+
+    /// Records and verifies the hashes of the remote modules in `path`
+    pub fn lockfile(mut self, path: PathBuf) -> Self {
+        self.lockfile = Some(Lockfile::new(path));
+        self
+    }
+
+    /// Permissions of the runtime, the net permission is checked before a module
+    /// is downloaded
+    pub fn permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = Arc::new(permissions);
+        self
+    }
+
+    /// Content of the module and the URL it was served from, after the redirects
+    fn fetch(&self, url: &Url) -> Result<(Vec<u8>, Url), ModuleError> {
+        // checked for the cached modules too, the cache doesn't change what a
+        // script can import. `--offline` never reaches the network.
+        if !self.offline {
+            self.permissions
+                .check_net(
+                    url.host_str().unwrap_or_default(),
+                    url.port_or_known_default(),
+                )
+                .map_err(ModuleError::PermissionDenied)?;
+        }
+
+        let cached = match self.reload {
+            true => None,
+            false => self.read_cache(url),
+        };
+
+        let (content, final_url) = match cached {
+            Some(cached) => cached,
+            None if self.offline => {
+                return Err(ModuleError::Other(format!(
+                    "Module '{}' is not cached and can't be downloaded with --offline",
+                    url
+                )))
+            }
+            None => {
+                let (content, final_url) = download(url, &self.permissions)?;
+                // the next run downloads the module again if it isn't written
+                let _ = self.write_cache(url, &final_url, &content);
+                (content, final_url)
+            }
+        };
+
+        if let Some(lockfile) = &self.lockfile {
+            lockfile.verify(url, &sha256(&content))?;
+        }
+
+        Ok((content, final_url))
+    }
+
+    fn read_cache(&self, url: &Url) -> Option<(Vec<u8>, Url)> {
+        let entry = std::fs::read_to_string(self.entry_path(url)).ok()?;
+        let entry: Value = serde_json::from_str(&entry).ok()?;
+        let hash = entry.get("hash")?.as_str()?;
+        let final_url = Url::parse(entry.get("finalUrl")?.as_str()?).ok()?;
+
+        let content = std::fs::read(self.cache_dir.join("blobs").join(hash)).ok()?;
+        // a blob that doesn't match its name is downloaded again
+        (sha256(&content) == hash).then_some((content, final_url))
+    }
+
+    fn write_cache(
+        &self,
+        url: &Url,
+        final_url: &Url,
+        content: &[u8],
+    ) -> std::io::Result<()> {
+        let hash = sha256(content);
+        let entry = json!({
+            "url": url.as_str(),
+            "finalUrl": final_url.as_str(),
+            "hash": hash,
+        });
+
+        std::fs::create_dir_all(self.cache_dir.join("blobs"))?;
+        std::fs::create_dir_all(self.cache_dir.join("urls"))?;
+        std::fs::write(self.cache_dir.join("blobs").join(&hash), content)?;
+        std::fs::write(self.entry_path(url), entry.to_string())
+    }
+
+    fn entry_path(&self, url: &Url) -> PathBuf {
+        self.cache_dir
+            .join("urls")
+            .join(format!("{}.json", sha256(url.as_str().as_bytes())))
+    }
+}
+
+impl ModuleLoader for RemoteModuleLoader {
+    fn can_handle(&self, module_id: &str) -> bool {
+        is_remote(module_id)
+    }
+
+    fn resolve(&self, module_id: &str) -> Result<String, ModuleError> {
+        Url::parse(module_id)
+            .map(|url| url.to_string())
+            .map_err(|_| ModuleError::NotFound(module_id.to_string()))
+    }
+
+    fn load(&self, module_id: &str) -> Result<String, ModuleError> {
+        let url = parse_url(module_id)?;
+        let (content, final_url) = self.fetch(&url)?;
+        let source = String::from_utf8(content).map_err(|_| {
+            ModuleError::InvalidModule(format!("Module '{}' is not UTF-8", url))
+        })?;
+        let source = rewrite_imports(&source, &final_url)?;
+
+        match is_typescript(Path::new(final_url.path())) {
+            true => self
+                .transpiler
+                .transpile(Path::new(final_url.as_str()), &source),
+            false => Ok(source),
+        }
+    }
+
+    fn load_bytes(&self, module_id: &str) -> Result<Vec<u8>, ModuleError> {
+        let url = parse_url(module_id)?;
+        self.fetch(&url).map(|(content, _)| content)
+    }
+}
+
+/// | ------------------------------- |
+/// |            Lockfile             |
+/// | ------------------------------- |
+/// | - path: PathBuf                 |
+/// | - remote: RefCell<Option<Map>>  |
+/// | ------------------------------- |
+///
+/// The `kedo.lock` of the project, read on the first remote import.
+///
+/// e.g.
+/// ```json
+/// {
+///   "version": "1",
+///   "remote": { "https://example.com/mod.ts": "<sha256 of the content>" }
+/// }
+/// ```
+struct Lockfile {
+    path: PathBuf,
+    remote: RefCell<Option<BTreeMap<String, String>>>,
+}
+
+impl Lockfile {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            remote: RefCell::new(None),
+        }
+    }
+
+    /// Fails if the hash differs from the recorded one, new modules are recorded
+    fn verify(&self, url: &Url, hash: &str) -> Result<(), ModuleError> {
+        let mut remote = self.remote.borrow_mut();
+        if remote.is_none() {
+            *remote = Some(self.read()?);
+        }
+
+        let remote = remote.get_or_insert_with(BTreeMap::new);
+        match remote.get(url.as_str()) {
+            Some(locked) if locked == hash => Ok(()),
+            Some(locked) => Err(ModuleError::Other(format!(
+                "Integrity check failed for module '{}', expected hash {} from {} but got {}",
+                url,
+                locked,
+                self.path.display(),
+                hash
+            ))),
+            None => {
+                remote.insert(url.to_string(), hash.to_string());
+                self.write(remote).map_err(|err| {
+                    ModuleError::Other(format!(
+                        "Cannot write {}: {}",
+                        self.path.display(),
+                        err
+                    ))
+                })
+            }
+        }
+    }
+
+    fn read(&self) -> Result<BTreeMap<String, String>, ModuleError> {
+        let source = match std::fs::read_to_string(&self.path) {
+            Ok(source) => source,
+            Err(_) => return Ok(BTreeMap::new()),
+        };
+
+        let invalid = || ModuleError::Other(format!("Invalid {}", self.path.display()));
+        let lock: Value = serde_json::from_str(&source).map_err(|_| invalid())?;
+        let remote = match lock.get("remote") {
+            Some(Value::Object(remote)) => remote,
+            Some(_) => return Err(invalid()),
+            None => return Ok(BTreeMap::new()),
+        };
+
+        remote
+            .iter()
+            .map(|(url, hash)| match hash.as_str() {
+                Some(hash) => Ok((url.clone(), hash.to_string())),
+                None => Err(invalid()),
+            })
+            .collect()
+    }
+
+    fn write(&self, remote: &BTreeMap<String, String>) -> std::io::Result<()> {
+        let lock = json!({ "version": LOCK_VERSION, "remote": remote });
+        let source = serde_json::to_string_pretty(&lock)?;
+        std::fs::write(&self.path, source + "\n")
+    }
+}
+
+fn parse_url(module_id: &str) -> Result<Url, ModuleError> {
+    Url::parse(module_id).map_err(|_| ModuleError::NotFound(module_id.to_string()))
+}
+
+fn sha256(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// Downloads the module on its own thread, the module hooks run synchronously
+/// inside the event loop of the runtime. Redirects to hosts without the net
+/// permission fail the download.
+fn download(
+    url: &Url,
+    permissions: &Arc<Permissions>,
+) -> Result<(Vec<u8>, Url), ModuleError> {
+    let error = |reason: String| {
+        ModuleError::Other(format!("Cannot download module '{}': {}", url, reason))
+    };
+
+    let uri = url
+        .as_str()
+        .parse::<Uri>()
+        .map_err(|err| error(err.to_string()))?;
+
+    let permissions = permissions.clone();
+    let redirect_check: RedirectCheck = Arc::new(move |uri: &Uri| {
+        let port = uri.port_u16().or_else(|| match uri.scheme_str() {
+            Some("https") => Some(443),
+            Some("http") => Some(80),
+            _ => None,
+        });
+        permissions
+            .check_net(uri.host().unwrap_or_default(), port)
+            .map_err(Into::into)
+    });
+
+    let result = std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()?
+                    .block_on(get(uri, redirect_check))
+            })
+            .join()
+            .unwrap_or_else(|_| Err(FetchError::new("the download panicked")))
+    });
+
+    let (content, final_uri) = result.map_err(|err| {
+        let denied = err
+            .inner
+            .as_ref()
+            .and_then(|inner| inner.downcast_ref::<PermissionError>());
+        match denied {
+            Some(denied) => ModuleError::PermissionDenied(denied.clone()),
+            None => error(err.describe()),
+        }
+    })?;
+    let final_url = Url::parse(&final_uri.to_string()).unwrap_or_else(|_| url.clone());
+    Ok((content, final_url))
+}
+
+async fn get(
+    uri: Uri,
+    redirect_check: RedirectCheck,
+) -> Result<(Vec<u8>, Uri), FetchError> {
+    let request = HttpRequestBuilder::new()
+        .method("GET")
+        .uri(uri.clone())
+        .build()
+        .map_err(FetchError::new)?;
+    let mut response = FetchClient::new()
+        .execute(request)?
+        .redirect_check(redirect_check)
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(FetchError::new(&format!(
+            "{} {}",
+            response.status_code(),
+            response.status_text()
+        )));
+    }
+
+    let final_uri = response.url().cloned().unwrap_or(uri);
+    let mut content = Vec::new();
+    match response.take_body() {
+        ResponseBody::Bytes(bytes) => content.extend_from_slice(&bytes),
+        ResponseBody::DecodedStream(stream) => {
+            let mut stream = pin!(stream);
+            while let Some(chunk) = stream.next().await {
+                content.extend_from_slice(&chunk?);
+            }
+        }
+        _ => {}
+    }
+
+    Ok((content, final_uri))
+}
+
+/// Rewrites the relative specifiers of the static and dynamic imports and the
+/// re-exports to absolute URLs, e.g. `import "./util.ts"` of
+/// `https://example.com/lib/mod.ts` imports `https://example.com/lib/util.ts`
+pub fn rewrite_imports(source: &str, base: &Url) -> Result<String, ModuleError> {
+    bundler::rewrite_imports(source, Path::new(base.path()), |specifier| {
+        let relative = ["./", "../", "/"]
+            .iter()
+            .any(|prefix| specifier.starts_with(prefix));
+        relative
+            .then(|| base.join(specifier).ok())
+            .flatten()
+            .map(|url| url.to_string())
+    })
+    .map_err(|err| ModuleError::InvalidModule(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::{body::Bytes, header::HeaderMap, StatusCode};
+    use kedo_runtime::AllowList;
+    use kedo_std::{HttpResponse, HttpServerBuilder, HttpSocketAddr};
+    use std::net::SocketAddr;
+    use tempfile::TempDir;
+
+    use crate::file_loader::FileModuleLoader;
+
+    /// Serves the files until the test process exits
+    fn serve(files: &'static [(&'static str, &'static str)]) -> SocketAddr {
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+
+            runtime.block_on(async move {
+                let (server, mut reader) =
+                    HttpServerBuilder::new(HttpSocketAddr::IpSocket(addr))
+                        .bind()
+                        .await
+                        .unwrap();
+                let _server = server.listen();
+
+                while let Some(event) = reader.next().await {
+                    let file = files
+                        .iter()
+                        .find(|(path, _)| *path == event.req.uri().path());
+                    let response = match file {
+                        Some((_, content)) => HttpResponse::new(
+                            StatusCode::OK,
+                            HeaderMap::new(),
+                            ResponseBody::Bytes(Bytes::from_static(content.as_bytes())),
+                        ),
+                        None => HttpResponse::new(
+                            StatusCode::NOT_FOUND,
+                            HeaderMap::new(),
+                            ResponseBody::None,
+                        ),
+                    };
+                    event.response(response.try_into().unwrap());
+                }
+            });
+        });
+
+        // wait until the server accepts connections
+        while std::net::TcpStream::connect(addr).is_err() {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        addr
+    }
+
+    /// Loader of the modules served by the tests, `root` holds the cache and the
+    /// lockfile
+    fn remote_loader(root: &Path) -> RemoteModuleLoader {
+        let transpiler = FileModuleLoader::new(None).transpiler().clone();
+        RemoteModuleLoader::new(root.join("cache"), transpiler)
+    }
+
+    #[test]
+    fn test_rewrite_imports() {
+        let base = Url::parse("https://example.com/lib/mod.ts").unwrap();
+        let source = r#"
+            import { add } from "./math.ts";
+            import '../side_effect.js';
+            export * from "/root.js";
+            import lodash from "lodash";
+            const util = await import("./util.js");
+            // import "./commented.js";
+            const path = "./not_an_import.js";
+        "#;
+
+        let output = rewrite_imports(source, &base).unwrap();
+        assert!(output.contains(r#"from "https://example.com/lib/math.ts""#));
+        assert!(output.contains("import 'https://example.com/side_effect.js'"));
+        assert!(output.contains(r#"from "https://example.com/root.js""#));
+        assert!(output.contains(r#"from "lodash""#));
+        assert!(output.contains(r#"import("https://example.com/lib/util.js")"#));
+        assert!(output.contains(r#"import "./commented.js""#));
+        assert!(output.contains(r#""./not_an_import.js""#));
+    }
+
+    #[test]
+    fn test_remote_modules() {
+        let addr = serve(&[
+            (
+                "/mod.ts",
+                "import { add } from './math.ts';\nexport const answer: number = add(40, 2);\n",
+            ),
+            ("/math.ts", "export const add = (a: number, b: number) => a + b;\n"),
+        ]);
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        let url = format!("http://{}/mod.ts", addr);
+        let loader = remote_loader(root).lockfile(root.join("kedo.lock"));
+
+        assert!(loader.can_handle(&url));
+        let code = loader.load(&url).unwrap();
+        assert!(code.contains(&format!("http://{}/math.ts", addr)));
+        assert!(code.contains("const answer = add(40, 2);"));
+
+        let lock = std::fs::read_to_string(root.join("kedo.lock")).unwrap();
+        assert!(lock.contains(&url));
+
+        // served from the cache
+        let offline = RemoteFlags {
+            reload: false,
+            offline: true,
+        };
+        let loader = remote_loader(root)
+            .flags(&offline)
+            .lockfile(root.join("kedo.lock"));
+        assert_eq!(loader.load(&url).unwrap(), code);
+        let error = loader
+            .load(&format!("http://{}/math.ts", addr))
+            .unwrap_err();
+        assert!(error.to_string().contains("--offline"));
+
+        let missing = remote_loader(root)
+            .load(&format!("http://{}/missing.ts", addr))
+            .unwrap_err();
+        assert!(missing.to_string().contains("404"));
+    }
+
+    #[test]
+    fn test_net_permission() {
+        let addr = serve(&[("/mod.js", "export default 1;\n")]);
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        let url = format!("http://{}/mod.js", addr);
+
+        let error = remote_loader(root)
+            .permissions(Permissions::deny_all())
+            .load(&url)
+            .unwrap_err();
+        assert!(matches!(error, ModuleError::PermissionDenied(_)));
+        assert!(error
+            .to_string()
+            .contains("Requires net access to \"127.0.0.1:"));
+
+        let allowed = AllowList::Only(vec![addr.to_string()]);
+        let loader =
+            remote_loader(root).permissions(Permissions::deny_all().allow_net(allowed));
+        assert!(loader.load(&url).is_ok());
+
+        // cached modules are loaded without network access
+        let offline = RemoteFlags {
+            reload: false,
+            offline: true,
+        };
+        let loader = remote_loader(root)
+            .flags(&offline)
+            .permissions(Permissions::deny_all());
+        assert!(loader.load(&url).is_ok());
+    }
+
+    #[test]
+    fn test_lockfile_mismatch() {
+        let addr = serve(&[("/mod.js", "export default 1;\n")]);
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        let url = format!("http://{}/mod.js", addr);
+        std::fs::write(
+            root.join("kedo.lock"),
+            json!({ "version": LOCK_VERSION, "remote": { url.clone(): "0000" } })
+                .to_string(),
+        )
+        .unwrap();
+
+        let loader = remote_loader(root).lockfile(root.join("kedo.lock"));
+        let error = loader.load(&url).unwrap_err();
+        assert!(error.to_string().contains("Integrity check failed"));
+    }
+}
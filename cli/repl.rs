@@ -0,0 +1,265 @@
+use std::{path::PathBuf, sync::mpsc, thread};
+
+use kedo_console::inspect;
+use kedo_runtime::runtime::{PromiseState, Runtime};
+use rust_jsc::JSError;
+use rustyline::{error::ReadlineError, DefaultEditor};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+const PROMPT: &str = "> ";
+const CONTINUATION_PROMPT: &str = "... ";
+const HISTORY_FILE: &str = ".kedo_history";
+
+/// Reads lines on a dedicated thread so the event loop keeps running
+/// while the REPL waits for input.
+struct LineReader {
+    prompts: mpsc::Sender<String>,
+    lines: UnboundedReceiver<Result<String, ReadlineError>>,
+}
+
+impl LineReader {
+    fn spawn() -> Self {
+        let (prompts, prompt_rx) = mpsc::channel::<String>();
+        let (line_tx, lines) = unbounded_channel();
+
+        thread::spawn(move || {
+            let mut editor = match DefaultEditor::new() {
+                Ok(editor) => editor,
+                Err(err) => {
+                    let _ = line_tx.send(Err(err));
+                    return;
+                }
+            };
+
+            let history = history_path();
+            if let Some(path) = &history {
+                let _ = editor.load_history(path);
+            }
+
+            for prompt in prompt_rx {
+                let line = editor.readline(&prompt);
+                if let Ok(line) = &line {
+                    if !line.trim().is_empty() {
+                        let _ = editor.add_history_entry(line.as_str());
+                        if let Some(path) = &history {
+                            let _ = editor.save_history(path);
+                        }
+                    }
+                }
+
+                if line_tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self { prompts, lines }
+    }
+
+    fn prompt(&self, prompt: &str) {
+        let _ = self.prompts.send(prompt.to_string());
+    }
+
+    async fn next_line(&mut self) -> Result<String, ReadlineError> {
+        self.lines.recv().await.unwrap_or(Err(ReadlineError::Eof))
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+/// Runs the REPL until `.exit` or ctrl+d, the runtime is kept alive
+/// between inputs so declarations, timers and pending promises persist.
+pub async fn run(runtime: &mut Runtime) {
+    println!("Kedo {}", env!("CARGO_PKG_VERSION"));
+    println!("exit using ctrl+d or .exit");
+
+    let mut reader = LineReader::spawn();
+    let mut source = String::new();
+    loop {
+        if source.is_empty() {
+            reader.prompt(PROMPT);
+        } else {
+            reader.prompt(CONTINUATION_PROMPT);
+        }
+
+        // Keep polling timers and jobs until the next line arrives
+        let line = tokio::select! {
+            line = reader.next_line() => line,
+            _ = runtime.idle() => reader.next_line().await,
+        };
+
+        match line {
+            Ok(line) => {
+                if source.is_empty() && line.trim() == ".exit" {
+                    break;
+                }
+
+                source.push_str(&line);
+                source.push('\n');
+
+                let input = match prepare_input(runtime, &source) {
+                    Some(input) => input,
+                    None => continue,
+                };
+
+                source.clear();
+                if input.script.trim().is_empty() {
+                    continue;
+                }
+
+                evaluate(runtime, &input).await;
+            }
+            Err(ReadlineError::Interrupted) => {
+                if source.is_empty() {
+                    println!("press ctrl+d or type .exit to exit");
+                }
+                source.clear();
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                break;
+            }
+        }
+    }
+}
+
+/// A complete input of the REPL
+struct Input {
+    script: String,
+    /// The input has a top level await, the REPL waits for its result
+    awaited: bool,
+}
+
+impl Input {
+    fn new(script: String, awaited: bool) -> Self {
+        Self { script, awaited }
+    }
+}
+
+/// Returns the script to evaluate, or `None` when the input is incomplete
+/// and the REPL should keep reading lines.
+fn prepare_input(runtime: &Runtime, source: &str) -> Option<Input> {
+    let message = match runtime.check_syntax(source, None) {
+        Ok(true) => return Some(Input::new(source.to_string(), false)),
+        Ok(false) => String::new(),
+        Err(error) => error_message(&error),
+    };
+
+    if is_incomplete(source, &message) {
+        return None;
+    }
+
+    // Top level await, the declarations stay global and the value of the last
+    // expression is the value of the input
+    if let Ok(Some(wrapped)) = bundler::wrap_top_level_await(source) {
+        return Some(Input::new(wrapped, true));
+    }
+
+    // Let the evaluation report the syntax error
+    Some(Input::new(source.to_string(), false))
+}
+
+fn is_incomplete(source: &str, message: &str) -> bool {
+    const INCOMPLETE_ERRORS: [&str; 3] = [
+        "Unexpected end of script",
+        "Unterminated template",
+        "Unterminated multiline comment",
+    ];
+
+    if INCOMPLETE_ERRORS
+        .iter()
+        .any(|error| message.contains(error))
+    {
+        return true;
+    }
+
+    message.is_empty() && has_open_delimiters(source)
+}
+
+fn has_open_delimiters(source: &str) -> bool {
+    let mut depth = 0i32;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for ch in source.chars() {
+        if let Some(open) = quote {
+            if escaped {
+                escaped = false;
+            } else if ch == '\\' {
+                escaped = true;
+            } else if ch == open {
+                quote = None;
+            }
+            continue;
+        }
+
+        match ch {
+            '"' | '\'' | '`' => quote = Some(ch),
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    depth > 0 || quote == Some('`')
+}
+
+/// Prints the result of the input, the event loop only runs until the result
+/// settles for a top level await. Any other promise is printed as it is now.
+async fn evaluate(runtime: &mut Runtime, input: &Input) {
+    let value = match runtime.evaluate_script(&input.script, None) {
+        Ok(value) => value,
+        Err(error) => return print_error(&error),
+    };
+
+    let state = match input.awaited {
+        true => runtime.settle(value).await,
+        false => runtime.promise_state(value),
+    };
+
+    match state {
+        Ok(PromiseState::Fulfilled(value)) => {
+            println!("{}", inspect(runtime.context(), &value))
+        }
+        Ok(PromiseState::Rejected(error)) => {
+            eprintln!("Uncaught {}", inspect(runtime.context(), &error))
+        }
+        Ok(PromiseState::Pending) => println!("Promise {{ <pending> }}"),
+        Err(error) => print_error(&error),
+    }
+}
+
+fn error_message(error: &JSError) -> String {
+    error
+        .message()
+        .map(|message| message.to_string())
+        .unwrap_or_default()
+}
+
+fn print_error(error: &JSError) {
+    eprintln!("Uncaught {}", error_message(error));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_open_delimiters() {
+        assert!(has_open_delimiters("function foo() {\n"));
+        assert!(has_open_delimiters("const list = [1,\n"));
+        assert!(has_open_delimiters("const text = `hello\n"));
+        assert!(!has_open_delimiters("const text = '{';\n"));
+        assert!(!has_open_delimiters("foo({ a: 1 });\n"));
+    }
+
+    #[test]
+    fn test_is_incomplete() {
+        assert!(is_incomplete("if (true) {", "Unexpected end of script"));
+        assert!(is_incomplete("if (true) {", ""));
+        assert!(!is_incomplete("const = 1;", "Unexpected token '='"));
+    }
+}
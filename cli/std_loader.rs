@@ -16,6 +16,9 @@ impl Default for StdModuleLoader {
             "@kedo/fs",
             "@kedo/utils",
             "@kedo/web",
+            "@kedo/runtime",
+            "@kedo/test",
+            "@kedo/module",
             "@kedo:int/std/stream",
             "@kedo:int/std/web",
         ]
@@ -27,6 +30,15 @@ impl Default for StdModuleLoader {
     }
 }
 
+impl StdModuleLoader {
+    /// Specifiers of the std modules, e.g. kept as imports by `kedo bundle`
+    pub fn modules(&self) -> Vec<String> {
+        let mut modules = self.modules.iter().cloned().collect::<Vec<_>>();
+        modules.sort();
+        modules
+    }
+}
+
 impl ModuleLoader for StdModuleLoader {
     fn resolve(&self, module: &str) -> Result<String, ModuleError> {
         self.modules
@@ -64,6 +76,15 @@ impl ModuleLoader for StdModuleLoader {
             "@kedo/web" => {
                 Ok(include_str!("../build/@std/dist/web/index.js").to_string())
             }
+            "@kedo/runtime" => {
+                Ok(include_str!("../build/@std/dist/runtime/index.js").to_string())
+            }
+            "@kedo/test" => {
+                Ok(include_str!("../build/@std/dist/test/index.js").to_string())
+            }
+            "@kedo/module" => {
+                Ok(include_str!("../build/@std/dist/module/index.js").to_string())
+            }
             _ => return Err(ModuleError::NotFound(module.to_string())),
         }
     }
@@ -84,6 +105,12 @@ mod tests {
         assert_eq!(std_modules.resolve("@kedo/fs").unwrap(), "@kedo/fs");
         assert_eq!(std_modules.resolve("@kedo/utils").unwrap(), "@kedo/utils");
         assert_eq!(std_modules.resolve("@kedo/web").unwrap(), "@kedo/web");
+        assert_eq!(
+            std_modules.resolve("@kedo/runtime").unwrap(),
+            "@kedo/runtime"
+        );
+        assert_eq!(std_modules.resolve("@kedo/test").unwrap(), "@kedo/test");
+        assert_eq!(std_modules.resolve("@kedo/module").unwrap(), "@kedo/module");
         assert_eq!(
             std_modules.resolve("@kedo:int/std/stream").unwrap(),
             "@kedo:int/std/stream"
@@ -0,0 +1,663 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use clap::{Args, ValueEnum};
+use kedo_console::inspect;
+use kedo_runtime::runtime::{PromiseState, Runtime};
+use rust_jsc::{JSArray, JSError, JSFunction, JSObject, JSResult, JSValue};
+
+use crate::{create_runtime, permissions::PermissionFlags, remote_loader::RemoteFlags};
+
+/// Runner registered by `@kedo/test`, see `kedo_js/@std/test/index.ts`
+const TEST_RUNNER: &str = r#"Kedo.test[Symbol.for("kedo.test.run")]"#;
+const TEST_EXTENSIONS: [&str; 2] = [".test.js", ".test.ts"];
+const IGNORED_DIRS: [&str; 3] = ["node_modules", "target", ".git"];
+
+#[derive(Args, Debug, Clone)]
+pub struct TestArgs {
+    /// Glob or directory of the test files, defaults to every `*.test.{js,ts}` file
+    pattern: Option<String>,
+
+    /// Only run the tests whose name contains the filter, use `/pattern/` for a regex
+    #[arg(long)]
+    filter: Option<String>,
+
+    /// Timeout in milliseconds of each test, unless the test sets its own
+    #[arg(long, default_value_t = 5000)]
+    timeout: u64,
+
+    /// Format of the report written to stdout
+    #[arg(long, value_enum, default_value_t = Reporter::Pretty)]
+    reporter: Reporter,
+
+    #[command(flatten)]
+    permissions: PermissionFlags,
+
+    #[command(flatten)]
+    remote: RemoteFlags,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Reporter {
+    Pretty,
+    Tap,
+    Junit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TestStatus {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug, Clone)]
+struct TestCase {
+    name: String,
+    status: TestStatus,
+    duration: Duration,
+    error: Option<String>,
+}
+
+/// Results of a single test file, `error` is set when the file
+/// could not be evaluated and no test ran.
+#[derive(Debug, Clone)]
+struct TestFile {
+    path: String,
+    cases: Vec<TestCase>,
+    error: Option<String>,
+    duration: Duration,
+}
+
+impl TestFile {
+    fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_string_lossy().to_string(),
+            cases: Vec::new(),
+            error: None,
+            duration: Duration::ZERO,
+        }
+    }
+
+    fn count(&self, status: TestStatus) -> usize {
+        self.cases
+            .iter()
+            .filter(|case| case.status == status)
+            .count()
+    }
+
+    fn failed(&self) -> usize {
+        self.count(TestStatus::Failed) + self.error.is_some() as usize
+    }
+
+    fn is_ok(&self) -> bool {
+        self.failed() == 0
+    }
+}
+
+/// Runs every test file in a fresh runtime and prints the report,
+/// returns `false` if any test or file failed.
+pub async fn run(args: &TestArgs) -> bool {
+    let files = match discover(args.pattern.as_deref()) {
+        Ok(files) => files,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            return false;
+        }
+    };
+
+    if files.is_empty() {
+        eprintln!("Error: No test files found");
+        return false;
+    }
+
+    let started = Instant::now();
+    let mut reports = Vec::with_capacity(files.len());
+    for path in files {
+        let file = run_file(&path, args).await;
+        if args.reporter == Reporter::Pretty {
+            print!("{}", format_pretty_file(&file));
+        }
+        reports.push(file);
+    }
+
+    match args.reporter {
+        Reporter::Pretty => {
+            print!("{}", format_pretty_summary(&reports, started.elapsed()))
+        }
+        Reporter::Tap => print!("{}", format_tap(&reports)),
+        Reporter::Junit => print!("{}", format_junit(&reports, started.elapsed())),
+    }
+
+    reports.iter().all(TestFile::is_ok)
+}
+
+fn discover(pattern: Option<&str>) -> Result<Vec<PathBuf>, String> {
+    let patterns: Vec<String> = match pattern {
+        Some(dir) if Path::new(dir).is_dir() => TEST_EXTENSIONS
+            .iter()
+            .map(|ext| format!("{}/**/*{}", dir.trim_end_matches('/'), ext))
+            .collect(),
+        Some(pattern) => vec![pattern.to_string()],
+        None => TEST_EXTENSIONS
+            .iter()
+            .map(|ext| format!("**/*{}", ext))
+            .collect(),
+    };
+
+    let mut files = Vec::new();
+    for pattern in patterns {
+        let paths = glob::glob(&pattern)
+            .map_err(|err| format!("Invalid pattern \"{}\": {}", pattern, err))?;
+
+        for path in paths.flatten() {
+            if path.is_file() && !is_ignored(&path) {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+fn is_ignored(path: &Path) -> bool {
+    path.components()
+        .any(|component| IGNORED_DIRS.iter().any(|dir| component.as_os_str() == *dir))
+}
+
+async fn run_file(path: &Path, args: &TestArgs) -> TestFile {
+    let started = Instant::now();
+    let mut file = TestFile::new(path);
+
+    let mut runtime = match create_runtime(&args.permissions, None, &args.remote) {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            file.error = Some(err.to_string());
+            return file;
+        }
+    };
+
+    match collect(&mut runtime, &file.path, args).await {
+        Ok(cases) => file.cases = cases,
+        Err(err) => file.error = Some(err),
+    }
+
+    file.duration = started.elapsed();
+    file
+}
+
+async fn collect(
+    runtime: &mut Runtime,
+    path: &str,
+    args: &TestArgs,
+) -> Result<Vec<TestCase>, String> {
+    runtime
+        .evaluate_module(path)
+        .map_err(|err| error_message(&err))?;
+
+    let run = start_run(runtime, args).map_err(|err| error_message(&err))?;
+    let results = match runtime.settle(run).await {
+        Ok(PromiseState::Fulfilled(results)) => results,
+        Ok(PromiseState::Rejected(error)) => {
+            return Err(inspect(runtime.context(), &error))
+        }
+        Ok(PromiseState::Pending) => return Err("The test run never settled".to_string()),
+        Err(err) => return Err(error_message(&err)),
+    };
+
+    read_results(&results).map_err(|err| error_message(&err))
+}
+
+fn start_run(runtime: &Runtime, args: &TestArgs) -> JSResult<JSValue> {
+    let ctx = runtime.context();
+    let runner = runtime.evaluate_script(TEST_RUNNER, None)?;
+    let runner = JSFunction::from(runner.as_object()?);
+
+    let options = JSObject::new(ctx);
+    if let Some(filter) = &args.filter {
+        options.set_property(
+            "filter",
+            &JSValue::string(ctx, filter.as_str()),
+            Default::default(),
+        )?;
+    }
+    options.set_property(
+        "timeout",
+        &JSValue::number(ctx, args.timeout as f64),
+        Default::default(),
+    )?;
+
+    runner.call(None, &[options.into()])
+}
+
+fn read_results(results: &JSValue) -> JSResult<Vec<TestCase>> {
+    let results = JSArray::new(results.as_object()?);
+    let length = results.length()? as u32;
+    let mut cases = Vec::with_capacity(length as usize);
+
+    for i in 0..length {
+        let result = results.get(i)?.as_object()?;
+        let status = match result
+            .get_property("status")?
+            .as_string()?
+            .to_string()
+            .as_str()
+        {
+            "passed" => TestStatus::Passed,
+            "skipped" => TestStatus::Skipped,
+            _ => TestStatus::Failed,
+        };
+
+        let error = result.get_property("error")?;
+        let error = match error.is_string() {
+            true => Some(error.as_string()?.to_string()),
+            false => None,
+        };
+
+        cases.push(TestCase {
+            name: result.get_property("name")?.as_string()?.to_string(),
+            status,
+            duration: Duration::from_millis(
+                result.get_property("duration")?.as_number()? as u64,
+            ),
+            error,
+        });
+    }
+
+    Ok(cases)
+}
+
+fn error_message(error: &JSError) -> String {
+    error
+        .message()
+        .map(|message| message.to_string())
+        .unwrap_or_else(|_| "Unknown error".to_string())
+}
+
+fn format_pretty_file(file: &TestFile) -> String {
+    let mut output = format!("running {} tests from {}\n", file.cases.len(), file.path);
+    for case in file.cases.iter() {
+        let status = match case.status {
+            TestStatus::Passed => "ok",
+            TestStatus::Failed => "FAILED",
+            TestStatus::Skipped => "skipped",
+        };
+        output.push_str(&format!(
+            "{} ... {} ({}ms)\n",
+            case.name,
+            status,
+            case.duration.as_millis()
+        ));
+    }
+
+    if let Some(error) = &file.error {
+        output.push_str(&format!("{} ... FAILED\n{}\n", file.path, error));
+    }
+
+    output
+}
+
+fn format_pretty_summary(files: &[TestFile], duration: Duration) -> String {
+    let mut output = String::new();
+    let failures: Vec<(&TestFile, &TestCase)> = files
+        .iter()
+        .flat_map(|file| file.cases.iter().map(move |case| (file, case)))
+        .filter(|(_, case)| case.status == TestStatus::Failed)
+        .collect();
+
+    if !failures.is_empty() {
+        output.push_str("\n ERRORS\n\n");
+        for (file, case) in failures {
+            output.push_str(&format!(
+                "{} => {}\n{}\n\n",
+                case.name,
+                file.path,
+                case.error.as_deref().unwrap_or_default()
+            ));
+        }
+    }
+
+    let passed: usize = files
+        .iter()
+        .map(|file| file.count(TestStatus::Passed))
+        .sum();
+    let failed: usize = files.iter().map(TestFile::failed).sum();
+    let skipped: usize = files
+        .iter()
+        .map(|file| file.count(TestStatus::Skipped))
+        .sum();
+    let result = if failed == 0 { "ok" } else { "FAILED" };
+
+    output.push_str(&format!(
+        "\n{} | {} passed | {} failed | {} skipped ({}ms)\n",
+        result,
+        passed,
+        failed,
+        skipped,
+        duration.as_millis()
+    ));
+    output
+}
+
+/// Formats the results as TAP version 13, https://testanything.org/tap-version-13-specification.html
+fn format_tap(files: &[TestFile]) -> String {
+    let mut points = Vec::new();
+    for file in files {
+        for case in file.cases.iter() {
+            let name = format!("{} > {}", file.path, case.name);
+            match case.status {
+                TestStatus::Passed => points.push(format!("ok {{index}} - {}", name)),
+                TestStatus::Skipped => {
+                    points.push(format!("ok {{index}} - {} # SKIP", name))
+                }
+                TestStatus::Failed => points.push(format!(
+                    "not ok {{index}} - {}\n{}",
+                    name,
+                    tap_diagnostic(case.error.as_deref().unwrap_or_default())
+                )),
+            }
+        }
+
+        if let Some(error) = &file.error {
+            points.push(format!(
+                "not ok {{index}} - {}\n{}",
+                file.path,
+                tap_diagnostic(error)
+            ));
+        }
+    }
+
+    let mut output = format!("TAP version 13\n1..{}\n", points.len());
+    for (index, point) in points.iter().enumerate() {
+        output.push_str(&point.replacen("{index}", &(index + 1).to_string(), 1));
+        output.push('\n');
+    }
+    output
+}
+
+fn tap_diagnostic(message: &str) -> String {
+    let mut output = String::from("  ---\n  message: |-\n");
+    for line in message.lines() {
+        output.push_str(&format!("    {}\n", line));
+    }
+    output.push_str("  ...");
+    output
+}
+
+/// Formats the results as a JUnit XML report, one `testsuite` per file
+fn format_junit(files: &[TestFile], duration: Duration) -> String {
+    let total: usize = files
+        .iter()
+        .map(|file| file.cases.len() + file.error.is_some() as usize)
+        .sum();
+    let failures: usize = files.iter().map(TestFile::failed).sum();
+    let skipped: usize = files
+        .iter()
+        .map(|file| file.count(TestStatus::Skipped))
+        .sum();
+
+    let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    output.push_str(&format!(
+        "<testsuites name=\"kedo test\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+        total,
+        failures,
+        skipped,
+        duration.as_secs_f64()
+    ));
+
+    for file in files {
+        let path = escape_xml(&file.path);
+        output.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+            path,
+            file.cases.len() + file.error.is_some() as usize,
+            file.failed(),
+            file.count(TestStatus::Skipped),
+            file.duration.as_secs_f64()
+        ));
+
+        for case in file.cases.iter() {
+            output.push_str(&format!(
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                escape_xml(&case.name),
+                path,
+                case.duration.as_secs_f64()
+            ));
+            match case.status {
+                TestStatus::Passed => output.push_str("/>\n"),
+                TestStatus::Skipped => {
+                    output.push_str(">\n      <skipped/>\n    </testcase>\n")
+                }
+                TestStatus::Failed => {
+                    let error = case.error.as_deref().unwrap_or_default();
+                    output.push_str(&format!(
+                        ">\n{}    </testcase>\n",
+                        junit_failure(error)
+                    ));
+                }
+            }
+        }
+
+        if let Some(error) = &file.error {
+            output.push_str(&format!(
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"0.000\">\n{}    </testcase>\n",
+                path,
+                path,
+                junit_failure(error)
+            ));
+        }
+
+        output.push_str("  </testsuite>\n");
+    }
+
+    output.push_str("</testsuites>\n");
+    output
+}
+
+fn junit_failure(error: &str) -> String {
+    let message = error.lines().next().unwrap_or_default();
+    format!(
+        "      <failure message=\"{}\">{}</failure>\n",
+        escape_xml(message),
+        escape_xml(error)
+    )
+}
+
+fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> Vec<TestFile> {
+        let case = |name: &str, status, error: Option<&str>| TestCase {
+            name: name.to_string(),
+            status,
+            duration: Duration::from_millis(2),
+            error: error.map(|error| error.to_string()),
+        };
+
+        vec![TestFile {
+            path: "math.test.js".to_string(),
+            cases: vec![
+                case("adds", TestStatus::Passed, None),
+                case("divides", TestStatus::Failed, Some("AssertionError: 1 < 2")),
+                case("rounds", TestStatus::Skipped, None),
+            ],
+            error: None,
+            duration: Duration::from_millis(6),
+        }]
+    }
+
+    #[test]
+    fn test_format_tap() {
+        let output = format_tap(&report());
+        assert_eq!(
+            output,
+            "TAP version 13\n\
+             1..3\n\
+             ok 1 - math.test.js > adds\n\
+             not ok 2 - math.test.js > divides\n  \
+             ---\n  \
+             message: |-\n    \
+             AssertionError: 1 < 2\n  \
+             ...\n\
+             ok 3 - math.test.js > rounds # SKIP\n"
+        );
+    }
+
+    #[test]
+    fn test_format_junit() {
+        let output = format_junit(&report(), Duration::from_millis(6));
+        assert!(output.contains(
+            "<testsuite name=\"math.test.js\" tests=\"3\" failures=\"1\" skipped=\"1\" time=\"0.006\">"
+        ));
+        assert!(output.contains("<failure message=\"AssertionError: 1 &lt; 2\">"));
+        assert!(output.contains("<skipped/>"));
+    }
+
+    #[test]
+    fn test_failed_file_counts_as_failure() {
+        let mut file = TestFile::new(Path::new("broken.test.js"));
+        assert!(file.is_ok());
+
+        file.error = Some("SyntaxError: Unexpected token".to_string());
+        assert!(!file.is_ok());
+        assert!(format_tap(&[file]).contains("not ok 1 - broken.test.js"));
+    }
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../tests/test/fixtures")
+            .join(name)
+            .canonicalize()
+            .unwrap()
+    }
+
+    fn args(pattern: &Path, filter: Option<&str>) -> TestArgs {
+        TestArgs {
+            pattern: Some(pattern.to_string_lossy().to_string()),
+            filter: filter.map(|filter| filter.to_string()),
+            timeout: 5000,
+            reporter: Reporter::Tap,
+            permissions: PermissionFlags::default(),
+            remote: RemoteFlags::default(),
+        }
+    }
+
+    fn statuses(file: &TestFile) -> Vec<(&str, TestStatus)> {
+        assert_eq!(file.error, None);
+        file.cases
+            .iter()
+            .map(|case| (case.name.as_str(), case.status))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_run_only() {
+        let path = fixture("only.js");
+        let file = run_file(&path, &args(&path, None)).await;
+        assert_eq!(statuses(&file), [("runs with only", TestStatus::Passed)]);
+    }
+
+    #[tokio::test]
+    async fn test_run_filter() {
+        let path = fixture("filter.js");
+        let file = run_file(&path, &args(&path, Some("parses"))).await;
+        assert_eq!(
+            statuses(&file),
+            [
+                ("parses a date", TestStatus::Passed),
+                ("parses a number", TestStatus::Passed),
+            ]
+        );
+
+        let file = run_file(&path, &args(&path, Some("/a date$/"))).await;
+        assert_eq!(
+            statuses(&file),
+            [
+                ("parses a date", TestStatus::Passed),
+                ("formats a date", TestStatus::Passed),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_timeout() {
+        let path = fixture("timeout.js");
+        let file = run_file(&path, &args(&path, None)).await;
+        assert_eq!(
+            statuses(&file),
+            [
+                ("never resolves in time", TestStatus::Failed),
+                ("runs after a timeout", TestStatus::Passed),
+            ]
+        );
+        assert!(file.cases[0]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("timed out after 20ms"));
+    }
+
+    #[tokio::test]
+    async fn test_run_sanitizer() {
+        let path = fixture("sanitizer.js");
+        let file = run_file(&path, &args(&path, None)).await;
+        assert_eq!(
+            statuses(&file),
+            [
+                ("closes a timer one tick late", TestStatus::Passed),
+                ("leaks a timer", TestStatus::Failed),
+            ]
+        );
+        assert!(file.cases[1]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("leaked resources or pending ops"));
+    }
+
+    #[tokio::test]
+    async fn test_run_fails_on_failure() {
+        let path = fixture("failure.js");
+        let file = run_file(&path, &args(&path, None)).await;
+        assert_eq!(
+            statuses(&file),
+            [
+                ("passes", TestStatus::Passed),
+                ("fails", TestStatus::Failed)
+            ]
+        );
+        assert!(!file.is_ok());
+
+        // `kedo test` exits with 1 when the run fails
+        assert!(!run(&args(&path, None)).await);
+        let path = fixture("only.js");
+        assert!(run(&args(&path, None)).await);
+    }
+
+    #[test]
+    fn test_is_ignored() {
+        assert!(is_ignored(Path::new("node_modules/pkg/a.test.js")));
+        assert!(is_ignored(Path::new("./target/debug/a.test.js")));
+        assert!(!is_ignored(Path::new("tests/a.test.js")));
+    }
+}
@@ -0,0 +1,247 @@
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    collections::{BTreeSet, HashSet},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::{
+    sync::mpsc::{unbounded_channel, UnboundedReceiver},
+    time::{timeout_at, Instant},
+};
+
+/// Changes closer than this are reported as a single restart,
+/// e.g. an editor that truncates and then writes the file
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Moves the cursor to the top left corner and clears the terminal
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[1;1H";
+
+pub fn clear_screen() {
+    print!("{}", CLEAR_SCREEN);
+}
+
+/// | ------------------------------- |
+/// |           FileWatcher           |
+/// | ------------------------------- |
+/// | - watcher: inotify on linux     |
+/// | - events: changed paths         |
+/// | - dirs: watched directories     |
+/// | - files: watched files          |
+/// | - patterns: --watch-path globs  |
+/// | - pending: debounced changes    |
+/// | - deadline: end of the debounce |
+/// | ------------------------------- |
+///
+/// Watches the files of the modules loaded by the runtime and the files
+/// matched by the `--watch-path` globs. The directories of the files are
+/// watched instead of the files, so the files replaced by the editors on
+/// save are still tracked.
+pub struct FileWatcher {
+    watcher: RecommendedWatcher,
+    events: UnboundedReceiver<PathBuf>,
+    dirs: HashSet<PathBuf>,
+    files: HashSet<PathBuf>,
+    patterns: Vec<glob::Pattern>,
+    pending: BTreeSet<PathBuf>,
+    deadline: Option<Instant>,
+}
+
+impl FileWatcher {
+    /// Creates a watcher for the glob patterns, relative patterns are resolved
+    /// from the current directory and a directory matches every file inside it.
+    pub fn new(patterns: &[String]) -> Result<Self, String> {
+        let (sender, events) = unbounded_channel();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let Ok(event) = event else { return };
+            if matches!(event.kind, EventKind::Access(_)) {
+                return;
+            }
+
+            for path in event.paths {
+                let _ = sender.send(path);
+            }
+        })
+        .map_err(|err| format!("Failed to start the file watcher: {}", err))?;
+
+        let mut watcher = Self {
+            watcher,
+            events,
+            dirs: HashSet::new(),
+            files: HashSet::new(),
+            patterns: Vec::new(),
+            pending: BTreeSet::new(),
+            deadline: None,
+        };
+
+        let cwd = std::env::current_dir().map_err(|err| err.to_string())?;
+        for pattern in patterns {
+            let mut pattern = cwd.join(pattern).to_string_lossy().to_string();
+            if Path::new(&pattern).is_dir() {
+                pattern = format!("{}/**/*", pattern.trim_end_matches('/'));
+            }
+
+            let base = glob_base(&pattern);
+            watcher
+                .watcher
+                .watch(&base, RecursiveMode::Recursive)
+                .map_err(|err| {
+                    format!("Failed to watch '{}': {}", base.display(), err)
+                })?;
+            watcher.patterns.push(
+                glob::Pattern::new(&pattern).map_err(|err| {
+                    format!("Invalid watch path '{}': {}", pattern, err)
+                })?,
+            );
+        }
+
+        Ok(watcher)
+    }
+
+    /// Replaces the watched files, e.g. with the modules of the new runtime.
+    /// Files that can't be watched, like a missing directory, are skipped.
+    pub fn watch(&mut self, files: impl IntoIterator<Item = PathBuf>) {
+        self.files.clear();
+        for file in files {
+            let Some(dir) = file.parent().map(Path::to_path_buf) else {
+                continue;
+            };
+
+            if !self.dirs.contains(&dir)
+                && self
+                    .watcher
+                    .watch(&dir, RecursiveMode::NonRecursive)
+                    .is_ok()
+            {
+                self.dirs.insert(dir);
+            }
+            self.files.insert(file);
+        }
+    }
+
+    fn is_watched(&self, path: &Path) -> bool {
+        self.files.contains(path)
+            || self
+                .patterns
+                .iter()
+                .any(|pattern| pattern.matches_path(path))
+    }
+
+    /// Waits for a watched file to change, the changes that follow within
+    /// `DEBOUNCE` are returned with it. The changes are kept in the watcher, so
+    /// the future can be dropped by a `select!` and awaited again.
+    pub async fn changed(&mut self) -> Vec<PathBuf> {
+        loop {
+            let path = match self.deadline {
+                None => self.events.recv().await,
+                Some(deadline) => match timeout_at(deadline, self.events.recv()).await {
+                    Ok(path) => path,
+                    Err(_) => break,
+                },
+            };
+
+            // the channel closes only once the watcher is dropped
+            let Some(path) = path else { break };
+            if self.is_watched(&path) {
+                self.pending.insert(path);
+                self.deadline = Some(Instant::now() + DEBOUNCE);
+            }
+        }
+
+        self.deadline = None;
+        std::mem::take(&mut self.pending).into_iter().collect()
+    }
+}
+
+/// Directory of the pattern before its first glob component,
+/// e.g. `/app/src/**/*.json` is watched from `/app/src`
+fn glob_base(pattern: &str) -> PathBuf {
+    let mut base = PathBuf::new();
+    for component in Path::new(pattern).components() {
+        let text = component.as_os_str().to_string_lossy();
+        if text.contains(['*', '?', '[', '{']) {
+            return base;
+        }
+        base.push(component);
+    }
+
+    // a pattern without globs is a single file
+    match base.parent() {
+        Some(parent) if !base.is_dir() => parent.to_path_buf(),
+        _ => base,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_glob_base() {
+        assert_eq!(glob_base("/app/src/**/*.json"), PathBuf::from("/app/src"));
+        assert_eq!(glob_base("/app/*.txt"), PathBuf::from("/app"));
+        assert_eq!(glob_base("/app/config[0-9].json"), PathBuf::from("/app"));
+        assert_eq!(
+            glob_base("/app/missing/config.json"),
+            PathBuf::from("/app/missing")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_changed() {
+        let temp = TempDir::new().unwrap();
+        let dir = std::fs::canonicalize(temp.path()).unwrap();
+        std::fs::create_dir_all(dir.join("data")).unwrap();
+        let main = dir.join("main.js");
+        std::fs::write(&main, "console.log(1);").unwrap();
+        std::fs::write(dir.join("other.js"), "").unwrap();
+
+        let pattern = format!("{}/data/*.json", dir.display());
+        let mut watcher = FileWatcher::new(&[pattern]).unwrap();
+        watcher.watch([main.clone()]);
+
+        // files next to the watched ones don't restart the runtime
+        std::fs::write(dir.join("other.js"), "console.log(2);").unwrap();
+        std::fs::write(&main, "console.log(2);").unwrap();
+        std::fs::write(&main, "console.log(3);").unwrap();
+        let changed = tokio::time::timeout(Duration::from_secs(5), watcher.changed())
+            .await
+            .unwrap();
+        assert_eq!(changed, vec![main.clone()]);
+
+        let data = dir.join("data/config.json");
+        std::fs::write(&data, "{}").unwrap();
+        let changed = tokio::time::timeout(Duration::from_secs(5), watcher.changed())
+            .await
+            .unwrap();
+        assert_eq!(changed, vec![data]);
+    }
+
+    #[tokio::test]
+    async fn test_changed_cancelled() {
+        let dir = TempDir::new().unwrap();
+        let main = std::fs::canonicalize(dir.path()).unwrap().join("main.js");
+        std::fs::write(&main, "console.log(1);").unwrap();
+
+        let mut watcher = FileWatcher::new(&[]).unwrap();
+        watcher.watch([main.clone()]);
+        std::fs::write(&main, "console.log(2);").unwrap();
+
+        // dropped in the middle of the debounce, like a `select!` on a runtime
+        let pending = async {
+            while watcher.pending.is_empty() {
+                let timeout = Duration::from_millis(20);
+                let _ = tokio::time::timeout(timeout, watcher.changed()).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), pending)
+            .await
+            .unwrap();
+
+        let changed = tokio::time::timeout(Duration::from_secs(5), watcher.changed())
+            .await
+            .unwrap();
+        assert_eq!(changed, vec![main]);
+        assert!(watcher.deadline.is_none());
+    }
+}
@@ -1,9 +1,16 @@
 import { DirEntry } from "@kedo/fs";
+import "@kedo/runtime";
+import "@kedo/test";
 import {
     AbortController,
     AbortSignal,
+    BroadcastChannel,
     DOMException,
+    ErrorEvent,
     Headers,
+    MessageChannel,
+    MessageEvent,
+    MessagePort,
     Request,
     Response,
     TextDecoder,
@@ -12,6 +19,8 @@ import {
     URLSearchParams,
     fetch,
     serve,
+    structuredClone,
+    Worker,
 } from "@kedo/web";
 
 globalThis.DOMException = DOMException;
@@ -25,6 +34,13 @@ globalThis.TextEncoder = TextEncoder;
 globalThis.Request = Request;
 globalThis.Response = Response;
 globalThis.fetch = fetch;
+globalThis.Worker = Worker;
+globalThis.MessageEvent = MessageEvent;
+globalThis.ErrorEvent = ErrorEvent;
+globalThis.structuredClone = structuredClone;
+globalThis.MessageChannel = MessageChannel;
+globalThis.MessagePort = MessagePort;
+globalThis.BroadcastChannel = BroadcastChannel;
 
 Kedo.serve = serve;
 Kedo.DirEntry = DirEntry;
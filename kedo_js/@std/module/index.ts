@@ -0,0 +1,3 @@
+import { createRequire } from "@kedo:op/module";
+
+export { createRequire };
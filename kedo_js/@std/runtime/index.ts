@@ -0,0 +1,121 @@
+import {
+    op_env_delete,
+    op_env_get,
+    op_env_set,
+    op_env_to_object,
+    op_permission_query,
+    op_resources,
+} from "@kedo:op/runtime";
+
+type PermissionName = "read" | "write" | "net" | "env";
+type PermissionState = "granted" | "denied";
+
+type PermissionDescriptor =
+    | { name: "read"; path?: string }
+    | { name: "write"; path?: string }
+    | { name: "net"; host?: string }
+    | { name: "env"; variable?: string };
+
+// ------------------------------------------------------------
+// |                        Errors                            |
+// ------------------------------------------------------------
+class PermissionDenied extends Error {
+    constructor(message?: string) {
+        super(message);
+        this.name = "PermissionDenied";
+    }
+
+    // ops create plain errors named "PermissionDenied"
+    static [Symbol.hasInstance](value: any): boolean {
+        return value instanceof Error && value.name === "PermissionDenied";
+    }
+}
+
+// ------------------------------------------------------------
+// |                      Permissions                         |
+// ------------------------------------------------------------
+class PermissionStatus {
+    readonly state: PermissionState;
+
+    constructor(state: PermissionState) {
+        this.state = state;
+    }
+}
+
+function descriptorResource(
+    descriptor: PermissionDescriptor,
+): string | undefined {
+    switch (descriptor.name) {
+        case "read":
+        case "write":
+            return descriptor.path;
+        case "net":
+            return descriptor.host;
+        case "env":
+            return descriptor.variable;
+        default:
+            throw new TypeError(
+                `Unknown permission name: ${(descriptor as any)?.name}`,
+            );
+    }
+}
+
+function querySync(descriptor: PermissionDescriptor): PermissionStatus {
+    const resource = descriptorResource(descriptor);
+    const state = op_permission_query(
+        descriptor.name as PermissionName,
+        resource,
+    );
+    return new PermissionStatus(state);
+}
+
+const permissions = {
+    query(descriptor: PermissionDescriptor): Promise<PermissionStatus> {
+        try {
+            return Promise.resolve(querySync(descriptor));
+        } catch (error) {
+            return Promise.reject(error);
+        }
+    },
+    querySync,
+};
+
+// ------------------------------------------------------------
+// |                      Environment                         |
+// ------------------------------------------------------------
+const env = {
+    get(key: string): string | undefined {
+        return op_env_get(String(key));
+    },
+    set(key: string, value: string): void {
+        op_env_set(String(key), String(value));
+    },
+    delete(key: string): void {
+        op_env_delete(String(key));
+    },
+    has(key: string): boolean {
+        return op_env_get(String(key)) !== undefined;
+    },
+    toObject(): Record<string, string> {
+        return op_env_to_object();
+    },
+};
+
+// ------------------------------------------------------------
+// |                       Resources                          |
+// ------------------------------------------------------------
+
+/**
+ * Open resources and pending ops keyed by rid,
+ * e.g. `{ "1": "timer", "3": "op_fs_read_file" }`
+ */
+function resources(): Record<string, string> {
+    return op_resources();
+}
+
+Kedo.resources = resources;
+Kedo.permissions = permissions;
+Kedo.env = env;
+Kedo.errors = Object.freeze({ PermissionDenied });
+
+export { env, PermissionDenied, permissions, PermissionStatus, resources };
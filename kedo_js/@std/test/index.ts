@@ -0,0 +1,207 @@
+import { op_resources } from "@kedo:op/runtime";
+
+type TestFn = () => void | Promise<void>;
+
+interface TestOptions {
+    /** Only run the tests marked with `only` */
+    only?: boolean;
+    /** Report the test as skipped without running it */
+    skip?: boolean;
+    /** Time in milliseconds before an async test fails */
+    timeout?: number;
+    /** Fail the test if it leaves resources open, e.g. timers or servers (default true) */
+    sanitizeResources?: boolean;
+    /** Fail the test if it leaves async ops pending (default true) */
+    sanitizeOps?: boolean;
+}
+
+interface TestDefinition {
+    name: string;
+    fn: TestFn;
+    only: boolean;
+    skip: boolean;
+    timeout?: number;
+    sanitizeResources: boolean;
+    sanitizeOps: boolean;
+}
+
+type TestStatus = "passed" | "failed" | "skipped";
+
+interface TestResult {
+    name: string;
+    status: TestStatus;
+    duration: number;
+    error?: string;
+}
+
+interface RunOptions {
+    filter?: string;
+    timeout?: number;
+}
+
+const DEFAULT_TIMEOUT = 5000;
+const TEST_RUNNER = Symbol.for("kedo.test.run");
+
+const definitions: TestDefinition[] = [];
+
+function test(name: string, fn: TestFn, options: TestOptions = {}): void {
+    if (typeof name !== "string") {
+        throw new TypeError("The test name must be a string");
+    }
+
+    if (typeof fn !== "function") {
+        throw new TypeError("The test body must be a function");
+    }
+
+    definitions.push({
+        name,
+        fn,
+        only: !!options.only,
+        skip: !!options.skip,
+        timeout: options.timeout,
+        sanitizeResources: options.sanitizeResources ?? true,
+        sanitizeOps: options.sanitizeOps ?? true,
+    });
+}
+
+test.only = function only(
+    name: string,
+    fn: TestFn,
+    options: TestOptions = {},
+): void {
+    test(name, fn, { ...options, only: true });
+};
+
+test.skip = function skip(
+    name: string,
+    fn: TestFn,
+    options: TestOptions = {},
+): void {
+    test(name, fn, { ...options, skip: true });
+};
+
+function createFilter(filter?: string): (name: string) => boolean {
+    if (!filter) return () => true;
+
+    // "/pattern/" filters by regular expression
+    if (filter.length > 2 && filter.startsWith("/") && filter.endsWith("/")) {
+        const pattern = new RegExp(filter.slice(1, -1));
+        return (name) => pattern.test(name);
+    }
+
+    return (name) => name.includes(filter);
+}
+
+function withTimeout(
+    promise: Promise<void>,
+    timeout: number,
+    name: string,
+): Promise<void> {
+    let timer: any;
+    const expired = new Promise<never>((_, reject) => {
+        timer = setTimeout(() => {
+            reject(new Error(`Test "${name}" timed out after ${timeout}ms`));
+        }, timeout);
+    });
+
+    return Promise.race([promise, expired]).finally(() => clearTimeout(timer));
+}
+
+// ops are tracked in the resource table under their op name
+function isOp(kind: string): boolean {
+    return kind === "op" || kind.startsWith("op_");
+}
+
+function leakedResources(
+    before: Record<string, string>,
+    definition: TestDefinition,
+): string[] {
+    const after = op_resources();
+    const leaks: string[] = [];
+    for (const rid of Object.keys(after)) {
+        if (rid in before) continue;
+
+        const kind = after[rid];
+        const checked = isOp(kind)
+            ? definition.sanitizeOps
+            : definition.sanitizeResources;
+        if (checked) leaks.push(`${kind} (rid ${rid})`);
+    }
+
+    return leaks;
+}
+
+async function sanitize(
+    before: Record<string, string>,
+    definition: TestDefinition,
+): Promise<void> {
+    if (!definition.sanitizeOps && !definition.sanitizeResources) return;
+    if (leakedResources(before, definition).length === 0) return;
+
+    // give the ops completed in the last tick a chance to be released
+    await new Promise((resolve) => setTimeout(resolve, 0));
+    const leaks = leakedResources(before, definition);
+    if (leaks.length > 0) {
+        throw new Error(
+            `Test "${definition.name}" leaked resources or pending ops:\n` +
+                leaks.map((leak) => `  - ${leak}`).join("\n") +
+                "\nMake sure timers are cleared, servers are shut down and bodies are consumed",
+        );
+    }
+}
+
+function formatError(error: unknown): string {
+    const text = String(error);
+    if (error instanceof Error && error.stack && !text.includes(error.stack)) {
+        return `${text}\n${error.stack}`;
+    }
+
+    return text;
+}
+
+async function runTests(options: RunOptions = {}): Promise<TestResult[]> {
+    const matches = createFilter(options.filter);
+    const hasOnly = definitions.some((definition) => definition.only);
+    const results: TestResult[] = [];
+
+    for (const definition of definitions) {
+        if (!matches(definition.name)) continue;
+        if (hasOnly && !definition.only) continue;
+
+        if (definition.skip) {
+            results.push({ name: definition.name, status: "skipped", duration: 0 });
+            continue;
+        }
+
+        const timeout = definition.timeout ?? options.timeout ?? DEFAULT_TIMEOUT;
+        const start = Date.now();
+        const resources = op_resources();
+        try {
+            const execution = new Promise<void>((resolve) => resolve(definition.fn()));
+            await withTimeout(execution, timeout, definition.name);
+            await sanitize(resources, definition);
+            results.push({
+                name: definition.name,
+                status: "passed",
+                duration: Date.now() - start,
+            });
+        } catch (error) {
+            results.push({
+                name: definition.name,
+                status: "failed",
+                duration: Date.now() - start,
+                error: formatError(error),
+            });
+        }
+    }
+
+    return results;
+}
+
+// The `kedo test` command looks up the runner once the test file is evaluated
+Object.defineProperty(test, TEST_RUNNER, { value: runTests });
+
+Kedo.test = test;
+
+export { runTests, test };
+export type { TestFn, TestOptions, TestResult };
@@ -0,0 +1,106 @@
+// | ---------------------------------------------------------------------------- |
+// |  https://html.spec.whatwg.org/multipage/web-messaging.html#broadcastchannel  |
+// |                               BroadcastChannel                               |
+// | ---------------------------------------------------------------------------- |
+
+import { EventTarget } from "@kedo/events";
+import { asyncOp } from "@kedo/utils";
+import {
+    BroadcastChannelResource,
+    op_broadcast_channel_close,
+    op_broadcast_channel_create,
+    op_broadcast_channel_post_message,
+    op_broadcast_channel_recv,
+} from "@kedo:op/web";
+import { DOMException } from "./DOMException";
+import { dispatch, EventHandler, MessageEvent } from "./MessageEvent";
+import { cloneOp } from "./StructuredClone";
+
+const _resource = Symbol("[resource]");
+const _name = Symbol("[name]");
+const _closed = Symbol("[closed]");
+const _polling = Symbol("[polling]");
+const _onmessage = Symbol("[onmessage]");
+
+/**
+ * Messages are delivered to every other `BroadcastChannel` with the same
+ * name, in this runtime and in its workers.
+ */
+class BroadcastChannel extends EventTarget {
+    [_resource]: BroadcastChannelResource;
+    [_name]: string;
+    [_closed]: boolean = false;
+    [_polling]: boolean = false;
+    [_onmessage]: EventHandler<MessageEvent> = null;
+    onmessageerror: EventHandler<MessageEvent> = null;
+
+    constructor(name: string) {
+        super();
+        if (arguments.length < 1) {
+            throw new TypeError(
+                "Failed to construct 'BroadcastChannel': 1 argument required, but only 0 present.",
+            );
+        }
+
+        this[_name] = String(name);
+        this[_resource] = op_broadcast_channel_create(this[_name]);
+    }
+
+    get name(): string {
+        return this[_name];
+    }
+
+    get onmessage(): EventHandler<MessageEvent> {
+        return this[_onmessage];
+    }
+
+    set onmessage(handler: EventHandler<MessageEvent>) {
+        this[_onmessage] = handler;
+        pollChannel(this);
+    }
+
+    addEventListener(type: string, callback: EventListener) {
+        super.addEventListener(type, callback);
+        if (type === "message") pollChannel(this);
+    }
+
+    postMessage(message: any): void {
+        if (this[_closed]) {
+            throw new DOMException("BroadcastChannel is closed", "InvalidStateError");
+        }
+
+        cloneOp(() => op_broadcast_channel_post_message(this[_resource], message));
+    }
+
+    close(): void {
+        if (this[_closed]) return;
+        this[_closed] = true;
+        op_broadcast_channel_close(this[_resource]);
+    }
+}
+
+/**
+ * Messages are only received once there is a listener,
+ * dispatches them until the channel is closed.
+ */
+async function pollChannel(channel: BroadcastChannel) {
+    if (channel[_polling] || channel[_closed]) return;
+    channel[_polling] = true;
+
+    while (true) {
+        let event: { data: any } | undefined;
+        try {
+            event = await asyncOp(op_broadcast_channel_recv, channel[_resource]);
+        } catch (error) {
+            const event = new MessageEvent("messageerror", { data: error });
+            dispatch(channel, channel.onmessageerror, event);
+            continue;
+        }
+
+        if (!event || channel[_closed]) break;
+        const message = new MessageEvent("message", { data: event.data });
+        dispatch(channel, channel[_onmessage], message);
+    }
+}
+
+export { BroadcastChannel };
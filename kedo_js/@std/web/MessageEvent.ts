@@ -0,0 +1,59 @@
+// | ------------------------------------------------------------------ |
+// |  https://html.spec.whatwg.org/multipage/comms.html#messageevent    |
+// |                          MessageEvent                              |
+// | ------------------------------------------------------------------ |
+
+import { Event, EventTarget } from "@kedo/events";
+
+type MessageEventInit<T = any> = EventInit & { data?: T };
+type ErrorEventInit = EventInit & { message?: string; error?: any };
+type EventHandler<E> = ((event: E) => void) | null;
+
+class MessageEvent<T = any> extends Event {
+    readonly data: T;
+
+    constructor(type: string, eventInitDict?: MessageEventInit<T>) {
+        super(type, eventInitDict);
+        this.data = eventInitDict?.data as T;
+    }
+}
+
+class ErrorEvent extends Event {
+    readonly message: string;
+    readonly error: any;
+
+    constructor(type: string, eventInitDict?: ErrorEventInit) {
+        super(type, eventInitDict);
+        this.message = eventInitDict?.message ?? "";
+        this.error = eventInitDict?.error;
+    }
+}
+
+function reportError(error: any) {
+    console.error(`Uncaught ${error?.stack ?? error}`);
+}
+
+/**
+ * Calls the `on<type>` handler and the listeners of the target,
+ * returns false if a handler threw or canceled the event.
+ */
+function dispatch(
+    target: EventTarget,
+    handler: EventHandler<Event> | undefined,
+    event: Event,
+): boolean {
+    let handled = true;
+    if (typeof handler === "function") {
+        try {
+            handler.call(target, event);
+        } catch (error) {
+            reportError(error);
+            handled = false;
+        }
+    }
+
+    return target.dispatchEvent(event) && handled;
+}
+
+export { dispatch, ErrorEvent, MessageEvent };
+export type { EventHandler };
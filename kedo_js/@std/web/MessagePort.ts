@@ -0,0 +1,111 @@
+// | ------------------------------------------------------------------------ |
+// |  https://html.spec.whatwg.org/multipage/web-messaging.html#message-ports |
+// |                      MessageChannel / MessagePort                        |
+// | ------------------------------------------------------------------------ |
+
+import { EventTarget } from "@kedo/events";
+import { asyncOp } from "@kedo/utils";
+import {
+    MessagePortResource,
+    op_message_channel_create,
+    op_message_port_close,
+    op_message_port_post_message,
+    op_message_port_recv,
+} from "@kedo:op/web";
+import { dispatch, EventHandler, MessageEvent } from "./MessageEvent";
+import {
+    cloneOp,
+    StructuredSerializeOptions,
+    transferList,
+} from "./StructuredClone";
+
+const _create = Symbol("[create]");
+const _resource = Symbol("[resource]");
+const _started = Symbol("[started]");
+const _closed = Symbol("[closed]");
+const _onmessage = Symbol("[onmessage]");
+
+class MessagePort extends EventTarget {
+    [_resource]: MessagePortResource;
+    [_started]: boolean = false;
+    [_closed]: boolean = false;
+    [_onmessage]: EventHandler<MessageEvent> = null;
+    onmessageerror: EventHandler<MessageEvent> = null;
+
+    constructor(key: typeof _create, resource: MessagePortResource) {
+        super();
+        if (key !== _create) {
+            throw new TypeError("Illegal constructor");
+        }
+
+        this[_resource] = resource;
+    }
+
+    get onmessage(): EventHandler<MessageEvent> {
+        return this[_onmessage];
+    }
+
+    /** Setting the handler starts the port */
+    set onmessage(handler: EventHandler<MessageEvent>) {
+        this[_onmessage] = handler;
+        this.start();
+    }
+
+    postMessage(
+        message: any,
+        options?: ArrayBuffer[] | StructuredSerializeOptions,
+    ): void {
+        const transfer = transferList(options);
+        cloneOp(() =>
+            op_message_port_post_message(this[_resource], message, transfer),
+        );
+    }
+
+    /** Starts dispatching the messages queued in the port */
+    start(): void {
+        if (this[_started] || this[_closed]) return;
+        this[_started] = true;
+        pollPort(this);
+    }
+
+    close(): void {
+        if (this[_closed]) return;
+        this[_closed] = true;
+        op_message_port_close(this[_resource]);
+    }
+
+    get [Symbol.toStringTag]() {
+        return "MessagePort";
+    }
+}
+
+/** Dispatches the messages of the port until it's closed */
+async function pollPort(port: MessagePort) {
+    while (true) {
+        let event: { data: any } | undefined;
+        try {
+            event = await asyncOp(op_message_port_recv, port[_resource]);
+        } catch (error) {
+            const event = new MessageEvent("messageerror", { data: error });
+            dispatch(port, port.onmessageerror, event);
+            continue;
+        }
+
+        if (!event) break;
+        const message = new MessageEvent("message", { data: event.data });
+        dispatch(port, port[_onmessage], message);
+    }
+}
+
+class MessageChannel {
+    readonly port1: MessagePort;
+    readonly port2: MessagePort;
+
+    constructor() {
+        const [port1, port2] = op_message_channel_create();
+        this.port1 = new MessagePort(_create, port1);
+        this.port2 = new MessagePort(_create, port2);
+    }
+}
+
+export { MessageChannel, MessagePort };
@@ -0,0 +1,53 @@
+// | ------------------------------------------------------------------------------- |
+// |  https://html.spec.whatwg.org/multipage/structured-data.html#structured-cloning |
+// |                              structuredClone                                    |
+// | ------------------------------------------------------------------------------- |
+
+import { op_structured_clone } from "@kedo:op/web";
+import { DOMException } from "./DOMException";
+
+type StructuredSerializeOptions = { transfer?: ArrayBuffer[] };
+
+/**
+ * The serializer throws errors named `DataCloneError`,
+ * they are rethrown as a `DOMException` like the spec requires.
+ */
+function cloneOp<T>(fn: () => T): T {
+    try {
+        return fn();
+    } catch (error: any) {
+        if (error?.name === "DataCloneError") {
+            throw new DOMException(error.message, "DataCloneError");
+        }
+
+        throw error;
+    }
+}
+
+/**
+ * `postMessage(message, transfer)` accepts the transfer list
+ * or the `{ transfer }` options.
+ */
+function transferList(
+    options: ArrayBuffer[] | StructuredSerializeOptions | undefined,
+): ArrayBuffer[] {
+    if (options === undefined || options === null) return [];
+    if (Array.isArray(options)) return options;
+    return options.transfer ?? [];
+}
+
+function structuredClone<T = any>(
+    value: T,
+    options?: StructuredSerializeOptions,
+): T {
+    if (arguments.length < 1) {
+        throw new TypeError(
+            "Failed to execute 'structuredClone': 1 argument required, but only 0 present.",
+        );
+    }
+
+    return cloneOp(() => op_structured_clone(value, transferList(options)));
+}
+
+export { cloneOp, structuredClone, transferList };
+export type { StructuredSerializeOptions };
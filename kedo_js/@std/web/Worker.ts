@@ -0,0 +1,175 @@
+// | ------------------------------------------------------------ |
+// |  https://html.spec.whatwg.org/multipage/workers.html#worker  |
+// |                           Worker                             |
+// | ------------------------------------------------------------ |
+
+import { EventTarget } from "@kedo/events";
+import { asyncOp } from "@kedo/utils";
+import {
+    op_worker_close,
+    op_worker_create,
+    op_worker_post_message,
+    op_worker_recv,
+    op_worker_scope,
+    op_worker_scope_post_error,
+    op_worker_scope_post_message,
+    op_worker_scope_recv,
+    op_worker_terminate,
+    WorkerResource,
+} from "@kedo:op/web";
+import { dispatch, ErrorEvent, EventHandler, MessageEvent } from "./MessageEvent";
+import {
+    cloneOp,
+    StructuredSerializeOptions,
+    transferList,
+} from "./StructuredClone";
+
+type WorkerOptions = {
+    type?: "classic" | "module";
+    name?: string;
+};
+
+type PostMessageOptions = ArrayBuffer[] | StructuredSerializeOptions;
+
+const _resource = Symbol("[resource]");
+const _terminated = Symbol("[terminated]");
+
+function createErrorEvent(message: string, stack: string): ErrorEvent {
+    const error = new Error(message);
+    error.stack = stack;
+    return new ErrorEvent("error", { message, error, cancelable: true });
+}
+
+// ------------------------------------------------------------
+// |                     Parent side                          |
+// ------------------------------------------------------------
+class Worker extends EventTarget {
+    [_resource]: WorkerResource;
+    [_terminated]: boolean = false;
+    onmessage: EventHandler<MessageEvent> = null;
+    onmessageerror: EventHandler<MessageEvent> = null;
+    onerror: EventHandler<ErrorEvent> = null;
+
+    constructor(specifier: string | URL, options: WorkerOptions = {}) {
+        super();
+        if (options?.type !== "module") {
+            throw new TypeError(
+                'Only module workers are supported, use { type: "module" }',
+            );
+        }
+
+        this[_resource] = op_worker_create(String(specifier), options.name ?? "");
+        pollWorker(this);
+    }
+
+    postMessage(message: any, options?: PostMessageOptions): void {
+        if (this[_terminated]) return;
+        const transfer = transferList(options);
+        cloneOp(() =>
+            op_worker_post_message(this[_resource], message, transfer),
+        );
+    }
+
+    terminate(): void {
+        if (this[_terminated]) return;
+        this[_terminated] = true;
+        op_worker_terminate(this[_resource]);
+    }
+}
+
+/** Dispatches the worker messages until the worker exits */
+async function pollWorker(worker: Worker) {
+    while (true) {
+        let event: WorkerEvent | undefined;
+        try {
+            event = await asyncOp(op_worker_recv, worker[_resource]);
+        } catch (error) {
+            if (!worker[_terminated]) {
+                const event = new MessageEvent("messageerror", { data: error });
+                dispatch(worker, worker.onmessageerror, event);
+            }
+            continue;
+        }
+
+        // the worker exited, messages received after terminate() are dropped
+        if (!event) break;
+        if (worker[_terminated]) continue;
+
+        if (event.type === "message") {
+            const message = new MessageEvent("message", { data: event.data });
+            dispatch(worker, worker.onmessage, message);
+            continue;
+        }
+
+        const error = createErrorEvent(event.message, event.stack);
+        if (dispatch(worker, worker.onerror, error)) {
+            console.error(`Uncaught (in worker) ${event.stack || event.message}`);
+        }
+    }
+}
+
+// ------------------------------------------------------------
+// |                     Worker side                          |
+// ------------------------------------------------------------
+function installWorkerScope(scope: { name: string }) {
+    const target = new EventTarget();
+    const global = globalThis as any;
+    const define = (name: string, value: any) =>
+        Object.defineProperty(globalThis, name, {
+            value,
+            writable: true,
+            configurable: true,
+            enumerable: true,
+        });
+
+    define("self", globalThis);
+    define("name", scope.name);
+    define("onmessage", null);
+    define("onmessageerror", null);
+    define("postMessage", (message: any, options?: PostMessageOptions) => {
+        const transfer = transferList(options);
+        cloneOp(() => op_worker_scope_post_message(message, transfer));
+    });
+    define("close", () => op_worker_close());
+    define("addEventListener", target.addEventListener.bind(target));
+    define("removeEventListener", target.removeEventListener.bind(target));
+    define("dispatchEvent", target.dispatchEvent.bind(target));
+
+    (async () => {
+        while (true) {
+            let event: WorkerEvent | undefined;
+            try {
+                event = await asyncOp(op_worker_scope_recv);
+            } catch (error) {
+                const event = new MessageEvent("messageerror", { data: error });
+                dispatch(target, global.onmessageerror, event);
+                continue;
+            }
+
+            // the parent terminated or dropped the worker
+            if (!event) break;
+            if (event.type !== "message") continue;
+
+            const message = new MessageEvent("message", { data: event.data });
+            try {
+                if (typeof global.onmessage === "function") {
+                    global.onmessage.call(globalThis, message);
+                }
+                target.dispatchEvent(message);
+            } catch (error: any) {
+                // uncaught errors are dispatched as an error event in the parent
+                op_worker_scope_post_error(
+                    String(error?.message ?? error),
+                    String(error?.stack ?? ""),
+                );
+            }
+        }
+    })();
+}
+
+const scope = op_worker_scope();
+if (scope) {
+    installWorkerScope(scope);
+}
+
+export { Worker };
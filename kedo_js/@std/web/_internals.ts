@@ -16,3 +16,8 @@ export { Response } from "./Response";
 export { serve } from "./Server";
 export { TextDecoder, TextEncoder } from "./TextDecoder";
 export { URL, URLSearchParams } from "./URL";
+export { BroadcastChannel } from "./BroadcastChannel";
+export { ErrorEvent, MessageEvent } from "./MessageEvent";
+export { MessageChannel, MessagePort } from "./MessagePort";
+export { structuredClone } from "./StructuredClone";
+export { Worker } from "./Worker";
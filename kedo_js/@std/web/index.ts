@@ -1,29 +1,43 @@
 import {
     AbortController,
     AbortSignal,
+    BroadcastChannel,
     DOMException,
+    ErrorEvent,
     fetch,
     Headers,
+    MessageChannel,
+    MessageEvent,
+    MessagePort,
     Request,
     Response,
     serve,
+    structuredClone,
     TextDecoder, TextEncoder,
     URL,
-    URLSearchParams
+    URLSearchParams,
+    Worker
 } from "@kedo:int/std/web";
 
 export {
     AbortController,
     AbortSignal,
+    BroadcastChannel,
     DOMException,
+    ErrorEvent,
     fetch,
     Headers,
+    MessageChannel,
+    MessageEvent,
+    MessagePort,
     Request,
     Response,
     serve,
+    structuredClone,
     TextDecoder,
     TextEncoder,
     URL,
-    URLSearchParams
+    URLSearchParams,
+    Worker
 };
 
@@ -1,7 +1,7 @@
 mod module_manager;
 mod module_scanner;
 
-use bundler::BundleArgs;
+use bundler::{BundleArgs, SourceMapMode, TransformOptions};
 use clap::{Parser, Subcommand};
 use module_manager::ModuleManager;
 
@@ -43,6 +43,8 @@ fn main() {
 
     manager.add_external_module("@kedo:op/web".to_string());
     manager.add_external_module("@kedo:op/fs".to_string());
+    manager.add_external_module("@kedo:op/runtime".to_string());
+    manager.add_external_module("@kedo:op/module".to_string());
     manager.add_entry("index.ts".to_string());
 
     let entries = manager.get_entries().clone();
@@ -67,12 +69,25 @@ fn main() {
                     entries: vec![(entry_path.clone(), entry_path.clone().into())],
                     outputs: vec![output_path.into()],
                     minify: *minify,
+                    // the classes of the std are matched by name, e.g. in `inspect`
+                    keep_classnames: true,
+                    keep_fnames: true,
+                    packages: None,
+                    // the std modules are embedded in the binary, so are their maps
+                    source_map: Some(SourceMapMode::Inline),
+                    tree_shaking: true,
+                    // every std module is loaded by its own specifier, see `StdModuleLoader`
+                    splitting: false,
+                    transform: TransformOptions::default(),
                 };
 
                 let result = bundler::bundle(args);
                 match result {
                     Ok(build_result) => {
                         total_time += build_result.duration.as_millis();
+                        for output in &build_result.outputs {
+                            println!("Created {}", output);
+                        }
                         println!(
                             "Bundled in \x1b[32m{:?}ms\x1b[0m",
                             build_result.duration.as_millis()
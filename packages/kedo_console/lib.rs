@@ -1,5 +1,6 @@
+use kedo_core::{downcast_state, JsProctectedCallable};
 use kedo_utils::define_globals;
-use rust_jsc::{callback, JSContext, JSObject, JSResult, JSString, JSValue};
+use rust_jsc::{callback, JSContext, JSFunction, JSObject, JSResult, JSValue};
 
 #[derive(Debug)]
 enum LogMessage {
@@ -9,100 +10,219 @@ enum LogMessage {
     Error,
 }
 
-pub fn format_args(_ctx: &JSContext, args: &[JSValue]) -> Result<String, JSValue> {
-    let mut arg_index = 1;
-    let target = match args.get(0) {
-        Some(value) => value,
+/// Formats the arguments of `console.log`, the first one can have `%s`, `%d`, `%i`,
+/// `%f`, `%o` and `%O` specifiers and the others are appended, see [`inspect`].
+pub fn format_args(ctx: &JSContext, args: &[JSValue]) -> Result<String, JSValue> {
+    let (mut formatted, mut arg_index) = match args.first() {
+        Some(target) if target.is_string() => (
+            target
+                .as_string()
+                .map(|string| string.to_string())
+                .unwrap_or_default(),
+            1,
+        ),
+        Some(_) => (String::new(), 0),
         None => return Ok("".to_string()),
     };
 
-    // TODO: check if target is a string or object
-    let mut formatted = target
-        .as_string()
-        .expect("Failed to convert to string")
-        .to_string();
-
-    while let Some(percent_pos) = formatted.find('%') {
-        if arg_index >= args.len() {
+    let mut search_from = 0;
+    while arg_index > 0 && arg_index < args.len() {
+        let Some(offset) = formatted[search_from..].find('%') else {
             break;
-        }
+        };
+        let percent_pos = search_from + offset;
 
-        let specifier = formatted.chars().nth(percent_pos + 1);
-        match specifier {
-            Some('o') | Some('O') => {
-                let arg = &args[arg_index];
-                let json = arg
-                    .as_json_string(0)
-                    .unwrap_or_else(|_| JSString::from("[Object]"));
-                formatted = formatted.replace(
-                    &formatted[percent_pos..=percent_pos + 1],
-                    json.to_string().as_str(),
-                );
-            }
+        let arg = &args[arg_index];
+        let replacement = match formatted[percent_pos + 1..].chars().next() {
+            Some('o') | Some('O') => format_value(ctx, arg, true),
             Some('d') | Some('i') => {
-                let arg = &args[arg_index].as_number().unwrap_or(0.into());
-                formatted = formatted.replace(
-                    &formatted[percent_pos..=percent_pos + 1],
-                    &format!("{:}", arg),
-                );
+                let number = arg.as_number().unwrap_or(f64::NAN);
+                format!("{}", number.trunc())
             }
-            Some('s') => {
-                let arg = &args[arg_index]
-                    .as_string()
-                    .unwrap_or_else(|_| JSString::from("[Invalid string]"));
-                formatted = formatted.replace(
-                    &formatted[percent_pos..=percent_pos + 1],
-                    arg.to_string().as_str(),
-                );
+            Some('s') => format_value(ctx, arg, false),
+            Some('f') => format!("{}", arg.as_number().unwrap_or(f64::NAN)),
+            _ => {
+                search_from = percent_pos + 1;
+                continue;
             }
-            Some('f') => {
-                let arg = &args[arg_index].as_number().unwrap_or(0.into());
-                formatted = formatted.replace(
-                    &formatted[percent_pos..=percent_pos + 1],
-                    &format!("{:}", arg),
-                );
-            }
-            _ => {}
-        }
+        };
 
+        formatted.replace_range(percent_pos..percent_pos + 2, &replacement);
+        search_from = percent_pos + replacement.len();
         arg_index += 1;
     }
 
     // add remaining args
     for arg in args.iter().skip(arg_index) {
-        match arg.get_type() {
-            rust_jsc::JSValueType::Number => {
-                formatted.push_str(format!(" {}", arg.as_number().unwrap()).as_str());
-            }
-            rust_jsc::JSValueType::String => {
-                formatted.push_str(format!(" {}", arg.as_string().unwrap()).as_str());
-            }
-            rust_jsc::JSValueType::Boolean => {
-                formatted.push_str(format!(" {}", arg.as_boolean()).as_str());
-            }
-            rust_jsc::JSValueType::Null => {
-                formatted.push_str(" null");
-            }
-            rust_jsc::JSValueType::Undefined => {
-                formatted.push_str(" undefined");
-            }
-            rust_jsc::JSValueType::Object => {
-                formatted.push_str(
-                    format!(
-                        " {}",
-                        arg.as_json_string(0)
-                            .unwrap_or_else(|_arg| { JSString::from("[Object]") })
-                    )
-                    .as_str(),
-                );
-            }
-            _ => {}
+        if !formatted.is_empty() {
+            formatted.push(' ');
         }
+        formatted.push_str(&format_value(ctx, arg, false));
     }
 
     Ok(formatted)
 }
 
+/// Formats a value like `console.log` does, `quote` quotes a string at the top
+/// level. Objects are printed two levels deep, e.g. `Map(1) { "a" => [ 1, 2 ] }`.
+const INSPECT: &str = r#"
+(function () {
+    const MAX_DEPTH = 2;
+    const MAX_LINE = 72;
+    const quote = (string) => JSON.stringify(string);
+    const isIdentifier = (key) => /^[A-Za-z_$][\w$]*$/.test(key);
+
+    function formatKey(key) {
+        if (typeof key === "symbol") return `[${String(key)}]`;
+        return isIdentifier(key) ? key : quote(key);
+    }
+
+    function formatFunction(fn) {
+        const name = fn.name || "(anonymous)";
+        if (Function.prototype.toString.call(fn).startsWith("class")) {
+            return fn.name ? `[class ${fn.name}]` : "[class (anonymous)]";
+        }
+        const kind = fn.constructor && fn.constructor.name;
+        const prefix = kind === "AsyncFunction" || kind === "GeneratorFunction"
+            || kind === "AsyncGeneratorFunction" ? kind : "Function";
+        return `[${prefix}: ${name}]`;
+    }
+
+    function join(open, entries, close, indent) {
+        if (entries.length === 0) return `${open}${close}`;
+        const line = `${open} ${entries.join(", ")} ${close}`;
+        if (line.length <= MAX_LINE && !line.includes("\n")) return line;
+        const padding = "  ".repeat(indent + 1);
+        const lines = entries.map(
+            (entry) => padding + entry.split("\n").join(`\n${padding}`),
+        );
+        return `${open}\n${lines.join(",\n")}\n${"  ".repeat(indent)}${close}`;
+    }
+
+    function properties(value, depth, seen, skipIndexes) {
+        const entries = [];
+        for (const key of Reflect.ownKeys(value)) {
+            const descriptor = Object.getOwnPropertyDescriptor(value, key);
+            if (!descriptor.enumerable) continue;
+            if (skipIndexes && typeof key === "string" && /^\d+$/.test(key)) continue;
+            const formatted = "get" in descriptor || "set" in descriptor
+                ? "[Getter/Setter]"
+                : format(descriptor.value, depth + 1, seen);
+            entries.push(`${formatKey(key)}: ${formatted}`);
+        }
+        return entries;
+    }
+
+    function formatObject(value, depth, seen) {
+        if (value instanceof Error) {
+            const stack = typeof value.stack === "string" ? value.stack : "";
+            if (!stack) return `[${value}]`;
+            return stack.startsWith(String(value)) ? stack : `${value}\n${stack}`;
+        }
+        if (value instanceof Date) {
+            return isNaN(value.getTime()) ? "Invalid Date" : value.toISOString();
+        }
+        if (value instanceof RegExp) return String(value);
+        if (value instanceof Promise) return "Promise {}";
+        if (value instanceof WeakMap) return "WeakMap { <items unknown> }";
+        if (value instanceof WeakSet) return "WeakSet { <items unknown> }";
+        if (value instanceof ArrayBuffer) {
+            return `ArrayBuffer { byteLength: ${value.byteLength} }`;
+        }
+
+        const prototype = Object.getPrototypeOf(value);
+        const constructor = prototype && prototype.constructor;
+        const name = prototype === null
+            ? "[Object: null prototype]"
+            : (typeof constructor === "function" && constructor.name) || "Object";
+        const nested = depth > MAX_DEPTH;
+
+        const typed = ArrayBuffer.isView(value) && !(value instanceof DataView);
+        if (Array.isArray(value) || typed) {
+            const prefix = Array.isArray(value) && name === "Array"
+                ? ""
+                : `${name}(${value.length}) `;
+            if (nested) return Array.isArray(value) ? "[Array]" : `[${name}]`;
+            const items = Array.from(value, (item) => format(item, depth + 1, seen));
+            const entries = items.concat(properties(value, depth, seen, true));
+            return prefix + join("[", entries, "]", depth);
+        }
+        if (value instanceof Map) {
+            if (nested) return "[Map]";
+            const entries = Array.from(value, ([key, item]) =>
+                `${format(key, depth + 1, seen)} => ${format(item, depth + 1, seen)}`);
+            return `Map(${value.size}) ` + join("{", entries, "}", depth);
+        }
+        if (value instanceof Set) {
+            if (nested) return "[Set]";
+            const entries = Array.from(value, (item) => format(item, depth + 1, seen));
+            return `Set(${value.size}) ` + join("{", entries, "}", depth);
+        }
+
+        if (nested) return `[${name}]`;
+        const prefix = name === "Object" ? "" : `${name} `;
+        return prefix + join("{", properties(value, depth, seen, false), "}", depth);
+    }
+
+    function format(value, depth, seen) {
+        switch (typeof value) {
+            case "string": return quote(value);
+            case "number": return Object.is(value, -0) ? "-0" : String(value);
+            case "bigint": return `${value}n`;
+            case "symbol": return String(value);
+            case "function": return formatFunction(value);
+            case "undefined": return "undefined";
+            case "boolean": return String(value);
+        }
+        if (value === null) return "null";
+        if (seen.includes(value)) return "[Circular]";
+
+        seen.push(value);
+        try {
+            return formatObject(value, depth, seen);
+        } finally {
+            seen.pop();
+        }
+    }
+
+    return (value, quoteString) => {
+        if (typeof value === "string" && !quoteString) return value;
+        return format(value, 0, []);
+    };
+})()
+"#;
+
+/// The formatter of the context, compiled on first use and kept in the state
+fn inspector(ctx: &JSContext) -> JSResult<JsProctectedCallable> {
+    let state = downcast_state(ctx);
+    if let Some(inspector) = state.inspector().borrow().as_ref() {
+        return Ok(inspector.clone());
+    }
+
+    let function = ctx.evaluate_script(INSPECT, None)?;
+    let inspector =
+        JsProctectedCallable::new(JSFunction::from(function.as_object()?), vec![]);
+    state.inspector().replace(Some(inspector.clone()));
+    Ok(inspector)
+}
+
+fn format_value(ctx: &JSContext, value: &JSValue, quote: bool) -> String {
+    inspector(ctx)
+        .and_then(|inspect| {
+            let args = [value.clone(), JSValue::boolean(ctx, quote)];
+            inspect.callable().call(None, &args)
+        })
+        .and_then(|formatted| formatted.as_string())
+        .map(|formatted| formatted.to_string())
+        .unwrap_or_else(|_| "[Object]".to_string())
+}
+
+/// Formats a single value the way the REPL echoes results, the same way
+/// `console.log` formats its arguments except that strings are quoted.
+pub fn inspect(ctx: &JSContext, value: &JSValue) -> String {
+    format_value(ctx, value, true)
+}
+
 pub struct Console;
 
 impl Console {
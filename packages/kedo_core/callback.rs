@@ -1,22 +1,68 @@
-#[derive(Clone)]
-pub struct JsProctectedCallable {
-    pub callable: rust_jsc::JSFunction,
-    pub args: Vec<rust_jsc::JSValue>,
+use crate::resource_table::ResourceHandle;
+use std::rc::Rc;
+
+struct ProtectedFunction {
+    function: rust_jsc::JSFunction,
+    // closed along with the function, e.g. the timer that owns the callback
+    resource: Option<ResourceHandle>,
 }
 
-impl Drop for JsProctectedCallable {
+impl Drop for ProtectedFunction {
     fn drop(&mut self) {
-        self.callable.unprotect();
+        self.function.unprotect();
     }
 }
 
+/// A JS function protected from GC along with its arguments.
+/// Clones share the protection, the function is unprotected once the last clone is dropped.
+#[derive(Clone)]
+pub struct JsProctectedCallable {
+    inner: Rc<ProtectedFunction>,
+    pub args: Vec<rust_jsc::JSValue>,
+}
+
 impl JsProctectedCallable {
     pub fn new(callable: rust_jsc::JSFunction, args: Vec<rust_jsc::JSValue>) -> Self {
+        Self::create(callable, args, None)
+    }
+
+    /// Keeps `resource` open until the callable is dropped
+    pub fn with_resource(
+        callable: rust_jsc::JSFunction,
+        args: Vec<rust_jsc::JSValue>,
+        resource: ResourceHandle,
+    ) -> Self {
+        Self::create(callable, args, Some(resource))
+    }
+
+    fn create(
+        callable: rust_jsc::JSFunction,
+        args: Vec<rust_jsc::JSValue>,
+        resource: Option<ResourceHandle>,
+    ) -> Self {
         callable.protect();
-        Self { callable, args }
+        Self {
+            inner: Rc::new(ProtectedFunction {
+                function: callable,
+                resource,
+            }),
+            args,
+        }
+    }
+
+    pub fn callable(&self) -> &rust_jsc::JSFunction {
+        &self.inner.function
+    }
+
+    /// Closes the resource before the callable is dropped, e.g. a timeout that
+    /// runs its callback for the last time
+    pub fn close_resource(&self) {
+        if let Some(resource) = &self.inner.resource {
+            resource.close();
+        }
     }
 
     pub fn call(&self) -> rust_jsc::JSResult<rust_jsc::JSValue> {
-        self.callable.call(None, &self.args.as_slice())
+        self.inner.function.call(None, &self.args.as_slice())
     }
 }
@@ -0,0 +1,830 @@
+use crate::{
+    define_exports,
+    modules::{js_string_literal, module_path, ModuleSource},
+    state::downcast_state,
+};
+use kedo_utils::js_error_typ;
+use rust_jsc::{callback, JSContext, JSFunction, JSObject, JSResult, JSValue};
+use serde_json::Value;
+use std::{collections::BTreeSet, path::Path};
+
+/// Module system of CommonJS, `ops` are the ops of `@kedo:op/module`. Modules are
+/// cached by file name in `require.cache`, a module required while it loads
+/// returns its partial exports.
+const REQUIRE_SOURCE: &str = r#"
+(function (ops) {
+    const cache = Object.create(null);
+
+    class Module {
+        constructor(filename, parent) {
+            this.id = filename;
+            this.filename = filename;
+            this.path = filename.replace(/[\\/][^\\/]*$/, "");
+            this.exports = {};
+            this.parent = parent;
+            this.children = [];
+            this.loaded = false;
+            this.require = createRequire(filename, this);
+            if (parent) {
+                parent.children.push(this);
+            }
+        }
+    }
+
+    function load(filename, parent = null) {
+        const cached = cache[filename];
+        if (cached) {
+            return cached.exports;
+        }
+
+        const module = new Module(filename, parent);
+        cache[filename] = module;
+        try {
+            const { format, source } = ops.op_require_load(filename);
+            if (format === "json") {
+                module.exports = JSON.parse(source);
+            } else {
+                const wrapper = ops.op_require_compile(source, filename);
+                wrapper.call(
+                    module.exports,
+                    module.exports,
+                    module.require,
+                    module,
+                    filename,
+                    module.path,
+                );
+            }
+        } catch (error) {
+            delete cache[filename];
+            throw error;
+        }
+
+        module.loaded = true;
+        return module.exports;
+    }
+
+    function createRequire(referrer, parent = null) {
+        referrer = String(referrer);
+        function require(specifier) {
+            return load(require.resolve(specifier), parent);
+        }
+
+        require.resolve = function resolve(specifier) {
+            return ops.op_require_resolve(String(specifier), referrer);
+        };
+        require.cache = cache;
+        return require;
+    }
+
+    return { createRequire: (referrer) => createRequire(referrer), load };
+})
+"#;
+
+/// Format of a module of the file system, see
+/// https://nodejs.org/api/packages.html#determining-module-system
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModuleFormat {
+    Module,
+    CommonJs,
+    Json,
+}
+
+impl ModuleFormat {
+    /// Detects the format by the extension, the `type` of the closest package.json
+    /// for `.js` files and the syntax otherwise. `None` means the source has neither
+    /// ES module nor CommonJS syntax, imports load it as an ES module and `require`
+    /// as CommonJS.
+    pub fn detect(path: &Path, source: &str) -> Option<Self> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => return Some(ModuleFormat::Json),
+            Some("cjs") => return Some(ModuleFormat::CommonJs),
+            Some("mjs" | "ts" | "mts" | "tsx") => return Some(ModuleFormat::Module),
+            Some("js") => match package_type(path).as_deref() {
+                Some("module") => return Some(ModuleFormat::Module),
+                Some("commonjs") => return Some(ModuleFormat::CommonJs),
+                _ => {}
+            },
+            _ => {}
+        }
+
+        let tokens = tokenize(source);
+        if has_module_syntax(&tokens) {
+            Some(ModuleFormat::Module)
+        } else if has_commonjs_syntax(&tokens) {
+            Some(ModuleFormat::CommonJs)
+        } else {
+            None
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            ModuleFormat::Module => "module",
+            ModuleFormat::CommonJs => "commonjs",
+            ModuleFormat::Json => "json",
+        }
+    }
+}
+
+/// `type` field of the closest package.json
+fn package_type(path: &Path) -> Option<String> {
+    let source = path
+        .ancestors()
+        .skip(1)
+        .find_map(|dir| std::fs::read_to_string(dir.join("package.json")).ok())?;
+    let package: Value = serde_json::from_str(&source).ok()?;
+    package.get("type")?.as_str().map(String::from)
+}
+
+/// Exports of a CommonJS module found without running it,
+/// `reexports` are the specifiers whose exports are exported too.
+#[derive(Debug, Default, PartialEq)]
+pub struct CommonJsExports {
+    pub exports: BTreeSet<String>,
+    pub reexports: Vec<String>,
+}
+
+/// Detects the named exports of a CommonJS module, the patterns are the ones
+/// emitted by hand written modules and by the TypeScript and Babel compilers.
+///
+/// e.g.
+/// ```js
+/// exports.a = 1;
+/// module.exports.b = 2;
+/// exports["c"] = 3;
+/// Object.defineProperty(exports, "d", { get: () => 4 });
+/// module.exports = { e, f: 6, g() {} };
+/// module.exports = require("./other"); // reexport
+/// __exportStar(require("./other"), exports); // reexport
+/// ```
+pub fn commonjs_exports(source: &str) -> CommonJsExports {
+    let tokens = tokenize(source);
+    let mut result = CommonJsExports::default();
+    let token = |index: usize| tokens.get(index);
+    let punct = |index: usize, c: char| token(index) == Some(&Token::Punct(c));
+    let ident = |index: usize, name: &str| token(index) == Some(&Token::Ident(name));
+    // `=` but not `==` or `=>`
+    let assign = |index: usize| {
+        punct(index, '=') && !punct(index + 1, '=') && !punct(index + 1, '>')
+    };
+    // `exports` or `module.exports`, returns the index after it
+    let exports_at = |index: usize| -> Option<usize> {
+        if index > 0 && punct(index - 1, '.') {
+            return None;
+        }
+        if ident(index, "module") && punct(index + 1, '.') && ident(index + 2, "exports")
+        {
+            return Some(index + 3);
+        }
+        ident(index, "exports").then_some(index + 1)
+    };
+    let require_at = |index: usize| -> Option<String> {
+        match (token(index), token(index + 2), token(index + 3)) {
+            (Some(Token::Ident("require")), Some(Token::Str(specifier)), Some(_))
+                if punct(index + 1, '(') && punct(index + 3, ')') =>
+            {
+                Some(specifier.clone())
+            }
+            _ => None,
+        }
+    };
+
+    for index in 0..tokens.len() {
+        if let Some(next) = exports_at(index) {
+            match (token(next), token(next + 1), token(next + 2)) {
+                // exports.name =
+                (Some(Token::Punct('.')), Some(Token::Ident(name)), _)
+                    if assign(next + 2) =>
+                {
+                    result.exports.insert(name.to_string());
+                }
+                // exports["name"] =
+                (Some(Token::Punct('[')), Some(Token::Str(name)), Some(_))
+                    if punct(next + 2, ']') && assign(next + 3) =>
+                {
+                    result.exports.insert(name.clone());
+                }
+                // module.exports = ...
+                (Some(Token::Punct('=')), _, _)
+                    if assign(next) && ident(index, "module") =>
+                {
+                    if let Some(specifier) = require_at(next + 1) {
+                        result.reexports.push(specifier);
+                    } else if punct(next + 1, '{') {
+                        object_keys(&tokens, next + 1, &mut result);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Object.defineProperty(exports, "name", ...)
+        if ident(index, "Object")
+            && punct(index + 1, '.')
+            && ident(index + 2, "defineProperty")
+            && punct(index + 3, '(')
+        {
+            if let Some(next) = exports_at(index + 4) {
+                if let (true, Some(Token::Str(name))) =
+                    (punct(next, ','), token(next + 1))
+                {
+                    result.exports.insert(name.clone());
+                }
+            }
+        }
+
+        // __exportStar(require("name"), exports)
+        if let Some(Token::Ident(name)) = token(index) {
+            if (name.ends_with("__exportStar") || *name == "__export")
+                && punct(index + 1, '(')
+            {
+                if let Some(specifier) = require_at(index + 2) {
+                    result.reexports.push(specifier);
+                }
+            }
+        }
+    }
+
+    result
+        .exports
+        .retain(|name| name != "default" && name != "__esModule" && is_identifier(name));
+    result
+}
+
+/// Keys of the object literal at `start`, the spreads of `require` are reexports
+fn object_keys(tokens: &[Token], start: usize, result: &mut CommonJsExports) {
+    let mut depth = 0;
+    let mut key_position = true;
+    let mut index = start;
+    while index < tokens.len() {
+        let at_key = depth == 1 && key_position;
+        match &tokens[index] {
+            Token::Punct('{' | '[' | '(') => depth += 1,
+            Token::Punct('}' | ']' | ')') => {
+                depth -= 1;
+                if depth == 0 {
+                    return;
+                }
+            }
+            Token::Punct(',') if depth == 1 => {
+                key_position = true;
+                index += 1;
+                continue;
+            }
+            Token::Punct('.') if at_key => {
+                // ...require("name")
+                let spread = (index..index + 3)
+                    .all(|index| tokens.get(index) == Some(&Token::Punct('.')));
+                if spread {
+                    if let Some(Token::Ident("require")) = tokens.get(index + 3) {
+                        if let (Some(Token::Punct('(')), Some(Token::Str(specifier))) =
+                            (tokens.get(index + 4), tokens.get(index + 5))
+                        {
+                            result.reexports.push(specifier.clone());
+                        }
+                    }
+                }
+            }
+            Token::Ident(name) if at_key => {
+                // get name() {}, set name(value) {}, async name() {}
+                let accessor = matches!(*name, "get" | "set" | "async");
+                let key = match tokens.get(index + 1) {
+                    Some(Token::Ident(key)) if accessor => {
+                        index += 1;
+                        key.to_string()
+                    }
+                    _ => name.to_string(),
+                };
+                result.exports.insert(key);
+            }
+            Token::Str(name) if at_key => {
+                result.exports.insert(name.clone());
+            }
+            _ => {}
+        }
+
+        if depth == 1 && !matches!(tokens[index], Token::Punct('{')) {
+            key_position = false;
+        }
+        index += 1;
+    }
+}
+
+/// Source of the ES module of a CommonJS module, `module.exports` is the default
+/// export and the named exports are its properties when the module is evaluated.
+pub fn commonjs_wrapper<'a>(
+    module_id: &str,
+    exports: impl IntoIterator<Item = &'a String>,
+) -> String {
+    let mut source = format!(
+        "import {{ load }} from \"@kedo:op/module\";\nconst module = load({});\nexport default module;\n",
+        js_string_literal(module_id)
+    );
+
+    let mut names = Vec::new();
+    for (index, name) in exports.into_iter().enumerate() {
+        source.push_str(&format!(
+            "const __export{} = module == null ? undefined : module[{}];\n",
+            index,
+            js_string_literal(name)
+        ));
+        names.push(format!("__export{} as {}", index, name));
+    }
+
+    if !names.is_empty() {
+        source.push_str(&format!("export {{ {} }};\n", names.join(", ")));
+    }
+
+    source
+}
+
+pub(crate) fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    let start = |c: char| c.is_alphabetic() || c == '_' || c == '$';
+    chars.next().is_some_and(start) && chars.all(|c| start(c) || c.is_alphanumeric())
+}
+
+/// Tokens of the syntax detection, comments are skipped and templates and
+/// regular expressions are a single `Literal`
+#[derive(Debug, Clone, PartialEq)]
+enum Token<'a> {
+    /// Identifiers, keywords and numbers
+    Ident(&'a str),
+    Str(String),
+    Punct(char),
+    Literal,
+}
+
+/// Keywords after which a `/` starts a regular expression
+const REGEX_KEYWORDS: [&str; 13] = [
+    "return",
+    "typeof",
+    "instanceof",
+    "in",
+    "of",
+    "new",
+    "delete",
+    "void",
+    "throw",
+    "case",
+    "do",
+    "else",
+    "yield",
+];
+
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    // brace depth of the `${` of the open templates
+    let mut templates: Vec<usize> = Vec::new();
+    let mut depth = 0;
+    let mut index = 0;
+    let word = |b: u8| b.is_ascii_alphanumeric() || b == b'_' || b == b'$' || b >= 0x80;
+
+    while index < bytes.len() {
+        let byte = bytes[index];
+        match byte {
+            b'/' if bytes.get(index + 1) == Some(&b'/') => {
+                while index < bytes.len() && bytes[index] != b'\n' {
+                    index += 1;
+                }
+            }
+            b'/' if bytes.get(index + 1) == Some(&b'*') => {
+                index = source[index + 2..]
+                    .find("*/")
+                    .map(|end| index + end + 4)
+                    .unwrap_or(bytes.len());
+            }
+            b'/' if regex_allowed(tokens.last()) => {
+                index = skip_regex(bytes, index);
+                tokens.push(Token::Literal);
+            }
+            b'"' | b'\'' => {
+                let (value, end) = read_string(source, index);
+                tokens.push(Token::Str(value));
+                index = end;
+            }
+            b'`' => {
+                index = skip_template(bytes, index + 1, &mut templates, depth);
+                tokens.push(Token::Literal);
+            }
+            b'}' if templates.last() == Some(&depth) => {
+                templates.pop();
+                index = skip_template(bytes, index + 1, &mut templates, depth);
+            }
+            _ if byte.is_ascii_whitespace() => index += 1,
+            _ if word(byte) => {
+                let start = index;
+                while index < bytes.len() && word(bytes[index]) {
+                    index += 1;
+                }
+                tokens.push(Token::Ident(&source[start..index]));
+            }
+            _ => {
+                match byte {
+                    b'{' => depth += 1,
+                    b'}' => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+                tokens.push(Token::Punct(byte as char));
+                index += 1;
+            }
+        }
+    }
+
+    tokens
+}
+
+fn regex_allowed(previous: Option<&Token>) -> bool {
+    match previous {
+        None => true,
+        Some(Token::Punct(c)) => !matches!(c, ')' | ']' | '}'),
+        Some(Token::Ident(word)) => REGEX_KEYWORDS.contains(word),
+        Some(_) => false,
+    }
+}
+
+/// Returns the index after the regular expression and its flags
+fn skip_regex(bytes: &[u8], start: usize) -> usize {
+    let mut index = start + 1;
+    let mut class = false;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\\' => index += 1,
+            b'[' => class = true,
+            b']' => class = false,
+            b'/' if !class => break,
+            b'\n' => return index,
+            _ => {}
+        }
+        index += 1;
+    }
+
+    index += 1;
+    while index < bytes.len() && bytes[index].is_ascii_alphabetic() {
+        index += 1;
+    }
+    index
+}
+
+/// Skips the text of a template until its end or a `${`, the brace depth of
+/// the `${` is pushed to `templates`
+fn skip_template(
+    bytes: &[u8],
+    start: usize,
+    templates: &mut Vec<usize>,
+    depth: usize,
+) -> usize {
+    let mut index = start;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\\' => index += 1,
+            b'`' => return index + 1,
+            b'$' if bytes.get(index + 1) == Some(&b'{') => {
+                templates.push(depth);
+                return index + 2;
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+    index
+}
+
+/// Reads the string literal at `start`, returns its value and the index after it
+fn read_string(source: &str, start: usize) -> (String, usize) {
+    let quote = source.as_bytes()[start] as char;
+    let mut value = String::new();
+    let mut chars = source[start + 1..].char_indices();
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some((_, 'n')) => value.push('\n'),
+                Some((_, 't')) => value.push('\t'),
+                Some((_, c)) => value.push(c),
+                None => break,
+            },
+            '\n' => return (value, start + 1 + offset),
+            c if c == quote => return (value, start + 2 + offset),
+            c => value.push(c),
+        }
+    }
+    (value, source.len())
+}
+
+fn has_module_syntax(tokens: &[Token]) -> bool {
+    tokens.iter().enumerate().any(|(index, token)| {
+        let member = index > 0 && tokens[index - 1] == Token::Punct('.');
+        let next = tokens.get(index + 1);
+        match token {
+            Token::Ident("import") if !member => match next {
+                Some(Token::Ident(_) | Token::Str(_) | Token::Punct('{' | '*')) => true,
+                // import.meta
+                Some(Token::Punct('.')) => {
+                    tokens.get(index + 2) == Some(&Token::Ident("meta"))
+                }
+                _ => false,
+            },
+            Token::Ident("export") if !member => {
+                matches!(next, Some(Token::Ident(_) | Token::Punct('{' | '*')))
+            }
+            _ => false,
+        }
+    })
+}
+
+fn has_commonjs_syntax(tokens: &[Token]) -> bool {
+    tokens.iter().enumerate().any(|(index, token)| {
+        let member = index > 0 && tokens[index - 1] == Token::Punct('.');
+        let next = tokens.get(index + 1);
+        match token {
+            Token::Ident("require") if !member => next == Some(&Token::Punct('(')),
+            Token::Ident("module") if !member => {
+                next == Some(&Token::Punct('.'))
+                    && tokens.get(index + 2) == Some(&Token::Ident("exports"))
+            }
+            Token::Ident("exports") if !member => {
+                matches!(next, Some(Token::Punct('.' | '[' | '=')))
+            }
+            _ => false,
+        }
+    })
+}
+
+/// [Op:RequireResolve]
+/// Resolves the specifier required by `referrer`, a file name or a `file://` URL
+///
+/// e.g. op_require_resolve("./data.json", "file:///app/main.js") // "/app/data.json"
+#[callback]
+fn op_require_resolve(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    specifier: String,
+    referrer: String,
+) -> JSResult<JSValue> {
+    let state = downcast_state(&ctx);
+    let loader = state.module_loader().borrow();
+    let referrer = module_path(&referrer)
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or(referrer);
+
+    match loader.resolve_require(&specifier, Some(&referrer)) {
+        Ok(module_id) => Ok(JSValue::string(&ctx, module_id)),
+        Err(failure) => Err(js_error_typ!(&ctx, failure.message())),
+    }
+}
+
+/// [Op:RequireLoad]
+/// Loads the source of a required module and its format, "commonjs" or "json"
+///
+/// e.g. op_require_load("/app/data.json") // { format: "json", source: "{}" }
+#[callback]
+fn op_require_load(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    module_id: String,
+) -> JSResult<JSValue> {
+    let state = downcast_state(&ctx);
+    let loader = state.module_loader().borrow();
+    let (format, source) = match loader.load_require(&module_id) {
+        Ok(module) => module,
+        Err(failure) => return Err(failure.js_error(&ctx)?),
+    };
+
+    if format == ModuleFormat::Module {
+        return Err(js_error_typ!(
+            &ctx,
+            format!(
+                "require() of ES module '{}' is not supported, use import() instead",
+                module_id
+            )
+        ));
+    }
+
+    let module = JSObject::new(&ctx);
+    module.set_property(
+        "format",
+        &JSValue::string(&ctx, format.as_str()),
+        Default::default(),
+    )?;
+    module.set_property("source", &JSValue::string(&ctx, source), Default::default())?;
+    Ok(module.into())
+}
+
+/// [Op:RequireCompile]
+/// Compiles a CommonJS module to its function wrapper
+///
+/// e.g. op_require_compile("exports.a = 1", "/app/a.js") // function (exports, require, module, __filename, __dirname)
+#[callback]
+fn op_require_compile(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    source: String,
+    filename: String,
+) -> JSResult<JSValue> {
+    // the shebang is only valid at the start of a script
+    let source = match source.starts_with("#!") {
+        true => source.split_once('\n').map(|(_, rest)| rest).unwrap_or(""),
+        false => source.as_str(),
+    };
+
+    let state = downcast_state(&ctx);
+    state
+        .module_loader()
+        .borrow()
+        .source_maps()
+        .register(&filename, source);
+
+    let wrapper = format!(
+        "(function (exports, require, module, __filename, __dirname) {{ {}\n}})\n//# sourceURL={}",
+        source, filename
+    );
+    ctx.evaluate_script(&wrapper, None)
+}
+
+struct CommonJsOps {}
+
+define_exports!(
+    CommonJsOps,
+    @template[],
+    @function[
+        op_require_resolve,
+        op_require_load,
+        op_require_compile,
+    ]
+);
+
+/// | ------------------------------- |
+/// |         CommonJsModule          |
+/// | ------------------------------- |
+///
+/// The `@kedo:op/module` module, exports `createRequire` and the `load` used by
+/// the ES module wrappers of the CommonJS modules.
+///
+/// e.g.
+/// ```js
+/// import { createRequire } from "@kedo:op/module";
+/// const require = createRequire(import.meta.url);
+/// const config = require("./config.json");
+/// ```
+pub struct CommonJsModule;
+
+impl CommonJsModule {
+    fn exports(ctx: &JSContext) -> JSResult<JSObject> {
+        let ops = JSObject::new(ctx);
+        CommonJsOps::export(ctx, &ops)?;
+
+        let factory = ctx.evaluate_script(REQUIRE_SOURCE, None)?;
+        let factory = JSFunction::from(factory.as_object()?);
+        factory.call(None, &[ops.into()])?.as_object()
+    }
+}
+
+impl ModuleSource for CommonJsModule {
+    fn evaluate(&self, ctx: &JSContext, _name: &str) -> JSObject {
+        Self::exports(ctx).expect("Failed to create the CommonJS module system")
+    }
+
+    fn name(&self) -> &str {
+        "@kedo:op/module"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn exports(source: &str) -> Vec<String> {
+        commonjs_exports(source).exports.into_iter().collect()
+    }
+
+    #[test]
+    fn test_detect_format() {
+        let detect =
+            |path: &str, source: &str| ModuleFormat::detect(Path::new(path), source);
+        assert_eq!(detect("/a/data.json", ""), Some(ModuleFormat::Json));
+        assert_eq!(
+            detect("/a/lib.cjs", "export {}"),
+            Some(ModuleFormat::CommonJs)
+        );
+        assert_eq!(detect("/a/lib.mjs", ""), Some(ModuleFormat::Module));
+        assert_eq!(
+            detect("/a/lib.js", "import fs from 'fs';"),
+            Some(ModuleFormat::Module)
+        );
+        assert_eq!(
+            detect("/a/lib.js", "console.log(import.meta.url)"),
+            Some(ModuleFormat::Module)
+        );
+        assert_eq!(
+            detect("/a/lib.js", "const fs = require('fs'); import('x');"),
+            Some(ModuleFormat::CommonJs)
+        );
+        assert_eq!(
+            detect("/a/lib.js", "module.exports = 1"),
+            Some(ModuleFormat::CommonJs)
+        );
+        // keywords in strings, comments, templates and regular expressions
+        assert_eq!(
+            detect(
+                "/a/lib.js",
+                "// import x from 'y'\nconst a = `export ${'{'} default`; /export {}/.test(a); exports.a = 1;"
+            ),
+            Some(ModuleFormat::CommonJs)
+        );
+        assert_eq!(detect("/a/lib.js", "const a = { import: 1 };"), None);
+    }
+
+    #[test]
+    fn test_detect_package_type() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        std::fs::create_dir_all(root.join("esm/lib")).unwrap();
+        std::fs::create_dir_all(root.join("cjs")).unwrap();
+        std::fs::write(root.join("esm/package.json"), r#"{ "type": "module" }"#).unwrap();
+        std::fs::write(root.join("cjs/package.json"), r#"{ "type": "commonjs" }"#)
+            .unwrap();
+
+        let detect = |path: &str| ModuleFormat::detect(&root.join(path), "");
+        assert_eq!(detect("esm/lib/index.js"), Some(ModuleFormat::Module));
+        assert_eq!(detect("cjs/index.js"), Some(ModuleFormat::CommonJs));
+        assert_eq!(detect("esm/lib/index.cjs"), Some(ModuleFormat::CommonJs));
+    }
+
+    #[test]
+    fn test_commonjs_exports() {
+        assert_eq!(
+            exports(
+                r#"
+                "use strict";
+                Object.defineProperty(exports, "__esModule", { value: true });
+                exports.a = 1;
+                module.exports.b = function () {};
+                exports["c"] = 3;
+                exports["not valid"] = 4;
+                Object.defineProperty(module.exports, "d", { get: () => 4 });
+                if (exports.a == 1) {}
+                foo.exports.e = 5;
+                exports.default = 6;
+            "#
+            ),
+            vec!["a", "b", "c", "d"]
+        );
+
+        assert_eq!(
+            exports(
+                r#"
+                const f = 6;
+                module.exports = {
+                    e: [1, { nested: 2 }],
+                    f,
+                    "g": call(a, b),
+                    h() { return { inner: 1 }; },
+                    get i() { return 1; },
+                };
+            "#
+            ),
+            vec!["e", "f", "g", "h", "i"]
+        );
+    }
+
+    #[test]
+    fn test_commonjs_reexports() {
+        let result = commonjs_exports(
+            r#"
+            module.exports = require("./lib");
+            __exportStar(require("./types"), exports);
+            tslib_1.__exportStar(require("./utils"), exports);
+            module.exports = { ...require("./more"), extra: 1 };
+        "#,
+        );
+        assert_eq!(
+            result.reexports,
+            vec!["./lib", "./types", "./utils", "./more"]
+        );
+        assert_eq!(
+            result.exports.into_iter().collect::<Vec<_>>(),
+            vec!["extra"]
+        );
+    }
+
+    #[test]
+    fn test_commonjs_wrapper() {
+        let exports = ["a".to_string(), "class".to_string()];
+        assert_eq!(
+            commonjs_wrapper("/app/lib.cjs", &exports),
+            concat!(
+                "import { load } from \"@kedo:op/module\";\n",
+                "const module = load(\"/app/lib.cjs\");\n",
+                "export default module;\n",
+                "const __export0 = module == null ? undefined : module[\"a\"];\n",
+                "const __export1 = module == null ? undefined : module[\"class\"];\n",
+                "export { __export0 as a, __export1 as class };\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use crate::modules::{js_string_literal, ModuleError};
+
+/// Type of a module given by the `type` import attribute,
+/// see https://github.com/tc39/proposal-import-attributes
+///
+/// e.g.
+/// ```js
+/// import config from "./config.json" with { type: "json" };
+/// import template from "./index.html" with { type: "text" };
+/// import logo from "./logo.png" with { type: "bytes" };
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportType {
+    JavaScript,
+    Json,
+    Text,
+    Bytes,
+}
+
+impl ImportType {
+    /// Parses the `type` attribute, imports without it are JavaScript
+    pub fn parse(value: Option<&str>) -> Result<Self, ModuleError> {
+        match value {
+            None => Ok(ImportType::JavaScript),
+            Some("json") => Ok(ImportType::Json),
+            Some("text") => Ok(ImportType::Text),
+            Some("bytes") => Ok(ImportType::Bytes),
+            Some(value) => Err(ModuleError::Other(format!(
+                "Import attribute type \"{}\" is not supported, expected \"json\", \"text\" or \"bytes\"",
+                value
+            ))),
+        }
+    }
+
+    /// JSON modules must be imported as JSON, other modules can't be
+    pub fn check(&self, module_id: &str) -> Result<(), ModuleError> {
+        let json = module_id.ends_with(".json");
+        match self {
+            ImportType::JavaScript if json => Err(ModuleError::Other(format!(
+                "Module '{}' is a JSON module, import it with {{ type: \"json\" }}",
+                module_id
+            ))),
+            ImportType::Json if !json => Err(ModuleError::Other(format!(
+                "Module '{}' is not a JSON module but was imported with {{ type: \"json\" }}",
+                module_id
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// How the module is imported, e.g. `with { type: "json" }`
+    pub fn attribute(&self) -> &'static str {
+        match self {
+            ImportType::JavaScript => "without a type",
+            ImportType::Json => "with { type: \"json\" }",
+            ImportType::Text => "with { type: \"text\" }",
+            ImportType::Bytes => "with { type: \"bytes\" }",
+        }
+    }
+
+    /// Source of the synthetic module that exports the content as default,
+    /// `content` is the text of the module or its bytes for `Bytes`
+    pub fn module_source(&self, content: &[u8]) -> String {
+        match self {
+            ImportType::JavaScript => String::from_utf8_lossy(content).to_string(),
+            ImportType::Json => format!(
+                "export default JSON.parse({});",
+                js_string_literal(&String::from_utf8_lossy(content))
+            ),
+            ImportType::Text => format!(
+                "export default {};",
+                js_string_literal(&String::from_utf8_lossy(content))
+            ),
+            // every byte is a char code of the string, e.g. 0xff is "ÿ"
+            ImportType::Bytes => {
+                let latin1 = content.iter().map(|byte| *byte as char).collect::<String>();
+                format!(
+                    "export default Uint8Array.from({}, (c) => c.charCodeAt(0));",
+                    js_string_literal(&latin1)
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_type() {
+        assert_eq!(ImportType::parse(None), Ok(ImportType::JavaScript));
+        assert_eq!(ImportType::parse(Some("json")), Ok(ImportType::Json));
+        assert!(ImportType::parse(Some("css")).is_err());
+
+        assert!(ImportType::Json.check("data.json").is_ok());
+        assert!(ImportType::Json.check("main.js").is_err());
+        assert!(ImportType::JavaScript.check("data.json").is_err());
+        assert!(ImportType::Text.check("data.json").is_ok());
+        assert!(ImportType::Bytes.check("logo.png").is_ok());
+    }
+
+    #[test]
+    fn test_module_source() {
+        assert_eq!(
+            ImportType::Json.module_source(br#"{ "a": 1 }"#),
+            r#"export default JSON.parse("{ \"a\": 1 }");"#
+        );
+        assert_eq!(
+            ImportType::Text.module_source(b"line\n"),
+            r#"export default "line\n";"#
+        );
+        assert_eq!(
+            ImportType::Bytes.module_source(&[0, 104, 255]),
+            r#"export default Uint8Array.from("\u0000hÿ", (c) => c.charCodeAt(0));"#
+        );
+    }
+}
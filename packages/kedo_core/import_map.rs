@@ -0,0 +1,381 @@
+use crate::modules::ModuleError;
+use serde_json::{Map, Value};
+use std::{fmt, path::Path};
+use url::Url;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportMapError(pub String);
+
+impl fmt::Display for ImportMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid import map: {}", self.0)
+    }
+}
+
+impl std::error::Error for ImportMapError {}
+
+/// Specifier keys sorted so the longest prefix is matched first,
+/// invalid addresses are kept as `None` and block the specifier.
+type SpecifierMap = Vec<(String, Option<Url>)>;
+
+/// | ------------------------------- |
+/// |            ImportMap            |
+/// | ------------------------------- |
+/// | - imports: SpecifierMap         |
+/// | - scopes: Vec<(String, Map)>    |
+/// | - base: Url                     |
+/// | ------------------------------- |
+///
+/// Import map as defined by the WHATWG HTML spec,
+/// see https://html.spec.whatwg.org/multipage/webappapis.html#import-maps
+///
+/// e.g.
+/// ```json
+/// {
+///   "imports": { "lodash": "./vendor/lodash.js", "utils/": "./src/utils/" },
+///   "scopes": { "./vendor/": { "lodash": "./vendor/lodash-legacy.js" } }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ImportMap {
+    imports: SpecifierMap,
+    scopes: Vec<(String, SpecifierMap)>,
+    base: Url,
+    warnings: Vec<String>,
+}
+
+impl ImportMap {
+    /// Parses the import map, relative addresses are resolved against `base`
+    pub fn parse(source: &str, base: Url) -> Result<Self, ImportMapError> {
+        let value: Value = serde_json::from_str(source)
+            .map_err(|err| ImportMapError(err.to_string()))?;
+        let Value::Object(map) = value else {
+            return Err(ImportMapError(
+                "the top-level value must be an object".into(),
+            ));
+        };
+
+        let mut import_map = Self {
+            imports: Vec::new(),
+            scopes: Vec::new(),
+            base,
+            warnings: Vec::new(),
+        };
+
+        for (key, value) in map.iter() {
+            match (key.as_str(), value) {
+                ("imports", Value::Object(imports)) => {
+                    import_map.imports = import_map.specifier_map(imports);
+                }
+                ("scopes", Value::Object(scopes)) => {
+                    import_map.scopes = import_map.scopes(scopes)?;
+                }
+                ("imports" | "scopes", _) => {
+                    return Err(ImportMapError(format!("\"{}\" must be an object", key)));
+                }
+                _ => import_map
+                    .warnings
+                    .push(format!("Unknown top-level key \"{}\" ignored", key)),
+            }
+        }
+
+        Ok(import_map)
+    }
+
+    /// Reads and parses the import map at `path`
+    pub fn from_file(path: &Path) -> Result<Self, ImportMapError> {
+        let source = std::fs::read_to_string(path).map_err(|err| {
+            ImportMapError(format!("cannot read '{}': {}", path.display(), err))
+        })?;
+        let path =
+            std::fs::canonicalize(path).map_err(|err| ImportMapError(err.to_string()))?;
+        let base = Url::from_file_path(&path)
+            .map_err(|_| ImportMapError(format!("invalid path '{}'", path.display())))?;
+
+        Self::parse(&source, base)
+    }
+
+    /// Entries that were ignored while parsing
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Maps the specifier imported by `referrer`, returns `None` when no entry matches.
+    /// File URLs are returned as paths, the module keys of the file system loader.
+    pub fn resolve(
+        &self,
+        specifier: &str,
+        referrer: Option<&str>,
+    ) -> Result<Option<String>, ModuleError> {
+        let referrer = referrer
+            .and_then(|referrer| match Path::new(referrer).is_absolute() {
+                true => Url::from_file_path(referrer).ok(),
+                false => Url::parse(referrer).ok(),
+            })
+            .unwrap_or_else(|| self.base.clone());
+
+        let as_url = url_like(specifier, &referrer);
+        let normalized = match &as_url {
+            Some(url) => url.as_str(),
+            None => specifier,
+        };
+
+        let scopes = self.scopes.iter().filter(|(prefix, _)| {
+            prefix == referrer.as_str()
+                || (prefix.ends_with('/')
+                    && referrer.as_str().starts_with(prefix.as_str()))
+        });
+
+        for (_, imports) in scopes {
+            if let Some(url) =
+                resolve_imports_match(normalized, as_url.as_ref(), imports)?
+            {
+                return Ok(Some(module_key(url)));
+            }
+        }
+
+        match resolve_imports_match(normalized, as_url.as_ref(), &self.imports)? {
+            Some(url) => Ok(Some(module_key(url))),
+            None => Ok(None),
+        }
+    }
+
+    fn specifier_map(&mut self, map: &Map<String, Value>) -> SpecifierMap {
+        let mut specifiers = Vec::new();
+        for (key, value) in map.iter() {
+            if key.is_empty() {
+                self.warnings
+                    .push("Empty specifier key ignored".to_string());
+                continue;
+            }
+
+            let key = match url_like(key, &self.base) {
+                Some(url) => url.to_string(),
+                None => key.clone(),
+            };
+
+            let address = match value {
+                Value::String(address) => url_like(address, &self.base),
+                _ => None,
+            };
+
+            let address = match address {
+                Some(address)
+                    if key.ends_with('/') && !address.as_str().ends_with('/') =>
+                {
+                    self.warnings.push(format!(
+                        "Address {} of \"{}\" must end with a slash",
+                        address, key
+                    ));
+                    None
+                }
+                Some(address) => Some(address),
+                None => {
+                    self.warnings
+                        .push(format!("Invalid address {} of \"{}\"", value, key));
+                    None
+                }
+            };
+
+            specifiers.push((key, address));
+        }
+
+        sort_by_specificity(&mut specifiers);
+        specifiers
+    }
+
+    fn scopes(
+        &mut self,
+        map: &Map<String, Value>,
+    ) -> Result<Vec<(String, SpecifierMap)>, ImportMapError> {
+        let mut scopes = Vec::new();
+        for (prefix, imports) in map.iter() {
+            let Value::Object(imports) = imports else {
+                return Err(ImportMapError(format!(
+                    "scope \"{}\" must be an object",
+                    prefix
+                )));
+            };
+
+            let prefix = match self.base.join(prefix) {
+                Ok(prefix) => prefix.to_string(),
+                Err(_) => {
+                    self.warnings
+                        .push(format!("Invalid scope prefix \"{}\" ignored", prefix));
+                    continue;
+                }
+            };
+
+            let imports = self.specifier_map(imports);
+            scopes.push((prefix, imports));
+        }
+
+        sort_by_specificity(&mut scopes);
+        Ok(scopes)
+    }
+}
+
+/// Sorts the keys in descending code unit order, so a key comes before its prefixes
+fn sort_by_specificity<T>(entries: &mut [(String, T)]) {
+    entries.sort_by(|(a, _), (b, _)| b.cmp(a));
+}
+
+/// Parses absolute URLs and specifiers starting with `/`, `./` or `../`,
+/// bare specifiers return `None`.
+fn url_like(specifier: &str, base: &Url) -> Option<Url> {
+    if specifier.starts_with('/')
+        || specifier.starts_with("./")
+        || specifier.starts_with("../")
+    {
+        return base.join(specifier).ok();
+    }
+
+    Url::parse(specifier).ok()
+}
+
+fn resolve_imports_match(
+    normalized: &str,
+    as_url: Option<&Url>,
+    imports: &SpecifierMap,
+) -> Result<Option<Url>, ModuleError> {
+    for (key, address) in imports.iter() {
+        if key == normalized {
+            return match address {
+                Some(address) => Ok(Some(address.clone())),
+                None => Err(blocked(normalized)),
+            };
+        }
+
+        let is_prefix = key.ends_with('/')
+            && normalized.starts_with(key.as_str())
+            && as_url.is_none_or(|url| url.is_special());
+        if !is_prefix {
+            continue;
+        }
+
+        let Some(address) = address else {
+            return Err(blocked(normalized));
+        };
+
+        let after_prefix = &normalized[key.len()..];
+        let url = match address.join(after_prefix) {
+            Ok(url) => url,
+            Err(_) => return Err(blocked(normalized)),
+        };
+
+        // e.g. "utils/../../secret.js" must stay under the address of "utils/"
+        if !url.as_str().starts_with(address.as_str()) {
+            return Err(ModuleError::Other(format!(
+                "Import of '{}' escapes the address of '{}' in the import map",
+                normalized, key
+            )));
+        }
+
+        return Ok(Some(url));
+    }
+
+    Ok(None)
+}
+
+fn blocked(specifier: &str) -> ModuleError {
+    ModuleError::Other(format!(
+        "Import of '{}' is blocked by an invalid entry in the import map",
+        specifier
+    ))
+}
+
+fn module_key(url: Url) -> String {
+    match url.scheme() {
+        "file" => match url.to_file_path() {
+            Ok(path) => path.to_string_lossy().to_string(),
+            Err(_) => url.to_string(),
+        },
+        _ => url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import_map(source: &str) -> ImportMap {
+        ImportMap::parse(source, Url::parse("file:///app/import_map.json").unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_resolve_imports() {
+        let map = import_map(
+            r#"{
+                "imports": {
+                    "lodash": "./vendor/lodash.js",
+                    "lodash/": "./vendor/lodash/",
+                    "@kedo/fs": "https://cdn.example.com/fs.js",
+                    "/app/src/old.js": "./src/new.js"
+                }
+            }"#,
+        );
+
+        let resolve = |specifier| map.resolve(specifier, Some("/app/main.js")).unwrap();
+        assert_eq!(resolve("lodash").as_deref(), Some("/app/vendor/lodash.js"));
+        assert_eq!(
+            resolve("lodash/fp/map.js").as_deref(),
+            Some("/app/vendor/lodash/fp/map.js")
+        );
+        assert_eq!(
+            resolve("@kedo/fs").as_deref(),
+            Some("https://cdn.example.com/fs.js")
+        );
+        assert_eq!(resolve("./src/old.js").as_deref(), Some("/app/src/new.js"));
+        assert_eq!(resolve("react"), None);
+    }
+
+    #[test]
+    fn test_resolve_scopes() {
+        let map = import_map(
+            r#"{
+                "imports": { "lodash": "./vendor/lodash.js" },
+                "scopes": {
+                    "./legacy/": { "lodash": "./vendor/lodash-legacy.js" },
+                    "./legacy/modern.js": { "lodash": "./vendor/lodash.js" }
+                }
+            }"#,
+        );
+
+        let resolve = |referrer| map.resolve("lodash", Some(referrer)).unwrap().unwrap();
+        assert_eq!(resolve("/app/main.js"), "/app/vendor/lodash.js");
+        assert_eq!(
+            resolve("/app/legacy/index.js"),
+            "/app/vendor/lodash-legacy.js"
+        );
+        assert_eq!(resolve("/app/legacy/modern.js"), "/app/vendor/lodash.js");
+    }
+
+    #[test]
+    fn test_invalid_entries() {
+        let map = import_map(
+            r#"{
+                "imports": {
+                    "bare": "lodash",
+                    "utils/": "./src/utils.js",
+                    "lib/": "./vendor/lib/"
+                },
+                "other": true
+            }"#,
+        );
+
+        assert_eq!(map.warnings().len(), 3);
+        assert!(map.resolve("bare", None).is_err());
+        assert!(map.resolve("utils/a.js", None).is_err());
+        assert!(map.resolve("lib/../../secret.js", None).is_err());
+        assert_eq!(
+            map.resolve("lib/a.js", None).unwrap().as_deref(),
+            Some("/app/vendor/lib/a.js")
+        );
+
+        let base = Url::parse("file:///app/").unwrap();
+        assert!(ImportMap::parse("[]", base.clone()).is_err());
+        assert!(ImportMap::parse(r#"{ "imports": [] }"#, base.clone()).is_err());
+        assert!(ImportMap::parse(r#"{ "scopes": { "/": 1 } }"#, base).is_err());
+    }
+}
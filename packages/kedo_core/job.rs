@@ -234,6 +234,15 @@ impl AsyncJobQueue {
             && self.inner.borrow().futures_prevent_exit_count == 0
     }
 
+    /// Drops the pending jobs and futures, e.g. when the runtime shuts down.
+    /// The resources owned by the futures, like servers, are closed as they drop.
+    pub fn clear(&mut self) {
+        self.futures = FuturesUnordered::new();
+        let mut inner = self.inner.borrow_mut();
+        inner.jobs.clear();
+        inner.futures_prevent_exit_count = 0;
+    }
+
     pub fn leak(&self) -> Weak<RefCell<AsyncJobQueueInner>> {
         Rc::downgrade(&self.inner)
     }
@@ -502,6 +511,33 @@ mod tests {
         assert!(queue.is_empty());
     }
 
+    #[test]
+    fn test_clear() {
+        let mut queue = AsyncJobQueue::new();
+        let dropped = Rc::new(RefCell::new(false));
+
+        struct DropGuard(Rc<RefCell<bool>>);
+        impl Drop for DropGuard {
+            fn drop(&mut self) {
+                *self.0.borrow_mut() = true;
+            }
+        }
+
+        let guard = DropGuard(dropped.clone());
+        let future_job: FutureJob = Box::pin(async move {
+            let _guard = guard;
+            std::future::pending::<NativeJob>().await
+        });
+        queue.spawn(future_job);
+        queue.enqueue_promise_job(NativeJob::new(|_ctx| Ok(())));
+        assert!(!queue.is_empty());
+
+        queue.clear();
+        assert!(queue.is_empty());
+        assert_eq!(queue.len(), 0);
+        assert!(*dropped.borrow());
+    }
+
     #[test]
     fn test_spawn_non_blocking() {
         // Test that spawn_non_blocking does not prevent exit
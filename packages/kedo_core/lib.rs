@@ -1,9 +1,16 @@
 mod callback;
 mod class_table;
+mod commonjs;
+mod import_attributes;
+mod import_map;
 mod job;
 mod modules;
+mod permissions;
 mod proto_table;
+mod resource_table;
+mod source_map;
 mod state;
+mod worker;
 
 pub use job::AsyncJobQueue;
 pub use job::AsyncJobQueueInner;
@@ -12,11 +19,42 @@ pub use job::NativeJob;
 pub use job::SimpleJobQueue;
 
 // modules
+pub use commonjs::CommonJsModule;
+pub use commonjs::ModuleFormat;
+pub use import_attributes::ImportType;
+pub use import_map::ImportMap;
+pub use import_map::ImportMapError;
 pub use modules::CoreModuleLoader;
 pub use modules::ModuleError;
+pub use modules::ModuleFailure;
 pub use modules::ModuleImportMetaFn;
 pub use modules::ModuleLoader;
 pub use modules::ModuleSource;
+pub use source_map::SourceLocation;
+pub use source_map::SourceMap;
+pub use source_map::SourceMaps;
+
+// permissions
+pub use permissions::permission_denied;
+pub use permissions::AllowList;
+pub use permissions::PermissionError;
+pub use permissions::PermissionName;
+pub use permissions::PermissionState;
+pub use permissions::Permissions;
+
+// resources
+pub use resource_table::ResourceHandle;
+pub use resource_table::ResourceId;
+pub use resource_table::ResourceInfo;
+pub use resource_table::ResourceTable;
+
+// workers
+pub use worker::worker_channel;
+pub use worker::WorkerError;
+pub use worker::WorkerHandle;
+pub use worker::WorkerLauncher;
+pub use worker::WorkerMessage;
+pub use worker::WorkerScope;
 
 pub use callback::JsProctectedCallable;
 // state
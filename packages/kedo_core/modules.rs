@@ -1,9 +1,25 @@
-use crate::state::downcast_state;
+use crate::{
+    commonjs::{commonjs_exports, commonjs_wrapper, CommonJsModule, ModuleFormat},
+    import_attributes::ImportType,
+    import_map::ImportMap,
+    permissions::{permission_denied, Permissions},
+    source_map::SourceMaps,
+    state::downcast_state,
+};
+use kedo_utils::js_error_typ;
 use rust_jsc::{
-    module_evaluate, module_fetch, module_import_meta, module_resolve, JSContext,
-    JSModuleLoader, JSObject, JSStringProctected, JSValue,
+    callback, module_evaluate, module_fetch, module_import_meta, module_resolve,
+    JSContext, JSError, JSFunction, JSModuleLoader, JSObject, JSResult,
+    JSStringProctected, JSValue,
+};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
 };
-use std::collections::HashMap;
+use url::Url;
 
 #[macro_export]
 macro_rules! define_exports {
@@ -36,17 +52,199 @@ macro_rules! define_exports {
     };
 }
 
-pub type ModuleImportMetaFn = Box<fn(name: &str) -> JSObject>;
+/// Creates the `import.meta` object of a module, replaces the default one
+pub type ModuleImportMetaFn = Box<fn(ctx: &JSContext, name: &str) -> JSObject>;
+
+/// Binds `import.meta.resolve` to the key of the module
+const IMPORT_META_RESOLVE: &str = r#"
+(function (op, referrer) {
+    return function resolve(specifier) {
+        return op(String(specifier), referrer);
+    };
+})
+"#;
+
+/// Keys of the modules that failed to resolve, fetching them returns a
+/// module that throws the resolution error.
+const FAILED_MODULE_PREFIX: &str = "kedo:failed-module/";
 
 // Error handling
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ModuleError {
     NotFound(String),
     LoadError(String),
+    /// Reason why the module is malformed, thrown as a `SyntaxError`
     InvalidModule(String),
+    /// Access the module needs but wasn't granted, e.g. reading its file
+    PermissionDenied(PermissionError),
     Other(String),
 }
 
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModuleError::NotFound(module) => write!(f, "Cannot find module '{}'", module),
+            ModuleError::LoadError(module) => {
+                write!(f, "Cannot load module '{}'", module)
+            }
+            ModuleError::InvalidModule(reason) => write!(f, "{}", reason),
+            ModuleError::PermissionDenied(error) => write!(f, "{}", error),
+            ModuleError::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// | ------------------------------- |
+/// |          ModuleFailure          |
+/// | ------------------------------- |
+/// | - specifier: String             |
+/// | - referrer: Option<String>      |
+/// | - loaders: Vec<String>          |
+/// | - error: ModuleError            |
+/// | ------------------------------- |
+///
+/// A module that could not be resolved or fetched, the importer receives it
+/// as a `TypeError` or a `SyntaxError` for invalid modules.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleFailure {
+    pub specifier: String,
+    pub referrer: Option<String>,
+    /// Loaders that were asked for the module, in order
+    pub loaders: Vec<String>,
+    pub error: ModuleError,
+}
+
+impl ModuleFailure {
+    /// Name of the JS error constructor
+    pub fn error_name(&self) -> &'static str {
+        match self.error {
+            ModuleError::InvalidModule(_) => "SyntaxError",
+            ModuleError::PermissionDenied(_) => "PermissionDenied",
+            _ => "TypeError",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        let mut message = self.error.to_string();
+        if let Some(referrer) = &self.referrer {
+            message.push_str(&format!(" imported from '{}'", referrer));
+        }
+
+        // the loaders are only at fault when none of them found or loaded it
+        if !matches!(
+            self.error,
+            ModuleError::NotFound(_) | ModuleError::LoadError(_)
+        ) {
+            return message;
+        }
+
+        if self.loaders.is_empty() {
+            message.push_str(", no module loader can handle it");
+        } else {
+            message.push_str(&format!(", tried loaders: {}", self.loaders.join(", ")));
+        }
+
+        message
+    }
+
+    /// Source of a module that throws the error once evaluated
+    pub fn source(&self) -> String {
+        let message = js_string_literal(&self.message());
+        match self.error {
+            // not a global constructor, named like the errors of the ops
+            ModuleError::PermissionDenied(_) => format!(
+                "const error = new Error({}); error.name = \"PermissionDenied\"; throw error;",
+                message
+            ),
+            _ => format!("throw new {}({});", self.error_name(), message),
+        }
+    }
+
+    /// Error thrown by the ops that load modules, e.g. `require()`
+    pub fn js_error(&self, ctx: &JSContext) -> JSResult<JSError> {
+        match &self.error {
+            ModuleError::PermissionDenied(error) => permission_denied(ctx, error),
+            _ => JSError::new_typ(ctx, self.message()),
+        }
+    }
+}
+
+/// Path of the modules loaded from the file system, their key is an absolute
+/// path or a `file://` URL
+pub(crate) fn module_path(module_id: &str) -> Option<PathBuf> {
+    if module_id.starts_with("file://") {
+        return Url::parse(module_id).ok()?.to_file_path().ok();
+    }
+
+    let path = Path::new(module_id);
+    path.is_absolute().then(|| path.to_path_buf())
+}
+
+/// URL of the module, the key of the modules outside of the file system
+fn module_url(module_id: &str) -> String {
+    module_path(module_id)
+        .and_then(|path| Url::from_file_path(path).ok())
+        .map(|url| url.to_string())
+        .unwrap_or_else(|| module_id.to_string())
+}
+
+/// First import attribute other than `type`, e.g. `lazy` in
+/// `with { type: "json", lazy: "true" }`
+const UNSUPPORTED_ATTRIBUTE: &str =
+    r#"(attributes) => Object.keys(attributes).find((key) => key !== "type")"#;
+
+/// Value of the `type` import attribute, e.g. `with { type: "json" }`.
+/// Any other attribute fails the import.
+fn import_type(
+    ctx: &JSContext,
+    attributes: &JSValue,
+) -> Result<Option<String>, ModuleError> {
+    if !attributes.is_object() {
+        return Ok(None);
+    }
+
+    let unsupported = downcast_state(ctx)
+        .helper(ctx, UNSUPPORTED_ATTRIBUTE)
+        .and_then(|find| find.callable().call(None, &[attributes.clone()]))
+        .ok()
+        .filter(|key| key.is_string())
+        .and_then(|key| key.as_string().ok());
+    if let Some(key) = unsupported {
+        return Err(ModuleError::Other(format!(
+            "Import attribute \"{}\" is not supported, expected \"type\"",
+            key
+        )));
+    }
+
+    let value = attributes
+        .as_object()
+        .and_then(|attributes| attributes.get_property("type"))
+        .ok()
+        .filter(|value| value.is_string());
+    Ok(value
+        .and_then(|value| value.as_string().ok())
+        .map(|value| value.to_string()))
+}
+
+pub(crate) fn js_string_literal(value: &str) -> String {
+    let mut literal = String::with_capacity(value.len() + 2);
+    literal.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\u{2028}' => literal.push_str("\\u2028"),
+            '\u{2029}' => literal.push_str("\\u2029"),
+            c if c.is_control() => literal.push_str(&format!("\\u{:04x}", c as u32)),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
 // Unified Module Source trait
 pub trait ModuleLoader {
     /// Returns true if this source can handle the given module ID
@@ -57,6 +255,34 @@ pub trait ModuleLoader {
     /// Resolves and loads the module content
     /// This is used to resolve and load the module content
     fn load(&self, module_id: &str) -> Result<String, ModuleError>;
+    /// Loads the raw content of the module, used by the JSON, text and bytes imports
+    fn load_bytes(&self, module_id: &str) -> Result<Vec<u8>, ModuleError> {
+        self.load(module_id).map(String::into_bytes)
+    }
+    /// Resolves the module ID imported by `referrer`, the module key of the importer.
+    /// Loaders that resolve relative to the importer override it, e.g. `node_modules`
+    fn resolve_from(
+        &self,
+        module_id: &str,
+        _referrer: Option<&str>,
+    ) -> Result<String, ModuleError> {
+        self.resolve(module_id)
+    }
+    /// Resolves the module ID required by `referrer` with `require()`, loaders
+    /// that resolve CommonJS modules differently override it, e.g. `node_modules`
+    fn resolve_require(
+        &self,
+        module_id: &str,
+        referrer: Option<&str>,
+    ) -> Result<String, ModuleError> {
+        self.resolve_from(module_id, referrer)
+    }
+
+    /// Name of the loader reported in the module errors
+    fn name(&self) -> &str {
+        let name = std::any::type_name::<Self>();
+        name.rsplit("::").next().unwrap_or(name)
+    }
 }
 
 // Unified Module Evaluate trait
@@ -74,6 +300,20 @@ pub struct CoreModuleLoader {
     fs_loader: Option<Box<dyn ModuleLoader>>,
     import_meta: Option<ModuleImportMetaFn>,
     module_loader: JSModuleLoader,
+    import_map: Option<ImportMap>,
+    /// Failures with the order they were recorded in, `kedo:failed-module/N` is
+    /// the index. A specifier failing again from the same referrer replaces
+    /// its previous failure, long running sessions don't keep every failure.
+    failures: RefCell<Vec<(usize, ModuleFailure)>>,
+    failure_count: Cell<usize>,
+    /// Importer of each resolved module, reported when the module fails to load
+    referrers: RefCell<HashMap<String, String>>,
+    /// Type each module was first fetched with, the module key doesn't include it
+    import_types: RefCell<HashMap<String, ImportType>>,
+    main_module: RefCell<Option<String>>,
+    source_maps: SourceMaps,
+    loaded_files: RefCell<BTreeSet<PathBuf>>,
+    permissions: Arc<Permissions>,
 }
 
 impl CoreModuleLoader {
@@ -86,13 +326,25 @@ impl CoreModuleLoader {
             moduleLoaderCreateImportMetaProperties: Some(Self::import_meta_properties),
         };
 
-        Self {
+        let mut loader = Self {
             loaders: Vec::new(),
             sources: HashMap::new(),
             fs_loader: None,
             import_meta: None,
             module_loader,
-        }
+            import_map: None,
+            failures: RefCell::new(Vec::new()),
+            failure_count: Cell::new(0),
+            referrers: RefCell::new(HashMap::new()),
+            import_types: RefCell::new(HashMap::new()),
+            main_module: RefCell::new(None),
+            source_maps: SourceMaps::default(),
+            loaded_files: RefCell::new(BTreeSet::new()),
+            permissions: Arc::new(Permissions::default()),
+        };
+        // imported by the ES module wrappers of the CommonJS modules
+        loader.add_source(CommonJsModule);
+        loader
     }
 
     pub fn disable_builtin_fs_loader(&mut self) {
@@ -107,6 +359,17 @@ impl CoreModuleLoader {
         self.import_meta = Some(import_meta);
     }
 
+    /// Specifiers are mapped by the import map before any loader is asked
+    pub fn set_import_map(&mut self, import_map: ImportMap) {
+        self.import_map = Some(import_map);
+    }
+
+    /// Permissions checked before the files of the JSON, text and bytes modules
+    /// and of `require()` are read
+    pub fn set_permissions(&mut self, permissions: Arc<Permissions>) {
+        self.permissions = permissions;
+    }
+
     pub fn set_file_system_loader(&mut self, loader: impl ModuleLoader + 'static) {
         self.fs_loader = Some(Box::new(loader));
         self.module_loader.disableBuiltinFileSystemLoader = true;
@@ -129,37 +392,497 @@ impl CoreModuleLoader {
             .insert(source.name().to_string(), Box::new(source));
     }
 
+    /// Resolves the specifier with the first loader that can handle it,
+    /// the file system loader is the last one asked.
+    pub fn resolve_specifier(
+        &self,
+        specifier: &str,
+        referrer: Option<&str>,
+    ) -> Result<String, ModuleFailure> {
+        self.resolve_with(specifier, referrer, false)
+    }
+
+    /// Resolves the specifier of a `require()` call, `referrer` is the file name
+    /// of the CommonJS module
+    pub fn resolve_require(
+        &self,
+        specifier: &str,
+        referrer: Option<&str>,
+    ) -> Result<String, ModuleFailure> {
+        self.resolve_with(specifier, referrer, true)
+    }
+
+    fn resolve_with(
+        &self,
+        specifier: &str,
+        referrer: Option<&str>,
+        require: bool,
+    ) -> Result<String, ModuleFailure> {
+        let failure = |loaders, error| ModuleFailure {
+            specifier: specifier.to_string(),
+            referrer: referrer.map(|referrer| referrer.to_string()),
+            loaders,
+            error,
+        };
+
+        // a remote module imports other remote modules and the builtin ones, never
+        // the files of the machine it runs on
+        let remote_referrer = referrer.is_some_and(|referrer| {
+            referrer.starts_with("https://") || referrer.starts_with("http://")
+        });
+        let local = |module_id: &str| remote_referrer && module_path(module_id).is_some();
+        let local_error = |module_id: &str| {
+            ModuleError::Other(format!(
+                "Remote modules cannot import the local module '{}'",
+                module_id
+            ))
+        };
+
+        let mapped = match &self.import_map {
+            Some(import_map) => import_map
+                .resolve(specifier, referrer)
+                .map_err(|error| failure(vec![], error))?,
+            None => None,
+        };
+
+        let specifier = match &mapped {
+            Some(path) if local(path) => return Err(failure(vec![], local_error(path))),
+            // file paths are loaded by the file system loader
+            Some(path) if Path::new(path).is_absolute() => return Ok(path.clone()),
+            Some(mapped) => mapped.as_str(),
+            None => specifier,
+        };
+
+        if local(specifier) {
+            return Err(failure(vec![], local_error(specifier)));
+        }
+
+        if self.sources.contains_key(specifier) {
+            return Ok(specifier.to_string());
+        }
+
+        let mut loaders = Vec::new();
+        let mut error = ModuleError::NotFound(specifier.to_string());
+        for loader in self.candidates(specifier) {
+            loaders.push(loader.name().to_string());
+            let resolved = match require {
+                true => loader.resolve_require(specifier, referrer),
+                false => loader.resolve_from(specifier, referrer),
+            };
+
+            match resolved {
+                Ok(module_id) if local(&module_id) => {
+                    return Err(failure(loaders, local_error(&module_id)))
+                }
+                Ok(module_id) => return Ok(module_id),
+                Err(err) => error = err,
+            }
+        }
+
+        Err(failure(loaders, error))
+    }
+
+    /// Loads the source of a resolved module
+    pub fn load_module(&self, module_id: &str) -> Result<String, ModuleFailure> {
+        self.fetch_module(module_id, ImportType::JavaScript)
+    }
+
+    /// Loads the module imported with the `type` attribute, JSON, text and bytes
+    /// modules are returned as a module with the content as default export and
+    /// CommonJS modules as a module with `module.exports` as default export.
+    ///
+    /// The files of JSON, text and bytes modules are data read by the program,
+    /// they need the read permission. JavaScript modules are the program itself.
+    pub fn fetch_module(
+        &self,
+        module_id: &str,
+        import_type: ImportType,
+    ) -> Result<String, ModuleFailure> {
+        if let Some(failure) = self.failure(module_id) {
+            return Err(failure);
+        }
+
+        import_type
+            .check(module_id)
+            .and_then(|_| self.check_import_type(module_id, import_type))
+            .map_err(|error| ModuleFailure {
+                specifier: module_id.to_string(),
+                referrer: self.referrer(module_id),
+                loaders: vec![],
+                error,
+            })?;
+
+        if import_type != ImportType::JavaScript {
+            self.check_read(module_id)?;
+        }
+
+        let content = self.load_content(module_id, import_type)?;
+        if import_type == ImportType::JavaScript {
+            self.source_maps
+                .register(module_id, &String::from_utf8_lossy(&content));
+        }
+
+        let path = module_path(module_id);
+        if let (ImportType::JavaScript, Some(path)) = (import_type, path) {
+            let source = String::from_utf8_lossy(&content);
+            if ModuleFormat::detect(&path, &source) == Some(ModuleFormat::CommonJs) {
+                let mut visited = HashSet::from([module_id.to_string()]);
+                let exports = self.commonjs_exports(module_id, &source, &mut visited);
+                return Ok(commonjs_wrapper(module_id, &exports));
+            }
+        }
+
+        Ok(import_type.module_source(&content))
+    }
+
+    /// A module is evaluated once per key, importing it again with another `type`
+    /// would return the module of the first import, so it's rejected instead.
+    fn check_import_type(
+        &self,
+        module_id: &str,
+        import_type: ImportType,
+    ) -> Result<(), ModuleError> {
+        let mut import_types = self.import_types.borrow_mut();
+        let first = *import_types
+            .entry(module_id.to_string())
+            .or_insert(import_type);
+        if first == import_type {
+            return Ok(());
+        }
+
+        Err(ModuleError::Other(format!(
+            "Module '{}' is imported {} and {}, a module can only have one type",
+            module_id,
+            first.attribute(),
+            import_type.attribute()
+        )))
+    }
+
+    /// Loads the module required with `require()` and its format, modules
+    /// without ES module or CommonJS syntax are CommonJS modules.
+    ///
+    /// Required files need the read permission, except the CommonJS modules
+    /// imported as JavaScript that are loaded again by their ES module wrapper.
+    pub fn load_require(
+        &self,
+        module_id: &str,
+    ) -> Result<(ModuleFormat, String), ModuleFailure> {
+        let imported = self.import_types.borrow().get(module_id).copied();
+        if imported != Some(ImportType::JavaScript) {
+            self.check_read(module_id)?;
+        }
+
+        let path = module_path(module_id).unwrap_or_else(|| PathBuf::from(module_id));
+        let import_type = match module_id.ends_with(".json") {
+            true => ImportType::Json,
+            false => ImportType::JavaScript,
+        };
+
+        let content = self.load_content(module_id, import_type)?;
+        let source = String::from_utf8_lossy(&content).to_string();
+        let format =
+            ModuleFormat::detect(&path, &source).unwrap_or(ModuleFormat::CommonJs);
+        Ok((format, source))
+    }
+
+    /// Named exports of a CommonJS module, including the ones of the modules it
+    /// reexports. `visited` stops the circular reexports.
+    fn commonjs_exports(
+        &self,
+        module_id: &str,
+        source: &str,
+        visited: &mut HashSet<String>,
+    ) -> BTreeSet<String> {
+        let analysis = commonjs_exports(source);
+        let mut exports = analysis.exports;
+        for specifier in analysis.reexports {
+            let Ok(reexported) = self.resolve_require(&specifier, Some(module_id)) else {
+                continue;
+            };
+
+            if !visited.insert(reexported.clone()) {
+                continue;
+            }
+
+            if let Ok((ModuleFormat::CommonJs, source)) = self.load_require(&reexported) {
+                exports.extend(self.commonjs_exports(&reexported, &source, visited));
+            }
+        }
+
+        exports
+    }
+
+    /// Content of the module with the first loader that can load it, JSON and
+    /// text modules are the raw content, not a transpiled source.
+    /// The read permission is checked by the callers, the JavaScript modules
+    /// don't need it.
+    fn load_content(
+        &self,
+        module_id: &str,
+        import_type: ImportType,
+    ) -> Result<Vec<u8>, ModuleFailure> {
+        let mut loaders = Vec::new();
+        let mut error = ModuleError::NotFound(module_id.to_string());
+        for loader in self.candidates(module_id) {
+            loaders.push(loader.name().to_string());
+            let content = match import_type {
+                ImportType::JavaScript => loader.load(module_id).map(String::into_bytes),
+                _ => loader.load_bytes(module_id),
+            };
+
+            match content {
+                Ok(content) => {
+                    self.track_file(module_id);
+                    return Ok(content);
+                }
+                Err(err) => error = err,
+            }
+        }
+
+        // paths resolved by the import map or a loader, without a file system loader
+        if loaders.is_empty() && Path::new(module_id).is_absolute() {
+            match std::fs::read(module_id) {
+                Ok(content) => {
+                    self.track_file(module_id);
+                    return Ok(content);
+                }
+                Err(_) => error = ModuleError::LoadError(module_id.to_string()),
+            }
+        }
+
+        Err(ModuleFailure {
+            specifier: module_id.to_string(),
+            referrer: self.referrer(module_id),
+            loaders,
+            error,
+        })
+    }
+
+    /// Fails unless the file of the module can be read, modules outside of the
+    /// file system have nothing to check.
+    fn check_read(&self, module_id: &str) -> Result<(), ModuleFailure> {
+        let Some(path) = module_path(module_id) else {
+            return Ok(());
+        };
+
+        self.permissions
+            .check_read(&path.to_string_lossy())
+            .map_err(|error| ModuleFailure {
+                specifier: module_id.to_string(),
+                referrer: self.referrer(module_id),
+                loaders: vec![],
+                error: ModuleError::PermissionDenied(error),
+            })
+    }
+
+    /// Records the first module that imported `module_id`
+    fn track_referrer(&self, module_id: &str, referrer: &str) {
+        self.referrers
+            .borrow_mut()
+            .entry(module_id.to_string())
+            .or_insert_with(|| referrer.to_string());
+    }
+
+    fn referrer(&self, module_id: &str) -> Option<String> {
+        self.referrers.borrow().get(module_id).cloned()
+    }
+
+    fn track_file(&self, module_id: &str) {
+        if let Some(path) = module_path(module_id) {
+            self.loaded_files.borrow_mut().insert(path);
+        }
+    }
+
+    /// Files of the modules imported or required so far, e.g. watched by
+    /// `kedo run --watch`. Remote and virtual modules are not included.
+    pub fn loaded_files(&self) -> Vec<PathBuf> {
+        self.loaded_files.borrow().iter().cloned().collect()
+    }
+
+    /// Failures recorded since the loader was created, in order
+    pub fn failures(&self) -> Vec<ModuleFailure> {
+        self.failures_since(0)
+    }
+
+    /// Number of failures recorded so far, including the replaced ones
+    pub fn failure_count(&self) -> usize {
+        self.failure_count.get()
+    }
+
+    /// Failures recorded once `count` failures were, in order
+    pub fn failures_since(&self, count: usize) -> Vec<ModuleFailure> {
+        let mut failures = self
+            .failures
+            .borrow()
+            .iter()
+            .filter(|(order, _)| *order >= count)
+            .cloned()
+            .collect::<Vec<_>>();
+        failures.sort_by_key(|(order, _)| *order);
+        failures.into_iter().map(|(_, failure)| failure).collect()
+    }
+
+    /// Source maps of the fetched modules, used to map the stack traces
+    pub fn source_maps(&self) -> &SourceMaps {
+        &self.source_maps
+    }
+
+    /// Key of the entry module, `None` until the runtime evaluates a module file,
+    /// e.g. in the REPL
+    pub fn main_module(&self) -> Option<String> {
+        self.main_module.borrow().clone()
+    }
+
+    /// Sets the entry module, its `import.meta.main` is true and the worker
+    /// paths start at its directory
+    pub fn set_main_module(&self, module_id: &str) {
+        *self.main_module.borrow_mut() = Some(module_id.to_string());
+    }
+
+    /// Properties of `import.meta`, `url` is a `file://` URL for the modules of
+    /// the file system and the module key for any other module.
+    ///
+    /// e.g.
+    /// ```js
+    /// import.meta.url; // "file:///app/src/main.js"
+    /// import.meta.dirname; // "/app/src"
+    /// import.meta.filename; // "/app/src/main.js"
+    /// import.meta.main; // true
+    /// import.meta.resolve("./util.js"); // "file:///app/src/util.js"
+    /// ```
+    fn import_meta(&self, ctx: &JSContext, module_id: &str) -> JSResult<JSObject> {
+        let meta = JSObject::new(ctx);
+        let url = module_url(module_id);
+        meta.set_property("url", &JSValue::string(ctx, url), Default::default())?;
+
+        if let Some(path) = module_path(module_id) {
+            let dirname = path.parent().unwrap_or(&path).to_string_lossy();
+            let filename = path.to_string_lossy();
+            meta.set_property(
+                "dirname",
+                &JSValue::string(ctx, dirname.as_ref()),
+                Default::default(),
+            )?;
+            meta.set_property(
+                "filename",
+                &JSValue::string(ctx, filename.as_ref()),
+                Default::default(),
+            )?;
+        }
+
+        let main = self.main_module.borrow().as_deref() == Some(module_id);
+        meta.set_property("main", &JSValue::boolean(ctx, main), Default::default())?;
+
+        let op = JSFunction::callback(
+            ctx,
+            Some("resolve"),
+            Some(Self::op_import_meta_resolve),
+        );
+        let bind = downcast_state(ctx).helper(ctx, IMPORT_META_RESOLVE)?;
+        let resolve = bind
+            .callable()
+            .call(None, &[op.into(), JSValue::string(ctx, module_id)])?;
+        meta.set_property("resolve", &resolve, Default::default())?;
+
+        Ok(meta)
+    }
+
+    /// [Op:ImportMetaResolve]
+    /// Resolves the specifier as if it was imported by the module
+    ///
+    /// e.g. import.meta.resolve("lodash") // "file:///app/node_modules/lodash/lodash.js"
+    #[callback]
+    fn op_import_meta_resolve(
+        ctx: JSContext,
+        _: JSObject,
+        _: JSObject,
+        specifier: String,
+        referrer: String,
+    ) -> JSResult<JSValue> {
+        let state = downcast_state(&ctx);
+        let loader = state.module_loader().borrow();
+        match loader.resolve_specifier(&specifier, Some(&referrer)) {
+            Ok(module_id) => Ok(JSValue::string(&ctx, module_url(&module_id))),
+            Err(failure) => Err(js_error_typ!(&ctx, failure.message())),
+        }
+    }
+
+    fn candidates<'a>(
+        &'a self,
+        module_id: &'a str,
+    ) -> impl Iterator<Item = &'a dyn ModuleLoader> + 'a {
+        let fs_loader = match self.module_loader.disableBuiltinFileSystemLoader {
+            true => self.fs_loader.as_deref(),
+            false => None,
+        };
+
+        self.loaders
+            .iter()
+            .map(|loader| loader.as_ref())
+            .filter(move |loader| loader.can_handle(module_id))
+            .chain(fs_loader)
+    }
+
+    /// Records the failure and returns the key of the module that throws it
+    fn fail(&self, failure: ModuleFailure) -> String {
+        let order = self.failure_count.get();
+        self.failure_count.set(order + 1);
+
+        let mut failures = self.failures.borrow_mut();
+        let previous = failures.iter().position(|(_, previous)| {
+            previous.specifier == failure.specifier
+                && previous.referrer == failure.referrer
+        });
+        let index = match previous {
+            Some(index) => {
+                failures[index] = (order, failure);
+                index
+            }
+            None => {
+                failures.push((order, failure));
+                failures.len() - 1
+            }
+        };
+        format!("{}{}", FAILED_MODULE_PREFIX, index)
+    }
+
+    fn failure(&self, module_id: &str) -> Option<ModuleFailure> {
+        let index = module_id.strip_prefix(FAILED_MODULE_PREFIX)?;
+        let index = index.parse::<usize>().ok()?;
+        let failures = self.failures.borrow();
+        failures.get(index).map(|(_, failure)| failure.clone())
+    }
+
     #[module_resolve]
     fn resolve(
         ctx: JSContext,
         module_name: JSValue,
-        _referrer: JSValue,
+        referrer: JSValue,
         _script_fetcher: JSValue,
     ) -> JSStringProctected {
         let state = downcast_state(&ctx);
         let module_loader = state.module_loader().borrow();
-        let module_id = module_name
+        let specifier = module_name
             .as_string()
-            .expect("Module name must be a string")
-            .to_string();
-
-        if let Some(_) = module_loader.sources.get(&module_id) {
-            return module_id.into();
-        }
-
-        for loader in module_loader.loaders.iter() {
-            if loader.can_handle(&module_id) {
-                return loader.resolve(&module_id).unwrap().into();
-            }
-        }
+            .map(|name| name.to_string())
+            .unwrap_or_default();
+        let referrer = match referrer.is_string() {
+            true => referrer
+                .as_string()
+                .ok()
+                .map(|referrer| referrer.to_string()),
+            false => None,
+        };
 
-        if module_loader.module_loader.disableBuiltinFileSystemLoader {
-            if let Some(fs_loader) = &module_loader.fs_loader {
-                return fs_loader.resolve(&module_id).unwrap().into();
+        match module_loader.resolve_specifier(&specifier, referrer.as_deref()) {
+            Ok(module_id) => {
+                if let Some(referrer) = referrer.as_deref() {
+                    module_loader.track_referrer(&module_id, referrer);
+                }
+                module_id.into()
             }
+            Err(failure) => module_loader.fail(failure).into(),
         }
-
-        unreachable!("Invalid Module: {:?}", module_id);
     }
 
     #[module_evaluate]
@@ -168,43 +891,51 @@ impl CoreModuleLoader {
         let loader = binding.module_loader().borrow();
         let module_id = module_name
             .as_string()
-            .expect("Module name must be a string")
-            .to_string();
+            .map(|name| name.to_string())
+            .unwrap_or_default();
 
-        if let Some(source) = loader.sources.get(&module_id) {
-            return source.evaluate(&ctx, &module_id).into();
+        match loader.sources.get(&module_id) {
+            Some(source) => source.evaluate(&ctx, &module_id).into(),
+            None => JSObject::new(&ctx).into(),
         }
-
-        unreachable!("Module: {:?} not found", module_id);
     }
 
+    /// Modules that fail to load are replaced by a module that throws the
+    /// error, so the import rejects instead of aborting the process.
     #[module_fetch]
     fn module_loader_fetch(
         ctx: JSContext,
         module_name: JSValue,
-        _attributes_value: JSValue,
+        attributes_value: JSValue,
         _script_fetcher: JSValue,
     ) -> JSStringProctected {
         let binding = downcast_state(&ctx);
         let loader = binding.module_loader().borrow();
         let module_id = module_name
             .as_string()
-            .expect("Module name must be a string")
-            .to_string();
-
-        for loader in loader.loaders.iter() {
-            if loader.can_handle(&module_id) {
-                return loader.load(&module_id).unwrap().into();
-            }
-        }
-
-        if loader.module_loader.disableBuiltinFileSystemLoader {
-            if let Some(fs_loader) = &loader.fs_loader {
-                return fs_loader.load(&module_id).unwrap().into();
+            .map(|name| name.to_string())
+            .unwrap_or_default();
+
+        let result = import_type(&ctx, &attributes_value)
+            .and_then(|import_type| ImportType::parse(import_type.as_deref()))
+            .map_err(|error| ModuleFailure {
+                specifier: module_id.clone(),
+                referrer: loader.referrer(&module_id),
+                loaders: vec![],
+                error,
+            })
+            .and_then(|import_type| loader.fetch_module(&module_id, import_type));
+
+        match result {
+            Ok(source) => source.into(),
+            Err(failure) => {
+                let source = failure.source();
+                if loader.failure(&module_id).is_none() {
+                    loader.fail(failure);
+                }
+                source.into()
             }
         }
-
-        unreachable!("No module found for: {:?}", module_id);
     }
 
     #[module_import_meta]
@@ -215,10 +946,16 @@ impl CoreModuleLoader {
     ) -> JSObject {
         let binding = downcast_state(&ctx);
         let loader = binding.module_loader().borrow();
-        if let Some(import_meta) = &loader.import_meta {
-            import_meta(&key.as_string().unwrap().to_string())
-        } else {
-            JSObject::new(&ctx)
+        let module_id = key
+            .as_string()
+            .map(|name| name.to_string())
+            .unwrap_or_default();
+
+        match &loader.import_meta {
+            Some(import_meta) => import_meta(&ctx, &module_id),
+            None => loader
+                .import_meta(&ctx, &module_id)
+                .unwrap_or_else(|_| JSObject::new(&ctx)),
         }
     }
 }
@@ -232,7 +969,10 @@ mod tests {
     use rust_jsc::{JSContext, JSObject, JSValue};
 
     use crate::{
-        class_table::ClassTable, job::AsyncJobQueue, proto_table::ProtoTable,
+        class_table::ClassTable,
+        job::AsyncJobQueue,
+        permissions::{PermissionError, PermissionName},
+        proto_table::ProtoTable,
         state::CoreState,
     };
 
@@ -519,4 +1259,290 @@ mod tests {
         assert!(result.is_number());
         assert_eq!(result.as_number().unwrap(), 3.0);
     }
+
+    #[test]
+    fn test_module_failure() {
+        let failure = ModuleFailure {
+            specifier: "@kedo/missing".to_string(),
+            referrer: Some("/app/main.js".to_string()),
+            loaders: vec!["KedoResolver".to_string()],
+            error: ModuleError::NotFound("@kedo/missing".to_string()),
+        };
+
+        assert_eq!(
+            failure.message(),
+            "Cannot find module '@kedo/missing' imported from '/app/main.js', tried loaders: KedoResolver"
+        );
+        assert_eq!(
+            failure.source(),
+            "throw new TypeError(\"Cannot find module '@kedo/missing' imported from '/app/main.js', tried loaders: KedoResolver\");"
+        );
+
+        let failure = ModuleFailure {
+            specifier: "bad.ts".to_string(),
+            referrer: None,
+            loaders: vec![],
+            error: ModuleError::InvalidModule("Unexpected \"}\"\n".to_string()),
+        };
+        assert_eq!(
+            failure.source(),
+            "throw new SyntaxError(\"Unexpected \\\"}\\\"\\n\");"
+        );
+    }
+
+    #[test]
+    fn test_resolve_specifier_failure() {
+        let mut loader = CoreModuleLoader::default();
+        loader.add_loader(KedoResolver {
+            keys: vec!["@kedo/syn".to_string()].into_iter().collect(),
+        });
+
+        assert_eq!(
+            loader.resolve_specifier("@kedo/syn", None).unwrap(),
+            "@kedo/syn"
+        );
+
+        let failure = loader.resolve_specifier("@kedo/other", Some("main.js"));
+        let failure = failure.unwrap_err();
+        assert!(failure.loaders.is_empty());
+        assert_eq!(failure.referrer.as_deref(), Some("main.js"));
+        assert_eq!(
+            failure.error,
+            ModuleError::NotFound("@kedo/other".to_string())
+        );
+
+        let key = loader.fail(failure.clone());
+        assert_eq!(loader.load_module(&key), Err(failure.clone()));
+        assert_eq!(loader.failures(), vec![failure.clone()]);
+
+        // failing again replaces the failure, a new one is added
+        let count = loader.failure_count();
+        assert_eq!(loader.fail(failure.clone()), key);
+        let other = loader.resolve_specifier("@kedo/other", None).unwrap_err();
+        assert_ne!(loader.fail(other.clone()), key);
+        assert_eq!(loader.failures(), vec![failure.clone(), other.clone()]);
+        assert_eq!(loader.failures_since(count), vec![failure, other]);
+        assert_eq!(loader.failure_count(), count + 2);
+    }
+
+    #[test]
+    fn test_remote_referrer() {
+        struct LocalResolver;
+
+        impl ModuleLoader for LocalResolver {
+            fn can_handle(&self, module_id: &str) -> bool {
+                module_id.starts_with("./")
+            }
+
+            fn resolve(&self, name: &str) -> Result<String, ModuleError> {
+                Ok(format!("/app/{}", name.trim_start_matches("./")))
+            }
+
+            fn load(&self, _module_id: &str) -> Result<String, ModuleError> {
+                Ok("".to_string())
+            }
+        }
+
+        let mut loader = CoreModuleLoader::default();
+        loader.add_loader(LocalResolver);
+        loader.add_loader(KedoResolver {
+            keys: vec!["@kedo/syn".to_string()].into_iter().collect(),
+        });
+
+        let remote = Some("https://example.com/mod.js");
+        assert_eq!(
+            loader.resolve_specifier("./util.js", Some("/app/main.js")),
+            Ok("/app/util.js".to_string())
+        );
+        assert_eq!(
+            loader.resolve_specifier("@kedo/syn", remote),
+            Ok("@kedo/syn".to_string())
+        );
+
+        let failure = loader.resolve_specifier("./util.js", remote).unwrap_err();
+        assert_eq!(failure.loaders, vec!["LocalResolver".to_string()]);
+        assert_eq!(
+            failure.error,
+            ModuleError::Other(
+                "Remote modules cannot import the local module '/app/util.js'"
+                    .to_string()
+            )
+        );
+
+        for specifier in ["/app/util.js", "file:///app/util.js"] {
+            let failure = loader.resolve_specifier(specifier, remote).unwrap_err();
+            assert!(failure.loaders.is_empty());
+            assert!(matches!(failure.error, ModuleError::Other(_)));
+        }
+    }
+
+    #[test]
+    fn test_load_failure_referrer() {
+        struct BrokenLoader;
+
+        impl ModuleLoader for BrokenLoader {
+            fn can_handle(&self, module_id: &str) -> bool {
+                module_id.starts_with("@broken:")
+            }
+
+            fn resolve(&self, name: &str) -> Result<String, ModuleError> {
+                Ok(name.to_string())
+            }
+
+            fn load(&self, name: &str) -> Result<String, ModuleError> {
+                Err(ModuleError::LoadError(name.to_string()))
+            }
+        }
+
+        let mut loader = CoreModuleLoader::default();
+        loader.add_loader(BrokenLoader);
+        loader.track_referrer("@broken:lib", "/app/main.js");
+        loader.track_referrer("@broken:lib", "/app/other.js");
+
+        let failure = loader.load_module("@broken:lib").unwrap_err();
+        assert_eq!(failure.referrer.as_deref(), Some("/app/main.js"));
+        assert_eq!(failure.loaders, vec!["BrokenLoader".to_string()]);
+
+        let ctx = JSContext::new();
+        loader.init(&ctx);
+        ctx.set_shared_data(Box::new(new_context_state(loader)));
+
+        let result =
+            ctx.evaluate_module_from_source("import '@broken:util';", "broken.js", None);
+        let message = result.unwrap_err().message().unwrap().to_string();
+        assert!(message.starts_with("Cannot load module '@broken:util' imported from '"));
+        assert!(message.contains("broken.js'"));
+    }
+
+    #[test]
+    fn test_import_type_mismatch() {
+        let mut loader = CoreModuleLoader::default();
+        loader.add_loader(KedoResolver {
+            keys: vec!["@kedo/data.json".to_string(), "@kedo/page.html".to_string()]
+                .into_iter()
+                .collect(),
+        });
+
+        assert!(loader
+            .fetch_module("@kedo/data.json", ImportType::Json)
+            .is_ok());
+        assert!(loader
+            .fetch_module("@kedo/data.json", ImportType::Json)
+            .is_ok());
+        let failure = loader
+            .fetch_module("@kedo/data.json", ImportType::Text)
+            .unwrap_err();
+        assert_eq!(
+            failure.error.to_string(),
+            "Module '@kedo/data.json' is imported with { type: \"json\" } and with { type: \"text\" }, a module can only have one type"
+        );
+
+        assert!(loader
+            .fetch_module("@kedo/page.html", ImportType::Bytes)
+            .is_ok());
+        assert!(loader
+            .fetch_module("@kedo/page.html", ImportType::JavaScript)
+            .is_err());
+    }
+
+    #[test]
+    fn test_read_permission() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path();
+        for (name, content) in [
+            ("data.json", "{}"),
+            ("notes.txt", "secret"),
+            ("key.bin", "secret"),
+            ("main.js", "export {};"),
+        ] {
+            std::fs::write(dir.join(name), content).unwrap();
+        }
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+        let read_error = |name: &str| PermissionError {
+            name: PermissionName::Read,
+            resource: path(name),
+        };
+
+        let mut loader = CoreModuleLoader::default();
+        loader.set_permissions(Arc::new(Permissions::deny_all()));
+        for (name, import_type) in [
+            ("data.json", ImportType::Json),
+            ("notes.txt", ImportType::Text),
+            ("key.bin", ImportType::Bytes),
+        ] {
+            let failure = loader.fetch_module(&path(name), import_type).unwrap_err();
+            assert_eq!(
+                failure.error,
+                ModuleError::PermissionDenied(read_error(name))
+            );
+        }
+
+        // the program itself can be imported without the read permission
+        assert!(loader.load_module(&path("main.js")).is_ok());
+
+        let failure = loader
+            .fetch_module(&path("notes.txt"), ImportType::Text)
+            .unwrap_err();
+        assert_eq!(failure.error_name(), "PermissionDenied");
+        assert!(failure
+            .source()
+            .starts_with("const error = new Error(\"Requires read access to \\\""));
+
+        let mut loader = CoreModuleLoader::default();
+        let allowed = crate::AllowList::Only(vec![dir.to_path_buf()]);
+        loader.set_permissions(Arc::new(Permissions::deny_all().allow_read(allowed)));
+        assert!(loader
+            .fetch_module(&path("notes.txt"), ImportType::Text)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_require_read_permission() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path();
+        std::fs::write(dir.join("config.json"), "{}").unwrap();
+        std::fs::write(dir.join("lib.cjs"), "exports.a = 1;").unwrap();
+        std::fs::write(dir.join("main.cjs"), "exports.b = 2;").unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+        let read_error = |name: &str| PermissionError {
+            name: PermissionName::Read,
+            resource: path(name),
+        };
+
+        let mut loader = CoreModuleLoader::default();
+        loader.set_permissions(Arc::new(Permissions::deny_all()));
+        for name in ["config.json", "lib.cjs"] {
+            let failure = loader.load_require(&path(name)).unwrap_err();
+            assert_eq!(
+                failure.error,
+                ModuleError::PermissionDenied(read_error(name))
+            );
+        }
+
+        // the ES module wrapper of an imported CommonJS module requires it again
+        assert!(loader.load_module(&path("main.cjs")).is_ok());
+        let (format, _) = loader.load_require(&path("main.cjs")).unwrap();
+        assert_eq!(format, ModuleFormat::CommonJs);
+    }
+
+    #[test]
+    fn test_missing_module_rejects() {
+        let loader = CoreModuleLoader::default();
+        let ctx = JSContext::new();
+        loader.init(&ctx);
+
+        let state = new_context_state(loader);
+        ctx.set_shared_data(Box::new(state));
+
+        let result = ctx.evaluate_module_from_source(
+            r"
+            import '@kedo/missing';
+        ",
+            "missing.js",
+            None,
+        );
+        let error = result.unwrap_err();
+        let message = error.message().unwrap().to_string();
+        assert!(message.contains("Cannot find module '@kedo/missing'"));
+    }
 }
@@ -0,0 +1,470 @@
+use rust_jsc::{JSContext, JSError, JSResult, JSValue};
+use std::{
+    fmt::Display,
+    path::{Component, Path, PathBuf},
+};
+
+#[macro_export]
+macro_rules! check_permission {
+    ($ctx:expr, $check:ident, $($arg:expr),* $(,)?) => {{
+        let state = $crate::downcast_state($ctx);
+        if let Err(err) = state.permissions().$check($($arg),*) {
+            return Err($crate::permission_denied($ctx, &err)?);
+        }
+    }};
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionName {
+    Read,
+    Write,
+    Net,
+    Env,
+}
+
+impl PermissionName {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PermissionName::Read => "read",
+            PermissionName::Write => "write",
+            PermissionName::Net => "net",
+            PermissionName::Env => "env",
+        }
+    }
+
+    pub fn flag(&self) -> &'static str {
+        match self {
+            PermissionName::Read => "--allow-read",
+            PermissionName::Write => "--allow-write",
+            PermissionName::Net => "--allow-net",
+            PermissionName::Env => "--allow-env",
+        }
+    }
+}
+
+impl TryFrom<&str> for PermissionName {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "read" => Ok(PermissionName::Read),
+            "write" => Ok(PermissionName::Write),
+            "net" => Ok(PermissionName::Net),
+            "env" => Ok(PermissionName::Env),
+            _ => Err(format!("Unknown permission name: {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionState {
+    Granted,
+    Denied,
+}
+
+impl PermissionState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PermissionState::Granted => "granted",
+            PermissionState::Denied => "denied",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionError {
+    pub name: PermissionName,
+    pub resource: String,
+}
+
+impl Display for PermissionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Requires {} access to \"{}\", run again with the {} flag",
+            self.name.as_str(),
+            self.resource,
+            self.name.flag()
+        )
+    }
+}
+
+impl std::error::Error for PermissionError {}
+
+/// Creates the `PermissionDenied` JS error thrown by the ops
+pub fn permission_denied(ctx: &JSContext, error: &PermissionError) -> JSResult<JSError> {
+    let error_value: JSValue = JSError::with_message(ctx, format!("{}", error))?.into();
+    let error_object = error_value.as_object()?;
+    error_object.set_property(
+        "name",
+        &JSValue::string(ctx, "PermissionDenied"),
+        Default::default(),
+    )?;
+    Ok(JSError::from(error_object))
+}
+
+/// The set of resources a permission was granted for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AllowList<T> {
+    All,
+    Only(Vec<T>),
+    Nothing,
+}
+
+impl<T> AllowList<T> {
+    /// `Some(empty)` (a bare `--allow-x`) grants every resource,
+    /// `Some(list)` only the listed ones, `None` denies.
+    pub fn from_option(values: Option<Vec<T>>) -> Self {
+        match values {
+            Some(values) if values.is_empty() => AllowList::All,
+            Some(values) => AllowList::Only(values),
+            None => AllowList::Nothing,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Permissions {
+    read: AllowList<PathBuf>,
+    write: AllowList<PathBuf>,
+    net: AllowList<String>,
+    env: AllowList<String>,
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Self::allow_all()
+    }
+}
+
+impl Permissions {
+    pub fn allow_all() -> Self {
+        Self {
+            read: AllowList::All,
+            write: AllowList::All,
+            net: AllowList::All,
+            env: AllowList::All,
+        }
+    }
+
+    pub fn deny_all() -> Self {
+        Self {
+            read: AllowList::Nothing,
+            write: AllowList::Nothing,
+            net: AllowList::Nothing,
+            env: AllowList::Nothing,
+        }
+    }
+
+    pub fn allow_read(mut self, paths: AllowList<PathBuf>) -> Self {
+        self.read = Self::resolve_paths(paths);
+        self
+    }
+
+    pub fn allow_write(mut self, paths: AllowList<PathBuf>) -> Self {
+        self.write = Self::resolve_paths(paths);
+        self
+    }
+
+    pub fn allow_net(mut self, hosts: AllowList<String>) -> Self {
+        self.net = hosts;
+        self
+    }
+
+    pub fn allow_env(mut self, variables: AllowList<String>) -> Self {
+        self.env = variables;
+        self
+    }
+
+    pub fn check_read(&self, path: &str) -> Result<(), PermissionError> {
+        Self::check_path(&self.read, PermissionName::Read, path)
+    }
+
+    pub fn check_write(&self, path: &str) -> Result<(), PermissionError> {
+        Self::check_path(&self.write, PermissionName::Write, path)
+    }
+
+    pub fn check_net(
+        &self,
+        host: &str,
+        port: Option<u16>,
+    ) -> Result<(), PermissionError> {
+        if self.query_net(Some(host), port) == PermissionState::Granted {
+            return Ok(());
+        }
+
+        let resource = match port {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        };
+
+        Err(PermissionError {
+            name: PermissionName::Net,
+            resource,
+        })
+    }
+
+    pub fn check_env(&self, variable: &str) -> Result<(), PermissionError> {
+        if self.query_env(Some(variable)) == PermissionState::Granted {
+            return Ok(());
+        }
+
+        Err(PermissionError {
+            name: PermissionName::Env,
+            resource: variable.to_string(),
+        })
+    }
+
+    /// Query the state of a permission, `resource` is the path, host or
+    /// variable to check. Without a resource the permission is only granted
+    /// if it was granted for every resource.
+    pub fn query(&self, name: PermissionName, resource: Option<&str>) -> PermissionState {
+        match name {
+            PermissionName::Read => Self::query_path(&self.read, resource),
+            PermissionName::Write => Self::query_path(&self.write, resource),
+            PermissionName::Net => match resource {
+                Some(resource) => {
+                    let (host, port) = split_host_port(resource);
+                    self.query_net(Some(host), port)
+                }
+                None => self.query_net(None, None),
+            },
+            PermissionName::Env => self.query_env(resource),
+        }
+    }
+
+    fn query_path(list: &AllowList<PathBuf>, path: Option<&str>) -> PermissionState {
+        let granted = match (list, path) {
+            (AllowList::All, _) => true,
+            (AllowList::Nothing, _) | (AllowList::Only(_), None) => false,
+            (AllowList::Only(paths), Some(path)) => {
+                let path = resolve_path(Path::new(path));
+                paths.iter().any(|allowed| path.starts_with(allowed))
+            }
+        };
+
+        if granted {
+            PermissionState::Granted
+        } else {
+            PermissionState::Denied
+        }
+    }
+
+    fn query_net(&self, host: Option<&str>, port: Option<u16>) -> PermissionState {
+        let granted = match (&self.net, host) {
+            (AllowList::All, _) => true,
+            (AllowList::Nothing, _) | (AllowList::Only(_), None) => false,
+            (AllowList::Only(hosts), Some(host)) => hosts.iter().any(|allowed| {
+                let (allowed_host, allowed_port) = split_host_port(allowed);
+                allowed_host.eq_ignore_ascii_case(host)
+                    && (allowed_port.is_none() || allowed_port == port)
+            }),
+        };
+
+        if granted {
+            PermissionState::Granted
+        } else {
+            PermissionState::Denied
+        }
+    }
+
+    fn query_env(&self, variable: Option<&str>) -> PermissionState {
+        let granted = match (&self.env, variable) {
+            (AllowList::All, _) => true,
+            (AllowList::Nothing, _) | (AllowList::Only(_), None) => false,
+            (AllowList::Only(variables), Some(variable)) => {
+                variables.iter().any(|allowed| allowed == variable)
+            }
+        };
+
+        if granted {
+            PermissionState::Granted
+        } else {
+            PermissionState::Denied
+        }
+    }
+
+    fn check_path(
+        list: &AllowList<PathBuf>,
+        name: PermissionName,
+        path: &str,
+    ) -> Result<(), PermissionError> {
+        if Self::query_path(list, Some(path)) == PermissionState::Granted {
+            return Ok(());
+        }
+
+        Err(PermissionError {
+            name,
+            resource: path.to_string(),
+        })
+    }
+
+    fn resolve_paths(paths: AllowList<PathBuf>) -> AllowList<PathBuf> {
+        match paths {
+            AllowList::Only(paths) => {
+                AllowList::Only(paths.iter().map(|path| resolve_path(path)).collect())
+            }
+            list => list,
+        }
+    }
+}
+
+/// Splits `host:port` into its parts, IPv6 addresses must be written in brackets.
+fn split_host_port(value: &str) -> (&str, Option<u16>) {
+    if let Some(rest) = value.strip_prefix('[') {
+        if let Some((host, port)) = rest.split_once("]:") {
+            return (host, port.parse().ok());
+        }
+        return (rest.trim_end_matches(']'), None);
+    }
+
+    match value.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') => match port.parse() {
+            Ok(port) => (host, Some(port)),
+            Err(_) => (value, None),
+        },
+        _ => (value, None),
+    }
+}
+
+/// Makes the path absolute, removes the `.` and `..` components and resolves the
+/// symlinks of the part that exists, so a symlink can't point out of an allowed
+/// directory and paths that do not exist yet can still be checked.
+fn resolve_path(path: &Path) -> PathBuf {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    };
+
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            component => resolved.push(component.as_os_str()),
+        }
+    }
+
+    for ancestor in resolved.ancestors() {
+        if let Ok(canonical) = std::fs::canonicalize(ancestor) {
+            let rest = resolved.strip_prefix(ancestor).unwrap_or(Path::new(""));
+            return match rest.as_os_str().is_empty() {
+                true => canonical,
+                false => canonical.join(rest),
+            };
+        }
+    }
+
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allow_all() {
+        let permissions = Permissions::allow_all();
+        assert!(permissions.check_read("/etc/passwd").is_ok());
+        assert!(permissions.check_write("./output.txt").is_ok());
+        assert!(permissions.check_net("example.com", Some(443)).is_ok());
+        assert!(permissions.check_env("HOME").is_ok());
+    }
+
+    #[test]
+    fn test_deny_all() {
+        let permissions = Permissions::deny_all();
+        assert!(permissions.check_read("/etc/passwd").is_err());
+        assert!(permissions.check_write("./output.txt").is_err());
+        assert!(permissions.check_net("example.com", None).is_err());
+        assert!(permissions.check_env("HOME").is_err());
+    }
+
+    #[test]
+    fn test_read_paths() {
+        let permissions = Permissions::deny_all()
+            .allow_read(AllowList::Only(vec![PathBuf::from("/tmp/kedo")]));
+
+        assert!(permissions.check_read("/tmp/kedo/data.txt").is_ok());
+        assert!(permissions
+            .check_read("/tmp/kedo/nested/../data.txt")
+            .is_ok());
+        assert!(permissions.check_read("/tmp/kedo/../secret.txt").is_err());
+        assert!(permissions.check_read("/tmp/kedos/data.txt").is_err());
+        assert!(permissions.check_write("/tmp/kedo/data.txt").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("allowed")).unwrap();
+        std::fs::write(root.join("secret.txt"), "secret").unwrap();
+        std::os::unix::fs::symlink(root.join("secret.txt"), root.join("allowed/link"))
+            .unwrap();
+        std::os::unix::fs::symlink(root.join("allowed"), root.join("alias")).unwrap();
+
+        let permissions = Permissions::deny_all()
+            .allow_read(AllowList::Only(vec![root.join("allowed")]));
+        let check =
+            |path: &str| permissions.check_read(root.join(path).to_str().unwrap());
+
+        assert!(check("allowed/link").is_err());
+        assert!(check("alias/new.txt").is_ok());
+        assert!(check("allowed/missing/file.txt").is_ok());
+    }
+
+    #[test]
+    fn test_net_hosts() {
+        let permissions = Permissions::deny_all().allow_net(AllowList::Only(vec![
+            "example.com".to_string(),
+            "127.0.0.1:8080".to_string(),
+        ]));
+
+        assert!(permissions.check_net("example.com", Some(443)).is_ok());
+        assert!(permissions.check_net("EXAMPLE.com", None).is_ok());
+        assert!(permissions.check_net("127.0.0.1", Some(8080)).is_ok());
+        assert!(permissions.check_net("127.0.0.1", Some(8081)).is_err());
+        assert!(permissions.check_net("api.example.com", None).is_err());
+    }
+
+    #[test]
+    fn test_query() {
+        let permissions = Permissions::deny_all()
+            .allow_env(AllowList::Only(vec!["HOME".to_string()]))
+            .allow_net(AllowList::All);
+
+        assert_eq!(
+            permissions.query(PermissionName::Env, Some("HOME")),
+            PermissionState::Granted
+        );
+        assert_eq!(
+            permissions.query(PermissionName::Env, None),
+            PermissionState::Denied
+        );
+        assert_eq!(
+            permissions.query(PermissionName::Net, Some("localhost:3000")),
+            PermissionState::Granted
+        );
+        assert_eq!(
+            permissions.query(PermissionName::Read, Some("/")),
+            PermissionState::Denied
+        );
+    }
+
+    #[test]
+    fn test_error_message() {
+        let permissions = Permissions::deny_all();
+        let error = permissions.check_read("data.txt").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Requires read access to \"data.txt\", run again with the --allow-read flag"
+        );
+    }
+}
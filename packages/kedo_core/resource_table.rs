@@ -0,0 +1,207 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    rc::{Rc, Weak},
+};
+
+pub type ResourceId = u32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceInfo {
+    pub rid: ResourceId,
+    pub kind: String,
+}
+
+/// | ------------------------------- |
+/// |          ResourceTable          |
+/// | ------------------------------- |
+/// | - next_rid: ResourceId          |
+/// | - resources: rid -> kind        |
+/// | - pending_close: Vec<rid>       |
+/// | ------------------------------- |
+///
+/// Keeps track of the resources and pending ops owned by the runtime,
+/// e.g. timers, http servers, stream readers and in-flight async ops.
+/// It's used by `Kedo.resources()` and the leak sanitizers.
+///
+/// Handles dropped while the table is borrowed queue their rid in `pending_close`,
+/// the queued resources are closed by the next `add` or `close` and are not
+/// listed as open meanwhile.
+#[derive(Debug, Default)]
+pub struct ResourceTable {
+    next_rid: ResourceId,
+    resources: BTreeMap<ResourceId, String>,
+    pending_close: Rc<RefCell<Vec<ResourceId>>>,
+}
+
+impl ResourceTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, kind: &str) -> ResourceId {
+        self.close_pending();
+        self.next_rid += 1;
+        self.resources.insert(self.next_rid, kind.to_string());
+        self.next_rid
+    }
+
+    /// Removes the resource, returns its kind if it was still open
+    pub fn close(&mut self, rid: ResourceId) -> Option<String> {
+        self.close_pending();
+        self.resources.remove(&rid)
+    }
+
+    pub fn contains(&self, rid: ResourceId) -> bool {
+        self.get(rid).is_some()
+    }
+
+    pub fn get(&self, rid: ResourceId) -> Option<&str> {
+        match self.pending().contains(&rid) {
+            true => None,
+            false => self.resources.get(&rid).map(|kind| kind.as_str()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.open().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Open resources ordered by rid
+    pub fn entries(&self) -> Vec<ResourceInfo> {
+        self.open()
+            .map(|(rid, kind)| ResourceInfo {
+                rid: *rid,
+                kind: kind.clone(),
+            })
+            .collect()
+    }
+
+    fn open(&self) -> impl Iterator<Item = (&ResourceId, &String)> {
+        let pending = self.pending();
+        self.resources
+            .iter()
+            .filter(move |(rid, _)| !pending.contains(rid))
+    }
+
+    /// Rids queued by the handles dropped while the table was borrowed
+    fn pending(&self) -> Vec<ResourceId> {
+        self.pending_close.borrow().clone()
+    }
+
+    fn close_pending(&mut self) {
+        for rid in self.pending_close.take() {
+            self.resources.remove(&rid);
+        }
+    }
+}
+
+/// Closes its resource when dropped, keep it alongside the Rust value
+/// that owns the resource (a future, a callback, a server...).
+#[derive(Debug)]
+pub struct ResourceHandle {
+    rid: ResourceId,
+    table: Weak<RefCell<ResourceTable>>,
+    pending_close: Rc<RefCell<Vec<ResourceId>>>,
+}
+
+impl ResourceHandle {
+    pub fn new(table: &Rc<RefCell<ResourceTable>>, kind: &str) -> Self {
+        let mut table_mut = table.borrow_mut();
+        let rid = table_mut.add(kind);
+        Self {
+            rid,
+            table: Rc::downgrade(table),
+            pending_close: table_mut.pending_close.clone(),
+        }
+    }
+
+    pub fn rid(&self) -> ResourceId {
+        self.rid
+    }
+
+    pub fn close(&self) {
+        let Some(table) = self.table.upgrade() else {
+            return;
+        };
+
+        // the table might be borrowed if the handle is dropped while listing resources
+        match table.try_borrow_mut() {
+            Ok(mut table) => {
+                table.close(self.rid);
+            }
+            Err(_) => self.pending_close.borrow_mut().push(self.rid),
+        }
+    }
+}
+
+impl Drop for ResourceHandle {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_and_close() {
+        let mut table = ResourceTable::new();
+        let timer = table.add("timer");
+        let server = table.add("httpServer");
+
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.get(timer), Some("timer"));
+        assert_eq!(table.close(timer), Some("timer".to_string()));
+        assert_eq!(table.close(timer), None);
+        assert_eq!(
+            table.entries(),
+            vec![ResourceInfo {
+                rid: server,
+                kind: "httpServer".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_handle_closes_on_drop() {
+        let table = Rc::new(RefCell::new(ResourceTable::new()));
+        let handle = ResourceHandle::new(&table, "op_fs_read_file");
+        let rid = handle.rid();
+        assert!(table.borrow().contains(rid));
+
+        drop(handle);
+        assert!(table.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_handle_closes_while_borrowed() {
+        let table = Rc::new(RefCell::new(ResourceTable::new()));
+        let handle = ResourceHandle::new(&table, "timer");
+        let other = ResourceHandle::new(&table, "timer");
+
+        let listing = table.borrow();
+        drop(handle);
+        drop(listing);
+        assert_eq!(table.borrow().len(), 1);
+        assert!(!table.borrow().contains(1));
+
+        table.borrow_mut().add("op");
+        drop(other);
+        assert_eq!(table.borrow().entries().len(), 1);
+        assert!(table.borrow().pending_close.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_rids_are_not_reused() {
+        let table = Rc::new(RefCell::new(ResourceTable::new()));
+        let first = ResourceHandle::new(&table, "timer").rid();
+        let second = ResourceHandle::new(&table, "timer").rid();
+        assert_ne!(first, second);
+    }
+}
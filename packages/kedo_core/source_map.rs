@@ -0,0 +1,459 @@
+use crate::modules::module_path;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::Value;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+use url::Url;
+
+const SOURCE_MAPPING_URL: [&str; 2] = ["//# sourceMappingURL=", "//@ sourceMappingURL="];
+
+/// A segment of the `mappings`, positions are 0-based
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Mapping {
+    column: u32,
+    original: Option<(u32, u32, u32, Option<u32>)>,
+}
+
+/// Position in the original source, `line` and `column` are 1-based
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+    pub name: Option<String>,
+}
+
+/// | ------------------------------- |
+/// |            SourceMap            |
+/// | ------------------------------- |
+/// | - sources: Vec<String>          |
+/// | - contents: Vec<Option<String>> |
+/// | - names: Vec<String>            |
+/// | - lines: Vec<Vec<Mapping>>      |
+/// | ------------------------------- |
+///
+/// Source map v3 of a generated module, see https://tc39.es/source-map/
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceMap {
+    sources: Vec<String>,
+    contents: Vec<Option<String>>,
+    names: Vec<String>,
+    lines: Vec<Vec<Mapping>>,
+}
+
+impl SourceMap {
+    /// Parses the JSON of a source map, the sources are resolved from `base`,
+    /// the directory of the map, when they are relative.
+    pub fn parse(json: &str, base: Option<&Path>) -> Result<Self, String> {
+        let value: Value = serde_json::from_str(json).map_err(|err| err.to_string())?;
+        let strings = |name: &str| -> Vec<Option<String>> {
+            value
+                .get(name)
+                .and_then(Value::as_array)
+                .map(|values| {
+                    values
+                        .iter()
+                        .map(|v| v.as_str().map(String::from))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        let mappings = value
+            .get("mappings")
+            .and_then(Value::as_str)
+            .ok_or("Source map has no mappings")?;
+        let root = value
+            .get("sourceRoot")
+            .and_then(Value::as_str)
+            .unwrap_or("");
+        let sources = strings("sources")
+            .into_iter()
+            .map(|source| resolve_source(root, &source.unwrap_or_default(), base))
+            .collect();
+
+        Ok(Self {
+            sources,
+            contents: strings("sourcesContent"),
+            names: strings("names")
+                .into_iter()
+                .map(Option::unwrap_or_default)
+                .collect(),
+            lines: decode_mappings(mappings)?,
+        })
+    }
+
+    /// Original position of a generated position, all 1-based. Columns before
+    /// the first segment of the line map to the first segment.
+    pub fn lookup(&self, line: u32, column: u32) -> Option<SourceLocation> {
+        let segments = self.lines.get(line.checked_sub(1)? as usize)?;
+        let column = column.saturating_sub(1);
+        let index = segments
+            .partition_point(|segment| segment.column <= column)
+            .saturating_sub(1);
+        let (source, line, column, name) = segments.get(index)?.original?;
+
+        Some(SourceLocation {
+            file: self.sources.get(source as usize)?.clone(),
+            line: line + 1,
+            column: column + 1,
+            name: name.and_then(|name| self.names.get(name as usize).cloned()),
+        })
+    }
+
+    /// Content of a source embedded in the map
+    pub fn source_content(&self, file: &str) -> Option<&str> {
+        let index = self.sources.iter().position(|source| source == file)?;
+        self.contents.get(index)?.as_deref()
+    }
+}
+
+enum Entry {
+    /// `sourceMappingURL` of the module, parsed on the first lookup
+    Url(String),
+    Parsed(Option<Rc<SourceMap>>),
+}
+
+/// | ------------------------------- |
+/// |           SourceMaps            |
+/// | ------------------------------- |
+/// | - maps: RefCell<HashMap>        |
+/// | ------------------------------- |
+///
+/// Source maps of the loaded modules by module key, the `sourceMappingURL` of
+/// a module is registered when it's fetched and the map is read once a stack
+/// trace needs it. Inline `data:` URLs and `.map` files next to the module are
+/// supported.
+#[derive(Default)]
+pub struct SourceMaps {
+    maps: RefCell<HashMap<String, Entry>>,
+}
+
+impl SourceMaps {
+    /// Registers the `sourceMappingURL` comment of the module code, if any
+    pub fn register(&self, module_id: &str, code: &str) {
+        if let Some(url) = source_mapping_url(code) {
+            self.maps
+                .borrow_mut()
+                .insert(module_id.to_string(), Entry::Url(url.to_string()));
+        }
+    }
+
+    /// Sets the source map of a module, e.g. of a bundle without a `sourceMappingURL`
+    pub fn insert(&self, module_id: &str, map: SourceMap) {
+        self.maps
+            .borrow_mut()
+            .insert(module_id.to_string(), Entry::Parsed(Some(Rc::new(map))));
+    }
+
+    pub fn get(&self, module_id: &str) -> Option<Rc<SourceMap>> {
+        let url = match self.maps.borrow().get(module_id)? {
+            Entry::Parsed(map) => return map.clone(),
+            Entry::Url(url) => url.clone(),
+        };
+
+        // a map that can't be read is not read again
+        let map = load_source_map(module_id, &url).map(Rc::new);
+        self.maps
+            .borrow_mut()
+            .insert(module_id.to_string(), Entry::Parsed(map.clone()));
+        map
+    }
+
+    /// Original location of a position of a module, the position itself when
+    /// the module has no source map or the position is not mapped.
+    pub fn original_location(
+        &self,
+        file: &str,
+        line: u32,
+        column: u32,
+    ) -> SourceLocation {
+        self.get(file)
+            .and_then(|map| map.lookup(line, column))
+            .unwrap_or_else(|| SourceLocation {
+                file: file.to_string(),
+                line,
+                column,
+                name: None,
+            })
+    }
+
+    /// Text of a source file, embedded in a source map already read or read from the
+    /// file system
+    pub fn source_text(&self, file: &str) -> Option<String> {
+        // the maps of the frames were read by `original_location`
+        let embedded = self.maps.borrow().values().find_map(|entry| match entry {
+            Entry::Parsed(Some(map)) => map.source_content(file).map(String::from),
+            _ => None,
+        });
+
+        embedded.or_else(|| std::fs::read_to_string(module_path(file)?).ok())
+    }
+}
+
+/// URL of the last `sourceMappingURL` comment at the end of the code
+pub fn source_mapping_url(code: &str) -> Option<&str> {
+    code.lines()
+        .rev()
+        .map(str::trim)
+        .take_while(|line| line.is_empty() || line.starts_with("//"))
+        .find_map(|line| {
+            SOURCE_MAPPING_URL
+                .iter()
+                .find_map(|prefix| line.strip_prefix(prefix))
+                .map(str::trim)
+        })
+}
+
+/// Reads the map of a `sourceMappingURL`, relative URLs are files next to the module
+fn load_source_map(module_id: &str, url: &str) -> Option<SourceMap> {
+    let dir =
+        module_path(module_id).and_then(|path| path.parent().map(Path::to_path_buf));
+    if let Some(data) = url.strip_prefix("data:") {
+        let (header, content) = data.split_once(',')?;
+        let json = match header.ends_with(";base64") {
+            true => String::from_utf8(STANDARD.decode(content).ok()?).ok()?,
+            false => content.to_string(),
+        };
+        return SourceMap::parse(&json, dir.as_deref()).ok();
+    }
+
+    let file = match url.starts_with("file://") {
+        true => Url::parse(url).ok()?.to_file_path().ok()?,
+        false => dir?.join(url),
+    };
+    let json = std::fs::read_to_string(&file).ok()?;
+    SourceMap::parse(&json, file.parent()).ok()
+}
+
+/// Path of a source of the map, sources stay as they are when there's no base
+fn resolve_source(root: &str, source: &str, base: Option<&Path>) -> String {
+    let source = match root.is_empty() {
+        true => source.to_string(),
+        false => format!("{}/{}", root.trim_end_matches('/'), source),
+    };
+
+    if let Ok(url) = Url::parse(&source) {
+        return match url.to_file_path() {
+            Ok(path) => path.to_string_lossy().to_string(),
+            Err(_) => source,
+        };
+    }
+
+    match base {
+        Some(base) if Path::new(&source).is_relative() => {
+            normalize(&base.join(&source)).to_string_lossy().to_string()
+        }
+        _ => source,
+    }
+}
+
+/// Removes the `.` and `..` of a path without touching the file system
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Decodes the `mappings` field, the segments of every generated line
+fn decode_mappings(mappings: &str) -> Result<Vec<Vec<Mapping>>, String> {
+    let mut lines = Vec::new();
+    // the fields of the segments are relative to the previous segment, the
+    // generated column is relative to the previous segment of the same line
+    let mut state = [0i64; 5];
+
+    for line in mappings.split(';') {
+        let mut segments = Vec::new();
+        state[0] = 0;
+
+        for segment in line.split(',').filter(|segment| !segment.is_empty()) {
+            let fields = decode_vlq(segment)?;
+            if !matches!(fields.len(), 1 | 4 | 5) {
+                return Err(format!("Invalid source map segment '{}'", segment));
+            }
+
+            for (index, field) in fields.iter().enumerate() {
+                state[index] += field;
+            }
+
+            let position = |value: i64| {
+                u32::try_from(value)
+                    .map_err(|_| "Invalid source map position".to_string())
+            };
+            let original = match fields.len() {
+                1 => None,
+                _ => Some((
+                    position(state[1])?,
+                    position(state[2])?,
+                    position(state[3])?,
+                    match fields.len() {
+                        5 => Some(position(state[4])?),
+                        _ => None,
+                    },
+                )),
+            };
+
+            segments.push(Mapping {
+                column: position(state[0])?,
+                original,
+            });
+        }
+
+        segments.sort_by_key(|segment| segment.column);
+        lines.push(segments);
+    }
+
+    Ok(lines)
+}
+
+/// Decodes the base64 VLQ numbers of a segment
+fn decode_vlq(segment: &str) -> Result<Vec<i64>, String> {
+    let mut values = Vec::new();
+    let mut value = 0i64;
+    let mut shift = 0;
+
+    for byte in segment.bytes() {
+        let digit = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return Err(format!("Invalid source map segment '{}'", segment)),
+        } as i64;
+
+        if shift > 60 {
+            return Err(format!("Invalid source map segment '{}'", segment));
+        }
+
+        value += (digit & 0b11111) << shift;
+        if digit & 0b100000 != 0 {
+            shift += 5;
+            continue;
+        }
+
+        // the lowest bit is the sign
+        values.push(match value & 1 {
+            1 => -(value >> 1),
+            _ => value >> 1,
+        });
+        value = 0;
+        shift = 0;
+    }
+
+    match shift {
+        0 => Ok(values),
+        _ => Err(format!("Invalid source map segment '{}'", segment)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // maps `const a = 1;\nthrow new Error(a);` to the TypeScript source
+    const MAP: &str = r#"{
+        "version": 3,
+        "sources": ["main.ts"],
+        "sourcesContent": ["const a: number = 1;\nthrow new Error(a);"],
+        "names": ["a", "Error"],
+        "mappings": "AAAA,MAAMA,IAAY;AAClB,MAAM,IAAIC,MAAMD"
+    }"#;
+
+    #[test]
+    fn test_decode_vlq() {
+        assert_eq!(decode_vlq("AAAA"), Ok(vec![0, 0, 0, 0]));
+        assert_eq!(decode_vlq("MAAMA"), Ok(vec![6, 0, 0, 6, 0]));
+        assert_eq!(decode_vlq("D"), Ok(vec![-1]));
+        assert_eq!(decode_vlq("gB"), Ok(vec![16]));
+        assert!(decode_vlq("g").is_err());
+        assert!(decode_vlq("!").is_err());
+    }
+
+    #[test]
+    fn test_lookup() {
+        let map = SourceMap::parse(MAP, Some(Path::new("/app/src"))).unwrap();
+        let location = |line, column| map.lookup(line, column).unwrap();
+
+        assert_eq!(location(1, 1).file, "/app/src/main.ts");
+        assert_eq!((location(1, 7).line, location(1, 7).column), (1, 7));
+        assert_eq!(location(1, 7).name.as_deref(), Some("a"));
+        // `throw new Error(a)` is on the second line of both files
+        assert_eq!((location(2, 11).line, location(2, 11).column), (2, 11));
+        assert_eq!(location(2, 11).name.as_deref(), Some("Error"));
+        assert_eq!((location(2, 13).line, location(2, 13).column), (2, 11));
+        assert!(map.lookup(3, 1).is_none());
+        assert_eq!(
+            map.source_content("/app/src/main.ts"),
+            Some("const a: number = 1;\nthrow new Error(a);")
+        );
+    }
+
+    #[test]
+    fn test_source_mapping_url() {
+        assert_eq!(
+            source_mapping_url("a();\n//# sourceMappingURL=main.js.map\n"),
+            Some("main.js.map")
+        );
+        assert_eq!(
+            source_mapping_url("a();\n//@ sourceMappingURL=data:x\n\n"),
+            Some("data:x")
+        );
+        assert_eq!(
+            source_mapping_url("//# sourceMappingURL=old.map\na();"),
+            None
+        );
+        assert_eq!(source_mapping_url("a();"), None);
+    }
+
+    #[test]
+    fn test_source_maps() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path().to_path_buf();
+        std::fs::write(dir.join("main.js.map"), MAP).unwrap();
+
+        let maps = SourceMaps::default();
+        let main = dir.join("main.js").to_string_lossy().to_string();
+        maps.register(&main, "throw 1;\n//# sourceMappingURL=main.js.map");
+        let inline = format!(
+            "throw 1;\n//# sourceMappingURL=data:application/json;base64,{}",
+            STANDARD.encode(MAP)
+        );
+        maps.register("/virtual/bundle.js", &inline);
+        maps.register("/virtual/plain.js", "throw 1;");
+
+        let location = maps.original_location(&main, 2, 11);
+        assert_eq!(location.file, dir.join("main.ts").to_string_lossy());
+        assert_eq!((location.line, location.column), (2, 11));
+        assert_eq!(
+            maps.original_location("/virtual/bundle.js", 2, 11).file,
+            "/virtual/main.ts"
+        );
+        assert_eq!(
+            maps.original_location("/virtual/plain.js", 2, 11),
+            SourceLocation {
+                file: "/virtual/plain.js".to_string(),
+                line: 2,
+                column: 11,
+                name: None,
+            }
+        );
+        assert_eq!(
+            maps.source_text("/virtual/main.ts").as_deref(),
+            Some("const a: number = 1;\nthrow new Error(a);")
+        );
+    }
+}
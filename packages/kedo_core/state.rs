@@ -1,16 +1,35 @@
 use crate::{
-    callback::JsProctectedCallable, class_table::ClassTable, modules::CoreModuleLoader,
-    proto_table::ProtoTable, AsyncJobQueue,
+    callback::JsProctectedCallable,
+    class_table::ClassTable,
+    modules::CoreModuleLoader,
+    permissions::Permissions,
+    proto_table::ProtoTable,
+    resource_table::{ResourceHandle, ResourceTable},
+    worker::{WorkerLauncher, WorkerScope},
+    AsyncJobQueue,
 };
 use kedo_std::TimerQueue;
 use kedo_utils::ManuallyDropClone;
-use std::{cell::RefCell, rc::Rc, sync::Arc};
+use rust_jsc::{JSContext, JSFunction, JSResult};
+use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::Arc};
 
+/// Spawns an async op, the op is tracked in the resource table
+/// under `$name` until its future completes.
 #[macro_export]
 macro_rules! enqueue_job {
     ($state:expr, $future:expr) => {
-        $state.job_queue().borrow().spawn(Box::pin($future));
+        $crate::enqueue_job!($state, "op", $future)
     };
+    ($state:expr, $name:expr, $future:expr) => {{
+        let state = &$state;
+        let future = $future;
+        let op = state.track_resource($name);
+        state.job_queue().borrow().spawn(Box::pin(async move {
+            let job = future.await;
+            drop(op);
+            job
+        }));
+    }};
 }
 
 pub struct CoreState {
@@ -19,6 +38,13 @@ pub struct CoreState {
     timer_queue: Arc<TimerQueue<JsProctectedCallable>>,
     class_manager: Arc<ClassTable>,
     proto_manager: Arc<ProtoTable>,
+    permissions: Arc<Permissions>,
+    resources: Rc<RefCell<ResourceTable>>,
+    worker_launcher: Option<WorkerLauncher>,
+    worker_scope: Rc<RefCell<Option<WorkerScope>>>,
+    inspector: Rc<RefCell<Option<JsProctectedCallable>>>,
+    helpers: Rc<RefCell<HashMap<&'static str, JsProctectedCallable>>>,
+    env: Rc<RefCell<HashMap<String, Option<String>>>>,
 }
 
 impl Clone for CoreState {
@@ -29,6 +55,13 @@ impl Clone for CoreState {
             timer_queue: self.timer_queue.clone(),
             class_manager: self.class_manager.clone(),
             proto_manager: self.proto_manager.clone(),
+            permissions: self.permissions.clone(),
+            resources: self.resources.clone(),
+            worker_launcher: self.worker_launcher.clone(),
+            worker_scope: self.worker_scope.clone(),
+            inspector: self.inspector.clone(),
+            helpers: self.helpers.clone(),
+            env: self.env.clone(),
         }
     }
 }
@@ -47,9 +80,39 @@ impl CoreState {
             timer_queue: Arc::new(timer_queue),
             class_manager: Arc::new(manager),
             proto_manager: Arc::new(proto),
+            permissions: Arc::new(Permissions::default()),
+            resources: Rc::new(RefCell::new(ResourceTable::new())),
+            worker_launcher: None,
+            worker_scope: Rc::new(RefCell::new(None)),
+            inspector: Rc::new(RefCell::new(None)),
+            helpers: Rc::new(RefCell::new(HashMap::new())),
+            env: Rc::new(RefCell::new(HashMap::new())),
         }
     }
 
+    /// Replaces the permissions granted to the ops,
+    /// must be called before the state is shared with the context.
+    pub fn with_permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = Arc::new(permissions);
+        self.module_loader
+            .borrow_mut()
+            .set_permissions(self.permissions.clone());
+        self
+    }
+
+    /// Enables `new Worker()`, the launcher runs the worker runtime on its own thread.
+    /// Must be called before the state is shared with the context.
+    pub fn with_worker_launcher(mut self, launcher: WorkerLauncher) -> Self {
+        self.worker_launcher = Some(launcher);
+        self
+    }
+
+    /// Marks the runtime as a worker, the scope connects it to its parent
+    pub fn with_worker_scope(self, scope: WorkerScope) -> Self {
+        self.worker_scope.replace(Some(scope));
+        self
+    }
+
     pub fn timers(&self) -> &TimerQueue<JsProctectedCallable> {
         &self.timer_queue
     }
@@ -66,14 +129,68 @@ impl CoreState {
         &self.proto_manager
     }
 
+    pub fn permissions(&self) -> &Permissions {
+        &self.permissions
+    }
+
+    pub fn resources(&self) -> &Rc<RefCell<ResourceTable>> {
+        &self.resources
+    }
+
+    /// Registers a resource that stays open until the handle is dropped
+    pub fn track_resource(&self, kind: &str) -> ResourceHandle {
+        ResourceHandle::new(&self.resources, kind)
+    }
+
+    pub fn worker_launcher(&self) -> Option<&WorkerLauncher> {
+        self.worker_launcher.as_ref()
+    }
+
+    /// Set when the runtime runs inside a worker
+    pub fn worker_scope(&self) -> &Rc<RefCell<Option<WorkerScope>>> {
+        &self.worker_scope
+    }
+
     pub fn module_loader(&self) -> &Rc<RefCell<CoreModuleLoader>> {
         &self.module_loader
     }
+
+    /// Formatter of `console.log`, compiled by its first call
+    pub fn inspector(&self) -> &Rc<RefCell<Option<JsProctectedCallable>>> {
+        &self.inspector
+    }
+
+    /// Environment variables set (`Some`) or deleted (`None`) by the runtime.
+    /// The process environment is shared with the workers running on other
+    /// threads, so it's read but never changed.
+    pub fn env(&self) -> &Rc<RefCell<HashMap<String, Option<String>>>> {
+        &self.env
+    }
+
+    /// Functions compiled by [`CoreState::helper`], keyed by their source
+    pub fn helpers(&self) -> &Rc<RefCell<HashMap<&'static str, JsProctectedCallable>>> {
+        &self.helpers
+    }
+
+    /// The function `source` evaluates to, compiled once per context
+    pub fn helper(
+        &self,
+        ctx: &JSContext,
+        source: &'static str,
+    ) -> JSResult<JsProctectedCallable> {
+        if let Some(helper) = self.helpers.borrow().get(source) {
+            return Ok(helper.clone());
+        }
+
+        let function = ctx.evaluate_script(source, None)?;
+        let helper =
+            JsProctectedCallable::new(JSFunction::from(function.as_object()?), vec![]);
+        self.helpers.borrow_mut().insert(source, helper.clone());
+        Ok(helper)
+    }
 }
 
-pub fn downcast_state(
-    context: &rust_jsc::JSContext,
-) -> ManuallyDropClone<Box<CoreState>> {
+pub fn downcast_state(context: &JSContext) -> ManuallyDropClone<Box<CoreState>> {
     let state = context
         .get_shared_data::<CoreState>()
         .expect("state not found");
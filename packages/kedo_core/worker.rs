@@ -0,0 +1,171 @@
+use kedo_std::{
+    BufferChannel, BufferChannelWriter, SerializedValue, StreamError,
+    UnboundedBufferChannel, UnboundedBufferChannelReader, UnboundedBufferChannelWriter,
+};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+/// Starts the worker runtime on the current thread,
+/// it's called from the thread spawned for each `new Worker()`.
+pub type WorkerLauncher = Arc<dyn Fn(WorkerScope) + Send + Sync>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkerError {
+    pub message: String,
+    pub stack: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkerMessage {
+    Message(SerializedValue),
+    /// Uncaught error in the worker, dispatched as an `error` event
+    Error(WorkerError),
+}
+
+/// Parent side of a worker, owned by the `Worker` object.
+/// Dropping it closes the channel to the worker.
+pub struct WorkerHandle {
+    sender: UnboundedBufferChannelWriter<WorkerMessage>,
+    receiver: UnboundedBufferChannelReader<WorkerMessage>,
+    terminate: Arc<Notify>,
+}
+
+impl WorkerHandle {
+    pub fn post_message(&self, message: WorkerMessage) -> Result<(), StreamError> {
+        self.sender.try_write(message)
+    }
+
+    pub fn receiver(&mut self) -> &mut UnboundedBufferChannelReader<WorkerMessage> {
+        &mut self.receiver
+    }
+
+    /// Stops the worker event loop, pending messages are discarded
+    pub fn terminate(&self) {
+        self.terminate.notify_one();
+    }
+}
+
+/// | ------------------------------- |
+/// |           WorkerScope           |
+/// | ------------------------------- |
+/// | - name: String                  |
+/// | - url: String                   |
+/// | - sender: to the parent         |
+/// | - receiver: from the parent     |
+/// | - close: Notify                 |
+/// | ------------------------------- |
+///
+/// Worker side of a worker, it's moved to the worker thread and
+/// stored in the state of the worker runtime.
+pub struct WorkerScope {
+    name: String,
+    url: String,
+    sender: UnboundedBufferChannelWriter<WorkerMessage>,
+    receiver: Option<UnboundedBufferChannelReader<WorkerMessage>>,
+    close: Arc<Notify>,
+}
+
+impl WorkerScope {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn post_message(&self, message: WorkerMessage) -> Result<(), StreamError> {
+        self.sender.try_write(message)
+    }
+
+    /// Writer to the parent, it outlives the scope when the worker fails to start
+    pub fn sender(&self) -> UnboundedBufferChannelWriter<WorkerMessage> {
+        self.sender.clone()
+    }
+
+    /// Takes the receiver while a read is in flight, put it back with [`Self::set_receiver`]
+    pub fn take_receiver(
+        &mut self,
+    ) -> Option<UnboundedBufferChannelReader<WorkerMessage>> {
+        self.receiver.take()
+    }
+
+    pub fn set_receiver(
+        &mut self,
+        receiver: UnboundedBufferChannelReader<WorkerMessage>,
+    ) {
+        self.receiver = Some(receiver);
+    }
+
+    /// Notified when the worker calls `close()` or the parent calls `terminate()`
+    pub fn close_signal(&self) -> Arc<Notify> {
+        self.close.clone()
+    }
+
+    pub fn close(&self) {
+        self.close.notify_one();
+    }
+}
+
+/// Creates both ends of a worker, messages are delivered in order on each side
+pub fn worker_channel(name: &str, url: &str) -> (WorkerHandle, WorkerScope) {
+    let mut to_worker = UnboundedBufferChannel::new();
+    let mut to_parent = UnboundedBufferChannel::new();
+    let close = Arc::new(Notify::new());
+
+    let handle = WorkerHandle {
+        sender: to_worker.acquire_writer().expect("worker writer"),
+        receiver: to_parent.acquire_reader().expect("parent reader"),
+        terminate: close.clone(),
+    };
+
+    let scope = WorkerScope {
+        name: name.to_string(),
+        url: url.to_string(),
+        sender: to_parent.acquire_writer().expect("parent writer"),
+        receiver: to_worker.acquire_reader(),
+        close,
+    };
+
+    (handle, scope)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kedo_std::BufferChannelReader;
+
+    #[tokio::test]
+    async fn test_worker_channel() {
+        let (mut handle, mut scope) = worker_channel("worker", "file:///worker.js");
+        let message = WorkerMessage::Message(SerializedValue::Number(1.0));
+
+        handle.post_message(message.clone()).unwrap();
+        let mut receiver = scope.take_receiver().unwrap();
+        assert_eq!(receiver.read().await, Ok(message));
+
+        scope
+            .post_message(WorkerMessage::Message(SerializedValue::Null))
+            .unwrap();
+        assert_eq!(
+            handle.receiver().read().await,
+            Ok(WorkerMessage::Message(SerializedValue::Null))
+        );
+
+        // the parent stops reading once the worker goes away
+        drop(scope);
+        assert_eq!(handle.receiver().read().await, Err(StreamError::Closed));
+
+        // and the worker once the parent drops the handle
+        drop(handle);
+        assert_eq!(receiver.try_read(), Err(StreamError::Closed));
+    }
+
+    #[tokio::test]
+    async fn test_terminate_notifies_the_scope() {
+        let (handle, scope) = worker_channel("worker", "file:///worker.js");
+        let close = scope.close_signal();
+        handle.terminate();
+        close.notified().await;
+    }
+}
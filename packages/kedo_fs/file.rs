@@ -1,4 +1,7 @@
-use kedo_core::{define_exports, downcast_state, enqueue_job, native_job, ModuleSource};
+use kedo_core::{
+    check_permission, define_exports, downcast_state, enqueue_job, native_job,
+    ModuleSource,
+};
 use kedo_utils::{js_error, js_undefined};
 use rust_jsc::{callback, JSArray, JSContext, JSError, JSObject, JSResult, JSValue};
 
@@ -43,6 +46,7 @@ fn op_fs_read_file_sync(
     __: JSObject,
     path: String,
 ) -> JSResult<JSValue> {
+    check_permission!(&ctx, check_read, &path);
     let content = StdFileSystem::read_file_evt(&path);
     match content {
         Ok(content) => Ok(JSValue::string(&ctx, content)),
@@ -57,6 +61,7 @@ fn op_fs_read_dir_sync(
     __: JSObject,
     path: String,
 ) -> JSResult<JSValue> {
+    check_permission!(&ctx, check_read, &path);
     let entries = StdFileSystem::read_dir_evt(&path);
     match entries {
         Ok(entries) => {
@@ -80,6 +85,7 @@ fn op_fs_write_file_sync(
     path: String,
     data: String,
 ) -> JSResult<JSValue> {
+    check_permission!(&ctx, check_write, &path);
     let content = StdFileSystem::write_file_evt(&path, &data);
     match content {
         Ok(_) => Ok(JSValue::undefined(&ctx)),
@@ -95,6 +101,7 @@ fn op_fs_remove_sync(
     path: String,
     recursive: bool,
 ) -> JSResult<JSValue> {
+    check_permission!(&ctx, check_write, &path);
     let content = StdFileSystem::remove_evt(&path, recursive);
     match content {
         Ok(_) => Ok(JSValue::undefined(&ctx)),
@@ -110,10 +117,12 @@ fn op_fs_read_file(
     path: String,
     callbak: JSObject,
 ) -> JSResult<JSValue> {
+    check_permission!(&ctx, check_read, &path);
+
     callbak.protect();
 
     let state = downcast_state(&ctx);
-    enqueue_job!(state, async move {
+    enqueue_job!(state, "op_fs_read_file", async move {
         let content = StdFileSystem::read_file_async_evt(&path).await;
         native_job!("FileSystem::read_file", move |ctx| {
             match content {
@@ -144,10 +153,12 @@ fn op_fs_remove(
     recursive: bool,
     callback: JSObject,
 ) -> JSResult<JSValue> {
+    check_permission!(&ctx, check_write, &path);
+
     callback.protect();
 
     let state = downcast_state(&ctx);
-    enqueue_job!(state, async move {
+    enqueue_job!(state, "op_fs_remove", async move {
         let content = StdFileSystem::remove_async_evt(&path, recursive).await;
         native_job!("FileSystem::remove", move |ctx| {
             match content {
@@ -174,10 +185,12 @@ fn op_fs_read_dir(
     path: String,
     callback: JSObject,
 ) -> JSResult<JSValue> {
+    check_permission!(&ctx, check_read, &path);
+
     callback.protect();
 
     let state = downcast_state(&ctx);
-    enqueue_job!(state, async move {
+    enqueue_job!(state, "op_fs_read_dir", async move {
         let entries = StdFileSystem::read_dir_async_evt(&path).await;
         native_job!("FileSystem::read_dir", move |ctx| {
             match entries {
@@ -213,10 +226,12 @@ fn op_fs_write_file(
     data: String,
     callback: JSObject,
 ) -> JSResult<JSValue> {
+    check_permission!(&ctx, check_write, &path);
+
     callback.protect();
 
     let state = downcast_state(&ctx);
-    enqueue_job!(state, async move {
+    enqueue_job!(state, "op_fs_write_file", async move {
         let content = StdFileSystem::write_file_async_evt(&path, &data).await;
         native_job!("FileSystem::write_file", move |ctx| {
             match content {
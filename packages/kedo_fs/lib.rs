@@ -1,6 +1,8 @@
 mod file;
 mod file_dir;
+mod node_modules;
 mod std;
 
 pub use file::FileSystemModule;
 pub use file::FileSystemModuleLoader;
+pub use node_modules::NodeModulesLoader;
@@ -0,0 +1,677 @@
+use kedo_core::{ModuleError, ModuleLoader};
+use serde_json::Value;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+/// Conditions matched in the `exports` and `imports` of a package, in the order
+/// of the package.json unless the key is not listed.
+const CONDITIONS: [&str; 3] = ["kedo", "import", "default"];
+const REQUIRE_CONDITIONS: [&str; 3] = ["kedo", "require", "default"];
+/// Extensions probed for the files without one
+const EXTENSIONS: [&str; 2] = [".js", ".mjs"];
+const REQUIRE_EXTENSIONS: [&str; 3] = [".js", ".cjs", ".json"];
+const INDEX_FILES: [&str; 2] = ["index.js", "index.mjs"];
+const REQUIRE_INDEX_FILES: [&str; 3] = ["index.js", "index.cjs", "index.json"];
+
+/// Resolution of an `import` or of a `require()` call
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Import,
+    Require,
+}
+
+impl Mode {
+    fn conditions(&self) -> &'static [&'static str] {
+        match self {
+            Mode::Import => &CONDITIONS,
+            Mode::Require => &REQUIRE_CONDITIONS,
+        }
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            Mode::Import => &EXTENSIONS,
+            Mode::Require => &REQUIRE_EXTENSIONS,
+        }
+    }
+
+    fn index_files(&self) -> &'static [&'static str] {
+        match self {
+            Mode::Import => &INDEX_FILES,
+            Mode::Require => &REQUIRE_INDEX_FILES,
+        }
+    }
+}
+
+/// Fields of a package.json used by the resolution
+#[derive(Debug, Default)]
+struct PackageJson {
+    name: Option<String>,
+    main: Option<String>,
+    module: Option<String>,
+    exports: Option<Value>,
+    imports: Option<Value>,
+}
+
+impl PackageJson {
+    fn parse(source: &str) -> Option<Self> {
+        let value: Value = serde_json::from_str(source).ok()?;
+        let field =
+            |name: &str| value.get(name).and_then(Value::as_str).map(String::from);
+
+        Some(Self {
+            name: field("name"),
+            main: field("main"),
+            module: field("module"),
+            exports: value.get("exports").cloned(),
+            imports: value.get("imports").cloned(),
+        })
+    }
+}
+
+/// | ------------------------------- |
+/// |        NodeModulesLoader        |
+/// | ------------------------------- |
+/// | - packages: RefCell<HashMap>    |
+/// | ------------------------------- |
+///
+/// Resolves the bare specifiers to the packages installed in the `node_modules`
+/// of the importer or of its parents, following the `exports`, `imports`, `module`
+/// and `main` fields of their package.json. `require()` matches the `require`
+/// condition and ignores `module`. The package.json files are read once, the
+/// resolved paths are loaded by the file system loader.
+///
+/// e.g.
+/// ```ignore
+/// RuntimeBuilder::new().with_loader(NodeModulesLoader::default())
+/// ```
+#[derive(Default)]
+pub struct NodeModulesLoader {
+    packages: RefCell<HashMap<PathBuf, Option<Arc<PackageJson>>>>,
+}
+
+impl NodeModulesLoader {
+    fn package_json(&self, dir: &Path) -> Option<Arc<PackageJson>> {
+        let path = dir.join("package.json");
+        if let Some(package) = self.packages.borrow().get(&path) {
+            return package.clone();
+        }
+
+        let package = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|source| PackageJson::parse(&source))
+            .map(Arc::new);
+        self.packages.borrow_mut().insert(path, package.clone());
+        package
+    }
+
+    /// Closest directory with a package.json, the scope of the `imports` field
+    fn package_scope(&self, dir: &Path) -> Option<(PathBuf, Arc<PackageJson>)> {
+        dir.ancestors()
+            .take_while(|dir| !dir.ends_with("node_modules"))
+            .find_map(|dir| Some((dir.to_path_buf(), self.package_json(dir)?)))
+    }
+
+    fn resolve_package(
+        &self,
+        specifier: &str,
+        referrer_dir: &Path,
+        mode: Mode,
+    ) -> Result<PathBuf, ModuleError> {
+        let not_found = || ModuleError::NotFound(specifier.to_string());
+        let (name, subpath) = split_package_name(specifier).ok_or_else(not_found)?;
+
+        // a package can import itself by its name
+        if let Some((dir, package)) = self.package_scope(referrer_dir) {
+            if package.name.as_deref() == Some(name) && package.exports.is_some() {
+                return self.resolve_exports(&dir, &package, &subpath, specifier, mode);
+            }
+        }
+
+        for dir in referrer_dir.ancestors() {
+            let package_dir = dir.join("node_modules").join(name);
+            if !package_dir.is_dir() {
+                continue;
+            }
+
+            let package = self.package_json(&package_dir).unwrap_or_default();
+            if package.exports.is_some() {
+                return self.resolve_exports(
+                    &package_dir,
+                    &package,
+                    &subpath,
+                    specifier,
+                    mode,
+                );
+            }
+
+            if subpath != "." {
+                return probe_file(&join(&package_dir, &subpath), mode)
+                    .ok_or_else(not_found);
+            }
+
+            let entry = match mode {
+                Mode::Import => package.module.as_deref().or(package.main.as_deref()),
+                Mode::Require => package.main.as_deref(),
+            };
+            if let Some(entry) =
+                entry.and_then(|entry| probe_file(&join(&package_dir, entry), mode))
+            {
+                return Ok(entry);
+            }
+
+            return probe_index(&package_dir, mode).ok_or_else(not_found);
+        }
+
+        Err(not_found())
+    }
+
+    fn resolve_exports(
+        &self,
+        package_dir: &Path,
+        package: &PackageJson,
+        subpath: &str,
+        specifier: &str,
+        mode: Mode,
+    ) -> Result<PathBuf, ModuleError> {
+        let exports = package.exports.as_ref().expect("package exports");
+        let sugar = match exports {
+            Value::Object(map) => !map.keys().any(|key| key.starts_with('.')),
+            _ => true,
+        };
+
+        // "exports": "./index.js" is the same as "exports": { ".": "./index.js" }
+        let target = match sugar {
+            true if subpath == "." => Some((exports, None)),
+            true => None,
+            false => match_subpath(exports, subpath),
+        };
+
+        let target = match target {
+            Some((target, pattern)) => {
+                resolve_target(package_dir, target, pattern, mode)?
+            }
+            None => None,
+        };
+
+        target.ok_or_else(|| {
+            ModuleError::Other(format!(
+                "Package subpath '{}' is not exported from '{}' imported as '{}'",
+                subpath,
+                package_dir.display(),
+                specifier
+            ))
+        })
+    }
+
+    /// Resolves the `#name` specifiers with the `imports` field of the importer package
+    fn resolve_imports(
+        &self,
+        specifier: &str,
+        referrer_dir: &Path,
+        mode: Mode,
+    ) -> Result<PathBuf, ModuleError> {
+        let not_found = || ModuleError::NotFound(specifier.to_string());
+        let (dir, package) = self.package_scope(referrer_dir).ok_or_else(not_found)?;
+        let imports = package.imports.as_ref().ok_or_else(not_found)?;
+        let (target, pattern) =
+            match_subpath(imports, specifier).ok_or_else(not_found)?;
+
+        // the imports can map to other packages, e.g. "#dep": "lodash"
+        if let Some(package) = target.as_str().filter(|target| is_bare(target)) {
+            let package = match pattern {
+                Some(pattern) => package.replace('*', pattern),
+                None => package.to_string(),
+            };
+            return self.resolve_package(&package, &dir, mode);
+        }
+
+        resolve_target(&dir, target, pattern, mode)?.ok_or_else(not_found)
+    }
+
+    fn resolve_mode(
+        &self,
+        module_id: &str,
+        referrer: Option<&str>,
+        mode: Mode,
+    ) -> Result<String, ModuleError> {
+        let cwd = std::env::current_dir()
+            .map_err(|_| ModuleError::NotFound(module_id.to_string()))?;
+        let referrer_dir = referrer
+            .map(|referrer| cwd.join(referrer))
+            .and_then(|referrer| referrer.parent().map(Path::to_path_buf))
+            .unwrap_or(cwd);
+
+        let path = if module_id.starts_with('#') {
+            self.resolve_imports(module_id, &referrer_dir, mode)?
+        } else {
+            self.resolve_package(module_id, &referrer_dir, mode)?
+        };
+
+        Ok(path.to_string_lossy().to_string())
+    }
+}
+
+impl ModuleLoader for NodeModulesLoader {
+    fn can_handle(&self, module_id: &str) -> bool {
+        is_bare(module_id) || module_id.starts_with('#')
+    }
+
+    fn resolve(&self, module_id: &str) -> Result<String, ModuleError> {
+        self.resolve_from(module_id, None)
+    }
+
+    fn resolve_from(
+        &self,
+        module_id: &str,
+        referrer: Option<&str>,
+    ) -> Result<String, ModuleError> {
+        self.resolve_mode(module_id, referrer, Mode::Import)
+    }
+
+    fn resolve_require(
+        &self,
+        module_id: &str,
+        referrer: Option<&str>,
+    ) -> Result<String, ModuleError> {
+        self.resolve_mode(module_id, referrer, Mode::Require)
+    }
+
+    fn load(&self, module_id: &str) -> Result<String, ModuleError> {
+        std::fs::read_to_string(module_id)
+            .map_err(|_| ModuleError::LoadError(module_id.to_string()))
+    }
+}
+
+/// Specifiers of packages, e.g. `lodash` or `@scope/pkg/sub`,
+/// module keys like `@kedo:op/web` are not packages.
+fn is_bare(specifier: &str) -> bool {
+    !specifier.is_empty()
+        && !specifier.starts_with('.')
+        && !specifier.starts_with('/')
+        && !specifier.starts_with('#')
+        && !specifier.contains(':')
+}
+
+/// Splits `@scope/name/sub/path` into `@scope/name` and `./sub/path`
+fn split_package_name(specifier: &str) -> Option<(&str, String)> {
+    let segments = match specifier.starts_with('@') {
+        true => 2,
+        false => 1,
+    };
+
+    let end = specifier
+        .match_indices('/')
+        .nth(segments - 1)
+        .map(|(index, _)| index)
+        .unwrap_or(specifier.len());
+    let name = &specifier[..end];
+    if name.is_empty() || (segments == 2 && !name.contains('/')) {
+        return None;
+    }
+
+    Some((name, format!(".{}", &specifier[end..])))
+}
+
+/// Finds the entry of `exports` or `imports` for the subpath, patterns with a `*`
+/// return the matched part, the longest pattern prefix wins.
+fn match_subpath<'a, 'b>(
+    map: &'a Value,
+    subpath: &'b str,
+) -> Option<(&'a Value, Option<&'b str>)> {
+    let map = map.as_object()?;
+    if let Some(target) = map.get(subpath).filter(|_| !subpath.contains('*')) {
+        return Some((target, None));
+    }
+
+    map.iter()
+        .filter_map(|(key, target)| {
+            let (prefix, suffix) = key.split_once('*')?;
+            let matched = subpath.strip_prefix(prefix)?.strip_suffix(suffix)?;
+            Some((prefix.len(), target, matched))
+        })
+        .max_by_key(|(prefix, _, _)| *prefix)
+        .map(|(_, target, matched)| (target, Some(matched)))
+}
+
+/// Resolves a target of `exports` or `imports`, conditions are tried in order.
+/// `None` means the subpath is excluded, e.g. `"./internal/*": null`
+fn resolve_target(
+    package_dir: &Path,
+    target: &Value,
+    pattern: Option<&str>,
+    mode: Mode,
+) -> Result<Option<PathBuf>, ModuleError> {
+    match target {
+        Value::String(target) => {
+            let target = match pattern {
+                Some(pattern) => target.replace('*', pattern),
+                None => target.clone(),
+            };
+
+            let escapes = Path::new(&target)
+                .components()
+                .any(|component| matches!(component, Component::ParentDir));
+            if !target.starts_with("./") || escapes {
+                return Err(ModuleError::InvalidModule(format!(
+                    "Invalid package target '{}' in {}",
+                    target,
+                    package_dir.join("package.json").display()
+                )));
+            }
+
+            let path = join(package_dir, &target);
+            match path.is_file() {
+                true => Ok(Some(path)),
+                false => Err(ModuleError::NotFound(path.to_string_lossy().to_string())),
+            }
+        }
+        Value::Array(targets) => {
+            let mut error = None;
+            for target in targets {
+                match resolve_target(package_dir, target, pattern, mode) {
+                    Ok(Some(path)) => return Ok(Some(path)),
+                    Ok(None) => continue,
+                    Err(err) => error = Some(err),
+                }
+            }
+
+            match error {
+                Some(error) => Err(error),
+                None => Ok(None),
+            }
+        }
+        Value::Object(conditions) => {
+            for (condition, target) in conditions {
+                if !mode.conditions().contains(&condition.as_str()) {
+                    continue;
+                }
+
+                if let Some(path) = resolve_target(package_dir, target, pattern, mode)? {
+                    return Ok(Some(path));
+                }
+            }
+
+            Ok(None)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Joins a relative path of a package.json, `./lib/index.js` becomes `<dir>/lib/index.js`
+fn join(dir: &Path, relative: &str) -> PathBuf {
+    Path::new(relative)
+        .components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .fold(dir.to_path_buf(), |path, component| path.join(component))
+}
+
+fn probe_file(path: &Path, mode: Mode) -> Option<PathBuf> {
+    if path.is_file() {
+        return Some(path.to_path_buf());
+    }
+
+    let with_extension = mode.extensions().iter().find_map(|extension| {
+        let mut file = path.as_os_str().to_owned();
+        file.push(extension);
+        let file = PathBuf::from(file);
+        file.is_file().then_some(file)
+    });
+
+    with_extension.or_else(|| probe_index(path, mode))
+}
+
+fn probe_index(dir: &Path, mode: Mode) -> Option<PathBuf> {
+    mode.index_files()
+        .iter()
+        .map(|index| dir.join(index))
+        .find(|index| index.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    fn project() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        write(dir.path(), "src/main.js", "");
+        dir
+    }
+
+    fn resolve(
+        loader: &NodeModulesLoader,
+        root: &Path,
+        specifier: &str,
+    ) -> Option<String> {
+        let referrer = root.join("src/main.js");
+        let path = loader.resolve_from(specifier, referrer.to_str()).ok()?;
+        let path = Path::new(&path).strip_prefix(root).unwrap().to_owned();
+        Some(path.to_string_lossy().replace('\\', "/"))
+    }
+
+    #[test]
+    fn test_resolve_main_and_probing() {
+        let dir = project();
+        let root = dir.path();
+        write(
+            &root,
+            "node_modules/legacy/package.json",
+            r#"{ "main": "lib/entry" }"#,
+        );
+        write(&root, "node_modules/legacy/lib/entry.js", "");
+        write(&root, "node_modules/legacy/lib/util/index.js", "");
+        write(
+            &root,
+            "node_modules/@scope/esm/package.json",
+            r#"{ "main": "main.js", "module": "module.mjs" }"#,
+        );
+        write(&root, "node_modules/@scope/esm/main.js", "");
+        write(&root, "node_modules/@scope/esm/module.mjs", "");
+        write(&root, "node_modules/bare/index.js", "");
+
+        let loader = NodeModulesLoader::default();
+        let resolve = |specifier| resolve(&loader, &root, specifier);
+        assert_eq!(
+            resolve("legacy").as_deref(),
+            Some("node_modules/legacy/lib/entry.js")
+        );
+        assert_eq!(
+            resolve("legacy/lib/util").as_deref(),
+            Some("node_modules/legacy/lib/util/index.js")
+        );
+        assert_eq!(
+            resolve("@scope/esm").as_deref(),
+            Some("node_modules/@scope/esm/module.mjs")
+        );
+        assert_eq!(
+            resolve("bare").as_deref(),
+            Some("node_modules/bare/index.js")
+        );
+        assert_eq!(resolve("missing"), None);
+    }
+
+    #[test]
+    fn test_resolve_exports() {
+        let dir = project();
+        let root = dir.path();
+        write(
+            &root,
+            "node_modules/pkg/package.json",
+            r#"{
+                "name": "pkg",
+                "exports": {
+                    ".": { "require": "./index.cjs", "import": "./index.mjs", "default": "./index.js" },
+                    "./feature": { "kedo": "./feature.kedo.js", "default": "./feature.js" },
+                    "./utils/*": "./src/utils/*.js",
+                    "./utils/internal/*": null
+                }
+            }"#,
+        );
+        for file in [
+            "index.cjs",
+            "index.mjs",
+            "index.js",
+            "feature.kedo.js",
+            "feature.js",
+            "src/utils/math.js",
+            "src/utils/internal/secret.js",
+        ] {
+            write(&root, &format!("node_modules/pkg/{}", file), "");
+        }
+        write(
+            &root,
+            "node_modules/sugar/package.json",
+            r#"{ "exports": "./main.js" }"#,
+        );
+        write(&root, "node_modules/sugar/main.js", "");
+
+        let loader = NodeModulesLoader::default();
+        let resolve = |specifier| resolve(&loader, &root, specifier);
+        assert_eq!(
+            resolve("pkg").as_deref(),
+            Some("node_modules/pkg/index.mjs")
+        );
+        assert_eq!(
+            resolve("pkg/feature").as_deref(),
+            Some("node_modules/pkg/feature.kedo.js")
+        );
+        assert_eq!(
+            resolve("pkg/utils/math").as_deref(),
+            Some("node_modules/pkg/src/utils/math.js")
+        );
+        assert_eq!(resolve("pkg/utils/internal/secret"), None);
+        assert_eq!(resolve("pkg/index.js"), None);
+        assert_eq!(
+            resolve("sugar").as_deref(),
+            Some("node_modules/sugar/main.js")
+        );
+        assert_eq!(resolve("sugar/main.js"), None);
+    }
+
+    #[test]
+    fn test_resolve_imports() {
+        let dir = project();
+        let root = dir.path();
+        write(
+            &root,
+            "package.json",
+            r##"{
+                "name": "app",
+                "imports": { "#config": "./src/config.js", "#dep/*": "dep/*" },
+                "exports": { "./self": "./src/self.js" }
+            }"##,
+        );
+        write(&root, "src/config.js", "");
+        write(&root, "src/self.js", "");
+        write(&root, "node_modules/dep/lib/a.js", "");
+
+        let loader = NodeModulesLoader::default();
+        let resolve = |specifier| resolve(&loader, &root, specifier);
+        assert_eq!(resolve("#config").as_deref(), Some("src/config.js"));
+        assert_eq!(
+            resolve("#dep/lib/a").as_deref(),
+            Some("node_modules/dep/lib/a.js")
+        );
+        assert_eq!(resolve("#missing"), None);
+        assert_eq!(resolve("app/self").as_deref(), Some("src/self.js"));
+    }
+
+    #[test]
+    fn test_resolve_require() {
+        let dir = project();
+        let root = dir.path();
+        write(
+            &root,
+            "node_modules/dual/package.json",
+            r#"{
+                "exports": {
+                    ".": { "import": "./index.mjs", "require": "./index.cjs" },
+                    "./data": { "require": "./data.json" }
+                }
+            }"#,
+        );
+        write(&root, "node_modules/dual/index.mjs", "");
+        write(&root, "node_modules/dual/index.cjs", "");
+        write(&root, "node_modules/dual/data.json", "{}");
+        write(
+            &root,
+            "node_modules/legacy/package.json",
+            r#"{ "main": "lib/main", "module": "lib/module.mjs" }"#,
+        );
+        write(&root, "node_modules/legacy/lib/main.cjs", "");
+        write(&root, "node_modules/legacy/lib/module.mjs", "");
+        write(&root, "node_modules/legacy/config/index.json", "{}");
+
+        let loader = NodeModulesLoader::default();
+        let require = |specifier| {
+            let referrer = root.join("src/main.js");
+            let path = loader.resolve_require(specifier, referrer.to_str()).ok()?;
+            let path = Path::new(&path).strip_prefix(&root).unwrap().to_owned();
+            Some(path.to_string_lossy().replace('\\', "/"))
+        };
+
+        assert_eq!(
+            require("dual").as_deref(),
+            Some("node_modules/dual/index.cjs")
+        );
+        assert_eq!(
+            resolve(&loader, &root, "dual").as_deref(),
+            Some("node_modules/dual/index.mjs")
+        );
+        assert_eq!(
+            require("dual/data").as_deref(),
+            Some("node_modules/dual/data.json")
+        );
+        assert_eq!(resolve(&loader, &root, "dual/data"), None);
+        assert_eq!(
+            require("legacy").as_deref(),
+            Some("node_modules/legacy/lib/main.cjs")
+        );
+        assert_eq!(
+            require("legacy/config").as_deref(),
+            Some("node_modules/legacy/config/index.json")
+        );
+    }
+
+    #[test]
+    fn test_package_json_cache() {
+        let dir = project();
+        let root = dir.path();
+        write(
+            &root,
+            "node_modules/pkg/package.json",
+            r#"{ "main": "a.js" }"#,
+        );
+        write(&root, "node_modules/pkg/a.js", "");
+        write(&root, "node_modules/pkg/b.js", "");
+
+        let loader = NodeModulesLoader::default();
+        assert_eq!(
+            resolve(&loader, &root, "pkg").as_deref(),
+            Some("node_modules/pkg/a.js")
+        );
+
+        write(
+            &root,
+            "node_modules/pkg/package.json",
+            r#"{ "main": "b.js" }"#,
+        );
+        assert_eq!(
+            resolve(&loader, &root, "pkg").as_deref(),
+            Some("node_modules/pkg/a.js")
+        );
+        assert!(!loader.can_handle("./relative.js"));
+        assert!(!loader.can_handle("@kedo:op/web"));
+    }
+}
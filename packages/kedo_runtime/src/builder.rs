@@ -0,0 +1,407 @@
+use crate::{
+    errors::KedoResult,
+    module::RuntimeModule,
+    runtime::Runtime,
+    worker::{worker_launcher, WorkerFactory},
+};
+use kedo_console::Console;
+use kedo_core::{
+    AsyncJobQueue, ClassTable, CoreModuleLoader, CoreState, ImportMap,
+    ModuleImportMetaFn, ModuleLoader, ModuleSource, Permissions, ProtoTable, SourceMap,
+    WorkerScope,
+};
+use kedo_fs::FileSystemModuleLoader;
+use kedo_std::TimerQueue;
+use kedo_timers::Timer;
+use kedo_utils::JSGlobalObject;
+use kedo_web::{
+    BroadcastChannelResource, DecodedStreamResource, EncodingTextDecoder,
+    FetchClientResource, FetchRequestResource, HttpRequestResource, InternalSignal,
+    MessagePortResource, NetworkBufferChannelReaderResource, ReadableStreamResource,
+    ReadableStreamResourceReader, RequestEventResource, StructuredClone,
+    UnboundedReadableStreamResource, UnboundedReadableStreamResourceReader, UrlRecord,
+    WebModule, WorkerResource,
+};
+use rust_jsc::{class::ClassError, JSContext, JSFunction, JSObject};
+use std::sync::Arc;
+
+/// Registers one or more classes in the [`ClassTable`] before the context is finalized.
+pub type ClassInitFn = fn(&mut ClassTable) -> Result<(), ClassError>;
+/// Registers one or more prototypes in the [`ProtoTable`] once the classes exist.
+pub type ProtoInitFn =
+    fn(&mut ProtoTable, &mut ClassTable, &JSContext) -> Result<(), ClassError>;
+
+/// Source of the std bundle evaluated right after the runtime is created.
+struct StdBundle {
+    source: String,
+    source_url: String,
+}
+
+/// | ------------------------------- |
+/// |          RuntimeBuilder         |
+/// | ------------------------------- |
+/// | - console: bool                 |
+/// | - timers: bool                  |
+/// | - web: bool                     |
+/// | - fs: bool                      |
+/// | - permissions                   |
+/// | - module_loader                 |
+/// | - classes / protos              |
+/// | - std_bundle                    |
+/// | - workers / worker_scope        |
+/// | ------------------------------- |
+///
+/// Configures which subsystems are installed in a [`Runtime`].
+/// Everything is enabled by default, which matches [`Runtime::new`].
+///
+/// e.g.
+/// ```ignore
+/// let runtime = RuntimeBuilder::new()
+///     .fs(false)
+///     .with_loader(MyLoader::default())
+///     .std_bundle(STD_INDEX, "src/@std/index.js")
+///     .build()?;
+/// ```
+pub struct RuntimeBuilder {
+    console: bool,
+    timers: bool,
+    web: bool,
+    fs: bool,
+    permissions: Permissions,
+    module_loader: CoreModuleLoader,
+    classes: Vec<ClassInitFn>,
+    protos: Vec<ProtoInitFn>,
+    std_bundle: Option<StdBundle>,
+    workers: Option<WorkerFactory>,
+    worker_scope: Option<WorkerScope>,
+}
+
+impl Default for RuntimeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RuntimeBuilder {
+    pub fn new() -> Self {
+        Self {
+            console: true,
+            timers: true,
+            web: true,
+            fs: true,
+            permissions: Permissions::default(),
+            module_loader: CoreModuleLoader::default(),
+            classes: Vec::new(),
+            protos: Vec::new(),
+            std_bundle: None,
+            workers: None,
+            worker_scope: None,
+        }
+    }
+
+    /// Install the `console` global
+    pub fn console(mut self, enabled: bool) -> Self {
+        self.console = enabled;
+        self
+    }
+
+    /// Install the `setTimeout`/`setInterval` globals
+    pub fn timers(mut self, enabled: bool) -> Self {
+        self.timers = enabled;
+        self
+    }
+
+    /// Install the web classes and the `@kedo:op/web` module
+    pub fn web(mut self, enabled: bool) -> Self {
+        self.web = enabled;
+        self
+    }
+
+    /// Install the `@kedo:op/fs` module
+    pub fn fs(mut self, enabled: bool) -> Self {
+        self.fs = enabled;
+        self
+    }
+
+    /// Permissions checked by the fs, net and env ops, everything is allowed by default
+    pub fn permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    pub fn with_loader(mut self, loader: impl ModuleLoader + 'static) -> Self {
+        self.module_loader.add_loader(loader);
+        self
+    }
+
+    pub fn with_source(mut self, source: impl ModuleSource + 'static) -> Self {
+        self.module_loader.add_source(source);
+        self
+    }
+
+    pub fn with_file_system_loader(
+        mut self,
+        loader: impl ModuleLoader + 'static,
+    ) -> Self {
+        self.module_loader.set_file_system_loader(loader);
+        self
+    }
+
+    /// Maps the stack traces of a module without a `sourceMappingURL`, e.g. the std bundle
+    /// with the map of its `.map` file
+    pub fn source_map(self, module_id: &str, map: SourceMap) -> Self {
+        self.module_loader.source_maps().insert(module_id, map);
+        self
+    }
+
+    pub fn with_class(mut self, init: ClassInitFn) -> Self {
+        self.classes.push(init);
+        self
+    }
+
+    pub fn with_proto(mut self, init: ProtoInitFn) -> Self {
+        self.protos.push(init);
+        self
+    }
+
+    /// Replaces the default `import.meta` with `url`, `dirname`, `filename`, `main` and `resolve`
+    pub fn import_meta(mut self, import_meta: ModuleImportMetaFn) -> Self {
+        self.module_loader.set_import_meta(import_meta);
+        self
+    }
+
+    /// Remaps the imported specifiers before they reach the loaders
+    pub fn import_map(mut self, import_map: ImportMap) -> Self {
+        self.module_loader.set_import_map(import_map);
+        self
+    }
+
+    /// Evaluate the std bundle once the runtime is ready.
+    /// The loaders required by the bundle must be registered with [`Self::with_loader`],
+    /// an inline `sourceMappingURL` of the bundle maps its stack traces.
+    pub fn std_bundle(mut self, source: &str, source_url: &str) -> Self {
+        self.module_loader
+            .source_maps()
+            .register(source_url, source);
+        self.std_bundle = Some(StdBundle {
+            source: source.to_string(),
+            source_url: source_url.to_string(),
+        });
+        self
+    }
+
+    /// Enables `new Worker()`, every worker runs a runtime created by `factory`
+    /// on its own thread. Workers are disabled by default.
+    ///
+    /// e.g.
+    /// ```ignore
+    /// fn builder() -> RuntimeBuilder {
+    ///     RuntimeBuilder::new().workers(builder)
+    /// }
+    /// ```
+    pub fn workers(
+        mut self,
+        factory: impl Fn() -> RuntimeBuilder + Send + Sync + 'static,
+    ) -> Self {
+        self.workers = Some(Arc::new(factory));
+        self
+    }
+
+    /// Runs the runtime as a worker, set by the thread spawned for the worker
+    pub fn worker_scope(mut self, scope: WorkerScope) -> Self {
+        self.worker_scope = Some(scope);
+        self
+    }
+
+    pub fn build(self) -> KedoResult<Runtime> {
+        let context = JSContext::new();
+        let mut class_table = ClassTable::new();
+        let mut proto_table = ProtoTable::new();
+        let mut module_loader = self.module_loader;
+        module_loader.add_source(RuntimeModule);
+
+        if self.web {
+            init_web_classes(&mut class_table)?;
+            init_web_protos(&mut proto_table, &mut class_table, &context)?;
+            module_loader.add_source(WebModule);
+        }
+
+        if self.fs {
+            module_loader.add_source(FileSystemModuleLoader);
+        }
+
+        for init in self.classes.iter() {
+            init(&mut class_table)?;
+        }
+
+        for init in self.protos.iter() {
+            init(&mut proto_table, &mut class_table, &context)?;
+        }
+
+        let unhandled_rejection = JSFunction::callback(
+            &context,
+            Some("unhandled_rejection"),
+            Some(Runtime::unhandled_rejection),
+        );
+        unhandled_rejection.protect(); // Protect the function from GC
+        context.set_unhandled_rejection_callback(unhandled_rejection.into())?;
+        context.set_uncaught_exception_handler(Some(Runtime::uncaught_exception));
+        context.set_uncaught_exception_at_event_loop_callback(Some(
+            Runtime::uncaught_exception_event_loop,
+        ));
+
+        let mut state = CoreState::new(
+            AsyncJobQueue::new(),
+            TimerQueue::new(),
+            class_table,
+            proto_table,
+            module_loader,
+        )
+        .with_permissions(self.permissions);
+
+        if let Some(factory) = self.workers {
+            state = state.with_worker_launcher(worker_launcher(factory));
+        }
+
+        if let Some(scope) = self.worker_scope {
+            state = state.with_worker_scope(scope);
+        }
+        context.set_shared_data(Box::new(state.clone()));
+
+        state.module_loader().borrow().init(&context);
+        if self.console {
+            Console::init_globals(&context)?;
+        }
+
+        if self.timers {
+            Timer::init_globals(&context)?;
+        }
+
+        let kedo = JSObject::new(&context);
+        kedo.protect();
+        context
+            .global_object()
+            .set_property("Kedo", &kedo, Default::default())?;
+
+        let runtime = Runtime::from_parts(context, state);
+        if let Some(bundle) = self.std_bundle {
+            runtime.evaluate_module_from_source(
+                &bundle.source,
+                &bundle.source_url,
+                None,
+            )?;
+        }
+
+        Ok(runtime)
+    }
+}
+
+fn init_web_classes(class_manager: &mut ClassTable) -> Result<(), ClassError> {
+    UrlRecord::init_class(class_manager)?;
+    EncodingTextDecoder::init_class(class_manager)?;
+    ReadableStreamResourceReader::init_class(class_manager)?;
+    UnboundedReadableStreamResourceReader::init_class(class_manager)?;
+    FetchClientResource::init_class(class_manager)?;
+    InternalSignal::init_class(class_manager)?;
+    FetchRequestResource::init_class(class_manager)?;
+    HttpRequestResource::init_class(class_manager)?;
+    ReadableStreamResource::init_class(class_manager)?;
+    UnboundedReadableStreamResource::init_class(class_manager)?;
+    DecodedStreamResource::init_class(class_manager)?;
+    RequestEventResource::init_class(class_manager)?;
+    NetworkBufferChannelReaderResource::init_class(class_manager)?;
+    WorkerResource::init_class(class_manager)?;
+    MessagePortResource::init_class(class_manager)?;
+    BroadcastChannelResource::init_class(class_manager)?;
+    Ok(())
+}
+
+fn init_web_protos(
+    proto_table: &mut ProtoTable,
+    class_table: &mut ClassTable,
+    ctx: &JSContext,
+) -> Result<(), ClassError> {
+    UrlRecord::init_proto(proto_table, class_table, ctx)?;
+    ReadableStreamResource::init_proto(proto_table, class_table, ctx)?;
+    UnboundedReadableStreamResource::init_proto(proto_table, class_table, ctx)?;
+    EncodingTextDecoder::init_proto(proto_table, class_table, ctx)?;
+    InternalSignal::init_proto(proto_table, class_table, ctx)?;
+    StructuredClone::init_proto(proto_table, class_table, ctx)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_utils::GreetingLoader;
+
+    struct VersionSource;
+
+    impl ModuleSource for VersionSource {
+        fn evaluate(&self, ctx: &JSContext, _name: &str) -> JSObject {
+            let exports = JSObject::new(ctx);
+            exports
+                .set_property(
+                    "version",
+                    &rust_jsc::JSValue::string(ctx, "1.0.0"),
+                    Default::default(),
+                )
+                .unwrap();
+            exports
+        }
+
+        fn name(&self) -> &str {
+            "@embed:version"
+        }
+    }
+
+    #[test]
+    fn test_builder_disables_subsystems() {
+        let runtime = RuntimeBuilder::new()
+            .console(false)
+            .timers(false)
+            .build()
+            .unwrap();
+
+        let result = runtime
+            .evaluate_script("typeof console + typeof setTimeout", None)
+            .unwrap();
+        assert_eq!(result.as_string().unwrap(), "undefinedundefined");
+
+        let result = runtime.evaluate_script("typeof Kedo", None).unwrap();
+        assert_eq!(result.as_string().unwrap(), "object");
+    }
+
+    #[test]
+    fn test_builder_custom_modules() {
+        let runtime = RuntimeBuilder::new()
+            .with_source(VersionSource)
+            .with_loader(GreetingLoader)
+            .build()
+            .unwrap();
+
+        let result = runtime.evaluate_module_from_source(
+            r#"
+            import { version } from '@embed:version';
+            import greeting from '@embed/greeting';
+            globalThis.embedded = `${greeting}@${version}`;
+        "#,
+            "embed.js",
+            None,
+        );
+        assert!(result.is_ok());
+
+        let result = runtime
+            .evaluate_script("globalThis.embedded", None)
+            .unwrap();
+        assert_eq!(result.as_string().unwrap(), "hello@1.0.0");
+    }
+
+    #[test]
+    fn test_builder_std_bundle_errors() {
+        let result = RuntimeBuilder::new()
+            .std_bundle("export const = ;", "std.js")
+            .build();
+        assert!(result.is_err());
+    }
+}
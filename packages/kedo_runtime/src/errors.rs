@@ -8,7 +8,6 @@ pub enum KedoError {
     JsError(rust_jsc::JSError),
 }
 
-#[allow(dead_code)]
 pub type KedoResult<T> = Result<T, KedoError>;
 
 impl KedoError {
@@ -51,6 +50,12 @@ impl From<rust_jsc::JSError> for KedoError {
     }
 }
 
+impl From<rust_jsc::class::ClassError> for KedoError {
+    fn from(error: rust_jsc::class::ClassError) -> Self {
+        KedoError::Basic(ErrorKind::Other, format!("{:?}", error))
+    }
+}
+
 impl From<(ErrorKind, String)> for KedoError {
     fn from((kind, message): (ErrorKind, String)) -> Self {
         KedoError::Basic(kind, message)
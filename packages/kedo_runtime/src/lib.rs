@@ -1,8 +1,26 @@
+mod builder;
 mod errors;
+mod module;
+mod pretty_error;
 mod tests;
+mod worker;
 
 pub mod runtime;
 
+pub use builder::ClassInitFn;
+pub use builder::ProtoInitFn;
+pub use builder::RuntimeBuilder;
+pub use errors::KedoError;
+pub use errors::KedoResult;
+pub use worker::WorkerFactory;
+
+pub use kedo_core::ImportMap;
+pub use kedo_core::ImportMapError;
 pub use kedo_core::ModuleError;
+pub use kedo_core::ModuleFailure;
 pub use kedo_core::ModuleImportMetaFn;
 pub use kedo_core::ModuleLoader;
+pub use kedo_core::ModuleSource;
+pub use kedo_core::SourceMap;
+pub use kedo_core::{AllowList, PermissionError, Permissions};
+pub use kedo_fs::NodeModulesLoader;
@@ -0,0 +1,304 @@
+use kedo_core::{
+    check_permission, define_exports, downcast_state, permission_denied, ModuleSource,
+    PermissionError, PermissionName, PermissionState,
+};
+use kedo_utils::{js_error_typ, js_undefined};
+use rust_jsc::{callback, JSContext, JSError, JSObject, JSResult, JSValue};
+use std::collections::HashMap;
+
+/// [Op:PermissionQuery]
+/// Returns the state of a permission for an optional resource
+///
+/// e.g. op_permission_query("read", "./data.txt") // "granted" | "denied"
+#[callback]
+fn op_permission_query(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    args: &[JSValue],
+) -> JSResult<JSValue> {
+    let name = match args.get(0) {
+        Some(name) => name.as_string()?.to_string(),
+        None => return Err(js_error_typ!(&ctx, "[Op:PermissionQuery] Missing name")),
+    };
+
+    let name = match PermissionName::try_from(name.as_str()) {
+        Ok(name) => name,
+        Err(err) => {
+            return Err(js_error_typ!(&ctx, format!("[Op:PermissionQuery] {}", err)))
+        }
+    };
+
+    let resource = match args.get(1) {
+        Some(resource) if resource.is_string() => Some(resource.as_string()?.to_string()),
+        _ => None,
+    };
+
+    let state = downcast_state(&ctx);
+    let status = state.permissions().query(name, resource.as_deref());
+    Ok(JSValue::string(&ctx, status.as_str()))
+}
+
+#[callback]
+fn op_env_get(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    key: String,
+) -> JSResult<JSValue> {
+    check_permission!(&ctx, check_env, &key);
+
+    let state = downcast_state(&ctx);
+    let value = match state.env().borrow().get(&key) {
+        Some(value) => value.clone(),
+        None => std::env::var(&key).ok(),
+    };
+
+    match value {
+        Some(value) => Ok(JSValue::string(&ctx, value)),
+        None => Ok(js_undefined!(&ctx)),
+    }
+}
+
+#[callback]
+fn op_env_set(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    key: String,
+    value: String,
+) -> JSResult<JSValue> {
+    check_permission!(&ctx, check_env, &key);
+
+    if key.is_empty() || key.contains('=') || key.contains('\0') || value.contains('\0') {
+        return Err(js_error_typ!(
+            &ctx,
+            "[Op:EnvSet] Invalid environment variable"
+        ));
+    }
+
+    let state = downcast_state(&ctx);
+    state.env().borrow_mut().insert(key, Some(value));
+    Ok(js_undefined!(&ctx))
+}
+
+#[callback]
+fn op_env_delete(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    key: String,
+) -> JSResult<JSValue> {
+    check_permission!(&ctx, check_env, &key);
+
+    if key.is_empty() || key.contains('=') || key.contains('\0') {
+        return Err(js_error_typ!(
+            &ctx,
+            "[Op:EnvDelete] Invalid environment variable"
+        ));
+    }
+
+    let state = downcast_state(&ctx);
+    state.env().borrow_mut().insert(key, None);
+    Ok(js_undefined!(&ctx))
+}
+
+#[callback]
+fn op_env_to_object(ctx: JSContext, _: JSObject, _: JSObject) -> JSResult<JSValue> {
+    let state = downcast_state(&ctx);
+    if state.permissions().query(PermissionName::Env, None) != PermissionState::Granted {
+        let error = PermissionError {
+            name: PermissionName::Env,
+            resource: "*".to_string(),
+        };
+        return Err(permission_denied(&ctx, &error)?);
+    }
+
+    let mut vars: HashMap<String, String> = std::env::vars().collect();
+    for (key, value) in state.env().borrow().iter() {
+        match value {
+            Some(value) => vars.insert(key.clone(), value.clone()),
+            None => vars.remove(key),
+        };
+    }
+
+    let object = JSObject::new(&ctx);
+    for (key, value) in vars {
+        object.set_property(&key, &JSValue::string(&ctx, value), Default::default())?;
+    }
+
+    Ok(object.into())
+}
+
+/// [Op:Resources]
+/// Returns the open resources and pending ops keyed by rid
+///
+/// e.g. op_resources() // { "1": "timer", "4": "op_fs_read_file" }
+#[callback]
+fn op_resources(ctx: JSContext, _: JSObject, _: JSObject) -> JSResult<JSValue> {
+    let state = downcast_state(&ctx);
+    let entries = state.resources().borrow().entries();
+
+    let object = JSObject::new(&ctx);
+    for entry in entries {
+        object.set_property(
+            &entry.rid.to_string(),
+            &JSValue::string(&ctx, entry.kind),
+            Default::default(),
+        )?;
+    }
+
+    Ok(object.into())
+}
+
+pub struct RuntimeOps {}
+
+define_exports!(
+    RuntimeOps,
+    @template[],
+    @function[
+        op_permission_query,
+        op_env_get,
+        op_env_set,
+        op_env_delete,
+        op_env_to_object,
+        op_resources,
+    ]
+);
+
+pub struct RuntimeModule;
+
+impl ModuleSource for RuntimeModule {
+    fn evaluate(&self, ctx: &JSContext, _name: &str) -> JSObject {
+        let exports = JSObject::new(ctx);
+        RuntimeOps::export(ctx, &exports).expect("Failed to export RuntimeOps");
+        exports
+    }
+
+    fn name(&self) -> &str {
+        "@kedo:op/runtime"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RuntimeBuilder;
+    use kedo_core::{AllowList, Permissions};
+
+    #[test]
+    fn test_permission_query() {
+        let runtime = RuntimeBuilder::new()
+            .permissions(
+                Permissions::deny_all()
+                    .allow_env(AllowList::Only(vec!["KEDO_TEST".to_string()])),
+            )
+            .build()
+            .unwrap();
+
+        let result = runtime.evaluate_module_from_source(
+            r#"
+            import { op_permission_query, op_env_get } from '@kedo:op/runtime';
+            globalThis.env = op_permission_query('env', 'KEDO_TEST');
+            globalThis.read = op_permission_query('read', '/');
+            try {
+                op_env_get('HOME');
+            } catch (error) {
+                globalThis.errorName = error.name;
+            }
+        "#,
+            "permissions.js",
+            None,
+        );
+        assert!(result.is_ok());
+
+        let result = runtime
+            .evaluate_script("`${env}:${read}:${errorName}`", None)
+            .unwrap();
+        assert_eq!(
+            result.as_string().unwrap(),
+            "granted:denied:PermissionDenied"
+        );
+    }
+
+    #[test]
+    fn test_env_overlay() {
+        let runtime = RuntimeBuilder::new().build().unwrap();
+
+        let result = runtime.evaluate_module_from_source(
+            r#"
+            import {
+                op_env_delete,
+                op_env_get,
+                op_env_set,
+                op_env_to_object,
+            } from '@kedo:op/runtime';
+            op_env_set('KEDO_TEST_OVERLAY', 'set');
+            op_env_delete('PATH');
+            const object = op_env_to_object();
+            globalThis.env = [
+                op_env_get('KEDO_TEST_OVERLAY'),
+                op_env_get('PATH') === undefined,
+                object.KEDO_TEST_OVERLAY,
+                'PATH' in object,
+            ].join(':');
+        "#,
+            "env.js",
+            None,
+        );
+        assert!(result.is_ok());
+
+        let result = runtime.evaluate_script("globalThis.env", None).unwrap();
+        assert_eq!(result.as_string().unwrap(), "set:true:set:false");
+
+        // the process environment is left untouched
+        assert!(std::env::var("KEDO_TEST_OVERLAY").is_err());
+        assert!(std::env::var("PATH").is_ok());
+    }
+
+    #[test]
+    fn test_resources_track_timers() {
+        let runtime = RuntimeBuilder::new().build().unwrap();
+
+        let result = runtime.evaluate_module_from_source(
+            r#"
+            import { op_resources } from '@kedo:op/runtime';
+            const id = setTimeout(() => {}, 1000);
+            globalThis.open = Object.values(op_resources()).join(',');
+            clearTimeout(id);
+            globalThis.closed = Object.values(op_resources()).length;
+        "#,
+            "resources.js",
+            None,
+        );
+        assert!(result.is_ok());
+
+        let result = runtime
+            .evaluate_script("`${open}:${closed}`", None)
+            .unwrap();
+        assert_eq!(result.as_string().unwrap(), "timer:0");
+    }
+
+    #[test]
+    fn test_fs_permission_denied() {
+        let runtime = RuntimeBuilder::new()
+            .permissions(Permissions::deny_all())
+            .build()
+            .unwrap();
+
+        let result = runtime.evaluate_module_from_source(
+            r#"
+            import { op_fs_read_file_sync } from '@kedo:op/fs';
+            op_fs_read_file_sync('./Cargo.toml');
+        "#,
+            "permissions.js",
+            None,
+        );
+        assert!(result.is_err());
+        let error = result.unwrap_err();
+        assert!(error
+            .message()
+            .unwrap()
+            .to_string()
+            .starts_with("Requires read access"));
+    }
+}
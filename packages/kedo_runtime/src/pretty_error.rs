@@ -0,0 +1,399 @@
+use kedo_core::{downcast_state, SourceMaps};
+use rust_jsc::{JSContext, JSValue};
+use std::{fmt, io::IsTerminal};
+
+/// Lines of source shown before and after the line of the error
+const CONTEXT_LINES: u32 = 2;
+/// Longer lines are minified code, the code frame is left out
+const MAX_LINE_LENGTH: usize = 300;
+
+const BOLD_RED: &str = "\x1b[1;31m";
+const DIM: &str = "\x1b[2m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/// A frame of a JavaScriptCore stack trace, e.g. `fail@/app/main.js:3:9`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackFrame {
+    /// Empty for anonymous functions
+    pub function: String,
+    pub file: String,
+    /// 1-based line and column, `None` for native code
+    pub position: Option<(u32, u32)>,
+}
+
+impl StackFrame {
+    /// Parses a line of `error.stack`, e.g. `fail@/app/main.js:3:9`,
+    /// `module code@/app/main.js:5:1` or `forEach@[native code]`
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+
+        // the function name has no `@`, the file can, e.g. `@@kedo/fs:1:2`
+        let (function, location) = line.split_once('@').unwrap_or(("", line));
+        let mut parts = location.rsplitn(3, ':');
+        let position = match (parts.next(), parts.next(), parts.next()) {
+            (Some(column), Some(line), Some(file)) => {
+                match (line.parse::<u32>(), column.parse::<u32>()) {
+                    (Ok(line), Ok(column)) => Some((file, (line, column))),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
+        let (file, position) = match position {
+            Some((file, position)) => (file, Some(position)),
+            None => (location, None),
+        };
+
+        Some(Self {
+            function: function.to_string(),
+            file: file.to_string(),
+            position,
+        })
+    }
+
+    /// The frame at the original location of its source map
+    pub fn map(&self, source_maps: &SourceMaps) -> Self {
+        let (line, column) = match self.position {
+            Some(position) => position,
+            None => return self.clone(),
+        };
+
+        let location = source_maps.original_location(&self.file, line, column);
+        Self {
+            function: self.function.clone(),
+            file: location.file,
+            position: Some((location.line, location.column)),
+        }
+    }
+}
+
+impl fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let location = match self.position {
+            Some((line, column)) => format!("{}:{}:{}", self.file, line, column),
+            None => self.file.clone(),
+        };
+
+        match self.function.is_empty() {
+            true => write!(f, "{}", location),
+            false => write!(f, "{} ({})", self.function, location),
+        }
+    }
+}
+
+fn paint(text: &str, style: &str, colors: bool) -> String {
+    match colors {
+        true => format!("{}{}{}", style, text, RESET),
+        false => text.to_string(),
+    }
+}
+
+/// Colors are used when stderr is a terminal, unless `NO_COLOR` is set
+pub fn use_colors() -> bool {
+    std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
+
+/// Lines around `line` with a caret under `column`, both 1-based
+///
+/// e.g.
+/// ```text
+///   1 | const a: number = 1;
+/// > 2 | throw new Error(a);
+///     |       ^
+/// ```
+pub fn code_frame(source: &str, line: u32, column: u32, colors: bool) -> Option<String> {
+    let lines = source.lines().collect::<Vec<_>>();
+    let text = lines.get(line.checked_sub(1)? as usize)?;
+    if text.len() > MAX_LINE_LENGTH {
+        return None;
+    }
+
+    let first = line.saturating_sub(CONTEXT_LINES).max(1);
+    let last = (line + CONTEXT_LINES).min(lines.len() as u32);
+    let width = last.to_string().len();
+
+    let mut frame = String::new();
+    for number in first..=last {
+        let text = lines[number as usize - 1].trim_end_matches('\r');
+        let gutter = paint(&format!("{:>width$} |", number), DIM, colors);
+        match number == line {
+            true => frame.push_str(&format!(
+                "{} {} {}\n",
+                paint(">", BOLD_RED, colors),
+                gutter,
+                text
+            )),
+            false => frame.push_str(&format!("  {} {}\n", gutter, text)),
+        }
+
+        if number == line {
+            // tabs are kept so the caret lines up with the code
+            let padding = text
+                .chars()
+                .take(column.saturating_sub(1) as usize)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect::<String>();
+            frame.push_str(&format!(
+                "  {} {}{}\n",
+                paint(&format!("{:>width$} |", ""), DIM, colors),
+                padding,
+                paint("^", BOLD_RED, colors)
+            ));
+        }
+    }
+
+    Some(frame)
+}
+
+/// Formats an error with its stack mapped to the original sources and the code
+/// frame of the first frame whose source can be read.
+///
+/// e.g.
+/// ```text
+/// error: Uncaught TypeError: boom
+///  --> /app/src/main.ts:2:7
+///   1 | const a: number = 1;
+/// > 2 | throw new TypeError("boom");
+///     |       ^
+///     at /app/src/main.ts:2:7
+/// ```
+pub fn format_error(
+    headline: &str,
+    frames: &[StackFrame],
+    source_maps: &SourceMaps,
+    colors: bool,
+) -> String {
+    let frames = frames
+        .iter()
+        .map(|frame| frame.map(source_maps))
+        .collect::<Vec<_>>();
+    let mut output = format!("{}: {}\n", paint("error", BOLD_RED, colors), headline);
+
+    let code_frame = frames.iter().find_map(|frame| {
+        let (line, column) = frame.position?;
+        let source = source_maps.source_text(&frame.file)?;
+        let code = code_frame(&source, line, column, colors)?;
+        Some((frame, line, column, code))
+    });
+
+    if let Some((frame, line, column, code)) = code_frame {
+        let location = format!("{}:{}:{}", frame.file, line, column);
+        output.push_str(&format!(
+            " {} {}\n",
+            paint("-->", DIM, colors),
+            paint(&location, CYAN, colors)
+        ));
+        output.push_str(&code);
+    }
+
+    for frame in frames {
+        output.push_str(&format!(
+            "    {}\n",
+            paint(&format!("at {}", frame), DIM, colors)
+        ));
+    }
+
+    output.trim_end().to_string()
+}
+
+/// Property of the exception as a string, `None` for non string values
+pub(crate) fn property(exception: &JSValue, name: &str) -> Option<String> {
+    let value = exception.as_object().ok()?.get_property(name).ok()?;
+    match value.is_string() {
+        true => value.as_string().ok().map(|value| value.to_string()),
+        false => None,
+    }
+}
+
+/// Formats a thrown value, `kind` prefixes the headline, e.g. "Uncaught".
+/// Errors without a stack, e.g. syntax errors, use their `sourceURL`, `line`
+/// and `column`.
+pub fn format_exception(ctx: &JSContext, exception: &JSValue, kind: &str) -> String {
+    let message = property(exception, "message");
+    let description = match (property(exception, "name"), &message) {
+        (Some(name), Some(message)) if !message.is_empty() => {
+            format!("{}: {}", name, message)
+        }
+        (Some(name), _) => name,
+        _ => exception
+            .as_string()
+            .map(|value| value.to_string())
+            .unwrap_or_else(|_| "unknown".to_string()),
+    };
+
+    let mut frames = property(exception, "stack")
+        .map(|stack| {
+            stack
+                .lines()
+                .filter_map(StackFrame::parse)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    if frames.is_empty() && exception.is_object() {
+        let number = |name: &str| {
+            let object = exception.as_object().ok()?;
+            let value = object.get_property(name).ok()?;
+            value.is_number().then(|| value.as_number().ok()).flatten()
+        };
+
+        if let (Some(file), Some(line)) =
+            (property(exception, "sourceURL"), number("line"))
+        {
+            let column = number("column").unwrap_or(1.0);
+            frames.push(StackFrame {
+                function: String::new(),
+                file,
+                position: Some((line as u32, column as u32)),
+            });
+        }
+    }
+
+    let state = downcast_state(ctx);
+    let loader = state.module_loader().borrow();
+    let headline = match kind.is_empty() {
+        true => description,
+        false => format!("{} {}", kind, description),
+    };
+    format_error(&headline, &frames, loader.source_maps(), use_colors())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::test_utils::DirLoader, RuntimeBuilder};
+    use kedo_core::SourceMap;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_stack_frame() {
+        assert_eq!(
+            StackFrame::parse("fail@/app/main.js:3:9"),
+            Some(StackFrame {
+                function: "fail".to_string(),
+                file: "/app/main.js".to_string(),
+                position: Some((3, 9)),
+            })
+        );
+        assert_eq!(
+            StackFrame::parse("@@kedo/fs:12:5").unwrap().file,
+            "@kedo/fs"
+        );
+        assert_eq!(
+            StackFrame::parse("module code@file:///app/main.js:1:1")
+                .unwrap()
+                .to_string(),
+            "module code (file:///app/main.js:1:1)"
+        );
+        assert_eq!(
+            StackFrame::parse("forEach@[native code]")
+                .unwrap()
+                .to_string(),
+            "forEach ([native code])"
+        );
+        assert_eq!(StackFrame::parse("  "), None);
+    }
+
+    #[test]
+    fn test_code_frame() {
+        let source = "const a = 1;\nconst b = 2;\n\tthrow new Error(a);\nconst c = 3;\n";
+        assert_eq!(
+            code_frame(source, 3, 8, false).unwrap(),
+            concat!(
+                "  1 | const a = 1;\n",
+                "  2 | const b = 2;\n",
+                "> 3 | \tthrow new Error(a);\n",
+                "    | \t      ^\n",
+                "  4 | const c = 3;\n",
+            )
+        );
+        assert!(code_frame(source, 9, 1, false).is_none());
+        assert!(code_frame(&"a".repeat(400), 1, 1, false).is_none());
+    }
+
+    #[test]
+    fn test_format_error_source_map() {
+        let temp = TempDir::new().unwrap();
+        let dir = std::fs::canonicalize(temp.path()).unwrap();
+        std::fs::write(
+            dir.join("main.ts"),
+            "const a: number = 1;\nthrow new Error(a);\n",
+        )
+        .unwrap();
+
+        // maps `const a = 1;\nthrow new Error(a);` to main.ts
+        let map = SourceMap::parse(
+            r#"{
+                "version": 3,
+                "sources": ["main.ts"],
+                "names": [],
+                "mappings": "AAAA,MAAM,IAAY;AAClB,MAAM,IAAI"
+            }"#,
+            Some(&dir),
+        )
+        .unwrap();
+        let source_maps = SourceMaps::default();
+        source_maps.insert("bundle.js", map);
+
+        let frames = [
+            StackFrame::parse("module code@bundle.js:2:11").unwrap(),
+            StackFrame::parse("forEach@[native code]").unwrap(),
+        ];
+        let main = dir.join("main.ts").to_string_lossy().to_string();
+        assert_eq!(
+            format_error("Uncaught Error: 1", &frames, &source_maps, false),
+            format!(
+                concat!(
+                    "error: Uncaught Error: 1\n",
+                    " --> {main}:2:11\n",
+                    "  1 | const a: number = 1;\n",
+                    "> 2 | throw new Error(a);\n",
+                    "    |           ^\n",
+                    "    at module code ({main}:2:11)\n",
+                    "    at forEach ([native code])",
+                ),
+                main = main
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_error_source_map() {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let temp = TempDir::new().unwrap();
+        let dir = std::fs::canonicalize(temp.path()).unwrap();
+        std::fs::write(
+            dir.join("main.ts"),
+            "const a: number = 1;\nthrow new Error(a);\n",
+        )
+        .unwrap();
+        // maps `const a = 1;\nthrow new Error(a);` to main.ts
+        let map = r#"{"version":3,"sources":["main.ts"],"names":[],"mappings":"AAAA,MAAM,IAAY;AAClB,MAAM,IAAI"}"#;
+        std::fs::write(
+            dir.join("main.js"),
+            format!(
+                "const a = 1;\nthrow new Error(a);\n//# sourceMappingURL=data:application/json;base64,{}\n",
+                STANDARD.encode(map)
+            ),
+        )
+        .unwrap();
+
+        let runtime = RuntimeBuilder::new()
+            .with_file_system_loader(DirLoader { dir: dir.clone() })
+            .build()
+            .unwrap();
+        let error = runtime.evaluate_module("main.js").unwrap_err();
+        let output = runtime.format_error(error);
+
+        assert!(output.starts_with("error: Uncaught Error: 1"));
+        assert!(output.contains(&format!("{}:2:", dir.join("main.ts").display())));
+        assert!(output.contains("> 2 | throw new Error(a);"));
+        assert!(!output.contains("main.js:"));
+    }
+}
@@ -1,81 +1,78 @@
-use futures::future::poll_fn;
-use kedo_console::Console;
-use kedo_core::{
-    AsyncJobQueue, ClassTable, CoreModuleLoader, CoreState, JobQueue, ProtoTable,
-};
-use kedo_fs::FileSystemModuleLoader;
-use kedo_std::TimerQueue;
-use kedo_timers::Timer;
-use kedo_utils::JSGlobalObject;
-use kedo_web::{
-    DecodedStreamResource, EncodingTextDecoder, FetchClientResource,
-    FetchRequestResource, HttpRequestResource, InternalSignal,
-    NetworkBufferChannelReaderResource, ReadableStreamResource,
-    ReadableStreamResourceReader, RequestEventResource, UnboundedReadableStreamResource,
-    UnboundedReadableStreamResourceReader, UrlRecord, WebModule,
+use crate::{
+    builder::RuntimeBuilder,
+    pretty_error::{format_exception, property},
 };
+use futures::future::poll_fn;
+use kedo_core::{downcast_state, CoreState, JobQueue, WorkerError, WorkerMessage};
 use rust_jsc::{
     callback, uncaught_exception, uncaught_exception_event_loop, JSContext, JSError,
     JSFunction, JSObject, JSResult, JSString, JSValue,
 };
 use std::{
+    cell::OnceCell,
     sync::Arc,
     task::{Context, Poll},
 };
 
+/// Subscribes to a promise and exposes its state as a plain object,
+/// returns undefined for any other value.
+const PROMISE_TRACKER: &str = r#"
+(function (value) {
+    if (!(value instanceof Promise)) return undefined;
+    const state = { settled: false, rejected: false, value: undefined };
+    value.then(
+        (result) => { state.settled = true; state.value = result; },
+        (error) => { state.settled = true; state.rejected = true; state.value = error; },
+    );
+    return state;
+})
+"#;
+
+/// State of a value returned by [`Runtime::settle`] or [`Runtime::promise_state`].
+pub enum PromiseState {
+    Fulfilled(JSValue),
+    Rejected(JSValue),
+    /// The promise hasn't settled yet, or the event loop ran out of work first
+    Pending,
+}
+
 pub struct Runtime {
     context: Arc<JSContext>,
     state: CoreState,
+    /// [`PROMISE_TRACKER`], compiled by the first [`Runtime::settle`]
+    promise_tracker: OnceCell<JSFunction>,
 }
 
 impl Drop for Runtime {
     fn drop(&mut self) {
+        if let Some(tracker) = self.promise_tracker.take() {
+            tracker.unprotect();
+        }
+        self.state.inspector().take();
+        self.state.helpers().borrow_mut().clear();
         self.context.set_shared_data(Box::new(()));
     }
 }
 
 impl Runtime {
+    /// Creates a runtime with every subsystem installed.
+    /// Use [`RuntimeBuilder`] to choose which subsystems are installed.
     pub fn new() -> Self {
-        let context = JSContext::new();
-        let timer_queue = TimerQueue::new();
-        let job_queue = AsyncJobQueue::new();
-        let mut class_table = ClassTable::new();
-        let mut proto_table = ProtoTable::new();
-        let mut module_loader = CoreModuleLoader::default();
-
-        Self::init_class(&mut class_table);
-        Self::init_proto(&mut proto_table, &mut class_table, &context);
-        Self::init_module_loaders(&mut module_loader);
-
-        let unhandled_rejection = JSFunction::callback(
-            &context,
-            Some("unhandled_rejection"),
-            Some(Self::unhandled_rejection),
-        );
-        unhandled_rejection.protect(); // Protect the function from GC
-        context
-            .set_unhandled_rejection_callback(unhandled_rejection.into())
-            .unwrap();
-        context.set_uncaught_exception_handler(Some(Self::uncaught_exception));
-        context.set_uncaught_exception_at_event_loop_callback(Some(
-            Self::uncaught_exception_event_loop,
-        ));
-
-        let state = CoreState::new(
-            job_queue,
-            timer_queue,
-            class_table,
-            proto_table,
-            module_loader,
-        );
-        context.set_shared_data(Box::new(state.clone()));
-        let runtime = Runtime {
+        RuntimeBuilder::new()
+            .build()
+            .expect("Failed to build the runtime")
+    }
+
+    pub fn builder() -> RuntimeBuilder {
+        RuntimeBuilder::new()
+    }
+
+    pub(crate) fn from_parts(context: JSContext, state: CoreState) -> Self {
+        Runtime {
             context: Arc::new(context),
             state,
-        };
-
-        runtime.init_module();
-        runtime
+            promise_tracker: OnceCell::new(),
+        }
     }
 
     pub fn add_loader(&self, loader: impl kedo_core::ModuleLoader + 'static) {
@@ -83,38 +80,83 @@ impl Runtime {
         module_loader.add_loader(loader);
     }
 
+    /// Evaluates the entry module, a module that can't be resolved is reported
+    /// by the evaluation
     pub fn evaluate_module(&self, filename: &str) -> JSResult<()> {
-        self.context.evaluate_module(filename)
+        {
+            let module_loader = self.state.module_loader().borrow();
+            if let Ok(module_id) = module_loader.resolve_specifier(filename, None) {
+                module_loader.set_main_module(&module_id);
+            }
+        }
+
+        let failures = self.module_failures();
+        self.context
+            .evaluate_module(filename)
+            .map_err(|error| self.module_error(failures, error))
+    }
+
+    fn module_failures(&self) -> usize {
+        self.state.module_loader().borrow().failure_count()
+    }
+
+    /// A module that failed to load throws its error once evaluated, but JSC links
+    /// the named imports first and reports "Importing binding name '..' is not found".
+    /// The load failure is the cause, so it's reported instead.
+    fn module_error(&self, since: usize, error: JSError) -> JSError {
+        let failures = self.state.module_loader().borrow().failures_since(since);
+        let failure = match failures.first() {
+            Some(failure) => failure,
+            None => return error,
+        };
+
+        let message = error.message().map(|m| m.to_string()).unwrap_or_default();
+        if !message.starts_with("Importing binding name") {
+            return error;
+        }
+
+        match self.context.evaluate_script(&failure.source(), None) {
+            Err(failure_error) => failure_error,
+            Ok(_) => error,
+        }
     }
 
     #[callback]
-    fn unhandled_rejection(
+    pub(crate) fn unhandled_rejection(
         ctx: JSContext,
         _function: JSObject,
         _this: JSObject,
         args: &[JSValue],
     ) -> JSResult<JSValue> {
-        let error = JSError::from(args[0].as_object()?);
-        println!(
-            "Error unhandled: {} - {}",
-            args[1].as_string().unwrap(),
-            error.stack()?,
-            // args.len()
-        );
+        report_uncaught(&ctx, &args[0], "Uncaught (in promise)");
         Ok(JSValue::undefined(&ctx))
     }
 
     #[uncaught_exception]
-    fn uncaught_exception(_ctx: JSContext, _filename: JSString, exception: JSValue) {
-        println!("Uncaught exception: {:?}", exception.as_string().unwrap());
+    pub(crate) fn uncaught_exception(
+        ctx: JSContext,
+        _filename: JSString,
+        exception: JSValue,
+    ) {
+        report_uncaught(&ctx, &exception, "Uncaught");
     }
 
     #[uncaught_exception_event_loop]
-    fn uncaught_exception_event_loop(_ctx: JSContext, exception: JSValue) {
-        println!(
-            "Uncaught exception in event loop: {:?}",
-            exception.as_string().unwrap()
-        );
+    pub(crate) fn uncaught_exception_event_loop(ctx: JSContext, exception: JSValue) {
+        report_uncaught(&ctx, &exception, "Uncaught");
+    }
+
+    /// Formats an uncaught error with its stack mapped to the original sources
+    /// and a code frame of the line that threw it.
+    ///
+    /// e.g.
+    /// ```ignore
+    /// if let Err(error) = runtime.evaluate_module("main.ts") {
+    ///     eprintln!("{}", runtime.format_error(error));
+    /// }
+    /// ```
+    pub fn format_error(&self, error: JSError) -> String {
+        format_exception(&self.context, &error.into(), "Uncaught")
     }
 
     pub fn evaluate_module_from_source(
@@ -123,72 +165,20 @@ impl Runtime {
         source_url: &str,
         starting_line_number: Option<i32>,
     ) -> JSResult<()> {
+        let failures = self.module_failures();
         self.context
             .evaluate_module_from_source(source, source_url, starting_line_number)
-    }
-
-    fn init_module_loaders(module_loader: &mut CoreModuleLoader) {
-        module_loader.add_source(WebModule);
-        module_loader.add_source(FileSystemModuleLoader);
-    }
-
-    fn init_module(&self) {
-        self.state.module_loader().borrow().init(&self.context);
-        Console::init_globals(&self.context).unwrap();
-        Timer::init_globals(&self.context).unwrap();
-        let kedo = JSObject::new(&self.context);
-        kedo.protect();
-
-        self.context
-            .global_object()
-            .set_property("Kedo", &kedo, Default::default())
-            .unwrap();
-    }
-
-    fn init_class(class_manager: &mut ClassTable) {
-        UrlRecord::init_class(class_manager).expect("Failed to init UrlRecord");
-        EncodingTextDecoder::init_class(class_manager)
-            .expect("Failed to init EncodingTextDecoder");
-        ReadableStreamResourceReader::init_class(class_manager)
-            .expect("Failed to init ReadableStreamResourceReader");
-        UnboundedReadableStreamResourceReader::init_class(class_manager)
-            .expect("Failed to init UnboundedReadableStreamResourceReader");
-        FetchClientResource::init_class(class_manager)
-            .expect("Failed to init FetchClientResource");
-        InternalSignal::init_class(class_manager).expect("Failed to init InternalSignal");
-        FetchRequestResource::init_class(class_manager)
-            .expect("Failed to init FetchRequestResource");
-        HttpRequestResource::init_class(class_manager)
-            .expect("Failed to init HttpRequestResource");
-        ReadableStreamResource::init_class(class_manager)
-            .expect("Failed to init JsReadableStream");
-        UnboundedReadableStreamResource::init_class(class_manager)
-            .expect("Failed to init JsUnboundedReadableStream");
-        DecodedStreamResource::init_class(class_manager)
-            .expect("Failed to init JsResponseBody");
-        RequestEventResource::init_class(class_manager)
-            .expect("Failed to init RequestEventResource");
-        NetworkBufferChannelReaderResource::init_class(class_manager)
-            .expect("Failed to init NetworkBufferChannelReaderResource");
-    }
-
-    fn init_proto(
-        proto_table: &mut ProtoTable,
-        class_table: &mut ClassTable,
-        ctx: &JSContext,
-    ) {
-        UrlRecord::init_proto(proto_table, class_table, ctx).unwrap();
-        ReadableStreamResource::init_proto(proto_table, class_table, ctx).unwrap();
-        UnboundedReadableStreamResource::init_proto(proto_table, class_table, ctx)
-            .unwrap();
-        EncodingTextDecoder::init_proto(proto_table, class_table, ctx).unwrap();
-        InternalSignal::init_proto(proto_table, class_table, ctx).unwrap();
+            .map_err(|error| self.module_error(failures, error))
     }
 
     pub fn context(&self) -> &JSContext {
         &self.context
     }
 
+    pub fn state(&self) -> &CoreState {
+        &self.state
+    }
+
     pub fn evaluate_script(&self, script: &str, line: Option<i32>) -> JSResult<JSValue> {
         self.context.evaluate_script(script, line)
     }
@@ -201,13 +191,23 @@ impl Runtime {
         self.context.check_syntax(script, line.unwrap_or(0))
     }
 
-    fn run_event_loop(&mut self, cx: &mut Context<'_>) -> Poll<()> {
-        let callbaks = self.state.timers().poll_timers(cx);
-        if let Poll::Ready(callbaks) = callbaks {
-            for callback in callbaks {
-                if let Err(error) = callback.call() {
-                    println!("Error timer callback: {}", error.message().unwrap());
+    /// Runs one turn of the event loop, it's ready once there are no pending jobs or timers.
+    /// Useful to drive the loop alongside other futures, e.g. while the REPL waits for input.
+    pub fn poll_event_loop(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let timers = self.state.timers().poll_timers(cx);
+        if let Poll::Ready(timers) = timers {
+            for timer in timers {
+                // an expired timeout is released before its callback runs, so the
+                // callback doesn't see its own timer among the open resources
+                if !self.state.timers().contains(&timer.id) {
+                    timer.callback.close_resource();
+                }
+                // timers scheduled from the callback are nested one level deeper
+                self.state.timers().set_nesting_level(timer.nesting_level);
+                if let Err(error) = timer.callback.call() {
+                    report_uncaught(&self.context, &error.into(), "Uncaught");
                 }
+                self.state.timers().set_nesting_level(0);
             }
         }
 
@@ -220,6 +220,14 @@ impl Runtime {
         }
     }
 
+    /// Clears the timers and drops the pending async ops, servers stop listening
+    /// once their op is dropped. The event loop is idle afterwards, e.g. before
+    /// `kedo run --watch` restarts the runtime.
+    pub fn shutdown(&mut self) {
+        self.state.timers().clear();
+        self.state.job_queue().borrow_mut().clear();
+    }
+
     pub fn garbage_collect(&self) {
         self.context.garbage_collect();
     }
@@ -229,6 +237,193 @@ impl Runtime {
     }
 
     pub async fn idle(&mut self) {
-        poll_fn(|cx| self.run_event_loop(cx)).await;
+        poll_fn(|cx| self.poll_event_loop(cx)).await;
+    }
+
+    fn promise_tracker(&self) -> JSResult<&JSFunction> {
+        if let Some(tracker) = self.promise_tracker.get() {
+            return Ok(tracker);
+        }
+
+        let tracker = self.context.evaluate_script(PROMISE_TRACKER, None)?;
+        let tracker = JSFunction::from(tracker.as_object()?);
+        tracker.protect();
+        Ok(self.promise_tracker.get_or_init(|| tracker))
+    }
+
+    /// Subscribes to `value` if it's a promise, see [`PROMISE_TRACKER`]
+    fn track(&self, value: &JSValue) -> JSResult<Option<JSObject>> {
+        if !value.is_object() {
+            return Ok(None);
+        }
+
+        let state = self.promise_tracker()?.call(None, &[value.clone()])?;
+        if state.is_undefined() {
+            return Ok(None);
+        }
+
+        Ok(Some(state.as_object()?))
+    }
+
+    /// State of `value` without running the event loop, only the microtasks
+    /// already queued have run. Any other value is returned as fulfilled.
+    pub fn promise_state(&self, value: JSValue) -> JSResult<PromiseState> {
+        match self.track(&value)? {
+            Some(state) => tracked_state(&state),
+            None => Ok(PromiseState::Fulfilled(value)),
+        }
+    }
+
+    /// Drives the event loop until `value` settles if it's a promise,
+    /// any other value is returned as fulfilled right away.
+    pub async fn settle(&mut self, value: JSValue) -> JSResult<PromiseState> {
+        let state = match self.track(&value)? {
+            Some(state) => state,
+            None => return Ok(PromiseState::Fulfilled(value)),
+        };
+
+        poll_fn(|cx| {
+            if is_settled(&state) {
+                return Poll::Ready(());
+            }
+
+            match self.poll_event_loop(cx) {
+                Poll::Ready(()) => Poll::Ready(()),
+                Poll::Pending if is_settled(&state) => Poll::Ready(()),
+                Poll::Pending => Poll::Pending,
+            }
+        })
+        .await;
+
+        tracked_state(&state)
+    }
+}
+
+fn is_settled(state: &JSObject) -> bool {
+    state
+        .get_property("settled")
+        .map(|settled| settled.as_boolean())
+        .unwrap_or(true)
+}
+
+/// Reads the state object returned by [`PROMISE_TRACKER`]
+fn tracked_state(state: &JSObject) -> JSResult<PromiseState> {
+    if !is_settled(state) {
+        return Ok(PromiseState::Pending);
+    }
+
+    let result = state.get_property("value")?;
+    if state.get_property("rejected")?.as_boolean() {
+        Ok(PromiseState::Rejected(result))
+    } else {
+        Ok(PromiseState::Fulfilled(result))
+    }
+}
+
+/// Reports an error nothing caught, a worker sends it to its parent as an
+/// `error` event and any other runtime prints it. `kind` prefixes the printed
+/// headline, e.g. "Uncaught (in promise)".
+fn report_uncaught(ctx: &JSContext, exception: &JSValue, kind: &str) {
+    let state = downcast_state(ctx);
+    if let Some(scope) = state.worker_scope().borrow().as_ref() {
+        let message = property(exception, "message").unwrap_or_else(|| {
+            exception
+                .as_string()
+                .map(|value| value.to_string())
+                .unwrap_or_default()
+        });
+        let error = WorkerError {
+            message,
+            stack: property(exception, "stack").unwrap_or_default(),
+        };
+        // the parent is gone once the channel is closed, the error is printed
+        if scope.post_message(WorkerMessage::Error(error)).is_ok() {
+            return;
+        }
+    }
+
+    eprintln!("{}", format_exception(ctx, exception, kind));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_utils::DirLoader;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_shutdown() {
+        let temp = TempDir::new().unwrap();
+        let dir = std::fs::canonicalize(temp.path()).unwrap();
+        std::fs::write(dir.join("lib.js"), "export const delay = 60000;").unwrap();
+        std::fs::write(
+            dir.join("main.js"),
+            r#"
+            import { delay } from './lib.js';
+            setInterval(() => {}, 10);
+            setTimeout(() => {}, delay);
+        "#,
+        )
+        .unwrap();
+
+        let mut runtime = RuntimeBuilder::new()
+            .with_file_system_loader(DirLoader { dir: dir.clone() })
+            .build()
+            .unwrap();
+        let result = runtime.evaluate_module("main.js");
+        assert!(result.is_ok());
+        assert_eq!(
+            runtime.state().module_loader().borrow().loaded_files(),
+            vec![dir.join("lib.js"), dir.join("main.js")]
+        );
+        assert!(!runtime.state().timers().is_empty());
+
+        runtime.shutdown();
+        assert_eq!(runtime.state().timers().len(), 0);
+        let idle =
+            tokio::time::timeout(std::time::Duration::from_secs(1), runtime.idle()).await;
+        assert!(idle.is_ok());
+    }
+
+    #[test]
+    fn test_inspect_state() {
+        let runtime = RuntimeBuilder::new().build().unwrap();
+        let globals = || {
+            let names = "Object.getOwnPropertyNames(globalThis).join()";
+            let names = runtime.evaluate_script(names, None).unwrap();
+            names.as_string().unwrap().to_string()
+        };
+        let before = globals();
+
+        let value = runtime
+            .evaluate_script("({ list: [1, 'two'] })", None)
+            .unwrap();
+        let formatted = kedo_console::inspect(runtime.context(), &value);
+        assert_eq!(formatted, r#"{ list: [ 1, "two" ] }"#);
+
+        // the formatter is kept in the state, not on the global object
+        assert!(runtime.state().inspector().borrow().is_some());
+        assert_eq!(globals(), before);
+    }
+
+    #[test]
+    fn test_promise_state() {
+        let runtime = RuntimeBuilder::new().build().unwrap();
+        runtime
+            .evaluate_script("setInterval(() => {}, 10)", None)
+            .unwrap();
+
+        let pending = runtime
+            .evaluate_script("new Promise(() => {})", None)
+            .unwrap();
+        let state = runtime.promise_state(pending).unwrap();
+        assert!(matches!(state, PromiseState::Pending));
+
+        let resolved = runtime.evaluate_script("Promise.resolve(1)", None).unwrap();
+        match runtime.promise_state(resolved).unwrap() {
+            PromiseState::Fulfilled(value) => assert_eq!(value.as_number().unwrap(), 1.0),
+            _ => panic!("the promise should be fulfilled"),
+        }
+        runtime.state().timers().clear();
     }
 }
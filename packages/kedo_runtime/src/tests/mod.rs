@@ -1,7 +1,17 @@
+#[cfg(test)]
+mod modules;
+#[cfg(test)]
+mod structured_clone;
+#[cfg(test)]
+mod timers;
+
 #[cfg(test)]
 pub mod test_utils {
     use crate::runtime::Runtime;
-    use kedo_core::{AsyncJobQueue, ClassTable, CoreModuleLoader, CoreState, ProtoTable};
+    use kedo_core::{
+        AsyncJobQueue, ClassTable, CoreModuleLoader, CoreState, ModuleError,
+        ModuleLoader, ProtoTable,
+    };
     use kedo_std::TimerQueue;
 
     pub fn new_context_state_with(loader: CoreModuleLoader) -> CoreState {
@@ -19,4 +29,54 @@ pub mod test_utils {
     pub fn new_runtime() -> Runtime {
         Runtime::new()
     }
+
+    /// Loads `@embed/greeting`, a module that exports `hello` as default
+    pub struct GreetingLoader;
+
+    impl ModuleLoader for GreetingLoader {
+        fn can_handle(&self, module_id: &str) -> bool {
+            module_id == "@embed/greeting"
+        }
+
+        fn resolve(&self, module_id: &str) -> Result<String, ModuleError> {
+            Ok(module_id.to_string())
+        }
+
+        fn load(&self, _module_id: &str) -> Result<String, ModuleError> {
+            Ok("export default 'hello';".to_string())
+        }
+    }
+
+    /// Resolves the modules relative to their importer, or to `dir` without one
+    pub struct DirLoader {
+        pub dir: std::path::PathBuf,
+    }
+
+    impl ModuleLoader for DirLoader {
+        fn can_handle(&self, _module_id: &str) -> bool {
+            true
+        }
+
+        fn resolve(&self, module_id: &str) -> Result<String, ModuleError> {
+            self.resolve_from(module_id, None)
+        }
+
+        fn resolve_from(
+            &self,
+            module_id: &str,
+            referrer: Option<&str>,
+        ) -> Result<String, ModuleError> {
+            let dir = referrer
+                .and_then(|referrer| std::path::Path::new(referrer).parent())
+                .unwrap_or(&self.dir);
+            std::fs::canonicalize(dir.join(module_id))
+                .map(|path| path.to_string_lossy().to_string())
+                .map_err(|_| ModuleError::NotFound(module_id.to_string()))
+        }
+
+        fn load(&self, module_id: &str) -> Result<String, ModuleError> {
+            std::fs::read_to_string(module_id)
+                .map_err(|_| ModuleError::LoadError(module_id.to_string()))
+        }
+    }
 }
@@ -0,0 +1,261 @@
+use super::test_utils::{DirLoader, GreetingLoader};
+use crate::{
+    runtime::PromiseState, ImportMap, ModuleError, ModuleLoader, RuntimeBuilder,
+};
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_module_resolution_errors() {
+    let mut runtime = RuntimeBuilder::new()
+        .with_loader(GreetingLoader)
+        .build()
+        .unwrap();
+
+    let result = runtime.evaluate_module_from_source(
+        r#"
+        import { missing } from '@embed/missing';
+    "#,
+        "missing.js",
+        None,
+    );
+    let error = result.unwrap_err();
+    let message = error.message().unwrap().to_string();
+    assert!(message.contains("Cannot find module '@embed/missing'"));
+
+    let result = runtime
+        .evaluate_script(
+            "import('@embed/missing').catch((e) => `${e.name}:${e.message}`)",
+            None,
+        )
+        .unwrap();
+    let result = match runtime.settle(result).await.unwrap() {
+        PromiseState::Fulfilled(value) => value.as_string().unwrap().to_string(),
+        _ => panic!("import should be caught"),
+    };
+    assert!(result.starts_with("TypeError:Cannot find module '@embed/missing'"));
+}
+
+#[test]
+fn test_import_map() {
+    let temp = TempDir::new().unwrap();
+    let dir = temp.path().to_path_buf();
+    std::fs::write(dir.join("greeting.js"), "export default 'mapped';").unwrap();
+    std::fs::write(
+        dir.join("import_map.json"),
+        r#"{ "imports": { "greeting": "./greeting.js", "blocked": null } }"#,
+    )
+    .unwrap();
+
+    let import_map = ImportMap::from_file(&dir.join("import_map.json")).unwrap();
+    let runtime = RuntimeBuilder::new()
+        .import_map(import_map)
+        .build()
+        .unwrap();
+
+    let result = runtime.evaluate_module_from_source(
+        r#"
+        import greeting from 'greeting';
+        globalThis.greeting = greeting;
+    "#,
+        "main.js",
+        None,
+    );
+    assert!(result.is_ok());
+
+    let result = runtime
+        .evaluate_script("globalThis.greeting", None)
+        .unwrap();
+    assert_eq!(result.as_string().unwrap(), "mapped");
+
+    let result =
+        runtime.evaluate_module_from_source("import 'blocked';", "blocked.js", None);
+    let message = result.unwrap_err().message().unwrap().to_string();
+    assert!(message.contains("blocked by an invalid entry in the import map"));
+}
+
+struct DataLoader;
+
+impl ModuleLoader for DataLoader {
+    fn can_handle(&self, module_id: &str) -> bool {
+        module_id.starts_with("@embed/data.")
+    }
+
+    fn resolve(&self, module_id: &str) -> Result<String, ModuleError> {
+        Ok(module_id.to_string())
+    }
+
+    fn load(&self, module_id: &str) -> Result<String, ModuleError> {
+        match module_id {
+            "@embed/data.json" => Ok(r#"{ "name": "kedo", "tags": [1, 2] }"#.to_string()),
+            _ => Ok("hello\n".to_string()),
+        }
+    }
+}
+
+#[test]
+fn test_import_attributes() {
+    let runtime = RuntimeBuilder::new()
+        .with_loader(DataLoader)
+        .build()
+        .unwrap();
+
+    let result = runtime.evaluate_module_from_source(
+        r#"
+        import data from '@embed/data.json' with { type: 'json' };
+        import text from '@embed/data.txt' with { type: 'text' };
+        import bytes from '@embed/data.bin' with { type: 'bytes' };
+        globalThis.imported = [data.name, data.tags.length, text, bytes.length].join(':');
+    "#,
+        "attributes.js",
+        None,
+    );
+    assert!(result.is_ok());
+
+    let result = runtime
+        .evaluate_script("globalThis.imported", None)
+        .unwrap();
+    assert_eq!(result.as_string().unwrap(), "kedo:2:hello\n:6");
+
+    let runtime = RuntimeBuilder::new()
+        .with_loader(DataLoader)
+        .build()
+        .unwrap();
+    let result = runtime.evaluate_module_from_source(
+        "import data from '@embed/data.json';",
+        "missing_attributes.js",
+        None,
+    );
+    let message = result.unwrap_err().message().unwrap().to_string();
+    assert!(message.contains("is a JSON module"));
+
+    let runtime = RuntimeBuilder::new()
+        .with_loader(DataLoader)
+        .build()
+        .unwrap();
+    let result = runtime.evaluate_module_from_source(
+        "import data from '@embed/data.json' with { type: 'json', lazy: 'true' };",
+        "unsupported_attributes.js",
+        None,
+    );
+    let message = result.unwrap_err().message().unwrap().to_string();
+    assert!(message.contains("Import attribute \"lazy\" is not supported"));
+}
+
+#[test]
+fn test_import_meta() {
+    let temp = TempDir::new().unwrap();
+    let dir = std::fs::canonicalize(temp.path()).unwrap();
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    std::fs::write(dir.join("lib/util.js"), "export const meta = import.meta;").unwrap();
+    std::fs::write(
+        dir.join("main.js"),
+        r#"
+        import { meta } from './lib/util.js';
+        let missing;
+        try {
+            import.meta.resolve('./missing.js');
+        } catch (error) {
+            missing = error instanceof TypeError;
+        }
+
+        globalThis.meta = [
+            import.meta.main,
+            meta.main,
+            import.meta.url,
+            meta.dirname,
+            meta.filename,
+            import.meta.resolve('./lib/util.js') === meta.url,
+            missing,
+        ].join('|');
+    "#,
+    )
+    .unwrap();
+
+    let runtime = RuntimeBuilder::new()
+        .with_file_system_loader(DirLoader { dir: dir.clone() })
+        .build()
+        .unwrap();
+    let result = runtime.evaluate_module("main.js");
+    assert!(result.is_ok());
+
+    let result = runtime.evaluate_script("globalThis.meta", None).unwrap();
+    let expected = format!(
+        "true|false|file://{}|{}|{}|true|true",
+        dir.join("main.js").display(),
+        dir.join("lib").display(),
+        dir.join("lib/util.js").display(),
+    );
+    assert_eq!(result.as_string().unwrap(), expected.as_str());
+}
+
+#[tokio::test]
+async fn test_import_meta_main_of_dynamic_imports() {
+    let temp = TempDir::new().unwrap();
+    let dir = std::fs::canonicalize(temp.path()).unwrap();
+    std::fs::write(dir.join("lib.js"), "export const main = import.meta.main;").unwrap();
+
+    // like the REPL, no entry module was evaluated
+    let mut runtime = RuntimeBuilder::new()
+        .with_file_system_loader(DirLoader { dir: dir.clone() })
+        .build()
+        .unwrap();
+    let result = runtime
+        .evaluate_script("import('./lib.js').then((lib) => lib.main)", None)
+        .unwrap();
+    match runtime.settle(result).await.unwrap() {
+        PromiseState::Fulfilled(main) => assert!(!main.as_boolean()),
+        _ => panic!("import should be fulfilled"),
+    }
+    assert_eq!(runtime.state().module_loader().borrow().main_module(), None);
+}
+
+#[test]
+fn test_commonjs_interop() {
+    let temp = TempDir::new().unwrap();
+    let dir = std::fs::canonicalize(temp.path()).unwrap();
+    std::fs::write(dir.join("config.json"), r#"{ "name": "kedo" }"#).unwrap();
+    std::fs::write(
+        dir.join("math.cjs"),
+        r#"
+        const config = require('./config.json');
+        exports.add = (a, b) => a + b;
+        exports.name = config.name;
+        exports.loaded = require.cache[__filename].loaded;
+    "#,
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("main.js"),
+        r#"
+        import math, { add, name } from './math.cjs';
+        import { createRequire } from '@kedo:op/module';
+        const require = createRequire(import.meta.url);
+        let esm;
+        try {
+            require('./main.js');
+        } catch (error) {
+            esm = error instanceof TypeError;
+        }
+
+        globalThis.cjs = [
+            add(1, 2),
+            name,
+            math.loaded,
+            require('./math.cjs') === math,
+            require('./config.json').name,
+            esm,
+        ].join('|');
+    "#,
+    )
+    .unwrap();
+
+    let runtime = RuntimeBuilder::new()
+        .with_file_system_loader(DirLoader { dir: dir.clone() })
+        .build()
+        .unwrap();
+    let result = runtime.evaluate_module("main.js");
+    assert!(result.is_ok());
+
+    let result = runtime.evaluate_script("globalThis.cjs", None).unwrap();
+    assert_eq!(result.as_string().unwrap(), "3|kedo|false|true|kedo|true");
+}
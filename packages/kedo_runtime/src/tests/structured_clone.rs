@@ -0,0 +1,136 @@
+use crate::RuntimeBuilder;
+
+#[test]
+fn test_structured_clone() {
+    let runtime = RuntimeBuilder::new().build().unwrap();
+    let result = runtime.evaluate_module_from_source(
+        r#"
+        import { op_structured_clone } from '@kedo:op/web';
+
+        const buffer = new Uint8Array([1, 2, 3]).buffer;
+        const map = new Map([['key', new Set([1n, /a+/gi])]]);
+        const value = {
+            map,
+            view: new Uint16Array(buffer, 0, 1),
+            error: new RangeError('range'),
+            boxed: new String('boxed'),
+        };
+        value.self = value;
+
+        const clone = op_structured_clone(value, [buffer]);
+        const [bigint, regexp] = clone.map.get('key');
+        globalThis.cloned = [
+            clone !== value && clone.self === clone,
+            bigint === 1n,
+            regexp.source + regexp.flags,
+            clone.view.buffer.byteLength,
+            new Uint8Array(clone.view.buffer)[2],
+            buffer.byteLength,
+            clone.error instanceof RangeError && clone.error.message,
+            typeof clone.boxed === 'object' && clone.boxed.valueOf(),
+        ].join(':');
+
+        try {
+            op_structured_clone(buffer, [buffer]);
+        } catch (error) {
+            globalThis.cloneError = error.name;
+        }
+    "#,
+        "main.js",
+        None,
+    );
+    assert!(result.is_ok());
+
+    let result = runtime
+        .evaluate_script("`${cloned}:${cloneError}`", None)
+        .unwrap();
+    assert_eq!(
+        result.as_string().unwrap(),
+        "true:true:a+gi:3:3:0:range:boxed:DataCloneError"
+    );
+}
+
+#[test]
+fn test_structured_clone_ports() {
+    let runtime = RuntimeBuilder::new().build().unwrap();
+    let result = runtime.evaluate_module_from_source(
+        r#"
+        import { op_structured_clone } from '@kedo:op/web';
+
+        // the kind the std MessagePort reports
+        class MessagePort {
+            get [Symbol.toStringTag]() {
+                return 'MessagePort';
+            }
+        }
+        const port = new MessagePort();
+        const errors = [];
+        for (const [value, transfer] of [[{ port }, []], [null, [port]]]) {
+            try {
+                op_structured_clone(value, transfer);
+            } catch (error) {
+                errors.push(`${error.name}: ${error.message}`);
+            }
+        }
+        globalThis.portErrors = errors.join('\n');
+    "#,
+        "main.js",
+        None,
+    );
+    assert!(result.is_ok());
+
+    let result = runtime.evaluate_script("portErrors", None).unwrap();
+    let error = "DataCloneError: MessagePort objects can't be transferred yet";
+    assert_eq!(
+        result.as_string().unwrap().to_string(),
+        format!("{}\n{}", error, error)
+    );
+}
+
+#[tokio::test]
+async fn test_message_channel() {
+    let mut runtime = RuntimeBuilder::new().build().unwrap();
+    let result = runtime.evaluate_module_from_source(
+        r#"
+        import {
+            op_broadcast_channel_close,
+            op_broadcast_channel_create,
+            op_broadcast_channel_post_message,
+            op_broadcast_channel_recv,
+            op_message_channel_create,
+            op_message_port_close,
+            op_message_port_post_message,
+            op_message_port_recv,
+        } from '@kedo:op/web';
+
+        globalThis.received = [];
+        const [port1, port2] = op_message_channel_create();
+        op_message_port_post_message(port1, { value: 'port' });
+        op_message_port_recv(port2, (error, event) => {
+            received.push(event.data.value);
+            op_message_port_close(port1);
+            op_message_port_recv(port2, (error, event) => {
+                received.push(event === undefined);
+            });
+        });
+
+        const sender = op_broadcast_channel_create('test');
+        const listener = op_broadcast_channel_create('test');
+        op_broadcast_channel_post_message(sender, 'broadcast');
+        op_broadcast_channel_recv(listener, (error, event) => {
+            received.push(event.data);
+            op_broadcast_channel_close(sender);
+            op_broadcast_channel_close(listener);
+        });
+    "#,
+        "main.js",
+        None,
+    );
+    assert!(result.is_ok());
+
+    runtime.idle().await;
+    let result = runtime
+        .evaluate_script("received.sort().join(':')", None)
+        .unwrap();
+    assert_eq!(result.as_string().unwrap(), "broadcast:port:true");
+}
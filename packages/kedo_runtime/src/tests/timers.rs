@@ -0,0 +1,53 @@
+use crate::RuntimeBuilder;
+
+#[tokio::test]
+async fn test_timer_handles() {
+    let mut runtime = RuntimeBuilder::new().build().unwrap();
+
+    let result = runtime.evaluate_script(
+        r#"
+        globalThis.order = [];
+        const first = setTimeout(() => order.push('a'), 0);
+        setTimeout(() => order.push('b'), 0);
+        setTimeout(() => order.push('c'), 0);
+        const idle = setTimeout(() => order.push('idle'), 60000).unref();
+        globalThis.handles = [
+            typeof first,
+            first.hasRef(),
+            idle.hasRef(),
+            Number(first) > 0,
+        ].join(':');
+    "#,
+        None,
+    );
+    assert!(result.is_ok());
+
+    // the unref'd timer doesn't keep the event loop alive
+    runtime.idle().await;
+    let result = runtime
+        .evaluate_script("`${handles}|${order.join(',')}`", None)
+        .unwrap();
+    assert_eq!(result.as_string().unwrap(), "object:true:false:true|a,b,c");
+}
+
+#[tokio::test]
+async fn test_timer_overflowing_delays() {
+    let mut runtime = RuntimeBuilder::new().build().unwrap();
+
+    // delays that don't fit in 32 bits run after 1ms instead of never
+    let result = runtime.evaluate_script(
+        r#"
+        globalThis.order = [];
+        setTimeout(() => order.push('infinity'), Infinity).refresh();
+        setTimeout(() => order.push('huge'), 1e20);
+        setTimeout(() => order.push('nan'), NaN);
+    "#,
+        None,
+    );
+    assert!(result.is_ok());
+
+    // the refreshed timer runs last
+    runtime.idle().await;
+    let result = runtime.evaluate_script("order.join(',')", None).unwrap();
+    assert_eq!(result.as_string().unwrap(), "huge,nan,infinity");
+}
@@ -0,0 +1,270 @@
+use crate::builder::RuntimeBuilder;
+use kedo_core::{WorkerError, WorkerLauncher, WorkerMessage, WorkerScope};
+use kedo_std::BufferChannelWriter;
+use std::sync::Arc;
+
+/// Creates the builder of every worker runtime, workers usually share the
+/// loaders and permissions of the runtime that spawned them.
+pub type WorkerFactory = Arc<dyn Fn() -> RuntimeBuilder + Send + Sync>;
+
+pub(crate) fn worker_launcher(factory: WorkerFactory) -> WorkerLauncher {
+    Arc::new(move |scope| run_worker(&factory, scope))
+}
+
+/// Runs the worker module on the current thread until its event loop is idle,
+/// it calls `close()` or the parent terminates it.
+fn run_worker(factory: &WorkerFactory, scope: WorkerScope) {
+    let url = scope.url().to_string();
+    let close = scope.close_signal();
+    let parent = scope.sender();
+    let report = |message: String, stack: String| {
+        let error = WorkerError { message, stack };
+        let _ = parent.try_write(WorkerMessage::Error(error));
+    };
+
+    let tokio_runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(tokio_runtime) => tokio_runtime,
+        Err(err) => return report(err.to_string(), String::new()),
+    };
+
+    tokio_runtime.block_on(async move {
+        let mut runtime = match factory().worker_scope(scope).build() {
+            Ok(runtime) => runtime,
+            Err(err) => return report(err.to_string(), String::new()),
+        };
+
+        if let Err(err) = runtime.evaluate_module(&url) {
+            let message = err.message().map(|m| m.to_string()).unwrap_or_default();
+            let stack = err.stack().map(|s| s.to_string()).unwrap_or_default();
+            return report(message, stack);
+        }
+
+        tokio::select! {
+            _ = runtime.idle() => {}
+            _ = close.notified() => {}
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_utils::DirLoader;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_worker_uncaught_errors() {
+        let temp = TempDir::new().unwrap();
+        let worker = temp.path().join("failing.js");
+        std::fs::write(
+            &worker,
+            r#"
+            setTimeout(() => {
+                throw new Error('timer failed');
+            }, 0);
+            Promise.reject(new Error('promise failed'));
+        "#,
+        )
+        .unwrap();
+
+        let mut runtime = RuntimeBuilder::new()
+            .workers(RuntimeBuilder::new)
+            .build()
+            .unwrap();
+        let source = format!(
+            r#"
+            import {{ op_worker_create, op_worker_recv }} from '@kedo:op/web';
+
+            const worker = op_worker_create({:?}, 'failing');
+            globalThis.errors = [];
+            const recv = () => op_worker_recv(worker, (error, event) => {{
+                if (error || !event) return;
+                if (event.type === 'error') errors.push(event.message);
+                recv();
+            }});
+            recv();
+        "#,
+            worker.to_string_lossy()
+        );
+
+        let result = runtime.evaluate_module_from_source(&source, "main.js", None);
+        assert!(result.is_ok());
+
+        // the errors after the evaluation of the worker reach the parent too
+        runtime.idle().await;
+        let result = runtime
+            .evaluate_script("errors.sort().join(',')", None)
+            .unwrap();
+        assert_eq!(result.as_string().unwrap(), "promise failed,timer failed");
+    }
+
+    #[tokio::test]
+    async fn test_worker_messages() {
+        let temp = TempDir::new().unwrap();
+        let worker = temp.path().join("worker_messages.js");
+        std::fs::write(
+            &worker,
+            r#"
+            import {
+                op_worker_close,
+                op_worker_scope,
+                op_worker_scope_post_message,
+                op_worker_scope_recv,
+            } from '@kedo:op/web';
+
+            op_worker_scope_recv((error, event) => {
+                const data = event.data;
+                op_worker_scope_post_message({
+                    name: op_worker_scope().name,
+                    echo: data,
+                    cycle: data.self === data,
+                    date: data.date instanceof Date && data.date.getTime(),
+                });
+                op_worker_close();
+            });
+        "#,
+        )
+        .unwrap();
+
+        let mut runtime = RuntimeBuilder::new()
+            .workers(RuntimeBuilder::new)
+            .build()
+            .unwrap();
+        let source = format!(
+            r#"
+            import {{
+                op_worker_create,
+                op_worker_post_message,
+                op_worker_recv,
+            }} from '@kedo:op/web';
+
+            const worker = op_worker_create({:?}, 'echo');
+            const data = {{ list: [1, 'two', null, undefined], date: new Date(42) }};
+            data.self = data;
+
+            try {{
+                op_worker_post_message(worker, {{ callback() {{}} }});
+            }} catch (error) {{
+                globalThis.cloneError = error.name;
+            }}
+
+            op_worker_post_message(worker, data);
+            globalThis.received = [];
+            const recv = () => op_worker_recv(worker, (error, event) => {{
+                if (error || !event) return;
+                received.push(event.data);
+                recv();
+            }});
+            recv();
+        "#,
+            worker.to_string_lossy()
+        );
+
+        let result = runtime.evaluate_module_from_source(&source, "main.js", None);
+        assert!(result.is_ok());
+
+        // the parent stays alive until the worker closes itself
+        runtime.idle().await;
+        let result = runtime
+            .evaluate_script(
+                r#"
+                const [message] = received;
+                [
+                    cloneError,
+                    received.length,
+                    message.name,
+                    message.echo.list.length,
+                    message.echo.list[1],
+                    message.echo.self === message.echo,
+                    message.cycle,
+                    message.date,
+                ].join(':')
+            "#,
+                None,
+            )
+            .unwrap();
+        assert_eq!(
+            result.as_string().unwrap(),
+            "DataCloneError:1:echo:4:two:true:true:42"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_worker_from_nested_module() {
+        let temp = TempDir::new().unwrap();
+        let dir = std::fs::canonicalize(temp.path()).unwrap();
+        std::fs::create_dir(dir.join("lib")).unwrap();
+        let worker = |name: &str| {
+            format!(
+                r#"
+                import {{ op_worker_close, op_worker_scope_post_message }} from '@kedo:op/web';
+                op_worker_scope_post_message('{}');
+                op_worker_close();
+            "#,
+                name
+            )
+        };
+        std::fs::write(dir.join("worker.js"), worker("main")).unwrap();
+        std::fs::write(dir.join("lib/worker.js"), worker("lib")).unwrap();
+        std::fs::write(
+            dir.join("lib/spawn.js"),
+            r#"
+            import { op_worker_create, op_worker_recv } from '@kedo:op/web';
+
+            const worker = op_worker_create('./worker.js', 'nested');
+            globalThis.received = [];
+            const recv = () => op_worker_recv(worker, (error, event) => {
+                if (error || !event) return;
+                received.push(event.data);
+                recv();
+            });
+            recv();
+        "#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("main.js"), "import './lib/spawn.js';").unwrap();
+
+        let mut runtime = RuntimeBuilder::new()
+            .with_file_system_loader(DirLoader { dir })
+            .workers(RuntimeBuilder::new)
+            .build()
+            .unwrap();
+        let result = runtime.evaluate_module("main.js");
+        assert!(result.is_ok());
+
+        // relative to the main module, not to the module that creates the worker
+        runtime.idle().await;
+        let result = runtime.evaluate_script("received.join(',')", None).unwrap();
+        assert_eq!(result.as_string().unwrap(), "main");
+    }
+
+    #[test]
+    fn test_workers_are_disabled_by_default() {
+        let runtime = RuntimeBuilder::new().build().unwrap();
+        let result = runtime.evaluate_module_from_source(
+            r#"
+            import { op_worker_create } from '@kedo:op/web';
+            try {
+                op_worker_create('./worker.js', '');
+            } catch (error) {
+                globalThis.workerError = error.message;
+            }
+        "#,
+            "main.js",
+            None,
+        );
+        assert!(result.is_ok());
+
+        let result = runtime
+            .evaluate_script("globalThis.workerError", None)
+            .unwrap();
+        assert!(result
+            .as_string()
+            .unwrap()
+            .to_string()
+            .contains("Workers are not enabled"));
+    }
+}
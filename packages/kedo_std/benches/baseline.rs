@@ -0,0 +1,336 @@
+//! The `BTreeMap` timer queue used before the timing wheel,
+//! kept as the baseline for the timer queue benchmarks.
+#![allow(dead_code)]
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+use tokio::time::{sleep_until, Duration, Instant, Sleep};
+
+pub type TimerId = usize;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimerType {
+    Timeout,
+    Interval,
+}
+
+/// Timers nested deeper than this level are clamped to `MIN_NESTED_DURATION`
+/// https://html.spec.whatwg.org/multipage/timers-and-user-prompts.html#timer-initialisation-steps
+const MAX_NESTING_LEVEL: u32 = 5;
+const MIN_NESTED_DURATION: Duration = Duration::from_millis(4);
+
+/// Orders timers by deadline first and then by the order they were scheduled,
+/// so timers with the same deadline run in FIFO order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct CompoundedTimerKey(Instant, u64);
+
+struct TimerData<T> {
+    key: CompoundedTimerKey,
+    callback: T,
+    duration: Duration,
+    timer_type: TimerType,
+    referenced: bool,
+    nesting_level: u32,
+}
+
+/// A timer ready to run, the runtime should set `nesting_level` on the queue
+/// while the callback runs so nested timers get clamped.
+pub struct ExpiredTimer<T> {
+    pub id: TimerId,
+    pub callback: T,
+    pub nesting_level: u32,
+}
+
+// Create a mutable sleep struct
+struct SleepWrapper {
+    sleep: RefCell<Option<Sleep>>,
+}
+
+pub struct BTreeTimerQueue<T>
+where
+    T: Clone,
+{
+    timers: RefCell<BTreeMap<CompoundedTimerKey, TimerId>>,
+    timer_data: RefCell<HashMap<TimerId, TimerData<T>>>,
+    next_timer_id: Cell<TimerId>,
+    next_sequence: Cell<u64>,
+    ref_count: Cell<usize>,
+    nesting_level: Cell<u32>,
+    sleep: Box<SleepWrapper>,
+    waker: RefCell<Option<Waker>>,
+}
+
+// implement Send for BTreeTimerQueue
+unsafe impl<T> Send for BTreeTimerQueue<T> where T: Clone {}
+
+unsafe impl<T> Sync for BTreeTimerQueue<T> where T: Clone {}
+
+impl<T> BTreeTimerQueue<T>
+where
+    T: Clone,
+{
+    pub fn new() -> Self {
+        Self {
+            timers: RefCell::new(BTreeMap::new()),
+            timer_data: RefCell::new(HashMap::new()),
+            next_timer_id: Cell::new(0),
+            next_sequence: Cell::new(0),
+            ref_count: Cell::new(0),
+            nesting_level: Cell::new(0),
+            sleep: Box::new(SleepWrapper {
+                sleep: RefCell::new(None),
+            }),
+            waker: RefCell::new(None),
+        }
+    }
+
+    pub fn add_timer(
+        &self,
+        duration: Duration,
+        timer_type: TimerType,
+        callback: T,
+        non_blocking: Option<bool>,
+    ) -> TimerId {
+        let nesting_level = self.nesting_level.get();
+        let duration = clamp_duration(duration, nesting_level);
+        let key = self.next_key(Instant::now() + duration);
+        let id = self.next_timer_id.get() + 1;
+        self.next_timer_id.set(id);
+
+        let referenced = !non_blocking.unwrap_or(false);
+        if referenced {
+            // ref_count is used to keep the BTreeTimerQueue alive
+            self.ref_count.set(self.ref_count.get() + 1);
+        }
+
+        self.timers.borrow_mut().insert(key, id);
+        self.timer_data.borrow_mut().insert(
+            id,
+            TimerData {
+                key,
+                callback,
+                duration,
+                timer_type,
+                referenced,
+                nesting_level: nesting_level + 1,
+            },
+        );
+        self.update_sleep_timer(key.0);
+        id
+    }
+
+    fn next_key(&self, expiration: Instant) -> CompoundedTimerKey {
+        let sequence = self.next_sequence.get();
+        self.next_sequence.set(sequence + 1);
+        CompoundedTimerKey(expiration, sequence)
+    }
+
+    fn wake(&self) {
+        if let Some(waker) = self.waker.borrow().as_ref() {
+            waker.wake_by_ref();
+        }
+    }
+
+    fn update_sleep_timer(&self, new_expiration: Instant) {
+        let mut sleep = self.sleep.sleep.borrow_mut();
+        let should_update = match &*sleep {
+            Some(existing_sleep) => new_expiration < existing_sleep.deadline(),
+            None => true,
+        };
+
+        if should_update {
+            *sleep = Some(sleep_until(new_expiration));
+            self.wake();
+        } else {
+            // wake if sleep deadline is already expired
+            if let Some(sleep) = &*sleep {
+                if sleep.deadline() <= Instant::now() {
+                    self.wake();
+                }
+            }
+        }
+    }
+
+    pub fn clear_timer(&self, id: &TimerId) -> Option<T> {
+        // dropping the returned callback releases the JS function
+        let data = self.timer_data.borrow_mut().remove(id)?;
+        self.timers.borrow_mut().remove(&data.key);
+        if data.referenced {
+            self.ref_count.set(self.ref_count.get() - 1);
+        }
+
+        Some(data.callback)
+    }
+
+    /// Marks the timer as referenced or not, unreferenced timers don't keep
+    /// the event loop alive. Returns false if the timer doesn't exist.
+    pub fn set_ref(&self, id: &TimerId, referenced: bool) -> bool {
+        let mut timer_data = self.timer_data.borrow_mut();
+        let Some(data) = timer_data.get_mut(id) else {
+            return false;
+        };
+
+        if data.referenced != referenced {
+            data.referenced = referenced;
+            if referenced {
+                self.ref_count.set(self.ref_count.get() + 1);
+            } else {
+                self.ref_count.set(self.ref_count.get() - 1);
+            }
+        }
+
+        true
+    }
+
+    pub fn has_ref(&self, id: &TimerId) -> bool {
+        self.timer_data
+            .borrow()
+            .get(id)
+            .map(|data| data.referenced)
+            .unwrap_or(false)
+    }
+
+    /// Restarts the timer with its original duration from now,
+    /// returns false if the timer already ran or was cleared.
+    pub fn refresh(&self, id: &TimerId) -> bool {
+        let mut timer_data = self.timer_data.borrow_mut();
+        let Some(data) = timer_data.get_mut(id) else {
+            return false;
+        };
+
+        let key = self.next_key(Instant::now() + data.duration);
+        let mut timers = self.timers.borrow_mut();
+        timers.remove(&data.key);
+        timers.insert(key, *id);
+        data.key = key;
+
+        drop(timers);
+        drop(timer_data);
+        self.update_sleep_timer(key.0);
+        true
+    }
+
+    /// Nesting level of the timer callback currently running, 0 outside timers
+    pub fn set_nesting_level(&self, level: u32) {
+        self.nesting_level.set(level);
+    }
+
+    pub fn nesting_level(&self) -> u32 {
+        self.nesting_level.get()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        // only referenced timers keep the queue alive
+        self.ref_count.get() == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.timers.borrow().len()
+    }
+
+    pub fn poll_timers(&self, cx: &mut Context<'_>) -> Poll<Vec<ExpiredTimer<T>>> {
+        // early return if there are no timers
+        if self.timers.borrow().is_empty() {
+            return Poll::Ready(Vec::new());
+        }
+
+        let now = Instant::now();
+        // 1. remove the expired timers in deadline and scheduling order
+        // 2. reschedule intervals once every expired timer was collected
+        // 3. poll the sleep future to determine when the next timer will expire
+        let mut timers = self.timers.borrow_mut();
+        let mut timer_data = self.timer_data.borrow_mut();
+
+        // hold tasks for deferred execution
+        let mut tasks = Vec::new();
+        let mut intervals = Vec::new();
+
+        while let Some(entry) = timers.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+
+            let id = entry.remove();
+            if timer_data[&id].timer_type == TimerType::Timeout {
+                let data = timer_data.remove(&id).expect("timer not found");
+                if data.referenced {
+                    self.ref_count.set(self.ref_count.get() - 1);
+                }
+
+                tasks.push(ExpiredTimer {
+                    id,
+                    callback: data.callback,
+                    nesting_level: data.nesting_level,
+                });
+            } else {
+                let data = timer_data.get_mut(&id).expect("timer not found");
+                tasks.push(ExpiredTimer {
+                    id,
+                    callback: data.callback.clone(),
+                    nesting_level: data.nesting_level,
+                });
+                // each repetition runs one level deeper than the previous one
+                data.duration = clamp_duration(data.duration, data.nesting_level);
+                data.nesting_level += 1;
+                intervals.push(id);
+            }
+        }
+
+        // intervals are added back after the loop, otherwise a 0ms interval
+        // could expire again in the same poll
+        for id in intervals {
+            let data = timer_data.get_mut(&id).expect("timer not found");
+            let key = self.next_key(Instant::now() + data.duration);
+            data.key = key;
+            timers.insert(key, id);
+        }
+
+        if let Some((next, _)) = timers.first_key_value() {
+            *self.waker.borrow_mut() = Some(cx.waker().clone());
+
+            // new sleep from the next expiration
+            *self.sleep.sleep.borrow_mut() = Some(sleep_until(next.0));
+
+            let pin = unsafe {
+                Pin::new_unchecked(&mut *self.sleep.sleep.borrow_mut().as_mut().unwrap())
+                    .poll(cx)
+                    .is_ready()
+            };
+
+            if pin {
+                self.waker.borrow().as_ref().unwrap().wake_by_ref();
+            }
+
+            if tasks.is_empty() {
+                return Poll::Pending;
+            }
+
+            return Poll::Ready(tasks);
+        }
+
+        Poll::Ready(tasks)
+    }
+}
+
+fn clamp_duration(duration: Duration, nesting_level: u32) -> Duration {
+    if nesting_level > MAX_NESTING_LEVEL && duration < MIN_NESTED_DURATION {
+        return MIN_NESTED_DURATION;
+    }
+
+    duration
+}
+
+impl<T> Default for BTreeTimerQueue<T>
+where
+    T: Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,142 @@
+use std::task::{Context, Poll};
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use futures::task::noop_waker_ref;
+use kedo_std::{TimerQueue, TimerType};
+use tokio::time::Duration;
+
+mod baseline;
+
+use baseline::BTreeTimerQueue;
+
+const TIMERS: usize = 10_000;
+
+/// Common surface of the queues being compared
+trait Queue: Default {
+    fn add(&self, duration: Duration) -> usize;
+    fn clear(&self, id: usize);
+    fn poll(&self, cx: &mut Context<'_>) -> usize;
+}
+
+impl Queue for TimerQueue<()> {
+    fn add(&self, duration: Duration) -> usize {
+        self.add_timer(duration, TimerType::Timeout, (), None)
+    }
+
+    fn clear(&self, id: usize) {
+        self.clear_timer(&id);
+    }
+
+    fn poll(&self, cx: &mut Context<'_>) -> usize {
+        match self.poll_timers(cx) {
+            Poll::Ready(timers) => timers.len(),
+            Poll::Pending => 0,
+        }
+    }
+}
+
+impl Queue for BTreeTimerQueue<()> {
+    fn add(&self, duration: Duration) -> usize {
+        self.add_timer(duration, baseline::TimerType::Timeout, (), None)
+    }
+
+    fn clear(&self, id: usize) {
+        self.clear_timer(&id);
+    }
+
+    fn poll(&self, cx: &mut Context<'_>) -> usize {
+        match self.poll_timers(cx) {
+            Poll::Ready(timers) => timers.len(),
+            Poll::Pending => 0,
+        }
+    }
+}
+
+/// Spread of request timeouts between 1ms and 30s
+fn durations() -> Vec<Duration> {
+    (0..TIMERS)
+        .map(|i| Duration::from_millis(1 + (i as u64 * 7919) % 30_000))
+        .collect()
+}
+
+fn filled<Q: Queue>(durations: &[Duration]) -> (Q, Vec<usize>) {
+    let queue = Q::default();
+    let ids = durations.iter().map(|d| queue.add(*d)).collect();
+    (queue, ids)
+}
+
+fn bench_queue<Q: Queue>(c: &mut Criterion, name: &str) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+    let _guard = runtime.enter();
+    let durations = durations();
+
+    let mut group = c.benchmark_group(name);
+    group.bench_function("insert", |b| {
+        b.iter_batched(
+            Q::default,
+            |queue| {
+                for duration in &durations {
+                    black_box(queue.add(*duration));
+                }
+                queue
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    // a timeout per request, cleared once the response is sent
+    group.bench_function("insert_and_clear", |b| {
+        let queue = Q::default();
+        b.iter(|| {
+            for duration in &durations {
+                let id = queue.add(*duration);
+                queue.clear(black_box(id));
+            }
+        })
+    });
+
+    group.bench_function("clear_out_of_order", |b| {
+        b.iter_batched(
+            || filled::<Q>(&durations),
+            |(queue, ids)| {
+                for id in ids.iter().step_by(2).chain(ids.iter().skip(1).step_by(2)) {
+                    queue.clear(*id);
+                }
+                queue
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.bench_function("poll_expired", |b| {
+        let mut cx = Context::from_waker(noop_waker_ref());
+        b.iter_batched(
+            || {
+                let queue = Q::default();
+                for _ in 0..TIMERS {
+                    queue.add(Duration::ZERO);
+                }
+                std::thread::sleep(Duration::from_millis(2));
+                queue
+            },
+            |queue| {
+                assert_eq!(queue.poll(&mut cx), TIMERS);
+                queue
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+fn timer_queues(c: &mut Criterion) {
+    bench_queue::<TimerQueue<()>>(c, "timer_wheel");
+    bench_queue::<BTreeTimerQueue<()>>(c, "btree_baseline");
+}
+
+criterion_group!(benches, timer_queues);
+criterion_main!(benches);
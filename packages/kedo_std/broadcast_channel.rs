@@ -0,0 +1,163 @@
+use crate::{
+    buffer_channel::{
+        BufferChannel, BufferChannelWriter, UnboundedBufferChannel,
+        UnboundedBufferChannelReader, UnboundedBufferChannelWriter,
+    },
+    SerializedValue,
+};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+};
+
+type Subscribers =
+    HashMap<String, Vec<(u64, UnboundedBufferChannelWriter<SerializedValue>)>>;
+
+/// Subscribers of every channel name in the process, workers included
+fn subscribers() -> &'static Mutex<Subscribers> {
+    static SUBSCRIBERS: OnceLock<Mutex<Subscribers>> = OnceLock::new();
+    SUBSCRIBERS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+static NEXT_SUBSCRIBER_ID: AtomicU64 = AtomicU64::new(1);
+
+/// | ------------------------------- |
+/// |       BroadcastSubscriber       |
+/// | ------------------------------- |
+/// | - id: u64                       |
+/// | - name: String                  |
+/// | - receiver                      |
+/// | ------------------------------- |
+///
+/// Subscription to a named broadcast channel, messages published by a
+/// subscriber are delivered to every other subscriber with the same name,
+/// in any thread. The subscription is closed when it's dropped.
+pub struct BroadcastSubscriber {
+    id: u64,
+    name: String,
+    receiver: Option<UnboundedBufferChannelReader<SerializedValue>>,
+}
+
+impl BroadcastSubscriber {
+    pub fn subscribe(name: &str) -> Self {
+        let mut channel = UnboundedBufferChannel::new();
+        let id = NEXT_SUBSCRIBER_ID.fetch_add(1, Ordering::Relaxed);
+        let writer = channel.acquire_writer().expect("broadcast writer");
+
+        subscribers()
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default()
+            .push((id, writer));
+
+        Self {
+            id,
+            name: name.to_string(),
+            receiver: channel.acquire_reader(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Sends the message to the other subscribers, returns how many received it
+    pub fn publish(&self, message: SerializedValue) -> usize {
+        let subscribers = subscribers().lock().unwrap();
+        let Some(channel) = subscribers.get(&self.name) else {
+            return 0;
+        };
+
+        // a closed subscriber is not in the list anymore
+        if !channel.iter().any(|(id, _)| *id == self.id) {
+            return 0;
+        }
+
+        channel
+            .iter()
+            .filter(|(id, _)| *id != self.id)
+            .filter(|(_, writer)| writer.try_write(message.clone()).is_ok())
+            .count()
+    }
+
+    /// Takes the receiver while a read is in flight, put it back with [`Self::set_receiver`]
+    pub fn take_receiver(
+        &mut self,
+    ) -> Option<UnboundedBufferChannelReader<SerializedValue>> {
+        self.receiver.take()
+    }
+
+    pub fn set_receiver(
+        &mut self,
+        receiver: UnboundedBufferChannelReader<SerializedValue>,
+    ) {
+        self.receiver = Some(receiver);
+    }
+
+    /// Stops receiving messages, a pending read returns `StreamError::Closed`
+    pub fn close(&self) {
+        let mut subscribers = subscribers().lock().unwrap();
+        if let Some(channel) = subscribers.get_mut(&self.name) {
+            channel.retain(|(id, _)| *id != self.id);
+            if channel.is_empty() {
+                subscribers.remove(&self.name);
+            }
+        }
+    }
+}
+
+impl Drop for BroadcastSubscriber {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{buffer_channel::BufferChannelReader, StreamError};
+
+    #[tokio::test]
+    async fn test_publish_to_other_subscribers() {
+        let sender = BroadcastSubscriber::subscribe("test:publish");
+        let mut first = BroadcastSubscriber::subscribe("test:publish");
+        let mut second = BroadcastSubscriber::subscribe("test:publish");
+        let mut other = BroadcastSubscriber::subscribe("test:other");
+
+        assert_eq!(sender.publish(SerializedValue::Number(1.0)), 2);
+
+        let mut receiver = first.take_receiver().unwrap();
+        assert_eq!(receiver.read().await, Ok(SerializedValue::Number(1.0)));
+        let mut receiver = second.take_receiver().unwrap();
+        assert_eq!(receiver.read().await, Ok(SerializedValue::Number(1.0)));
+        let mut receiver = other.take_receiver().unwrap();
+        assert_eq!(receiver.try_read(), Err(StreamError::Empty));
+    }
+
+    #[tokio::test]
+    async fn test_close_ends_pending_reads() {
+        let sender = BroadcastSubscriber::subscribe("test:close");
+        let mut subscriber = BroadcastSubscriber::subscribe("test:close");
+        let mut receiver = subscriber.take_receiver().unwrap();
+
+        subscriber.close();
+        assert_eq!(receiver.read().await, Err(StreamError::Closed));
+        assert_eq!(sender.publish(SerializedValue::Null), 0);
+
+        // a closed subscriber can't publish either
+        sender.close();
+        let _listener = BroadcastSubscriber::subscribe("test:close");
+        assert_eq!(sender.publish(SerializedValue::Null), 0);
+    }
+
+    #[test]
+    fn test_drop_unsubscribes() {
+        let subscriber = BroadcastSubscriber::subscribe("test:drop");
+        drop(subscriber);
+        assert!(!subscribers().lock().unwrap().contains_key("test:drop"));
+    }
+}
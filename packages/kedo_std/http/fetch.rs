@@ -74,15 +74,27 @@ impl TryFrom<&mut HttpRequest> for Request<HttpBody> {
     }
 }
 
+/// Called with the URI of every redirect before it's followed, an error fails
+/// the request, e.g. when the new host isn't allowed
+pub type RedirectCheck =
+    Arc<dyn Fn(&Uri) -> Result<(), Box<dyn Error + Send + Sync>> + Send + Sync>;
+
 pub struct PendingRequest {
     fetch_request: HttpRequest,
     in_flight: ResponseFuture,
     urls: Vec<Uri>,
     redirect_count: u32,
     client: Arc<FetchClient>,
+    redirect_check: Option<RedirectCheck>,
 }
 
 impl PendingRequest {
+    /// Checks the URI of every redirect with `check` before following it
+    pub fn redirect_check(mut self, check: RedirectCheck) -> Self {
+        self.redirect_check = Some(check);
+        self
+    }
+
     fn must_follow_redirect(&self) -> bool {
         matches!(self.fetch_request.redirect(), RequestRedirect::Follow)
         // self.fetch_request.redirect() == RequestRedirect::Follow
@@ -132,6 +144,10 @@ impl Future for PendingRequest {
                         inner: None,
                     })?;
 
+                if let Some(check) = &self.redirect_check {
+                    check(&new_uri).map_err(FetchError::from)?;
+                }
+
                 let fetch_request_mut = &mut self.fetch_request;
                 FetchClient::remove_sensitive_headers(fetch_request_mut, &new_uri);
 
@@ -224,6 +240,7 @@ impl FetchClient {
             urls,
             redirect_count: 0,
             client: Arc::new(self.clone()),
+            redirect_check: None,
         })
     }
 
@@ -311,6 +328,10 @@ mod tests {
             "/" => Ok(build_redirect_response("/1", redirect_status)),
             "/1" => Ok(build_redirect_response("/2", redirect_status)),
             "/many" => Ok(build_redirect_response("/many", redirect_status)),
+            "/external" => Ok(build_redirect_response(
+                "http://denied.example/secret",
+                redirect_status,
+            )),
             "/2" => Ok(Response::builder()
                 .status(StatusCode::OK)
                 .body(Full::from(Bytes::from_static(b"Hello, world!")))
@@ -558,6 +579,54 @@ mod tests {
         assert_eq!(format!("{}", err_data.unwrap()), "Too many redirects");
     }
 
+    #[tokio::test]
+    async fn test_fetch_redirect_check() {
+        let client = FetchClient::new();
+        let request = HttpRequestBuilder::new()
+            .method("GET")
+            .uri(Uri::from_static("http://localhost:3009/external"))
+            .body(RequestBody::None)
+            .redirect(RequestRedirect::Follow)
+            .build()
+            .unwrap();
+
+        let (tx, rx) = tokio::sync::broadcast::channel::<()>(1);
+        let server = start_test_server(handler_redirect, rx, 3009);
+
+        let checked = Arc::new(std::sync::Mutex::new(vec![]));
+        let checked_uris = checked.clone();
+        let response = client.execute(request).unwrap().redirect_check(Arc::new(
+            move |uri: &Uri| {
+                checked_uris.lock().unwrap().push(uri.to_string());
+                match uri.host() {
+                    Some("localhost") => Ok(()),
+                    host => Err(format!(
+                        "Requires net access to \"{}\"",
+                        host.unwrap_or_default()
+                    )
+                    .into()),
+                }
+            },
+        ));
+
+        let mut err_data: Option<FetchError> = None;
+        tokio::select! {
+            _ = server => {}
+            response = response => {
+                err_data = response.err();
+                let _ = tx.send(());
+            }
+        };
+
+        let err = err_data.expect("the redirect to a denied host must fail");
+        assert_eq!(err.message, "Requires net access to \"denied.example\"");
+        assert!(err.inner.is_some());
+        assert_eq!(
+            *checked.lock().unwrap(),
+            vec!["http://denied.example/secret".to_string()]
+        );
+    }
+
     async fn basic_auth_handler(
         req: Request<body::Incoming>,
     ) -> Result<Response<Full<Bytes>>, std::convert::Infallible> {
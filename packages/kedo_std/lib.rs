@@ -1,13 +1,30 @@
+mod broadcast_channel;
 mod buffer_channel;
 mod http;
+mod message_channel;
 mod net;
+mod serialized_value;
 mod timer_queue;
+mod timer_wheel;
 mod utils;
 
 // timer queue
+pub use timer_queue::timeout_duration;
+pub use timer_queue::ExpiredTimer;
+pub use timer_queue::TimerId;
 pub use timer_queue::TimerQueue;
 pub use timer_queue::TimerType;
 
+// structured clone
+pub use serialized_value::SerializedValue;
+
+// broadcast channel
+pub use broadcast_channel::BroadcastSubscriber;
+
+// message channel
+pub use message_channel::message_channel;
+pub use message_channel::MessagePortHandle;
+
 // channels
 pub use buffer_channel::BoundedBufferChannel;
 pub use buffer_channel::BoundedBufferChannelReader;
@@ -32,6 +49,7 @@ pub use http::decoder::StreamDecoder;
 pub use http::encoder::StreamEncoder;
 pub use http::errors::FetchError;
 pub use http::fetch::FetchClient;
+pub use http::fetch::RedirectCheck;
 pub use http::request::HttpRequest;
 pub use http::request::HttpRequestBuilder;
 pub use http::request::RequestBody;
@@ -0,0 +1,102 @@
+use crate::{
+    buffer_channel::{
+        BufferChannel, BufferChannelReader, BufferChannelWriter, UnboundedBufferChannel,
+        UnboundedBufferChannelReader, UnboundedBufferChannelWriter,
+    },
+    SerializedValue, StreamError,
+};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+/// | ------------------------------- |
+/// |        MessagePortHandle        |
+/// | ------------------------------- |
+/// | - sender: Option<Writer>        |
+/// | - receiver: Reader              |
+/// | - closed: Arc<Notify>           |
+/// | ------------------------------- |
+///
+/// One end of a message channel, the messages posted to a port are read
+/// from the port it's entangled with.
+pub struct MessagePortHandle {
+    sender: Option<UnboundedBufferChannelWriter<SerializedValue>>,
+    receiver: UnboundedBufferChannelReader<SerializedValue>,
+    closed: Arc<Notify>,
+}
+
+impl MessagePortHandle {
+    /// The message is dropped once either port is closed
+    pub fn post_message(&self, message: SerializedValue) -> Result<(), StreamError> {
+        match &self.sender {
+            Some(sender) => sender.try_write(message),
+            None => Err(StreamError::Closed),
+        }
+    }
+
+    /// Waits for the next message, returns `StreamError::Closed` once
+    /// this port or the entangled one is closed.
+    pub async fn recv(&mut self) -> Result<SerializedValue, StreamError> {
+        if self.sender.is_none() {
+            return Err(StreamError::Closed);
+        }
+
+        tokio::select! {
+            message = self.receiver.read() => message,
+            _ = self.closed.notified() => Err(StreamError::Closed),
+        }
+    }
+
+    /// Disentangles the port, a pending `recv` returns `StreamError::Closed`
+    pub fn close(&mut self) {
+        self.sender.take();
+        self.closed.notify_waiters();
+    }
+}
+
+/// Creates a pair of entangled ports
+pub fn message_channel() -> (MessagePortHandle, MessagePortHandle) {
+    let mut first = UnboundedBufferChannel::new();
+    let mut second = UnboundedBufferChannel::new();
+
+    let port1 = MessagePortHandle {
+        sender: first.acquire_writer(),
+        receiver: second.acquire_reader().expect("message channel reader"),
+        closed: Arc::new(Notify::new()),
+    };
+    let port2 = MessagePortHandle {
+        sender: second.acquire_writer(),
+        receiver: first.acquire_reader().expect("message channel reader"),
+        closed: Arc::new(Notify::new()),
+    };
+
+    (port1, port2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_message_channel() {
+        let (mut port1, mut port2) = message_channel();
+
+        port1.post_message(SerializedValue::Number(1.0)).unwrap();
+        port2.post_message(SerializedValue::Number(2.0)).unwrap();
+
+        assert_eq!(port2.recv().await, Ok(SerializedValue::Number(1.0)));
+        assert_eq!(port1.recv().await, Ok(SerializedValue::Number(2.0)));
+    }
+
+    #[tokio::test]
+    async fn test_close_disentangles_ports() {
+        let (mut port1, mut port2) = message_channel();
+
+        port1.close();
+        assert_eq!(port1.recv().await, Err(StreamError::Closed));
+        assert_eq!(port2.recv().await, Err(StreamError::Closed));
+        assert_eq!(
+            port1.post_message(SerializedValue::Null),
+            Err(StreamError::Closed)
+        );
+    }
+}
@@ -0,0 +1,74 @@
+/// | ------------------------------------ |
+/// |           SerializedValue            |
+/// | ------------------------------------ |
+/// | - primitives                         |
+/// | - Boxed(primitive)                   |
+/// | - Date, RegExp, Error                |
+/// | - Array { length, properties }       |
+/// | - Object(properties)                 |
+/// | - Map(entries), Set(values)          |
+/// | - ArrayBuffer(bytes)                 |
+/// | - ArrayBufferView { .. }             |
+/// | - Reference(index)                   |
+/// | ------------------------------------ |
+///
+/// Output of the structured serialize algorithm, it only holds plain Rust data
+/// so it can be sent to another thread and deserialized in another context.
+///
+/// Every object gets an index in the order it's first visited, a
+/// `Reference` points to an object serialized earlier which keeps cycles
+/// and shared references intact.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SerializedValue {
+    Undefined,
+    Null,
+    Boolean(bool),
+    Number(f64),
+    String(String),
+    /// Decimal representation of a `BigInt`
+    BigInt(String),
+    /// `Boolean`, `Number`, `String` or `BigInt` object wrapping the primitive
+    Boxed(Box<SerializedValue>),
+    /// Time value of a `Date` in milliseconds
+    Date(f64),
+    RegExp {
+        source: String,
+        flags: String,
+    },
+    /// The name is one of the native error constructors, `Error` otherwise
+    Error {
+        name: String,
+        message: Option<String>,
+        stack: Option<String>,
+    },
+    /// Holes are not part of the properties, only the length is kept
+    Array {
+        length: u32,
+        properties: Vec<(String, SerializedValue)>,
+    },
+    /// Own enumerable properties of a plain object
+    Object(Vec<(String, SerializedValue)>),
+    Map(Vec<(SerializedValue, SerializedValue)>),
+    Set(Vec<SerializedValue>),
+    ArrayBuffer(Vec<u8>),
+    /// Typed array or `DataView`, `length` is in elements for typed arrays
+    /// and in bytes for a `DataView`
+    ArrayBufferView {
+        kind: String,
+        buffer: Box<SerializedValue>,
+        byte_offset: usize,
+        length: usize,
+    },
+    Reference(u32),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialized_value_is_send() {
+        fn assert_send<T: Send + 'static>() {}
+        assert_send::<SerializedValue>();
+    }
+}
@@ -1,6 +1,5 @@
 use std::{
     cell::{Cell, RefCell},
-    collections::{BTreeMap, HashMap},
     future::Future,
     pin::Pin,
     task::{Context, Poll, Waker},
@@ -8,6 +7,8 @@ use std::{
 
 use tokio::time::{sleep_until, Duration, Instant, Sleep};
 
+use crate::timer_wheel::{TimerWheel, MAX_TICKS};
+
 pub type TimerId = usize;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -16,32 +17,69 @@ pub enum TimerType {
     Interval,
 }
 
-// TODO - !("Add CompoundedTimerKey struct to execute tasks in order")
-// #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
-// struct CompoundedTimerKey(Instant, TimerId);
+/// Timers nested deeper than this level are clamped to `MIN_NESTED_DURATION`
+/// https://html.spec.whatwg.org/multipage/timers-and-user-prompts.html#timer-initialisation-steps
+const MAX_NESTING_LEVEL: u32 = 5;
+const MIN_NESTED_DURATION: Duration = Duration::from_millis(4);
+
+/// Longest delay of a timer in ms, the delays of a signed 32 bit integer
+/// https://nodejs.org/api/timers.html#settimeoutcallback-delay-args
+const TIMEOUT_MAX: f64 = 2_147_483_647.0;
+
+/// Duration of a timer delay in ms, delays that aren't a number or are longer
+/// than `TIMEOUT_MAX` run after 1ms and negative delays run right away
+pub fn timeout_duration(delay: f64) -> Duration {
+    if delay.is_nan() || delay > TIMEOUT_MAX {
+        return Duration::from_millis(1);
+    }
+
+    Duration::from_millis(delay.max(0.0) as u64)
+}
 
-#[derive(Clone)]
-struct TimerData {
-    id: TimerId,
+/// Orders timers by deadline first and then by the order they were scheduled,
+/// so timers with the same deadline run in FIFO order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct CompoundedTimerKey(Instant, u64);
+
+struct TimerData<T> {
+    key: CompoundedTimerKey,
+    callback: T,
     duration: Duration,
     timer_type: TimerType,
-    non_blocking: bool,
+    referenced: bool,
+    nesting_level: u32,
 }
 
-// Create a mutable sleep struct
-struct SleepWrapper {
-    sleep: RefCell<Option<Sleep>>,
+/// A timer ready to run, the runtime should set `nesting_level` on the queue
+/// while the callback runs so nested timers get clamped.
+pub struct ExpiredTimer<T> {
+    pub id: TimerId,
+    pub callback: T,
+    pub nesting_level: u32,
 }
 
+/// | ------------------------------------ |
+/// |              TimerQueue              |
+/// | ------------------------------------ |
+/// | - wheel: timing wheel of 1ms ticks   |
+/// | - start: instant of tick 0           |
+/// | - sleep: reused until the next tick  |
+/// | ------------------------------------ |
+///
+/// Timers are stored in a hierarchical timing wheel so adding and clearing
+/// a timer is O(1), the expired timers of a poll are sorted by their
+/// `CompoundedTimerKey` before running.
 pub struct TimerQueue<T>
 where
     T: Clone,
 {
-    timers: RefCell<BTreeMap<Instant, Vec<TimerData>>>,
-    callback_map: RefCell<HashMap<TimerId, T>>,
+    wheel: RefCell<TimerWheel<TimerData<T>>>,
+    start: Instant,
     next_timer_id: Cell<TimerId>,
+    next_sequence: Cell<u64>,
     ref_count: Cell<usize>,
-    sleep: Box<SleepWrapper>,
+    nesting_level: Cell<u32>,
+    sleep: RefCell<Option<Pin<Box<Sleep>>>>,
     waker: RefCell<Option<Waker>>,
 }
 
@@ -56,13 +94,13 @@ where
 {
     pub fn new() -> Self {
         Self {
-            timers: RefCell::new(BTreeMap::new()),
-            callback_map: RefCell::new(HashMap::new()),
+            wheel: RefCell::new(TimerWheel::new()),
+            start: Instant::now(),
             next_timer_id: Cell::new(0),
+            next_sequence: Cell::new(0),
             ref_count: Cell::new(0),
-            sleep: Box::new(SleepWrapper {
-                sleep: RefCell::new(None),
-            }),
+            nesting_level: Cell::new(0),
+            sleep: RefCell::new(None),
             waker: RefCell::new(None),
         }
     }
@@ -74,176 +112,258 @@ where
         callback: T,
         non_blocking: Option<bool>,
     ) -> TimerId {
-        let expiration = Instant::now() + duration;
+        let nesting_level = self.nesting_level.get();
+        let duration = clamp_duration(duration, nesting_level);
+        let key = self.next_key(deadline(duration));
         let id = self.next_timer_id.get() + 1;
         self.next_timer_id.set(id);
 
-        let non_blocking_flag = non_blocking.unwrap_or(false);
-        if !non_blocking_flag {
+        let referenced = !non_blocking.unwrap_or(false);
+        if referenced {
             // ref_count is used to keep the TimerQueue alive
             self.ref_count.set(self.ref_count.get() + 1);
         }
 
-        let mut timers = self.timers.borrow_mut();
-        timers
-            .entry(expiration)
-            .or_insert_with(Vec::new)
-            .push(TimerData {
-                id,
-                timer_type,
+        let tick = self.tick_for(key.0);
+        self.wheel.borrow_mut().insert(
+            id,
+            tick,
+            TimerData {
+                key,
+                callback,
                 duration,
-                non_blocking: non_blocking_flag,
-            });
-        // not sure why rust extends the lifetime of the timers borrow
-        drop(timers);
-        self.callback_map.borrow_mut().insert(id, callback);
-        self.update_sleep_timer(expiration);
+                timer_type,
+                referenced,
+                nesting_level: nesting_level + 1,
+            },
+        );
+        self.update_sleep_timer(tick);
         id
     }
 
+    fn next_key(&self, expiration: Instant) -> CompoundedTimerKey {
+        let sequence = self.next_sequence.get();
+        self.next_sequence.set(sequence + 1);
+        CompoundedTimerKey(expiration, sequence)
+    }
+
+    /// Ticks are rounded up so timers never run before their deadline
+    fn tick_for(&self, deadline: Instant) -> u64 {
+        let elapsed = deadline.saturating_duration_since(self.start);
+        let ticks = elapsed.as_millis() as u64;
+        if elapsed > Duration::from_millis(ticks) {
+            ticks + 1
+        } else {
+            ticks
+        }
+    }
+
+    fn now_tick(&self) -> u64 {
+        Instant::now()
+            .saturating_duration_since(self.start)
+            .as_millis() as u64
+    }
+
     fn wake(&self) {
         if let Some(waker) = self.waker.borrow().as_ref() {
             waker.wake_by_ref();
         }
     }
 
-    fn update_sleep_timer(&self, new_expiration: Instant) {
-        let mut sleep = self.sleep.sleep.borrow_mut();
-        let should_update = match &*sleep {
-            Some(existing_sleep) => new_expiration < existing_sleep.deadline(),
+    fn update_sleep_timer(&self, tick: u64) {
+        let expiration = self.start + Duration::from_millis(tick);
+        // the sleep is reset on the next poll
+        let should_wake = match self.sleep.borrow().as_ref() {
+            Some(sleep) => expiration < sleep.deadline() || sleep.is_elapsed(),
             None => true,
         };
 
-        if should_update {
-            *sleep = Some(sleep_until(new_expiration));
+        if should_wake {
             self.wake();
-        } else {
-            // wake if sleep deadline is already expired
-            if let Some(sleep) = &*sleep {
-                if sleep.deadline() <= Instant::now() {
-                    self.wake();
-                }
-            }
         }
     }
 
     pub fn clear_timer(&self, id: &TimerId) -> Option<T> {
-        // TODO: Removing the timeout from the callback map will prevent the callback from being called
-        // but the timer will still be in the timers map
-        // this will cause the event loop to keep polling the timer until it is removed
-        let callable = match self.callback_map.borrow_mut().remove(id) {
-            Some(callback) => {
-                // Todo: we need to unprotect the callback after calling it
-                // callback.callable.unprotect();
+        // dropping the returned callback releases the JS function
+        let data = self.wheel.borrow_mut().remove(id)?;
+        if data.referenced {
+            self.ref_count.set(self.ref_count.get() - 1);
+        }
+
+        Some(data.callback)
+    }
+
+    /// Removes every timer, e.g. when the runtime shuts down
+    pub fn clear(&self) {
+        // dropping the callbacks releases the JS functions
+        self.wheel.borrow_mut().clear();
+        self.ref_count.set(0);
+        self.sleep.borrow_mut().take();
+    }
+
+    /// Marks the timer as referenced or not, unreferenced timers don't keep
+    /// the event loop alive. Returns false if the timer doesn't exist.
+    pub fn set_ref(&self, id: &TimerId, referenced: bool) -> bool {
+        let mut wheel = self.wheel.borrow_mut();
+        let Some(data) = wheel.get_mut(id) else {
+            return false;
+        };
+
+        if data.referenced != referenced {
+            data.referenced = referenced;
+            if referenced {
+                self.ref_count.set(self.ref_count.get() + 1);
+            } else {
                 self.ref_count.set(self.ref_count.get() - 1);
-                Some(callback)
             }
-            None => None,
+        }
+
+        true
+    }
+
+    /// True while the timer is scheduled, a timeout is removed once it expires
+    pub fn contains(&self, id: &TimerId) -> bool {
+        self.wheel.borrow().get(id).is_some()
+    }
+
+    pub fn has_ref(&self, id: &TimerId) -> bool {
+        self.wheel
+            .borrow()
+            .get(id)
+            .map(|data| data.referenced)
+            .unwrap_or(false)
+    }
+
+    /// Restarts the timer with its original duration from now,
+    /// returns false if the timer already ran or was cleared.
+    pub fn refresh(&self, id: &TimerId) -> bool {
+        let mut wheel = self.wheel.borrow_mut();
+        let Some(data) = wheel.get_mut(id) else {
+            return false;
         };
 
-        // remove the timer from the timers map
-        let mut timers = self.timers.borrow_mut();
-        for (_, timer_data) in timers.iter_mut() {
-            timer_data.retain(|timer| timer.id != *id);
-        }
+        let key = self.next_key(deadline(data.duration));
+        data.key = key;
+        let tick = self.tick_for(key.0);
+        wheel.reschedule(id, tick);
+
+        drop(wheel);
+        self.update_sleep_timer(tick);
+        true
+    }
 
-        callable
+    /// Nesting level of the timer callback currently running, 0 outside timers
+    pub fn set_nesting_level(&self, level: u32) {
+        self.nesting_level.set(level);
+    }
+
+    pub fn nesting_level(&self) -> u32 {
+        self.nesting_level.get()
     }
 
     pub fn is_empty(&self) -> bool {
-        // check if the timers map is empty or the ref_count is 0
-        self.timers.borrow().is_empty() || self.ref_count.get() == 0
+        // only referenced timers keep the queue alive
+        self.ref_count.get() == 0
     }
 
     pub fn len(&self) -> usize {
-        self.timers.borrow().len()
+        self.wheel.borrow().len()
     }
 
-    pub fn poll_timers(&self, cx: &mut Context<'_>) -> Poll<Vec<T>> {
+    pub fn poll_timers(&self, cx: &mut Context<'_>) -> Poll<Vec<ExpiredTimer<T>>> {
         // early return if there are no timers
-        if self.timers.borrow().is_empty() {
+        if self.wheel.borrow().is_empty() {
             return Poll::Ready(Vec::new());
         }
 
-        let now = Instant::now();
-        // 1. check for ready timers and remove them after calling their callbacks
-        // 2. poll the sleep future to determine when the next timer will expire
-        // 3. if all timers are expired, return Poll::Ready(()), otherwise return Poll::Pending
-        let mut timers = self.timers.borrow_mut();
-        let keys: Vec<_> = timers.keys().cloned().take_while(|&k| k <= now).collect();
+        let now = self.now_tick();
+        // 1. drain the expired timers from the wheel
+        // 2. reschedule intervals once every expired timer was collected
+        // 3. sleep until the next tick of the wheel
+        let mut wheel = self.wheel.borrow_mut();
 
         // hold tasks for deferred execution
         let mut tasks = Vec::new();
+        let mut intervals = Vec::new();
 
-        for key in keys {
-            // 1. remove timer and callbacks from the map if timer type is Timeout
-            // check if the timer type is Timeout before removing it dont call the remove method
-            // get the timer data from the timers map
-            let data = timers.remove(&key).expect("timer not found");
-            let mut ref_counter = self.ref_count.get();
-
-            for timer in data {
-                // remove the callback from the callback map if the timer type is Timeout
-                if timer.timer_type == TimerType::Timeout {
-                    if let Some(callback) =
-                        self.callback_map.borrow_mut().remove(&timer.id)
-                    {
-                        // Todo: we need to unprotect the callback after calling it
-                        // callback.callable.unprotect();
-                        tasks.push(callback);
-                        // if timer is not non-blocking, decrement the ref_counter
-                        if !timer.non_blocking {
-                            ref_counter = ref_counter - 1;
-                        }
-                    }
-                } else {
-                    // if the timer type is Interval, add the timer back to the timers map
-                    // with a new expiration time
-                    let expiration = Instant::now() + timer.duration;
-                    let timer_id = timer.id;
-                    if let Some(callback) = self.callback_map.borrow_mut().get(&timer_id)
-                    {
-                        timers
-                            .entry(expiration)
-                            .or_insert_with(Vec::new)
-                            .push(timer);
-                        tasks.push(callback.clone());
-                    }
+        while let Some((id, mut data)) = wheel.pop_expired(now) {
+            let key = data.key;
+            if data.timer_type == TimerType::Timeout {
+                if data.referenced {
+                    self.ref_count.set(self.ref_count.get() - 1);
+                }
+
+                let timer = ExpiredTimer {
+                    id,
+                    callback: data.callback,
+                    nesting_level: data.nesting_level,
+                };
+                tasks.push((key, timer));
+            } else {
+                let timer = ExpiredTimer {
+                    id,
+                    callback: data.callback.clone(),
+                    nesting_level: data.nesting_level,
                 };
+                tasks.push((key, timer));
+                // each repetition runs one level deeper than the previous one
+                data.duration = clamp_duration(data.duration, data.nesting_level);
+                data.nesting_level += 1;
+                intervals.push((id, data));
             }
-            // update ref_count
-            self.ref_count.set(ref_counter);
         }
 
-        if !timers.is_empty() {
-            *self.waker.borrow_mut() = Some(cx.waker().clone());
+        // intervals are added back after the loop, otherwise a 0ms interval
+        // could expire again in the same poll
+        for (id, mut data) in intervals {
+            data.key = self.next_key(deadline(data.duration));
+            let tick = self.tick_for(data.key.0);
+            wheel.insert(id, tick, data);
+        }
 
-            let next_expiration = timers.keys().next().unwrap();
+        // timers sharing a tick run in deadline and scheduling order
+        tasks.sort_by_key(|(key, _)| *key);
+        let tasks: Vec<_> = tasks.into_iter().map(|(_, timer)| timer).collect();
 
-            // new sleep from the next expiration
-            *self.sleep.sleep.borrow_mut() = Some(sleep_until(*next_expiration));
+        if let Some(next) = wheel.next_deadline() {
+            *self.waker.borrow_mut() = Some(cx.waker().clone());
 
-            let pin = unsafe {
-                Pin::new_unchecked(&mut *self.sleep.sleep.borrow_mut().as_mut().unwrap())
-                    .poll(cx)
-                    .is_ready()
-            };
+            let expiration = self.start + Duration::from_millis(next);
+            let mut sleep = self.sleep.borrow_mut();
+            let sleep = sleep.get_or_insert_with(|| Box::pin(sleep_until(expiration)));
+            if sleep.deadline() != expiration {
+                sleep.as_mut().reset(expiration);
+            }
 
-            if pin {
-                self.waker.borrow().as_ref().unwrap().wake_by_ref();
+            if sleep.as_mut().poll(cx).is_ready() {
+                cx.waker().wake_by_ref();
             }
 
             if tasks.is_empty() {
                 return Poll::Pending;
             }
-
-            return Poll::Ready(tasks);
         }
 
         Poll::Ready(tasks)
     }
 }
 
+/// Deadline of a timer started now, the wheel can't hold timers past
+/// `MAX_TICKS` so longer durations don't overflow `Instant`
+fn deadline(duration: Duration) -> Instant {
+    let now = Instant::now();
+    now.checked_add(duration)
+        .unwrap_or_else(|| now + Duration::from_millis(MAX_TICKS))
+}
+
+fn clamp_duration(duration: Duration, nesting_level: u32) -> Duration {
+    if nesting_level > MAX_NESTING_LEVEL && duration < MIN_NESTED_DURATION {
+        return MIN_NESTED_DURATION;
+    }
+
+    duration
+}
+
 impl<T> Default for TimerQueue<T>
 where
     T: Clone,
@@ -252,3 +372,165 @@ where
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::task::noop_waker_ref;
+
+    fn expired_ids(queue: &TimerQueue<&'static str>) -> Vec<TimerId> {
+        let mut cx = Context::from_waker(noop_waker_ref());
+        match queue.poll_timers(&mut cx) {
+            Poll::Ready(timers) => timers.into_iter().map(|timer| timer.id).collect(),
+            Poll::Pending => Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timers_run_in_order() {
+        let queue = TimerQueue::new();
+        let late =
+            queue.add_timer(Duration::from_millis(5), TimerType::Timeout, "late", None);
+        let first = queue.add_timer(Duration::ZERO, TimerType::Timeout, "first", None);
+        let second = queue.add_timer(Duration::ZERO, TimerType::Timeout, "second", None);
+        let third = queue.add_timer(Duration::ZERO, TimerType::Timeout, "third", None);
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(expired_ids(&queue), vec![first, second, third, late]);
+        assert!(queue.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_contains() {
+        let queue = TimerQueue::new();
+        let timeout =
+            queue.add_timer(Duration::ZERO, TimerType::Timeout, "timeout", None);
+        let interval =
+            queue.add_timer(Duration::ZERO, TimerType::Interval, "interval", None);
+        assert!(queue.contains(&timeout));
+
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(expired_ids(&queue), vec![timeout, interval]);
+        // the interval is scheduled again once it expired
+        assert!(!queue.contains(&timeout));
+        assert!(queue.contains(&interval));
+    }
+
+    #[tokio::test]
+    async fn test_clear_timer() {
+        let queue = TimerQueue::new();
+        let id = queue.add_timer(Duration::ZERO, TimerType::Interval, "interval", None);
+
+        assert_eq!(queue.clear_timer(&id), Some("interval"));
+        assert_eq!(queue.clear_timer(&id), None);
+        assert_eq!(queue.len(), 0);
+        assert!(queue.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_clear() {
+        let queue = TimerQueue::new();
+        queue.add_timer(Duration::ZERO, TimerType::Interval, "interval", None);
+        queue.add_timer(Duration::from_secs(1), TimerType::Timeout, "timeout", None);
+        queue.clear();
+        assert_eq!(queue.len(), 0);
+        assert!(queue.is_empty());
+
+        let id = queue.add_timer(Duration::ZERO, TimerType::Timeout, "after", None);
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(expired_ids(&queue), vec![id]);
+    }
+
+    #[tokio::test]
+    async fn test_ref_and_unref() {
+        let queue = TimerQueue::new();
+        let id =
+            queue.add_timer(Duration::from_secs(1), TimerType::Timeout, "timer", None);
+        assert!(queue.has_ref(&id));
+
+        assert!(queue.set_ref(&id, false));
+        assert!(!queue.has_ref(&id));
+        assert!(queue.is_empty());
+        assert_eq!(queue.len(), 1);
+
+        assert!(queue.set_ref(&id, true));
+        assert!(queue.set_ref(&id, true));
+        assert!(!queue.is_empty());
+
+        queue.clear_timer(&id);
+        assert!(queue.is_empty());
+        assert!(!queue.set_ref(&id, true));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_moves_timer_to_the_back() {
+        let queue = TimerQueue::new();
+        let first = queue.add_timer(Duration::ZERO, TimerType::Timeout, "first", None);
+        let second = queue.add_timer(Duration::ZERO, TimerType::Timeout, "second", None);
+        assert!(queue.refresh(&first));
+
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(expired_ids(&queue), vec![second, first]);
+        assert!(!queue.refresh(&first));
+    }
+
+    #[test]
+    fn test_timeout_duration() {
+        assert_eq!(timeout_duration(10.5), Duration::from_millis(10));
+        assert_eq!(timeout_duration(-5.0), Duration::ZERO);
+        assert_eq!(
+            timeout_duration(TIMEOUT_MAX),
+            Duration::from_millis(2_147_483_647)
+        );
+        for delay in [f64::INFINITY, 1e20, TIMEOUT_MAX + 1.0, f64::NAN] {
+            assert_eq!(timeout_duration(delay), Duration::from_millis(1));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_durations_past_instant() {
+        let queue = TimerQueue::new();
+        let id = queue.add_timer(Duration::MAX, TimerType::Interval, "far", None);
+        assert!(queue.refresh(&id));
+        assert!(queue.contains(&id));
+        assert_eq!(expired_ids(&queue), Vec::<TimerId>::new());
+    }
+
+    #[tokio::test]
+    async fn test_nested_timers_are_clamped() {
+        let queue = TimerQueue::new();
+        queue.set_nesting_level(MAX_NESTING_LEVEL);
+        let shallow =
+            queue.add_timer(Duration::ZERO, TimerType::Timeout, "shallow", None);
+        queue.set_nesting_level(MAX_NESTING_LEVEL + 1);
+        let nested = queue.add_timer(Duration::ZERO, TimerType::Timeout, "nested", None);
+        queue.set_nesting_level(0);
+
+        let wheel = queue.wheel.borrow();
+        assert_eq!(wheel.get(&shallow).unwrap().duration, Duration::ZERO);
+        assert_eq!(wheel.get(&nested).unwrap().duration, MIN_NESTED_DURATION);
+        assert_eq!(
+            wheel.get(&nested).unwrap().nesting_level,
+            MAX_NESTING_LEVEL + 2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_interval_nesting_level() {
+        let queue = TimerQueue::new();
+        let id = queue.add_timer(Duration::ZERO, TimerType::Interval, "interval", None);
+
+        let mut cx = Context::from_waker(noop_waker_ref());
+        for level in 1..=MAX_NESTING_LEVEL + 2 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            let Poll::Ready(timers) = queue.poll_timers(&mut cx) else {
+                panic!("interval should have expired");
+            };
+            assert_eq!(timers.len(), 1);
+            assert_eq!(timers[0].nesting_level, level);
+        }
+
+        let duration = queue.wheel.borrow().get(&id).unwrap().duration;
+        assert_eq!(duration, MIN_NESTED_DURATION);
+    }
+}
@@ -0,0 +1,390 @@
+use std::{
+    collections::HashMap,
+    hash::{BuildHasherDefault, Hasher},
+};
+
+use crate::timer_queue::TimerId;
+
+const LEVELS: usize = 6;
+const SLOT_BITS: u32 = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+const SLOT_MASK: u64 = SLOTS as u64 - 1;
+/// List holding the timers that already expired, after the wheel slots
+const PENDING: usize = LEVELS * SLOTS;
+
+/// Furthest tick the wheel can hold relative to the last poll,
+/// ~2 years with 1ms ticks, later timers are clamped to it.
+pub(crate) const MAX_TICKS: u64 = (1 << (SLOT_BITS * LEVELS as u32)) - 1;
+
+/// Timer ids are sequential integers, a multiplicative hash spreads them
+/// without paying for SipHash on every insert and clear.
+#[derive(Default)]
+struct TimerIdHasher(u64);
+
+impl Hasher for TimerIdHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0.rotate_left(8) ^ *byte as u64)
+                .wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        }
+    }
+
+    fn write_usize(&mut self, id: usize) {
+        self.0 = (id as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    }
+}
+
+type TimerIdMap = HashMap<TimerId, usize, BuildHasherDefault<TimerIdHasher>>;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct List {
+    head: Option<usize>,
+    tail: Option<usize>,
+}
+
+struct Entry<V> {
+    id: TimerId,
+    value: V,
+    when: u64,
+    list: usize,
+    prev: Option<usize>,
+    next: Option<usize>,
+}
+
+/// | ------------------------------------ |
+/// |              TimerWheel              |
+/// | ------------------------------------ |
+/// | - elapsed: last tick processed       |
+/// | - keys: id -> slab key               |
+/// | - slab: entries and free keys        |
+/// | - lists: 6 levels x 64 slots         |
+/// | - occupied: slot bitmap per level    |
+/// | ------------------------------------ |
+///
+/// Hierarchical timing wheel, level `n` slots cover `64^n` ticks. Every slot
+/// is a doubly linked list of slab keys so insert and remove are O(1), timers
+/// cascade to lower levels as their slot comes up.
+pub(crate) struct TimerWheel<V> {
+    elapsed: u64,
+    keys: TimerIdMap,
+    slab: Vec<Option<Entry<V>>>,
+    free: Vec<usize>,
+    lists: Vec<List>,
+    occupied: [u64; LEVELS],
+}
+
+impl<V> TimerWheel<V> {
+    pub fn new() -> Self {
+        Self {
+            elapsed: 0,
+            keys: TimerIdMap::default(),
+            slab: Vec::new(),
+            free: Vec::new(),
+            lists: vec![List::default(); PENDING + 1],
+            occupied: [0; LEVELS],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn get(&self, id: &TimerId) -> Option<&V> {
+        let key = *self.keys.get(id)?;
+        Some(&self.entry(key).value)
+    }
+
+    pub fn get_mut(&mut self, id: &TimerId) -> Option<&mut V> {
+        let key = *self.keys.get(id)?;
+        Some(&mut self.entry_mut(key).value)
+    }
+
+    /// Schedules the timer at tick `when`, elapsed ticks expire on the next poll
+    pub fn insert(&mut self, id: TimerId, when: u64, value: V) {
+        let when = when.min(self.elapsed + MAX_TICKS);
+        let list = self.list_for(when);
+        let entry = Entry {
+            id,
+            value,
+            when,
+            list,
+            prev: None,
+            next: None,
+        };
+
+        let key = match self.free.pop() {
+            Some(key) => {
+                self.slab[key] = Some(entry);
+                key
+            }
+            None => {
+                self.slab.push(Some(entry));
+                self.slab.len() - 1
+            }
+        };
+
+        if let Some(previous) = self.keys.insert(id, key) {
+            self.release(previous);
+        }
+        self.push_back(list, key);
+    }
+
+    /// Removes every timer, the elapsed tick is kept so new timers are
+    /// scheduled from the current tick
+    pub fn clear(&mut self) {
+        self.keys.clear();
+        self.slab.clear();
+        self.free.clear();
+        self.lists = vec![List::default(); PENDING + 1];
+        self.occupied = [0; LEVELS];
+    }
+
+    pub fn remove(&mut self, id: &TimerId) -> Option<V> {
+        let key = self.keys.remove(id)?;
+        Some(self.release(key))
+    }
+
+    /// Moves the timer to tick `when`, returns false if it doesn't exist
+    pub fn reschedule(&mut self, id: &TimerId, when: u64) -> bool {
+        let Some(&key) = self.keys.get(id) else {
+            return false;
+        };
+
+        self.unlink(key);
+        let when = when.min(self.elapsed + MAX_TICKS);
+        let list = self.list_for(when);
+        self.entry_mut(key).when = when;
+        self.push_back(list, key);
+        true
+    }
+
+    /// Removes the next timer expired at tick `now`,
+    /// call it until it returns `None` to drain every expired timer.
+    pub fn pop_expired(&mut self, now: u64) -> Option<(TimerId, V)> {
+        loop {
+            if let Some(key) = self.lists[PENDING].head {
+                let id = self.entry(key).id;
+                self.keys.remove(&id);
+                return Some((id, self.release(key)));
+            }
+
+            match self.next_expiration() {
+                Some((level, slot, deadline)) if deadline <= now => {
+                    self.elapsed = deadline;
+                    self.cascade(level, slot);
+                }
+                _ => {
+                    self.elapsed = self.elapsed.max(now);
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Tick at which the wheel has to be polled again, the deadline of a
+    /// higher level slot might come before its timers expire.
+    pub fn next_deadline(&self) -> Option<u64> {
+        if self.lists[PENDING].head.is_some() {
+            return Some(self.elapsed);
+        }
+
+        self.next_expiration().map(|(_, _, deadline)| deadline)
+    }
+
+    fn next_expiration(&self) -> Option<(usize, usize, u64)> {
+        // lower levels always expire before the next slot of a higher level
+        for level in 0..LEVELS {
+            let occupied = self.occupied[level];
+            if occupied == 0 {
+                continue;
+            }
+
+            let slot_range = 1u64 << (level as u32 * SLOT_BITS);
+            let level_range = slot_range << SLOT_BITS;
+            let now_slot = ((self.elapsed / slot_range) & SLOT_MASK) as u32;
+            let slot = (occupied.rotate_right(now_slot).trailing_zeros() + now_slot)
+                as usize
+                % SLOTS;
+
+            let level_start = self.elapsed & !(level_range - 1);
+            let mut deadline = level_start + slot as u64 * slot_range;
+            if deadline <= self.elapsed {
+                // the slot wrapped around the top level
+                deadline += level_range;
+            }
+
+            return Some((level, slot, deadline));
+        }
+
+        None
+    }
+
+    /// Moves the timers of a slot that came up to a lower level or to pending
+    fn cascade(&mut self, level: usize, slot: usize) {
+        let index = level * SLOTS + slot;
+        let mut next = self.lists[index].head;
+        self.lists[index] = List::default();
+        self.occupied[level] &= !(1 << slot);
+
+        while let Some(key) = next {
+            next = self.entry(key).next;
+            let list = self.list_for(self.entry(key).when);
+            self.push_back(list, key);
+        }
+    }
+
+    fn list_for(&self, when: u64) -> usize {
+        if when <= self.elapsed {
+            return PENDING;
+        }
+
+        let level = level_for(self.elapsed, when);
+        let slot = ((when >> (level as u32 * SLOT_BITS)) & SLOT_MASK) as usize;
+        level * SLOTS + slot
+    }
+
+    fn entry(&self, key: usize) -> &Entry<V> {
+        self.slab[key].as_ref().expect("timer not found")
+    }
+
+    fn entry_mut(&mut self, key: usize) -> &mut Entry<V> {
+        self.slab[key].as_mut().expect("timer not found")
+    }
+
+    /// Unlinks the entry and frees its slab key
+    fn release(&mut self, key: usize) -> V {
+        self.unlink(key);
+        let entry = self.slab[key].take().expect("timer not found");
+        self.free.push(key);
+        entry.value
+    }
+
+    fn push_back(&mut self, list: usize, key: usize) {
+        let tail = self.lists[list].tail;
+        match tail {
+            Some(tail) => self.entry_mut(tail).next = Some(key),
+            None => self.lists[list].head = Some(key),
+        }
+
+        let entry = self.entry_mut(key);
+        entry.list = list;
+        entry.prev = tail;
+        entry.next = None;
+        self.lists[list].tail = Some(key);
+
+        if list != PENDING {
+            self.occupied[list / SLOTS] |= 1 << (list % SLOTS);
+        }
+    }
+
+    fn unlink(&mut self, key: usize) {
+        let entry = self.entry(key);
+        let (list, prev, next) = (entry.list, entry.prev, entry.next);
+
+        match prev {
+            Some(prev) => self.entry_mut(prev).next = next,
+            None => self.lists[list].head = next,
+        }
+
+        match next {
+            Some(next) => self.entry_mut(next).prev = prev,
+            None => self.lists[list].tail = prev,
+        }
+
+        if list != PENDING && self.lists[list].head.is_none() {
+            self.occupied[list / SLOTS] &= !(1 << (list % SLOTS));
+        }
+    }
+}
+
+/// Level whose slots tell `elapsed` and `when` apart
+fn level_for(elapsed: u64, when: u64) -> usize {
+    let masked = (elapsed ^ when) | SLOT_MASK;
+    let significant = 63 - masked.leading_zeros() as usize;
+    (significant / SLOT_BITS as usize).min(LEVELS - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(wheel: &mut TimerWheel<&'static str>, now: u64) -> Vec<&'static str> {
+        let mut expired = Vec::new();
+        while let Some((_, value)) = wheel.pop_expired(now) {
+            expired.push(value);
+        }
+        expired
+    }
+
+    #[test]
+    fn test_level_for() {
+        assert_eq!(level_for(0, 1), 0);
+        assert_eq!(level_for(0, 63), 0);
+        assert_eq!(level_for(0, 64), 1);
+        assert_eq!(level_for(60, 70), 1);
+        assert_eq!(level_for(0, 4096), 2);
+        assert_eq!(level_for(0, u64::MAX), LEVELS - 1);
+    }
+
+    #[test]
+    fn test_timers_cascade_across_levels() {
+        let mut wheel = TimerWheel::new();
+        wheel.insert(1, 5, "level 0");
+        wheel.insert(2, 100, "level 1");
+        wheel.insert(3, 5_000, "level 2");
+        wheel.insert(4, 300_000, "level 3");
+
+        assert_eq!(drain(&mut wheel, 4), Vec::<&str>::new());
+        assert_eq!(drain(&mut wheel, 5), vec!["level 0"]);
+        assert_eq!(drain(&mut wheel, 99), Vec::<&str>::new());
+        assert_eq!(drain(&mut wheel, 4_999), vec!["level 1"]);
+        assert_eq!(drain(&mut wheel, 299_999), vec!["level 2"]);
+        assert_eq!(drain(&mut wheel, 300_000), vec!["level 3"]);
+        assert!(wheel.is_empty());
+        assert_eq!(wheel.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_remove_and_reschedule() {
+        let mut wheel = TimerWheel::new();
+        wheel.insert(1, 10, "first");
+        wheel.insert(2, 10, "second");
+        wheel.insert(3, 10, "third");
+
+        assert_eq!(wheel.remove(&2), Some("second"));
+        assert_eq!(wheel.remove(&2), None);
+        assert!(wheel.reschedule(&1, 20));
+        assert!(!wheel.reschedule(&2, 20));
+
+        assert_eq!(drain(&mut wheel, 10), vec!["third"]);
+        assert_eq!(wheel.next_deadline(), Some(20));
+        assert_eq!(drain(&mut wheel, 20), vec!["first"]);
+    }
+
+    #[test]
+    fn test_elapsed_ticks_expire_on_next_poll() {
+        let mut wheel = TimerWheel::new();
+        assert_eq!(drain(&mut wheel, 50), Vec::<&str>::new());
+
+        wheel.insert(1, 10, "late");
+        wheel.insert(2, 50, "now");
+        assert_eq!(wheel.next_deadline(), Some(50));
+        assert_eq!(drain(&mut wheel, 50), vec!["late", "now"]);
+    }
+
+    #[test]
+    fn test_far_timers_are_clamped() {
+        let mut wheel = TimerWheel::new();
+        wheel.insert(1, u64::MAX, "far");
+        assert_eq!(drain(&mut wheel, MAX_TICKS - 1), Vec::<&str>::new());
+        assert_eq!(drain(&mut wheel, MAX_TICKS), vec!["far"]);
+    }
+}
@@ -1,13 +1,85 @@
 use kedo_core::{downcast_state, JsProctectedCallable};
-use kedo_utils::define_globals;
+use kedo_std::{timeout_duration, TimerId, TimerType};
+use kedo_utils::JSGlobalObject;
 use rust_jsc::{callback, JSContext, JSFunction, JSObject, JSResult, JSValue};
-use tokio::time::Duration;
+
+/// Installs the timer globals on top of the native timer functions.
+/// Timers are returned as `Timeout` handles, they convert to their numeric id
+/// so `clearTimeout(Number(timer))` and `+timer` keep working.
+const TIMERS_SOURCE: &str = r#"
+(function (ops) {
+    "use strict";
+
+    class Timeout {
+        #id;
+
+        constructor(id) {
+            this.#id = id;
+        }
+
+        ref() {
+            ops.ref(this.#id, true);
+            return this;
+        }
+
+        unref() {
+            ops.ref(this.#id, false);
+            return this;
+        }
+
+        hasRef() {
+            return ops.hasRef(this.#id);
+        }
+
+        refresh() {
+            ops.refresh(this.#id);
+            return this;
+        }
+
+        [Symbol.toPrimitive]() {
+            return this.#id;
+        }
+    }
+
+    function start(callback, delay, repeat, args) {
+        if (typeof callback !== "function") {
+            throw new TypeError("The callback must be a function");
+        }
+
+        // the delay is clamped by the native timers
+        return new Timeout(ops.start(callback, delay, repeat, ...args));
+    }
+
+    function clear(timer) {
+        const id = Number(timer);
+        if (id > 0) ops.clear(id);
+    }
+
+    const globals = {
+        setTimeout: (callback, delay, ...args) => start(callback, delay, false, args),
+        setInterval: (callback, delay, ...args) => start(callback, delay, true, args),
+        clearTimeout: (timer) => clear(timer),
+        clearInterval: (timer) => clear(timer),
+    };
+
+    for (const [name, value] of Object.entries(globals)) {
+        Object.defineProperty(value, "name", { value: name });
+        Object.defineProperty(globalThis, name, {
+            value,
+            writable: false,
+            configurable: true,
+            enumerable: false,
+        });
+    }
+})
+"#;
 
 pub struct Timer {}
 
 impl Timer {
+    /// start(callback, delay, repeat, ...args) -> id
     #[callback]
-    fn set_timeout(
+    fn start(
         ctx: JSContext,
         _: JSObject,
         __: JSObject,
@@ -16,58 +88,98 @@ impl Timer {
         let state = downcast_state(&ctx);
         let function = args[0].as_object()?;
         let function = JSFunction::from(function);
-        let timeout = args[1].as_number()? as u64;
-        let args = args[2..].to_vec();
-        function.protect();
+        let timeout = timeout_duration(args[1].as_number()?);
+        let timer_type = if args[2].as_boolean() {
+            TimerType::Interval
+        } else {
+            TimerType::Timeout
+        };
+        let args = args[3..].to_vec();
+        let resource = state.track_resource("timer");
         let id = state.timers().add_timer(
-            Duration::from_millis(timeout),
-            kedo_std::TimerType::Timeout,
-            JsProctectedCallable::new(function, args),
+            timeout,
+            timer_type,
+            JsProctectedCallable::with_resource(function, args, resource),
             None,
         );
         Ok(JSValue::number(&ctx, id as f64))
     }
 
     #[callback]
-    fn set_interval(
+    fn clear(
         ctx: JSContext,
         _: JSObject,
         __: JSObject,
         args: &[JSValue],
     ) -> JSResult<JSValue> {
         let state = downcast_state(&ctx);
-        let function = args[0].as_object()?;
-        let function = JSFunction::from(function);
-        let timeout = args[1].as_number()? as u64;
-        let args = args[2..].to_vec();
-        function.protect();
-        let id = state.timers().add_timer(
-            Duration::from_millis(timeout),
-            kedo_std::TimerType::Interval,
-            JsProctectedCallable::new(function, args),
-            None,
-        );
-        Ok(JSValue::number(&ctx, id as f64))
+        let timer_id = args[0].as_number()? as TimerId;
+        // dropping the callback unprotects the function and closes the resource
+        let _ = state.timers().clear_timer(&timer_id);
+        Ok(JSValue::undefined(&ctx))
     }
 
+    /// ref(id, referenced)
     #[callback]
-    fn clear_timer(
+    fn set_ref(
         ctx: JSContext,
         _: JSObject,
         __: JSObject,
         args: &[JSValue],
     ) -> JSResult<JSValue> {
         let state = downcast_state(&ctx);
-        let timer_id = args[0].as_number()? as usize;
-        let _ = state.timers().clear_timer(&timer_id);
+        let timer_id = args[0].as_number()? as TimerId;
+        let referenced = args[1].as_boolean();
+        state.timers().set_ref(&timer_id, referenced);
+        Ok(JSValue::undefined(&ctx))
+    }
+
+    #[callback]
+    fn has_ref(
+        ctx: JSContext,
+        _: JSObject,
+        __: JSObject,
+        args: &[JSValue],
+    ) -> JSResult<JSValue> {
+        let state = downcast_state(&ctx);
+        let timer_id = args[0].as_number()? as TimerId;
+        let referenced = state.timers().has_ref(&timer_id);
+        Ok(JSValue::boolean(&ctx, referenced))
+    }
+
+    #[callback]
+    fn refresh(
+        ctx: JSContext,
+        _: JSObject,
+        __: JSObject,
+        args: &[JSValue],
+    ) -> JSResult<JSValue> {
+        let state = downcast_state(&ctx);
+        let timer_id = args[0].as_number()? as TimerId;
+        state.timers().refresh(&timer_id);
         Ok(JSValue::undefined(&ctx))
     }
 }
 
-define_globals!(
-    Timer,
-    setTimeout => Timer::set_timeout,
-    setInterval => Timer::set_interval,
-    clearTimeout => Timer::clear_timer,
-    clearInterval => Timer::clear_timer
-);
+impl JSGlobalObject for Timer {
+    fn init_globals(ctx: &JSContext) -> JSResult<()> {
+        let ops = JSObject::new(ctx);
+
+        let start = JSFunction::callback(ctx, Some("start"), Some(Timer::start));
+        let clear = JSFunction::callback(ctx, Some("clear"), Some(Timer::clear));
+        let set_ref = JSFunction::callback(ctx, Some("ref"), Some(Timer::set_ref));
+        let has_ref = JSFunction::callback(ctx, Some("hasRef"), Some(Timer::has_ref));
+        let refresh = JSFunction::callback(ctx, Some("refresh"), Some(Timer::refresh));
+
+        ops.set_property("start", &start, Default::default())?;
+        ops.set_property("clear", &clear, Default::default())?;
+        ops.set_property("ref", &set_ref, Default::default())?;
+        ops.set_property("hasRef", &has_ref, Default::default())?;
+        ops.set_property("refresh", &refresh, Default::default())?;
+
+        let install = ctx.evaluate_script(TIMERS_SOURCE, None)?;
+        let install = JSFunction::from(install.as_object()?);
+        install.call(None, &[ops.into()])?;
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{message_port::message_data, structured_clone::serialize};
+use kedo_core::{define_exports, downcast_state, enqueue_job, native_job};
+use kedo_macros::js_class;
+use kedo_std::{BroadcastSubscriber, BufferChannelReader};
+use kedo_utils::{downcast_ref, js_error_typ, js_undefined};
+use rust_jsc::{callback, JSContext, JSObject, JSResult, JSValue};
+
+#[js_class(
+    resource = BroadcastSubscriber,
+)]
+pub struct BroadcastChannelResource {}
+
+#[callback]
+fn op_broadcast_channel_create(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    name: JSValue,
+) -> JSResult<JSValue> {
+    let state = downcast_state(&ctx);
+    let name = name.as_string()?.to_string();
+    let subscriber = BroadcastSubscriber::subscribe(&name);
+
+    let object = state
+        .classes()
+        .get(BroadcastChannelResource::CLASS_NAME)
+        .expect("BroadcastChannelResource class not found")
+        .object::<BroadcastSubscriber>(&ctx, Some(Box::new(subscriber)));
+    Ok(object.into())
+}
+
+#[callback]
+fn op_broadcast_channel_post_message(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    channel: JSObject,
+    message: JSValue,
+) -> JSResult<JSValue> {
+    let subscriber = match downcast_ref::<BroadcastSubscriber>(&channel) {
+        Some(subscriber) => subscriber,
+        None => {
+            return Err(js_error_typ!(
+                &ctx,
+                "[Op:BroadcastChannelPostMessage] Invalid resource object"
+            ))
+        }
+    };
+
+    let message = serialize(&ctx, &message, &[])?;
+    subscriber.publish(message);
+    Ok(js_undefined!(&ctx))
+}
+
+/// Waits for the next message of the channel, the callback receives
+/// `undefined` once the channel is closed.
+#[callback]
+fn op_broadcast_channel_recv(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    channel: JSObject,
+    callback: JSObject,
+) -> JSResult<JSValue> {
+    let state = downcast_state(&ctx);
+    let mut subscriber = match downcast_ref::<BroadcastSubscriber>(&channel) {
+        Some(subscriber) => subscriber,
+        None => {
+            return Err(js_error_typ!(
+                &ctx,
+                "[Op:BroadcastChannelRecv] Invalid resource object"
+            ))
+        }
+    };
+    let mut receiver = match subscriber.take_receiver() {
+        Some(receiver) => receiver,
+        None => {
+            return Err(js_error_typ!(
+                &ctx,
+                "[Op:BroadcastChannelRecv] A read is already pending"
+            ))
+        }
+    };
+
+    callback.protect();
+    enqueue_job!(state, "op_broadcast_channel_recv", async move {
+        let message = receiver.read().await;
+
+        native_job!("op_broadcast_channel_recv", move |ctx| {
+            subscriber.set_receiver(receiver);
+            match message_data(&ctx, message) {
+                Ok(data) => callback.call(None, &[js_undefined!(&ctx), data])?,
+                Err(err) => callback.call(None, &[err.into()])?,
+            };
+            callback.unprotect();
+            Ok(())
+        })
+    });
+
+    Ok(js_undefined!(&ctx))
+}
+
+#[callback]
+fn op_broadcast_channel_close(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    channel: JSObject,
+) -> JSResult<JSValue> {
+    if let Some(subscriber) = downcast_ref::<BroadcastSubscriber>(&channel) {
+        subscriber.close();
+    }
+
+    Ok(js_undefined!(&ctx))
+}
+
+pub struct BroadcastChannelModule {}
+
+define_exports!(
+    BroadcastChannelModule,
+    @template[],
+    @function[
+        op_broadcast_channel_create,
+        op_broadcast_channel_post_message,
+        op_broadcast_channel_recv,
+        op_broadcast_channel_close,
+    ]
+);
@@ -1,11 +1,16 @@
 use crate::http::response::FetchResponseExt;
 use crate::stream_codec::op_read_decoded_stream;
 use crate::{http::request::HttpRequestExt, signals::InternalSignal};
-use kedo_core::{define_exports, downcast_state, enqueue_job, native_job};
+use hyper::Uri;
+use kedo_core::{
+    check_permission, define_exports, downcast_state, enqueue_job, native_job,
+    permission_denied, PermissionError,
+};
 use kedo_macros::js_class;
 use kedo_std::{FetchClient, FetchError, HttpRequest};
 use kedo_utils::{downcast_ref, js_error, js_undefined};
 use rust_jsc::{callback, JSContext, JSError, JSObject, JSResult, JSValue};
+use std::sync::Arc;
 
 #[js_class(
     resource = FetchClient,
@@ -31,6 +36,7 @@ fn op_new_fetch_client(ctx: JSContext, _: JSObject, _: JSObject) -> JSResult<JSV
 /// This operation is used to fetch a resource from the network
 /// It takes a client, request and a callback function as arguments
 /// The callback function is called with the response or an error
+/// The net permission is checked for the URI and for every redirect
 ///
 /// e.g. op_internal_fetch(client, request, callback)
 #[callback]
@@ -42,8 +48,11 @@ fn op_internal_fetch(
     request_arg: JSValue,
     callback: JSObject,
 ) -> JSResult<JSValue> {
-    callback.protect();
     let request = HttpRequest::from_value(&request_arg, &ctx)?;
+    let uri = request.uri();
+    check_permission!(&ctx, check_net, uri.host().unwrap_or_default(), port(uri));
+
+    callback.protect();
     let signal = request_arg.as_object()?.get_property("signal")?;
     let mut internal_signal = None;
     if !signal.is_undefined() && signal.is_object() {
@@ -61,14 +70,19 @@ fn op_internal_fetch(
         Some(client) => client,
         None => return Err(JSError::new_typ(&ctx, "[Op:InternalFetch] Invalid client")?),
     };
+    let permissions = state.permissions().clone();
     let response = match client.execute(request) {
-        Ok(response) => response,
+        Ok(response) => response.redirect_check(Arc::new(move |uri: &Uri| {
+            permissions
+                .check_net(uri.host().unwrap_or_default(), port(uri))
+                .map_err(Into::into)
+        })),
         Err(err) => {
             return Err(JSError::new_typ(&ctx, format!("Failed to fetch: {}", err))?)
         }
     };
 
-    enqueue_job!(state, async move {
+    enqueue_job!(state, "op_internal_fetch", async move {
         let result = if let Some(mut signal) = internal_signal {
             tokio::select! {
                 res = response => res,
@@ -96,7 +110,14 @@ fn op_internal_fetch(
                     }
                 }
                 Err(err) => {
-                    let err_value = js_error!(ctx, format!("{}", err));
+                    let permission_error = err
+                        .inner
+                        .as_ref()
+                        .and_then(|inner| inner.downcast_ref::<PermissionError>());
+                    let err_value = match permission_error {
+                        Some(error) => permission_denied(ctx, error)?,
+                        None => js_error!(ctx, format!("{}", err)),
+                    };
                     callback.call(None, &[err_value.into()])?;
                 }
             }
@@ -109,6 +130,15 @@ fn op_internal_fetch(
     Ok(js_undefined!(&ctx))
 }
 
+/// Port of a URI, the default one of its scheme when it has none
+fn port(uri: &Uri) -> Option<u16> {
+    uri.port_u16().or_else(|| match uri.scheme_str() {
+        Some("https") => Some(443),
+        Some("http") => Some(80),
+        _ => None,
+    })
+}
+
 pub struct FetchModule {}
 
 define_exports!(
@@ -4,7 +4,10 @@ use crate::{
     HttpRequestResource,
 };
 use futures::future::poll_fn;
-use kedo_core::{downcast_state, enqueue_job, native_job, ClassTable, NativeJob};
+use kedo_core::{
+    check_permission, downcast_state, enqueue_job, native_job, ClassTable, NativeJob,
+    ResourceHandle,
+};
 use kedo_macros::js_class;
 use kedo_std::{
     BufferChannelReader, HttpConfig, HttpRequest, HttpRequestEvent, HttpResponse,
@@ -158,7 +161,7 @@ fn op_read_async_request_event(
 
     callback.protect();
 
-    enqueue_job!(state, async move {
+    enqueue_job!(state, "op_read_async_request_event", async move {
         let event = reader.read().await;
 
         native_job!("op_read_async_request_event", move |ctx| {
@@ -209,6 +212,7 @@ fn op_read_async_request_event(
 async fn handle_server_shutdown(
     server: HttpServer,
     mut signal: Option<OneshotSignal>,
+    resource: ResourceHandle,
 ) -> NativeJob {
     let shutdown = server.listen();
 
@@ -231,6 +235,7 @@ async fn handle_server_shutdown(
         }
     }
 
+    drop(resource);
     NativeJob::new(|_| Ok(()))
 }
 
@@ -242,8 +247,10 @@ fn op_internal_start_server(
     options_args: JSValue,
     callback: JSObject,
 ) -> JSResult<JSValue> {
-    callback.protect();
     let options = ServerOptions::from_value(&options_args, &ctx)?;
+    check_permission!(&ctx, check_net, &options.hostname, Some(options.port));
+
+    callback.protect();
     let signal = options_args.as_object()?.get_property("signal")?;
     let mut internal_signal = None;
     if !signal.is_null() && signal.is_object() {
@@ -260,7 +267,8 @@ fn op_internal_start_server(
         .config(HttpConfig::default())
         .bind();
 
-    enqueue_job!(downcast_state(&ctx), async move {
+    let state = downcast_state(&ctx);
+    enqueue_job!(state, "op_internal_start_server", async move {
         let server = server.await;
 
         native_job!("op_internal_start_server", move |ctx| {
@@ -274,8 +282,9 @@ fn op_internal_start_server(
                         .expect("NetworkBufferChannelReaderResource not found")
                         .object(&ctx, Some(Box::new(reader)));
 
+                    let resource = state.track_resource("httpServer");
                     let shutdown_signal =
-                        handle_server_shutdown(http_server, internal_signal);
+                        handle_server_shutdown(http_server, internal_signal, resource);
                     state.job_queue().borrow().spawn(Box::pin(shutdown_signal));
 
                     let address = JSValue::string(&ctx, format!("{}", options.address()));
@@ -1,10 +1,15 @@
+mod broadcast_channel;
 mod encoding;
 mod http;
+mod message_port;
 mod module;
 mod signals;
 mod stream_codec;
 mod streams;
+mod structured_clone;
+mod worker;
 
+pub use broadcast_channel::BroadcastChannelResource;
 pub use encoding::text_decoder_inner::EncodingTextDecoder;
 pub use http::fetch::FetchClientResource;
 pub use http::request::FetchRequestResource;
@@ -12,6 +17,7 @@ pub use http::request::HttpRequestResource;
 pub use http::server::NetworkBufferChannelReaderResource;
 pub use http::server::RequestEventResource;
 pub use http::url_record::UrlRecord;
+pub use message_port::MessagePortResource;
 pub use module::WebModule;
 pub use signals::InternalSignal;
 pub use stream_codec::DecodedStreamResource;
@@ -20,3 +26,8 @@ pub use streams::ReadableStreamResourceReader;
 pub use streams::StreamResourceModule;
 pub use streams::UnboundedReadableStreamResource;
 pub use streams::UnboundedReadableStreamResourceReader;
+pub use structured_clone::deserialize;
+pub use structured_clone::serialize;
+pub use structured_clone::transfer_list;
+pub use structured_clone::StructuredClone;
+pub use worker::WorkerResource;
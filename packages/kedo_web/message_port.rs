@@ -0,0 +1,145 @@
+use crate::structured_clone::{deserialize, serialize, transfer_list};
+use kedo_core::{define_exports, downcast_state, enqueue_job, native_job};
+use kedo_macros::js_class;
+use kedo_std::{message_channel, MessagePortHandle, SerializedValue, StreamError};
+use kedo_utils::{downcast_ref, js_error_typ, js_undefined};
+use rust_jsc::{callback, JSArray, JSContext, JSObject, JSResult, JSValue};
+
+#[js_class(
+    resource = MessagePortHandle,
+)]
+pub struct MessagePortResource {}
+
+/// Converts a received message to `{ data }`, closed ports resolve to `undefined`
+pub(crate) fn message_data(
+    ctx: &JSContext,
+    message: Result<SerializedValue, StreamError>,
+) -> JSResult<JSValue> {
+    let message = match message {
+        Ok(message) => message,
+        Err(_) => return Ok(js_undefined!(ctx)),
+    };
+
+    let object = JSObject::new(ctx);
+    let data = deserialize(ctx, &message)?;
+    object.set_property("data", &data, Default::default())?;
+    Ok(object.into())
+}
+
+#[callback]
+fn op_message_channel_create(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    _: &[JSValue],
+) -> JSResult<JSValue> {
+    let state = downcast_state(&ctx);
+    let class = state
+        .classes()
+        .get(MessagePortResource::CLASS_NAME)
+        .expect("MessagePortResource class not found");
+
+    let (port1, port2) = message_channel();
+    let port1 = class.object::<MessagePortHandle>(&ctx, Some(Box::new(port1)));
+    let port2 = class.object::<MessagePortHandle>(&ctx, Some(Box::new(port2)));
+    let ports = JSArray::new_array(&ctx, &[port1.into(), port2.into()])?;
+    Ok(ports.into())
+}
+
+/// op_message_port_post_message(port, message, transfer)
+#[callback]
+fn op_message_port_post_message(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    args: &[JSValue],
+) -> JSResult<JSValue> {
+    let port = match args.first().map(|port| port.as_object()) {
+        Some(Ok(port)) => downcast_ref::<MessagePortHandle>(&port),
+        _ => None,
+    };
+    let port = match port {
+        Some(port) => port,
+        None => {
+            return Err(js_error_typ!(
+                &ctx,
+                "[Op:MessagePortPostMessage] Invalid resource object"
+            ))
+        }
+    };
+
+    let message = match args.get(1) {
+        Some(message) => message.clone(),
+        None => js_undefined!(&ctx),
+    };
+    let transfer = transfer_list(&ctx, args.get(2))?;
+    let message = serialize(&ctx, &message, &transfer)?;
+    // the message is dropped if the port is closed
+    let _ = port.post_message(message);
+    Ok(js_undefined!(&ctx))
+}
+
+/// Waits for the next message of the port, the callback receives
+/// `undefined` once the port is closed.
+#[callback]
+fn op_message_port_recv(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    port: JSObject,
+    callback: JSObject,
+) -> JSResult<JSValue> {
+    let state = downcast_state(&ctx);
+    let mut port = match downcast_ref::<MessagePortHandle>(&port) {
+        Some(port) => port,
+        None => {
+            return Err(js_error_typ!(
+                &ctx,
+                "[Op:MessagePortRecv] Invalid resource object"
+            ))
+        }
+    };
+
+    callback.protect();
+    enqueue_job!(state, "op_message_port_recv", async move {
+        let message = port.recv().await;
+
+        native_job!("op_message_port_recv", move |ctx| {
+            match message_data(&ctx, message) {
+                Ok(data) => callback.call(None, &[js_undefined!(&ctx), data])?,
+                Err(err) => callback.call(None, &[err.into()])?,
+            };
+            callback.unprotect();
+            Ok(())
+        })
+    });
+
+    Ok(js_undefined!(&ctx))
+}
+
+#[callback]
+fn op_message_port_close(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    port: JSObject,
+) -> JSResult<JSValue> {
+    if let Some(mut port) = downcast_ref::<MessagePortHandle>(&port) {
+        port.close();
+    }
+
+    Ok(js_undefined!(&ctx))
+}
+
+pub struct MessagePortModule {}
+
+define_exports!(
+    MessagePortModule,
+    @template[],
+    @function[
+        op_message_channel_create,
+        op_message_port_post_message,
+        op_message_port_recv,
+        op_message_port_close,
+    ]
+);
@@ -1,10 +1,14 @@
 use crate::{
+    broadcast_channel::BroadcastChannelModule,
     encoding::text_encoding::TextEncodingModule,
     http::{
         fetch::FetchModule, request::FetchRequestOps, server::server_exports,
         url_module::UrlModule,
     },
+    message_port::MessagePortModule,
     signals::signal_exports,
+    structured_clone::StructuredCloneModule,
+    worker::WorkerModule,
     StreamResourceModule,
 };
 use kedo_core::{downcast_state, JsProctectedCallable, ModuleSource};
@@ -36,7 +40,6 @@ pub fn queue_internal_timeout(
     let function = JSFunction::from(function);
     let timeout = args[1].as_number()? as u64;
     let args = args[2..].to_vec();
-    function.protect();
     let id = state.timers().add_timer(
         Duration::from_millis(timeout),
         kedo_std::TimerType::Timeout,
@@ -80,6 +83,12 @@ fn exports(ctx: &JSContext) -> JSObject {
         .expect("Failed to export StreamResourceModule");
     FetchModule::export(ctx, &exports).expect("Failed to export FetchModule");
     FetchRequestOps::export(ctx, &exports).expect("Failed to export FetchRequestOps");
+    WorkerModule::export(ctx, &exports).expect("Failed to export WorkerModule");
+    StructuredCloneModule::export(ctx, &exports)
+        .expect("Failed to export StructuredCloneModule");
+    MessagePortModule::export(ctx, &exports).expect("Failed to export MessagePortModule");
+    BroadcastChannelModule::export(ctx, &exports)
+        .expect("Failed to export BroadcastChannelModule");
 
     server_exports(ctx, &exports);
     signal_exports(ctx, &exports);
@@ -36,7 +36,7 @@ pub fn op_read_decoded_stream(
         }
     };
 
-    enqueue_job!(state, async move {
+    enqueue_job!(state, "op_read_decoded_stream", async move {
         let result = decoded_stream.next().await;
         native_job!("op_read_decoded_stream", move |ctx| {
             match result {
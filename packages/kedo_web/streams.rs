@@ -1,4 +1,6 @@
-use kedo_core::{define_exports, downcast_state, enqueue_job, native_job};
+use kedo_core::{
+    define_exports, downcast_state, enqueue_job, native_job, ResourceHandle, ResourceId,
+};
 use kedo_macros::js_class;
 use kedo_std::{
     BoundedBufferChannel, BoundedBufferChannelReader, BufferChannel, BufferChannelReader,
@@ -50,7 +52,7 @@ fn stream_reource_constructor(
 }
 
 #[js_class(
-    resource = BoundedBufferChannelReader<Vec<u8>>,
+    resource = StreamReader<BoundedBufferChannelReader<Vec<u8>>>,
 )]
 pub struct ReadableStreamResourceReader {}
 
@@ -87,10 +89,17 @@ fn unbounded_stream_resource_constructor(
 }
 
 #[js_class(
-    resource = UnboundedBufferChannelReader<Vec<u8>>,
+    resource = StreamReader<UnboundedBufferChannelReader<Vec<u8>>>,
 )]
 pub struct UnboundedReadableStreamResourceReader {}
 
+/// Private data of the reader objects, the rid of the reader is closed when the
+/// object is collected, even if the stream never ends
+pub struct StreamReader<R> {
+    reader: R,
+    _resource: ResourceHandle,
+}
+
 fn bytes_to_js_value(ctx: &JSContext, bytes: Vec<u8>) -> JSResult<JSValue> {
     let mut bytes = ManuallyDrop::new(bytes);
     let chunk = JSTypedArray::with_bytes(
@@ -102,6 +111,21 @@ fn bytes_to_js_value(ctx: &JSContext, bytes: Vec<u8>) -> JSResult<JSValue> {
     Ok(chunk)
 }
 
+/// Rid of the reader registered by the acquire ops, stored on the reader
+/// object as `rid` and on the stream object as `readerRid`
+fn reader_rid(object: &JSObject, property: &str) -> Option<ResourceId> {
+    match object.get_property(property) {
+        Ok(rid) if rid.is_number() => rid.as_number().ok().map(|rid| rid as ResourceId),
+        _ => None,
+    }
+}
+
+fn close_reader(ctx: &JSContext, rid: Option<ResourceId>) {
+    if let Some(rid) = rid {
+        downcast_state(ctx).resources().borrow_mut().close(rid);
+    }
+}
+
 /// read macro
 macro_rules! channel_op_read {
     ($name:ident, $stream:ident) => {
@@ -114,15 +138,16 @@ macro_rules! channel_op_read {
             callback: JSObject,
         ) -> JSResult<JSValue> {
             callback.protect();
-            let reader = downcast_ref::<$stream<Vec<u8>>>(&reader);
+            let rid = reader_rid(&reader, "rid");
+            let reader = downcast_ref::<StreamReader<$stream<Vec<u8>>>>(&reader);
             let mut stream_reader = match reader {
                 Some(reader) => reader,
                 None => return Err(js_error_typ!(&ctx, "[Op:Read] Invalid reader")),
             };
 
             let state = downcast_state(&ctx);
-            enqueue_job!(state, async move {
-                let result = stream_reader.read().await;
+            enqueue_job!(state, stringify!($name), async move {
+                let result = stream_reader.reader.read().await;
                 native_job!(stringify!($name), move |ctx| {
                     match result {
                         Ok(bytes) => {
@@ -131,6 +156,7 @@ macro_rules! channel_op_read {
                         }
                         Err(err) => match err {
                             StreamError::Closed => {
+                                close_reader(&ctx, rid);
                                 callback.call(
                                     None,
                                     &[
@@ -174,7 +200,8 @@ macro_rules! channel_op_read_sync {
             _: JSObject,
             reader: JSObject,
         ) -> JSResult<JSValue> {
-            let reader = downcast_ref::<$stream<Vec<u8>>>(&reader);
+            let rid = reader_rid(&reader, "rid");
+            let reader = downcast_ref::<StreamReader<$stream<Vec<u8>>>>(&reader);
             let mut readable_stream = match reader {
                 Some(stream) => stream,
                 None => {
@@ -185,13 +212,16 @@ macro_rules! channel_op_read_sync {
                 }
             };
 
-            match readable_stream.try_read() {
+            match readable_stream.reader.try_read() {
                 Ok(bytes) => {
                     let chunk = bytes_to_js_value(&ctx, bytes)?;
                     Ok(chunk)
                 }
                 Err(err) => match err {
-                    StreamError::Closed => Ok(JSValue::number(&ctx, err.into())),
+                    StreamError::Closed => {
+                        close_reader(&ctx, rid);
+                        Ok(JSValue::number(&ctx, err.into()))
+                    }
                     StreamError::Empty => Ok(JSValue::number(&ctx, err.into())),
                     _ => Err(js_error!(&ctx, format!("{}", err))),
                 },
@@ -233,7 +263,7 @@ macro_rules! channel_op_write {
             let len = bytes.len() as f64;
 
             let state = downcast_state(&ctx);
-            enqueue_job!(state, async move {
+            enqueue_job!(state, stringify!($name), async move {
                 let result = stream_writer.write(bytes).await;
                 native_job!(stringify!($name), move |ctx| {
                     match result {
@@ -366,6 +396,7 @@ macro_rules! channel_op_close {
             _: JSObject,
             resource: JSObject,
         ) -> JSResult<JSValue> {
+            let rid = reader_rid(&resource, "readerRid");
             let internal_stream = downcast_ref::<$stream<Vec<u8>>>(&resource);
             let mut channel = match internal_stream {
                 Some(stream) => stream,
@@ -373,6 +404,7 @@ macro_rules! channel_op_close {
             };
 
             channel.close();
+            close_reader(&ctx, rid);
             Ok(JSValue::undefined(&ctx))
         }
     };
@@ -410,8 +442,19 @@ macro_rules! channel_op_acquire_reader {
                 .get($class_name::CLASS_NAME)
                 .expect("ReadableStreamResourceReader class not found");
 
-            let stream_reader =
-                class.object::<$reader<Vec<u8>>>(&ctx, Some(Box::new(reader)));
+            // closed once the reader reaches the end of the stream, the stream is
+            // closed or the reader is collected
+            let resource_handle = state.track_resource(stringify!($class_name));
+            let rid = JSValue::number(&ctx, resource_handle.rid() as f64);
+            let reader = StreamReader {
+                reader,
+                _resource: resource_handle,
+            };
+            let stream_reader = class
+                .object::<StreamReader<$reader<Vec<u8>>>>(&ctx, Some(Box::new(reader)));
+
+            stream_reader.set_property("rid", &rid, Default::default())?;
+            resource.set_property("readerRid", &rid, Default::default())?;
 
             Ok(stream_reader.into())
         }
@@ -0,0 +1,669 @@
+use kedo_core::{define_exports, downcast_state, ClassTable, ProtoTable};
+use kedo_std::SerializedValue;
+use rust_jsc::{
+    callback, class::ClassError, JSArray, JSArrayBuffer, JSContext, JSError, JSFunction,
+    JSObject, JSResult, JSTypedArray, JSTypedArrayType, JSValue, JSValueType,
+};
+use std::mem::ManuallyDrop;
+
+/// What the C API doesn't expose to the serializer: the kind of an object
+/// using brand checks, the internal slots of the supported objects and a map
+/// to remember the objects already visited.
+const STRUCTURED_CLONE_HELPERS: &str = r#"
+(function () {
+    "use strict";
+    const toString = Object.prototype.toString;
+    const getter = (target, name) =>
+        Object.getOwnPropertyDescriptor(target, name).get;
+    const TypedArray = Object.getPrototypeOf(Uint8Array).prototype;
+    const typedArrayTag = getter(TypedArray, Symbol.toStringTag);
+    const typedArray = [
+        getter(TypedArray, "buffer"),
+        getter(TypedArray, "byteOffset"),
+        getter(TypedArray, "length"),
+    ];
+    const dataView = [
+        getter(DataView.prototype, "buffer"),
+        getter(DataView.prototype, "byteOffset"),
+        getter(DataView.prototype, "byteLength"),
+    ];
+    const regExpSource = getter(RegExp.prototype, "source");
+    const regExpFlags = getter(RegExp.prototype, "flags");
+    const transfer = ArrayBuffer.prototype.transfer;
+    const { entries: mapEntries, set: mapSet } = Map.prototype;
+    const { values: setValues, add: setAdd } = Set.prototype;
+    const brands = {
+        Date: Date.prototype.getTime,
+        RegExp: regExpSource,
+        Map: getter(Map.prototype, "size"),
+        Set: getter(Set.prototype, "size"),
+        ArrayBuffer: getter(ArrayBuffer.prototype, "byteLength"),
+        DataView: dataView[2],
+        Boolean: Boolean.prototype.valueOf,
+        Number: Number.prototype.valueOf,
+        String: String.prototype.valueOf,
+        BigInt: BigInt.prototype.valueOf,
+        Symbol: Symbol.prototype.valueOf,
+    };
+    const views = {
+        DataView, Int8Array, Uint8Array, Uint8ClampedArray, Int16Array,
+        Uint16Array, Int32Array, Uint32Array, Float32Array, Float64Array,
+        BigInt64Array, BigUint64Array,
+    };
+    const errors = {
+        Error, EvalError, RangeError, ReferenceError, SyntaxError,
+        TypeError, URIError,
+    };
+    const is = (brand, value) => {
+        try {
+            brand.call(value);
+            return true;
+        } catch {
+            return false;
+        }
+    };
+
+    return {
+        kind(value) {
+            switch (typeof value) {
+                case "function": return "Function";
+                case "bigint": return "BigInt";
+                case "symbol": return "Symbol";
+            }
+            if (Array.isArray(value)) return "Array";
+            const typed = typedArrayTag.call(value);
+            if (typed !== undefined) return typed;
+            for (const name in brands) {
+                if (is(brands[name], value)) return name;
+            }
+            // a Symbol.toStringTag can't pretend to be a known kind
+            const tag = toString.call(value).slice(8, -1);
+            const known = tag === "Array" || Object.hasOwn(brands, tag)
+                || Object.hasOwn(views, tag);
+            return known ? "Object" : tag;
+        },
+        keys: (value) => Object.keys(value),
+        memory: () => new Map(),
+        recall: (memory, value) => memory.get(value),
+        remember: (memory, value, index) => void memory.set(value, index),
+        time: (date) => brands.Date.call(date),
+        date: (time) => new Date(time),
+        array: (length) => new Array(length),
+        unbox: (kind, value) => brands[kind].call(value),
+        box: (value) => Object(value),
+        bigint: (value) => String(value),
+        toBigInt: (value) => BigInt(value),
+        regexp: (value) => [regExpSource.call(value), regExpFlags.call(value)],
+        createRegExp: (source, flags) => new RegExp(source, flags),
+        error(value) {
+            const name = value.name;
+            const message = Object.getOwnPropertyDescriptor(value, "message");
+            const stack = value.stack;
+            return [
+                Object.hasOwn(errors, name) ? name : "Error",
+                message && "value" in message ? String(message.value) : undefined,
+                typeof stack === "string" ? stack : undefined,
+            ];
+        },
+        createError(name, message, stack) {
+            const error = new errors[name](message);
+            if (stack !== undefined) error.stack = stack;
+            return error;
+        },
+        mapEntries: (map) => Array.from(mapEntries.call(map)),
+        createMap: () => new Map(),
+        mapSet: (map, key, value) => void mapSet.call(map, key, value),
+        setValues: (set) => Array.from(setValues.call(set)),
+        createSet: () => new Set(),
+        setAdd: (set, value) => void setAdd.call(set, value),
+        bytes: (buffer) => new Uint8Array(buffer),
+        createBuffer: (length) => new ArrayBuffer(length),
+        view: (kind, value) =>
+            (kind === "DataView" ? dataView : typedArray).map((get) => get.call(value)),
+        createView: (kind, buffer, offset, length) =>
+            new views[kind](buffer, offset, length),
+        detach: (buffer) => void transfer.call(buffer),
+    };
+})()
+"#;
+
+/// Objects the serializer doesn't know how to clone, anything else that is
+/// not a known kind is cloned as a plain object.
+const UNCLONEABLE: &[&str] = &[
+    "Function",
+    "Symbol",
+    "Promise",
+    "WeakMap",
+    "WeakSet",
+    "WeakRef",
+    "FinalizationRegistry",
+    "Generator",
+    "AsyncGenerator",
+    "SharedArrayBuffer",
+];
+
+/// Ports are bound to the event loop of the runtime that created them, posting
+/// one or listing it in the transfer list throws instead of sending a dead copy.
+const PORT_NOT_TRANSFERABLE: &str = "MessagePort objects can't be transferred yet";
+
+const ERROR_NAMES: &[&str] = &[
+    "Error",
+    "EvalError",
+    "RangeError",
+    "ReferenceError",
+    "SyntaxError",
+    "TypeError",
+    "URIError",
+];
+
+const VIEW_KINDS: &[&str] = &[
+    "DataView",
+    "Int8Array",
+    "Uint8Array",
+    "Uint8ClampedArray",
+    "Int16Array",
+    "Uint16Array",
+    "Int32Array",
+    "Uint32Array",
+    "Float32Array",
+    "Float64Array",
+    "BigInt64Array",
+    "BigUint64Array",
+];
+
+/// Errors thrown by the serializer have the `DataCloneError` name,
+/// the std library rethrows them as a `DOMException`.
+fn data_clone_error(ctx: &JSContext, message: impl Into<String>) -> JSResult<JSError> {
+    let error: JSValue = JSError::with_message(ctx, message.into())?.into();
+    let error = error.as_object()?;
+    error.set_property(
+        "name",
+        &JSValue::string(ctx, "DataCloneError"),
+        Default::default(),
+    )?;
+    Ok(JSError::from(error))
+}
+
+pub struct StructuredClone {}
+
+impl StructuredClone {
+    pub const PROTO_NAME: &'static str = "StructuredCloneHelpers";
+
+    pub fn init_proto(
+        proto_manager: &mut ProtoTable,
+        _: &mut ClassTable,
+        ctx: &JSContext,
+    ) -> Result<(), ClassError> {
+        let helpers = ctx
+            .evaluate_script(STRUCTURED_CLONE_HELPERS, None)
+            .and_then(|helpers| helpers.as_object())
+            .map_err(|_| ClassError::CreateFailed)?;
+        proto_manager.insert(Self::PROTO_NAME.to_string(), helpers);
+        Ok(())
+    }
+}
+
+struct Helpers {
+    helpers: JSObject,
+}
+
+impl Helpers {
+    fn new(ctx: &JSContext) -> Self {
+        let state = downcast_state(ctx);
+        let helpers = state
+            .protos()
+            .get(StructuredClone::PROTO_NAME)
+            .expect("StructuredCloneHelpers not found")
+            .clone();
+
+        Self { helpers }
+    }
+
+    fn call(&self, name: &str, args: &[JSValue]) -> JSResult<JSValue> {
+        let function = JSFunction::from(self.helpers.get_property(name)?.as_object()?);
+        function.call(None, args)
+    }
+
+    fn kind(&self, value: &JSValue) -> JSResult<String> {
+        Ok(self
+            .call("kind", &[value.clone()])?
+            .as_string()?
+            .to_string())
+    }
+}
+
+fn array_items(value: &JSValue) -> JSResult<Vec<JSValue>> {
+    let array = JSArray::new(value.as_object()?);
+    let length = array.length()? as u32;
+    (0..length).map(|i| array.get(i)).collect()
+}
+
+fn optional_string(value: JSValue) -> JSResult<Option<String>> {
+    if value.is_undefined() {
+        return Ok(None);
+    }
+
+    Ok(Some(value.as_string()?.to_string()))
+}
+
+/// Reads the transfer list, `undefined` and `null` are an empty list
+pub fn transfer_list(ctx: &JSContext, value: Option<&JSValue>) -> JSResult<Vec<JSValue>> {
+    match value {
+        None => Ok(Vec::new()),
+        Some(value) if value.is_undefined() || value.is_null() => Ok(Vec::new()),
+        Some(value) if value.is_object() => array_items(value),
+        Some(_) => Err(data_clone_error(ctx, "The transfer list must be an array")?),
+    }
+}
+
+/// Serializes `value` with the structured serialize algorithm, the buffers of
+/// the transfer list are detached once the value is serialized.
+/// Throws a `DataCloneError` if the value holds something that can't be cloned,
+/// only `ArrayBuffer`s can be transferred.
+pub fn serialize(
+    ctx: &JSContext,
+    value: &JSValue,
+    transfer: &[JSValue],
+) -> JSResult<SerializedValue> {
+    let helpers = Helpers::new(ctx);
+    let transferred = helpers.call("memory", &[])?;
+    for (index, buffer) in transfer.iter().enumerate() {
+        let kind = helpers.kind(buffer)?;
+        if kind == "MessagePort" {
+            return Err(data_clone_error(ctx, PORT_NOT_TRANSFERABLE)?);
+        }
+        if kind != "ArrayBuffer" {
+            return Err(data_clone_error(
+                ctx,
+                format!("{} object could not be transferred", kind),
+            )?);
+        }
+        if JSArrayBuffer::from_object(buffer.as_object()?).is_detached() {
+            return Err(data_clone_error(ctx, "ArrayBuffer is detached")?);
+        }
+        if helpers
+            .call("recall", &[transferred.clone(), buffer.clone()])?
+            .is_number()
+        {
+            return Err(data_clone_error(
+                ctx,
+                "ArrayBuffer is in the transfer list more than once",
+            )?);
+        }
+
+        let index = JSValue::number(ctx, index as f64);
+        helpers.call("remember", &[transferred.clone(), buffer.clone(), index])?;
+    }
+
+    let memory = helpers.call("memory", &[])?;
+    let mut serializer = Serializer {
+        ctx,
+        helpers,
+        memory,
+        next_index: 0,
+    };
+    let value = serializer.serialize(value)?;
+
+    for buffer in transfer {
+        serializer.helpers.call("detach", &[buffer.clone()])?;
+    }
+
+    Ok(value)
+}
+
+/// Creates the value in `ctx`, objects are created in the order they were serialized
+pub fn deserialize(ctx: &JSContext, value: &SerializedValue) -> JSResult<JSValue> {
+    let mut deserializer = Deserializer {
+        ctx,
+        helpers: Helpers::new(ctx),
+        memory: Vec::new(),
+    };
+
+    deserializer.deserialize(value)
+}
+
+struct Serializer<'a> {
+    ctx: &'a JSContext,
+    helpers: Helpers,
+    memory: JSValue,
+    next_index: u32,
+}
+
+impl Serializer<'_> {
+    fn serialize(&mut self, value: &JSValue) -> JSResult<SerializedValue> {
+        match value.get_type() {
+            JSValueType::Undefined => Ok(SerializedValue::Undefined),
+            JSValueType::Null => Ok(SerializedValue::Null),
+            JSValueType::Boolean => Ok(SerializedValue::Boolean(value.as_boolean())),
+            JSValueType::Number => Ok(SerializedValue::Number(value.as_number()?)),
+            JSValueType::String => {
+                Ok(SerializedValue::String(value.as_string()?.to_string()))
+            }
+            JSValueType::Object => self.serialize_object(value),
+            _ => match self.helpers.kind(value)?.as_str() {
+                "BigInt" => {
+                    let value = self.helpers.call("bigint", &[value.clone()])?;
+                    Ok(SerializedValue::BigInt(value.as_string()?.to_string()))
+                }
+                kind => Err(data_clone_error(
+                    self.ctx,
+                    format!("{} could not be cloned", kind),
+                )?),
+            },
+        }
+    }
+
+    fn serialize_object(&mut self, value: &JSValue) -> JSResult<SerializedValue> {
+        let index = self
+            .helpers
+            .call("recall", &[self.memory.clone(), value.clone()])?;
+        if index.is_number() {
+            return Ok(SerializedValue::Reference(index.as_number()? as u32));
+        }
+
+        let kind = self.helpers.kind(value)?;
+        if kind == "MessagePort" {
+            return Err(data_clone_error(self.ctx, PORT_NOT_TRANSFERABLE)?);
+        }
+        if UNCLONEABLE.contains(&kind.as_str()) {
+            return Err(data_clone_error(
+                self.ctx,
+                format!("{} object could not be cloned", kind),
+            )?);
+        }
+
+        self.remember(value)?;
+        match kind.as_str() {
+            "Boolean" | "Number" | "String" | "BigInt" => {
+                let kind = JSValue::string(self.ctx, kind.as_str());
+                let primitive = self.helpers.call("unbox", &[kind, value.clone()])?;
+                Ok(SerializedValue::Boxed(Box::new(
+                    self.serialize(&primitive)?,
+                )))
+            }
+            "Date" => {
+                let time = self.helpers.call("time", &[value.clone()])?;
+                Ok(SerializedValue::Date(time.as_number()?))
+            }
+            "RegExp" => {
+                let parts = self.helpers.call("regexp", &[value.clone()])?;
+                let parts = array_items(&parts)?;
+                Ok(SerializedValue::RegExp {
+                    source: parts[0].as_string()?.to_string(),
+                    flags: parts[1].as_string()?.to_string(),
+                })
+            }
+            "Error" => {
+                let parts = self.helpers.call("error", &[value.clone()])?;
+                let mut parts = array_items(&parts)?.into_iter();
+                let mut next = || parts.next().expect("error parts");
+                Ok(SerializedValue::Error {
+                    name: next().as_string()?.to_string(),
+                    message: optional_string(next())?,
+                    stack: optional_string(next())?,
+                })
+            }
+            "ArrayBuffer" => self.serialize_buffer(value),
+            kind if VIEW_KINDS.contains(&kind) => {
+                let kind_value = JSValue::string(self.ctx, kind);
+                let parts = self.helpers.call("view", &[kind_value, value.clone()])?;
+                let parts = array_items(&parts)?;
+                Ok(SerializedValue::ArrayBufferView {
+                    kind: kind.to_string(),
+                    buffer: Box::new(self.serialize(&parts[0])?),
+                    byte_offset: parts[1].as_number()? as usize,
+                    length: parts[2].as_number()? as usize,
+                })
+            }
+            "Map" => {
+                let entries = self.helpers.call("mapEntries", &[value.clone()])?;
+                let mut map = Vec::new();
+                for entry in array_items(&entries)? {
+                    let entry = array_items(&entry)?;
+                    map.push((self.serialize(&entry[0])?, self.serialize(&entry[1])?));
+                }
+                Ok(SerializedValue::Map(map))
+            }
+            "Set" => {
+                let values = self.helpers.call("setValues", &[value.clone()])?;
+                let set = array_items(&values)?
+                    .iter()
+                    .map(|value| self.serialize(value))
+                    .collect::<JSResult<Vec<_>>>()?;
+                Ok(SerializedValue::Set(set))
+            }
+            "Array" => {
+                let object = value.as_object()?;
+                let length = object.get_property("length")?.as_number()? as u32;
+                Ok(SerializedValue::Array {
+                    length,
+                    properties: self.properties(value)?,
+                })
+            }
+            _ => Ok(SerializedValue::Object(self.properties(value)?)),
+        }
+    }
+
+    fn serialize_buffer(&mut self, value: &JSValue) -> JSResult<SerializedValue> {
+        if JSArrayBuffer::from_object(value.as_object()?).is_detached() {
+            return Err(data_clone_error(self.ctx, "ArrayBuffer is detached")?);
+        }
+
+        let bytes = self.helpers.call("bytes", &[value.clone()])?;
+        let bytes = JSTypedArray::from_value(&bytes)?.as_vec::<u8>()?;
+        Ok(SerializedValue::ArrayBuffer(bytes))
+    }
+
+    fn remember(&mut self, value: &JSValue) -> JSResult<()> {
+        let index = JSValue::number(self.ctx, self.next_index as f64);
+        self.helpers
+            .call("remember", &[self.memory.clone(), value.clone(), index])?;
+        self.next_index += 1;
+        Ok(())
+    }
+
+    fn properties(
+        &mut self,
+        value: &JSValue,
+    ) -> JSResult<Vec<(String, SerializedValue)>> {
+        let object = value.as_object()?;
+        let keys = self.helpers.call("keys", &[value.clone()])?;
+        let keys = array_items(&keys)?;
+
+        let mut properties = Vec::with_capacity(keys.len());
+        for key in keys {
+            let key = key.as_string()?.to_string();
+            let property = object.get_property(key.as_str())?;
+            properties.push((key, self.serialize(&property)?));
+        }
+
+        Ok(properties)
+    }
+}
+
+struct Deserializer<'a> {
+    ctx: &'a JSContext,
+    helpers: Helpers,
+    memory: Vec<JSValue>,
+}
+
+impl Deserializer<'_> {
+    fn deserialize(&mut self, value: &SerializedValue) -> JSResult<JSValue> {
+        let ctx = self.ctx;
+        match value {
+            SerializedValue::Undefined => Ok(JSValue::undefined(ctx)),
+            SerializedValue::Null => Ok(JSValue::null(ctx)),
+            SerializedValue::Boolean(value) => Ok(JSValue::boolean(ctx, *value)),
+            SerializedValue::Number(value) => Ok(JSValue::number(ctx, *value)),
+            SerializedValue::String(value) => Ok(JSValue::string(ctx, value.as_str())),
+            SerializedValue::BigInt(value) => {
+                let value = JSValue::string(ctx, value.as_str());
+                self.helpers.call("toBigInt", &[value])
+            }
+            SerializedValue::Boxed(value) => {
+                let primitive = self.deserialize(value)?;
+                let boxed = self.helpers.call("box", &[primitive])?;
+                Ok(self.remember(boxed))
+            }
+            SerializedValue::Date(time) => {
+                let time = JSValue::number(ctx, *time);
+                let date = self.helpers.call("date", &[time])?;
+                Ok(self.remember(date))
+            }
+            SerializedValue::RegExp { source, flags } => {
+                let source = JSValue::string(ctx, source.as_str());
+                let flags = JSValue::string(ctx, flags.as_str());
+                let regexp = self.helpers.call("createRegExp", &[source, flags])?;
+                Ok(self.remember(regexp))
+            }
+            SerializedValue::Error {
+                name,
+                message,
+                stack,
+            } => {
+                let name = match ERROR_NAMES.contains(&name.as_str()) {
+                    true => name.as_str(),
+                    false => "Error",
+                };
+                let optional = |value: &Option<String>| match value {
+                    Some(value) => JSValue::string(ctx, value.as_str()),
+                    None => JSValue::undefined(ctx),
+                };
+                let args = [
+                    JSValue::string(ctx, name),
+                    optional(message),
+                    optional(stack),
+                ];
+                let error = self.helpers.call("createError", &args)?;
+                Ok(self.remember(error))
+            }
+            SerializedValue::ArrayBuffer(bytes) => {
+                let buffer = self.deserialize_buffer(bytes)?;
+                Ok(self.remember(buffer))
+            }
+            SerializedValue::ArrayBufferView {
+                kind,
+                buffer,
+                byte_offset,
+                length,
+            } => {
+                if !VIEW_KINDS.contains(&kind.as_str()) {
+                    return Err(data_clone_error(ctx, format!("Unknown view {}", kind))?);
+                }
+
+                // the view is visited before its buffer
+                let index = self.memory.len();
+                self.memory.push(JSValue::undefined(ctx));
+                let args = [
+                    JSValue::string(ctx, kind.as_str()),
+                    self.deserialize(buffer)?,
+                    JSValue::number(ctx, *byte_offset as f64),
+                    JSValue::number(ctx, *length as f64),
+                ];
+                let view = self.helpers.call("createView", &args)?;
+                self.memory[index] = view.clone();
+                Ok(view)
+            }
+            SerializedValue::Map(entries) => {
+                let map = self.helpers.call("createMap", &[])?;
+                self.remember(map.clone());
+                for (key, value) in entries {
+                    let args = [
+                        map.clone(),
+                        self.deserialize(key)?,
+                        self.deserialize(value)?,
+                    ];
+                    self.helpers.call("mapSet", &args)?;
+                }
+                Ok(map)
+            }
+            SerializedValue::Set(values) => {
+                let set = self.helpers.call("createSet", &[])?;
+                self.remember(set.clone());
+                for value in values {
+                    let value = self.deserialize(value)?;
+                    self.helpers.call("setAdd", &[set.clone(), value])?;
+                }
+                Ok(set)
+            }
+            SerializedValue::Array { length, properties } => {
+                let length = JSValue::number(ctx, *length as f64);
+                let array = self.helpers.call("array", &[length])?;
+                self.remember(array.clone());
+                self.set_properties(&array.as_object()?, properties)?;
+                Ok(array)
+            }
+            SerializedValue::Object(properties) => {
+                let object = JSObject::new(ctx);
+                let value = self.remember(object.clone().into());
+                self.set_properties(&object, properties)?;
+                Ok(value)
+            }
+            SerializedValue::Reference(index) => match self.memory.get(*index as usize) {
+                Some(value) => Ok(value.clone()),
+                None => Err(data_clone_error(ctx, "Invalid object reference")?),
+            },
+        }
+    }
+
+    fn remember(&mut self, value: JSValue) -> JSValue {
+        self.memory.push(value.clone());
+        value
+    }
+
+    fn deserialize_buffer(&mut self, bytes: &[u8]) -> JSResult<JSValue> {
+        if bytes.is_empty() {
+            let length = JSValue::number(self.ctx, 0.0);
+            return self.helpers.call("createBuffer", &[length]);
+        }
+
+        let mut bytes: ManuallyDrop<Vec<u8>> = ManuallyDrop::new(bytes.to_vec());
+        let array: JSValue = JSTypedArray::with_bytes(
+            self.ctx,
+            bytes.as_mut_slice(),
+            JSTypedArrayType::Uint8Array,
+        )?
+        .into();
+        array.as_object()?.get_property("buffer")
+    }
+
+    fn set_properties(
+        &mut self,
+        object: &JSObject,
+        properties: &[(String, SerializedValue)],
+    ) -> JSResult<()> {
+        for (key, value) in properties {
+            let value = self.deserialize(value)?;
+            object.set_property(key.as_str(), &value, Default::default())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// op_structured_clone(value, transfer) clones the value in the same context
+#[callback]
+fn op_structured_clone(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    args: &[JSValue],
+) -> JSResult<JSValue> {
+    let value = match args.first() {
+        Some(value) => value.clone(),
+        None => JSValue::undefined(&ctx),
+    };
+    let transfer = transfer_list(&ctx, args.get(1))?;
+    let serialized = serialize(&ctx, &value, &transfer)?;
+    deserialize(&ctx, &serialized)
+}
+
+pub struct StructuredCloneModule {}
+
+define_exports!(
+    StructuredCloneModule,
+    @template[],
+    @function[
+        op_structured_clone,
+    ]
+);
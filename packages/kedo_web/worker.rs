@@ -0,0 +1,440 @@
+use crate::structured_clone::{deserialize, serialize, transfer_list};
+use kedo_core::{
+    define_exports, downcast_state, enqueue_job, native_job, worker_channel, WorkerError,
+    WorkerHandle, WorkerMessage,
+};
+use kedo_macros::js_class;
+use kedo_std::BufferChannelReader;
+use kedo_utils::{downcast_ref, js_error, js_error_typ, js_undefined};
+use rust_jsc::{callback, JSContext, JSError, JSObject, JSResult, JSValue};
+use std::path::PathBuf;
+use url::Url;
+
+#[js_class(
+    resource = WorkerHandle,
+)]
+pub struct WorkerResource {}
+
+/// Resolves the worker module to a file path, relative paths start at the
+/// directory of `referrer`, the main module of the runtime, or at the current
+/// directory when the referrer isn't a file.
+fn resolve_worker_path(
+    specifier: &str,
+    referrer: Option<&str>,
+) -> Result<String, String> {
+    let path = match Url::parse(specifier) {
+        Ok(url) if url.scheme() == "file" => url
+            .to_file_path()
+            .map_err(|_| format!("Invalid file URL: {}", specifier))?,
+        // drive letters parse as a scheme on windows
+        Ok(url) if url.scheme().len() > 1 => {
+            return Err(format!("Unsupported worker URL scheme: {}", url.scheme()))
+        }
+        _ => PathBuf::from(specifier),
+    };
+
+    let path = if path.is_relative() {
+        let base = match referrer.and_then(referrer_dir) {
+            Some(dir) => dir,
+            None => std::env::current_dir().map_err(|err| err.to_string())?,
+        };
+        base.join(path)
+    } else {
+        path
+    };
+
+    if !path.is_file() {
+        return Err(format!("Worker module not found: {}", path.display()));
+    }
+
+    Ok(path.to_string_lossy().to_string())
+}
+
+fn referrer_dir(referrer: &str) -> Option<PathBuf> {
+    let path = match Url::parse(referrer) {
+        Ok(url) if url.scheme() == "file" => url.to_file_path().ok()?,
+        _ => PathBuf::from(referrer),
+    };
+
+    match path.is_absolute() {
+        true => path.parent().map(|dir| dir.to_path_buf()),
+        false => None,
+    }
+}
+
+/// Converts a message received from the other side to the object handed to JS:
+/// `{ type: "message", data }` or `{ type: "error", message, stack }`
+fn message_object(ctx: &JSContext, message: WorkerMessage) -> JSResult<JSValue> {
+    let object = JSObject::new(ctx);
+    match message {
+        WorkerMessage::Message(value) => {
+            let data = deserialize(ctx, &value)?;
+            object.set_property(
+                "type",
+                &JSValue::string(ctx, "message"),
+                Default::default(),
+            )?;
+            object.set_property("data", &data, Default::default())?;
+        }
+        WorkerMessage::Error(error) => {
+            object.set_property(
+                "type",
+                &JSValue::string(ctx, "error"),
+                Default::default(),
+            )?;
+            object.set_property(
+                "message",
+                &JSValue::string(ctx, error.message),
+                Default::default(),
+            )?;
+            object.set_property(
+                "stack",
+                &JSValue::string(ctx, error.stack),
+                Default::default(),
+            )?;
+        }
+    }
+
+    Ok(object.into())
+}
+
+/// op_worker_create(specifier, name) -> WorkerResource
+#[callback]
+fn op_worker_create(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    args: &[JSValue],
+) -> JSResult<JSValue> {
+    let state = downcast_state(&ctx);
+    let launcher = match state.worker_launcher() {
+        Some(launcher) => launcher.clone(),
+        None => {
+            return Err(js_error_typ!(
+                &ctx,
+                "[Op:WorkerCreate] Workers are not enabled in this runtime"
+            ))
+        }
+    };
+
+    let specifier = match args.get(0) {
+        Some(specifier) => specifier.as_string()?.to_string(),
+        None => return Err(js_error_typ!(&ctx, "[Op:WorkerCreate] Missing arguments")),
+    };
+    let name = match args.get(1) {
+        Some(name) if name.is_string() => name.as_string()?.to_string(),
+        _ => String::new(),
+    };
+
+    // other modules pass `new URL("./worker.js", import.meta.url)` to start at
+    // their own directory
+    let referrer = state.module_loader().borrow().main_module();
+    let path = match resolve_worker_path(&specifier, referrer.as_deref()) {
+        Ok(path) => path,
+        Err(err) => {
+            return Err(js_error_typ!(&ctx, format!("[Op:WorkerCreate] {}", err)))
+        }
+    };
+
+    let (handle, scope) = worker_channel(&name, &path);
+    let thread = std::thread::Builder::new()
+        .name(format!("worker {}", name))
+        .spawn(move || launcher(scope));
+    if let Err(err) = thread {
+        return Err(js_error!(&ctx, format!("[Op:WorkerCreate] {}", err)));
+    }
+
+    let object = state
+        .classes()
+        .get(WorkerResource::CLASS_NAME)
+        .expect("WorkerResource class not found")
+        .object::<WorkerHandle>(&ctx, Some(Box::new(handle)));
+    Ok(object.into())
+}
+
+/// op_worker_post_message(worker, message, transfer)
+#[callback]
+fn op_worker_post_message(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    args: &[JSValue],
+) -> JSResult<JSValue> {
+    let handle = match args.first().map(|worker| worker.as_object()) {
+        Some(Ok(worker)) => downcast_ref::<WorkerHandle>(&worker),
+        _ => None,
+    };
+    let handle = match handle {
+        Some(handle) => handle,
+        None => {
+            return Err(js_error_typ!(
+                &ctx,
+                "[Op:WorkerPostMessage] Invalid resource object"
+            ))
+        }
+    };
+
+    let message = match args.get(1) {
+        Some(message) => message.clone(),
+        None => js_undefined!(&ctx),
+    };
+    let transfer = transfer_list(&ctx, args.get(2))?;
+    let message = serialize(&ctx, &message, &transfer)?;
+    // the message is dropped if the worker already exited
+    let _ = handle.post_message(WorkerMessage::Message(message));
+    Ok(js_undefined!(&ctx))
+}
+
+/// Waits for the next message from the worker, the callback receives
+/// `undefined` once the worker exited.
+#[callback]
+fn op_worker_recv(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    worker: JSObject,
+    callback: JSObject,
+) -> JSResult<JSValue> {
+    let state = downcast_state(&ctx);
+    let mut handle = match downcast_ref::<WorkerHandle>(&worker) {
+        Some(handle) => handle,
+        None => {
+            return Err(js_error_typ!(
+                &ctx,
+                "[Op:WorkerRecv] Invalid resource object"
+            ))
+        }
+    };
+
+    callback.protect();
+    enqueue_job!(state, "op_worker_recv", async move {
+        let message = handle.receiver().read().await;
+
+        native_job!("op_worker_recv", move |ctx| {
+            let result = match message {
+                Ok(message) => message_object(&ctx, message),
+                Err(_) => Ok(js_undefined!(&ctx)),
+            };
+
+            match result {
+                Ok(event) => callback.call(None, &[js_undefined!(&ctx), event])?,
+                Err(err) => callback.call(None, &[err.into()])?,
+            };
+            callback.unprotect();
+            Ok(())
+        })
+    });
+
+    Ok(js_undefined!(&ctx))
+}
+
+/// Stops the worker once its event loop turns, a worker busy running a
+/// synchronous loop keeps running until it yields to the event loop.
+#[callback]
+fn op_worker_terminate(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    worker: JSObject,
+) -> JSResult<JSValue> {
+    if let Some(handle) = downcast_ref::<WorkerHandle>(&worker) {
+        handle.terminate();
+    }
+
+    Ok(js_undefined!(&ctx))
+}
+
+/// Returns `{ name }` inside a worker, `undefined` otherwise
+#[callback]
+fn op_worker_scope(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    _: &[JSValue],
+) -> JSResult<JSValue> {
+    let state = downcast_state(&ctx);
+    let worker_scope = state.worker_scope().borrow();
+    let scope = match worker_scope.as_ref() {
+        Some(scope) => scope,
+        None => return Ok(js_undefined!(&ctx)),
+    };
+
+    let object = JSObject::new(&ctx);
+    object.set_property(
+        "name",
+        &JSValue::string(&ctx, scope.name()),
+        Default::default(),
+    )?;
+    Ok(object.into())
+}
+
+/// op_worker_scope_post_message(message, transfer)
+#[callback]
+fn op_worker_scope_post_message(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    args: &[JSValue],
+) -> JSResult<JSValue> {
+    let state = downcast_state(&ctx);
+    let message = match args.first() {
+        Some(message) => message.clone(),
+        None => js_undefined!(&ctx),
+    };
+    let transfer = transfer_list(&ctx, args.get(1))?;
+    let message = serialize(&ctx, &message, &transfer)?;
+    if let Some(scope) = state.worker_scope().borrow().as_ref() {
+        let _ = scope.post_message(WorkerMessage::Message(message));
+    }
+
+    Ok(js_undefined!(&ctx))
+}
+
+/// op_worker_scope_post_error(message, stack), dispatched as an error event in the parent
+#[callback]
+fn op_worker_scope_post_error(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    args: &[JSValue],
+) -> JSResult<JSValue> {
+    let state = downcast_state(&ctx);
+    let string = |index: usize| -> String {
+        args.get(index)
+            .and_then(|value| value.as_string().ok())
+            .map(|value| value.to_string())
+            .unwrap_or_default()
+    };
+
+    let error = WorkerError {
+        message: string(0),
+        stack: string(1),
+    };
+    if let Some(scope) = state.worker_scope().borrow().as_ref() {
+        let _ = scope.post_message(WorkerMessage::Error(error));
+    }
+
+    Ok(js_undefined!(&ctx))
+}
+
+/// Waits for the next message from the parent, the callback receives
+/// `undefined` once the parent dropped the worker.
+#[callback]
+fn op_worker_scope_recv(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    callback: JSObject,
+) -> JSResult<JSValue> {
+    let state = downcast_state(&ctx);
+    let receiver = state
+        .worker_scope()
+        .borrow_mut()
+        .as_mut()
+        .and_then(|scope| scope.take_receiver());
+    let mut receiver = match receiver {
+        Some(receiver) => receiver,
+        None => {
+            return Err(js_error_typ!(
+                &ctx,
+                "[Op:WorkerScopeRecv] Not in a worker or a read is already pending"
+            ))
+        }
+    };
+
+    callback.protect();
+    enqueue_job!(state, "op_worker_scope_recv", async move {
+        let message = receiver.read().await;
+
+        native_job!("op_worker_scope_recv", move |ctx| {
+            let state = downcast_state(&ctx);
+            if let Some(scope) = state.worker_scope().borrow_mut().as_mut() {
+                scope.set_receiver(receiver);
+            }
+
+            let result = match message {
+                Ok(message) => message_object(&ctx, message),
+                Err(_) => Ok(js_undefined!(&ctx)),
+            };
+
+            match result {
+                Ok(event) => callback.call(None, &[js_undefined!(&ctx), event])?,
+                Err(err) => callback.call(None, &[err.into()])?,
+            };
+            callback.unprotect();
+            Ok(())
+        })
+    });
+
+    Ok(js_undefined!(&ctx))
+}
+
+#[callback]
+fn op_worker_close(
+    ctx: JSContext,
+    _: JSObject,
+    _: JSObject,
+    _: &[JSValue],
+) -> JSResult<JSValue> {
+    let state = downcast_state(&ctx);
+    if let Some(scope) = state.worker_scope().borrow().as_ref() {
+        scope.close();
+    }
+
+    Ok(js_undefined!(&ctx))
+}
+
+pub struct WorkerModule {}
+
+define_exports!(
+    WorkerModule,
+    @template[],
+    @function[
+        op_worker_create,
+        op_worker_post_message,
+        op_worker_recv,
+        op_worker_terminate,
+        op_worker_scope,
+        op_worker_scope_post_message,
+        op_worker_scope_post_error,
+        op_worker_scope_recv,
+        op_worker_close,
+    ]
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_worker_path() {
+        let manifest = env!("CARGO_MANIFEST_DIR");
+        let file = format!("{}/lib.rs", manifest);
+        assert_eq!(resolve_worker_path(&file, None), Ok(file.clone()));
+
+        let url = Url::from_file_path(&file).unwrap();
+        assert_eq!(resolve_worker_path(url.as_str(), None), Ok(file.clone()));
+
+        assert!(resolve_worker_path("https://example.com/worker.js", None).is_err());
+        assert!(resolve_worker_path("./missing-worker.js", None).is_err());
+
+        // relative to the main module of the runtime, not the current directory
+        let referrer = format!("{}/http/fetch.rs", manifest);
+        let expected = format!("{}/http/../lib.rs", manifest);
+        assert_eq!(
+            resolve_worker_path("../lib.rs", Some(&referrer)),
+            Ok(expected)
+        );
+
+        let referrer = Url::from_file_path(format!("{}/worker.rs", manifest)).unwrap();
+        assert_eq!(
+            resolve_worker_path("./lib.rs", Some(referrer.as_str())),
+            Ok(format!("{}/./lib.rs", manifest))
+        );
+        // internal modules aren't files, cargo runs the tests in the manifest dir
+        assert_eq!(
+            resolve_worker_path("./lib.rs", Some("@kedo/web")),
+            Ok(format!("{}/./lib.rs", manifest))
+        );
+    }
+}
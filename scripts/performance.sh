@@ -17,3 +17,5 @@ hyperfine "deno run ./tests/performance/headers.js" "bun ./tests/performance/hea
 hyperfine "deno run ./tests/headers.js" "bun ./tests/headers.js" "node ./tests/headers.js" "./target/release/kedo run ./tests/headers.js" --warmup=10
 
 hyperfine "deno run ./tests/streams/node-readable.js" "bun ./tests/streams/node-readable.js" "./target/release/kedo run ./tests/streams/readable.js" --warmup=10
+
+hyperfine "./target/release/kedo run ./tests/performance/startup.js" --warmup=10
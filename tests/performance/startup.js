@@ -0,0 +1,9 @@
+// Startup benchmark: the std bundle and the @kedo/* modules dominate
+// the run time of a script that does nothing.
+import assert from "@kedo/assert";
+import { EventEmitter } from "@kedo/events";
+import { ReadableStream } from "@kedo/stream";
+
+assert.ok(typeof fetch === "function");
+assert.ok(typeof EventEmitter === "function");
+assert.ok(typeof ReadableStream === "function");
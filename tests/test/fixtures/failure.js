@@ -0,0 +1,6 @@
+// Fixture of the test runner, a failing test fails the run
+Kedo.test("passes", () => {});
+
+Kedo.test("fails", () => {
+    throw new Error("expected failure");
+});
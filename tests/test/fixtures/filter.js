@@ -0,0 +1,6 @@
+// Fixture of the test runner, filtered with `--filter`
+Kedo.test("parses a date", () => {});
+
+Kedo.test("parses a number", () => {});
+
+Kedo.test("formats a date", () => {});
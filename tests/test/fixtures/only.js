@@ -0,0 +1,8 @@
+// Fixture of the test runner, only the tests marked with `only` run
+Kedo.test("runs without only", () => {});
+
+Kedo.test.only("runs with only", () => {});
+
+Kedo.test("fails without only", () => {
+    throw new Error("tests without only never run");
+});
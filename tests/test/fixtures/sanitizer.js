@@ -0,0 +1,9 @@
+// Fixture of the test runner, a timer that closes one tick after the test
+// is not a leak, a timer that outlives the sanitizer is one
+Kedo.test("closes a timer one tick late", () => {
+    setTimeout(() => {}, 0);
+});
+
+Kedo.test("leaks a timer", () => {
+    setTimeout(() => {}, 1000);
+});
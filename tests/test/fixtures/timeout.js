@@ -0,0 +1,10 @@
+// Fixture of the test runner, the test outlives its timeout
+Kedo.test(
+    "never resolves in time",
+    async () => {
+        await new Promise((resolve) => setTimeout(resolve, 200));
+    },
+    { timeout: 20 },
+);
+
+Kedo.test("runs after a timeout", () => {});
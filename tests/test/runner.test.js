@@ -0,0 +1,25 @@
+import assert from "@kedo/assert";
+
+Kedo.test("registers sync tests", () => {
+    assert.strictEqual(1 + 1, 2);
+});
+
+Kedo.test("awaits async tests", async () => {
+    const value = await new Promise((resolve) => {
+        setTimeout(() => resolve("done"), 10);
+    });
+
+    assert.strictEqual(value, "done");
+});
+
+Kedo.test(
+    "accepts a per test timeout",
+    async () => {
+        await new Promise((resolve) => setTimeout(resolve, 50));
+    },
+    { timeout: 1000 },
+);
+
+Kedo.test.skip("skips tests", () => {
+    throw new Error("skipped tests never run");
+});
@@ -0,0 +1,81 @@
+import assert from "@kedo/assert";
+
+function testMessageChannel() {
+  return new Promise((resolve) => {
+    const { port1, port2 } = new MessageChannel();
+    const received = [];
+
+    port2.onmessage = (event) => {
+      received.push(event.data);
+      if (received.length < 2) return;
+
+      assert.strictEqual(received[0].value, 1);
+      assert.ok(received[1] instanceof Map, "Messages should be cloned");
+      port1.close();
+      port2.close();
+      resolve();
+    };
+
+    port1.postMessage({ value: 1 });
+    port1.postMessage(new Map());
+  });
+}
+
+function testMessagesAreQueuedUntilStart() {
+  return new Promise((resolve) => {
+    const { port1, port2 } = new MessageChannel();
+    port1.postMessage("queued");
+
+    port2.addEventListener("message", (event) => {
+      assert.strictEqual(event.data, "queued");
+      port2.close();
+      resolve();
+    });
+
+    setTimeout(() => port2.start(), 10);
+  });
+}
+
+function testMessagePortIsNotCloneable() {
+  const { port1, port2 } = new MessageChannel();
+  const isPortError = (error) =>
+    error instanceof DOMException &&
+    error.name === "DataCloneError" &&
+    error.message === "MessagePort objects can't be transferred yet";
+  assert.throws(() => structuredClone(port1), isPortError);
+  assert.throws(() => port1.postMessage(null, [port2]), isPortError);
+  assert.throws(() => new MessagePort(), TypeError);
+  port1.close();
+  port2.close();
+}
+
+function testBroadcastChannel() {
+  return new Promise((resolve) => {
+    const sender = new BroadcastChannel("kedo");
+    const listener = new BroadcastChannel("kedo");
+    const other = new BroadcastChannel("other");
+
+    other.onmessage = () => assert.ok(false, "Other channels should not receive");
+    sender.onmessage = () => assert.ok(false, "The sender should not receive");
+    listener.onmessage = (event) => {
+      assert.deepStrictEqual(event.data, { hello: "kedo" });
+      sender.close();
+      listener.close();
+      other.close();
+      assert.throws(
+        () => sender.postMessage("closed"),
+        (error) => error.name === "InvalidStateError",
+      );
+      resolve();
+    };
+
+    sender.postMessage({ hello: "kedo" });
+  });
+}
+
+testMessagePortIsNotCloneable();
+await testMessageChannel();
+await testMessagesAreQueuedUntilStart();
+await testBroadcastChannel();
+
+console.log("All MessageChannel tests passed");
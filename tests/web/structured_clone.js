@@ -0,0 +1,79 @@
+import assert from "@kedo/assert";
+
+function testPrimitivesAndObjects() {
+  const value = {
+    number: 1,
+    bigint: 10n,
+    date: new Date(42),
+    regexp: /kedo/gi,
+    boxed: new Number(3),
+    list: [1, , "three"],
+  };
+  value.self = value;
+
+  const clone = structuredClone(value);
+  assert.notStrictEqual(clone, value, "Objects should be copied");
+  assert.strictEqual(clone.self, clone, "Cycles should be preserved");
+  assert.strictEqual(clone.bigint, 10n);
+  assert.strictEqual(clone.date.getTime(), 42);
+  assert.strictEqual(clone.regexp.source, "kedo");
+  assert.strictEqual(clone.regexp.flags, "gi");
+  assert.strictEqual(typeof clone.boxed, "object");
+  assert.strictEqual(clone.boxed.valueOf(), 3);
+  assert.strictEqual(clone.list.length, 3);
+  assert.ok(!(1 in clone.list), "Holes should be preserved");
+}
+
+function testMapsSetsAndErrors() {
+  const shared = { shared: true };
+  const map = new Map([["key", shared]]);
+  const set = new Set([shared]);
+  const error = new TypeError("bad type");
+
+  const clone = structuredClone({ map, set, error });
+  assert.ok(clone.map instanceof Map, "Maps should be cloned");
+  assert.ok(clone.set instanceof Set, "Sets should be cloned");
+  assert.ok(clone.set.has(clone.map.get("key")), "References should be shared");
+  assert.ok(clone.error instanceof TypeError, "Error types should be kept");
+  assert.strictEqual(clone.error.message, "bad type");
+}
+
+function testBuffersAndTransfer() {
+  const bytes = new Uint8Array([1, 2, 3, 4]);
+  const view = new DataView(bytes.buffer, 1, 2);
+
+  const copy = structuredClone({ bytes, view });
+  assert.strictEqual(copy.bytes[3], 4);
+  assert.strictEqual(copy.view.buffer, copy.bytes.buffer, "Buffers should be shared");
+  assert.strictEqual(copy.view.byteOffset, 1);
+  assert.strictEqual(bytes.byteLength, 4, "Copies should not detach the buffer");
+
+  const moved = structuredClone(bytes, { transfer: [bytes.buffer] });
+  assert.strictEqual(moved[0], 1);
+  assert.strictEqual(bytes.byteLength, 0, "Transferred buffers should be detached");
+}
+
+function testUncloneableValues() {
+  const isDataCloneError = (error) =>
+    error instanceof DOMException && error.name === "DataCloneError";
+
+  assert.throws(() => structuredClone(() => {}), isDataCloneError);
+  assert.throws(() => structuredClone(Symbol("kedo")), isDataCloneError);
+  assert.throws(() => structuredClone(Promise.resolve()), isDataCloneError);
+  assert.throws(() => structuredClone(new WeakMap()), isDataCloneError);
+
+  const buffer = new ArrayBuffer(1);
+  assert.throws(
+    () => structuredClone(buffer, { transfer: [buffer, buffer] }),
+    isDataCloneError,
+    "Duplicated transfers should throw",
+  );
+  assert.strictEqual(buffer.byteLength, 1, "Failed clones should not detach");
+}
+
+testPrimitivesAndObjects();
+testMapsSetsAndErrors();
+testBuffersAndTransfer();
+testUncloneableValues();
+
+console.log("All structuredClone tests passed");
@@ -0,0 +1,67 @@
+import assert from "@kedo/assert";
+
+// relative to this file, the main module
+const WORKER = "./worker_echo.js";
+
+function testWorkerRequiresModuleType() {
+  assert.throws(
+    () => new Worker(WORKER),
+    TypeError,
+    "Classic workers should not be supported",
+  );
+}
+
+function testWorkerRejectsUncloneableMessages() {
+  const worker = new Worker(WORKER, { type: "module" });
+
+  assert.throws(
+    () => worker.postMessage({ callback() {} }),
+    (error) => error instanceof DOMException && error.name === "DataCloneError",
+    "Functions should not be cloned",
+  );
+
+  worker.terminate();
+}
+
+function testWorkerMessages() {
+  return new Promise((resolve) => {
+    const worker = new Worker(WORKER, { type: "module", name: "echo" });
+    const data = { list: [1, "two", null], date: new Date(0) };
+    data.self = data;
+
+    worker.onmessage = (event) => {
+      const { name, echo } = event.data;
+      assert.strictEqual(name, "echo", "Worker should receive its name");
+      assert.deepStrictEqual(echo.list, [1, "two", null]);
+      assert.ok(echo.date instanceof Date, "Dates should be cloned");
+      assert.strictEqual(echo.self, echo, "Cycles should be preserved");
+      assert.notStrictEqual(echo, data, "Messages should be copied");
+      worker.terminate();
+      resolve();
+    };
+
+    worker.postMessage(data);
+  });
+}
+
+function testWorkerErrors() {
+  return new Promise((resolve) => {
+    const worker = new Worker(WORKER, { type: "module" });
+
+    worker.onerror = (event) => {
+      assert.strictEqual(event.message, "Worker failed");
+      event.preventDefault();
+      worker.terminate();
+      resolve();
+    };
+
+    worker.postMessage("throw");
+  });
+}
+
+testWorkerRequiresModuleType();
+testWorkerRejectsUncloneableMessages();
+await testWorkerMessages();
+await testWorkerErrors();
+
+console.log("All Worker tests passed");
@@ -0,0 +1,7 @@
+onmessage = (event) => {
+  if (event.data === "throw") {
+    throw new Error("Worker failed");
+  }
+
+  postMessage({ name, echo: event.data });
+};
@@ -47,6 +47,10 @@ type InternalServerOptions = {
     onError?: OnErrorHandler;
 };
 
+type WorkerEvent =
+    | { type: "message"; data: any }
+    | { type: "error"; message: string; stack: string };
+
 declare module "@kedo:op/web" {
     class FetchClient {
         constructor();
@@ -182,6 +186,91 @@ declare module "@kedo:op/web" {
         constructor();
     }
 
+    // workers
+    export class WorkerResource {}
+    export function op_worker_create(
+        specifier: string,
+        name: string,
+    ): WorkerResource;
+    export function op_worker_post_message(
+        worker: WorkerResource,
+        message: any,
+        transfer: ArrayBuffer[],
+    ): void;
+    /**
+     * Waits for the next message from the worker
+     *
+     * @returns undefined once the worker exited
+     */
+    export function op_worker_recv(
+        worker: WorkerResource,
+        callback: OpStyleCallback<WorkerEvent | undefined>,
+    ): void;
+    export function op_worker_terminate(worker: WorkerResource): void;
+    /**
+     * @returns the worker scope, undefined outside of a worker
+     */
+    export function op_worker_scope(): { name: string } | undefined;
+    export function op_worker_scope_post_message(
+        message: any,
+        transfer: ArrayBuffer[],
+    ): void;
+    export function op_worker_scope_post_error(
+        message: string,
+        stack: string,
+    ): void;
+    export function op_worker_scope_recv(
+        callback: OpStyleCallback<WorkerEvent | undefined>,
+    ): void;
+    export function op_worker_close(): void;
+
+    // structured clone
+    export function op_structured_clone<T>(value: T, transfer: ArrayBuffer[]): T;
+
+    // message channel
+    export class MessagePortResource {}
+    export function op_message_channel_create(): [
+        MessagePortResource,
+        MessagePortResource,
+    ];
+    export function op_message_port_post_message(
+        port: MessagePortResource,
+        message: any,
+        transfer: ArrayBuffer[],
+    ): void;
+    /**
+     * Waits for the next message of the port
+     *
+     * @returns undefined once the port is closed
+     */
+    export function op_message_port_recv(
+        port: MessagePortResource,
+        callback: OpStyleCallback<{ data: any } | undefined>,
+    ): void;
+    export function op_message_port_close(port: MessagePortResource): void;
+
+    // broadcast channel
+    export class BroadcastChannelResource {}
+    export function op_broadcast_channel_create(
+        name: string,
+    ): BroadcastChannelResource;
+    export function op_broadcast_channel_post_message(
+        channel: BroadcastChannelResource,
+        message: any,
+    ): void;
+    /**
+     * Waits for the next message of the channel
+     *
+     * @returns undefined once the channel is closed
+     */
+    export function op_broadcast_channel_recv(
+        channel: BroadcastChannelResource,
+        callback: OpStyleCallback<{ data: any } | undefined>,
+    ): void;
+    export function op_broadcast_channel_close(
+        channel: BroadcastChannelResource,
+    ): void;
+
     export class ReadableStreamResource {
         constructor(hwm: number);
     }
@@ -236,3 +325,24 @@ declare module "@kedo:op/fs" {
         callback: OpStyleCallback<void>,
     ): void;
 }
+
+declare module "@kedo:op/runtime" {
+    export function op_permission_query(
+        name: "read" | "write" | "net" | "env",
+        resource?: string,
+    ): "granted" | "denied";
+    export function op_env_get(key: string): string | undefined;
+    export function op_env_set(key: string, value: string): void;
+    export function op_env_delete(key: string): void;
+    export function op_env_to_object(): Record<string, string>;
+    export function op_resources(): Record<string, string>;
+}
+
+declare module "@kedo:op/module" {
+    export function createRequire(referrer: string | URL): {
+        (specifier: string): any;
+        resolve(specifier: string): string;
+        cache: Record<string, any>;
+    };
+    export function load(filename: string): any;
+}
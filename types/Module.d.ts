@@ -0,0 +1,32 @@
+declare module "@kedo/module" {
+    interface Module {
+        id: string;
+        filename: string;
+        path: string;
+        exports: any;
+        parent: Module | null;
+        children: Module[];
+        loaded: boolean;
+        require: Require;
+    }
+
+    interface Require {
+        /** Loads a CommonJS or JSON module, ES modules must be imported */
+        (specifier: string): any;
+        /** Resolves the specifier to the file name of the module */
+        resolve(specifier: string): string;
+        /** Modules loaded by `require`, keyed by file name */
+        cache: Record<string, Module>;
+    }
+
+    /**
+     * Creates a `require` function that resolves the specifiers from `referrer`,
+     * a file name or a `file://` URL.
+     *
+     * @example
+     * import { createRequire } from "@kedo/module";
+     * const require = createRequire(import.meta.url);
+     * const config = require("./config.json");
+     */
+    export function createRequire(referrer: string | URL): Require;
+}
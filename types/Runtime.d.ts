@@ -0,0 +1,55 @@
+declare module "@kedo/runtime" {
+    type PermissionState = "granted" | "denied";
+
+    type PermissionDescriptor =
+        | { name: "read"; path?: string }
+        | { name: "write"; path?: string }
+        | { name: "net"; host?: string }
+        | { name: "env"; variable?: string };
+
+    /**
+     * Error thrown when an operation is not allowed by the granted permissions,
+     * e.g. reading a file without `--allow-read`.
+     */
+    export class PermissionDenied extends Error {
+        constructor(message?: string);
+    }
+
+    export class PermissionStatus {
+        readonly state: PermissionState;
+    }
+
+    export const permissions: {
+        /**
+         * Resolves the state of a permission, without a resource the permission
+         * is only granted if it was granted for every resource.
+         *
+         * @example
+         * const status = await Kedo.permissions.query({ name: "read", path: "./data.txt" });
+         * status.state; // "granted" | "denied"
+         */
+        query(descriptor: PermissionDescriptor): Promise<PermissionStatus>;
+        querySync(descriptor: PermissionDescriptor): PermissionStatus;
+    };
+
+    /**
+     * Environment variables of the process. Variables set or deleted are only
+     * seen by this runtime, the process environment and the workers keep theirs.
+     */
+    export const env: {
+        get(key: string): string | undefined;
+        set(key: string, value: string): void;
+        delete(key: string): void;
+        has(key: string): boolean;
+        toObject(): Record<string, string>;
+    };
+
+    /**
+     * Returns the open resources and pending ops keyed by resource id.
+     *
+     * @example
+     * setTimeout(() => {}, 1000);
+     * Kedo.resources(); // { "1": "timer" }
+     */
+    export function resources(): Record<string, string>;
+}
@@ -0,0 +1,43 @@
+declare module "@kedo/test" {
+    type TestFn = () => void | Promise<void>;
+
+    interface TestOptions {
+        /** Only run the tests marked with `only` */
+        only?: boolean;
+        /** Report the test as skipped without running it */
+        skip?: boolean;
+        /** Time in milliseconds before an async test fails, defaults to 5000 */
+        timeout?: number;
+        /** Fail the test if it leaves resources open, e.g. timers or servers (default true) */
+        sanitizeResources?: boolean;
+        /** Fail the test if it leaves async ops pending (default true) */
+        sanitizeOps?: boolean;
+    }
+
+    interface TestResult {
+        name: string;
+        status: "passed" | "failed" | "skipped";
+        duration: number;
+        error?: string;
+    }
+
+    interface Test {
+        /**
+         * Registers a test, run the test files with `kedo test`.
+         *
+         * @example
+         * Kedo.test("adds numbers", () => {
+         *     assert.strictEqual(1 + 1, 2);
+         * });
+         */
+        (name: string, fn: TestFn, options?: TestOptions): void;
+        only(name: string, fn: TestFn, options?: TestOptions): void;
+        skip(name: string, fn: TestFn, options?: TestOptions): void;
+    }
+
+    export const test: Test;
+    export function runTests(options?: {
+        filter?: string;
+        timeout?: number;
+    }): Promise<TestResult[]>;
+}
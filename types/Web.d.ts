@@ -184,21 +184,28 @@ declare module "@kedo/web" {
     export {
         AbortController,
         AbortSignal,
+        BroadcastChannel,
         DOMException,
+        ErrorEvent,
         fetch,
         Headers,
+        MessageChannel,
+        MessageEvent,
+        MessagePort,
         Request,
         Response,
         serve,
+        structuredClone,
         TextDecoder,
         TextEncoder,
         URL,
         URLSearchParams,
+        Worker,
     } from "@kedo:int/std/web";
 }
 
 declare module "@kedo:int/std/web" {
-    import { EventTarget } from "@kedo/events";
+    import { Event, EventTarget } from "@kedo/events";
     type ForEachCallback = (
         value: string,
         name: string,
@@ -730,6 +737,95 @@ declare module "@kedo:int/std/web" {
 
     function fetch(input: RequestInfo, init?: RequestInit): Promise<Response>;
 
+    type WorkerOptions = {
+        type?: "classic" | "module";
+        name?: string;
+    };
+
+    class MessageEvent<T = any> extends Event {
+        constructor(type: string, eventInitDict?: EventInit & { data?: T });
+        readonly data: T;
+    }
+
+    class ErrorEvent extends Event {
+        constructor(
+            type: string,
+            eventInitDict?: EventInit & { message?: string; error?: any },
+        );
+        readonly message: string;
+        readonly error: any;
+    }
+
+    /**
+     * Runs a module on its own thread with its own event loop,
+     * messages are copied with the structured clone algorithm.
+     *
+     * @example
+     * ```ts
+     * const worker = new Worker("./worker.js", { type: "module" });
+     * worker.onmessage = (event) => console.log(event.data);
+     * worker.postMessage({ hello: "worker" });
+     * ```
+     */
+    class Worker extends EventTarget {
+        constructor(specifier: string | URL, options?: WorkerOptions);
+        onmessage: ((event: MessageEvent) => void) | null;
+        onmessageerror: ((event: MessageEvent) => void) | null;
+        onerror: ((event: ErrorEvent) => void) | null;
+        postMessage(message: any, options?: PostMessageOptions): void;
+        terminate(): void;
+    }
+
+    type StructuredSerializeOptions = { transfer?: ArrayBuffer[] };
+    type PostMessageOptions = ArrayBuffer[] | StructuredSerializeOptions;
+
+    /**
+     * Deep copies the value with the structured clone algorithm, the
+     * buffers of the transfer list are moved to the copy and detached.
+     *
+     * @example
+     * ```ts
+     * const buffer = new ArrayBuffer(8);
+     * const copy = structuredClone({ buffer }, { transfer: [buffer] });
+     * buffer.byteLength; // 0
+     * ```
+     */
+    function structuredClone<T = any>(
+        value: T,
+        options?: StructuredSerializeOptions,
+    ): T;
+
+    /**
+     * One end of a `MessageChannel`, messages are queued until `start()`
+     * is called or `onmessage` is set.
+     */
+    class MessagePort extends EventTarget {
+        onmessage: ((event: MessageEvent) => void) | null;
+        onmessageerror: ((event: MessageEvent) => void) | null;
+        postMessage(message: any, options?: PostMessageOptions): void;
+        start(): void;
+        close(): void;
+    }
+
+    class MessageChannel {
+        constructor();
+        readonly port1: MessagePort;
+        readonly port2: MessagePort;
+    }
+
+    /**
+     * Delivers messages to every other `BroadcastChannel` with the same name,
+     * including the ones created in workers.
+     */
+    class BroadcastChannel extends EventTarget {
+        constructor(name: string);
+        readonly name: string;
+        onmessage: ((event: MessageEvent) => void) | null;
+        onmessageerror: ((event: MessageEvent) => void) | null;
+        postMessage(message: any): void;
+        close(): void;
+    }
+
     function serve(
         options: ServeOptions | ServerHandler | (ServeOptions & TlsCertificate),
         _serverOptions?: ServeOptions | (ServeOptions & TlsCertificate),
@@ -738,19 +834,26 @@ declare module "@kedo:int/std/web" {
     export {
         AbortController,
         AbortSignal,
+        BroadcastChannel,
         createDependentAbortSignal,
         DOMException,
         emptyHeader,
+        ErrorEvent,
         fetch,
         fillHeadersMapFrom,
         headerInnerList,
         Headers,
+        MessageChannel,
+        MessageEvent,
+        MessagePort,
         Request,
         Response,
         serve,
+        structuredClone,
         TextDecoder,
         TextEncoder,
         URL,
         URLSearchParams,
+        Worker,
     };
 }